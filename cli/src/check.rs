@@ -0,0 +1,44 @@
+use knus::schema::Schema;
+use knus::span::Span;
+
+use crate::{option_value, positional, report, Failure, Input};
+
+/// Parses the files and, optionally, validates them against the schema
+pub fn run(args: Vec<String>) -> Result<bool, Failure> {
+    let mut schema = None;
+    let mut paths = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--schema" => schema = Some(option_value(&arg, args.next())?),
+            _ => paths.push(positional(arg)?),
+        }
+    }
+    let schema = match schema {
+        Some(path) => {
+            let input = Input::read(Some(&path))?;
+            Some(Schema::parse(&input.name, input.text()?)?)
+        }
+        None => None,
+    };
+    let mut ok = true;
+    for input in Input::read_all(&paths) {
+        if let Err(err) = check(input, schema.as_ref()) {
+            report(err);
+            ok = false;
+        }
+    }
+    Ok(ok)
+}
+
+fn check(input: Result<Input, miette::Report>, schema: Option<&Schema>) -> miette::Result<()> {
+    let input = input?;
+    let text = input.text()?;
+    match schema {
+        Some(schema) => schema.validate_text(&input.name, text)?,
+        None => {
+            knus::parse_ast::<Span>(&input.name, text)?;
+        }
+    }
+    Ok(())
+}
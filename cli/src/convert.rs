@@ -0,0 +1,154 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use knus::ast::Document;
+use knus::print::PrintOptions;
+use knus::span::Span;
+
+use crate::{option_value, positional, Failure, Input};
+
+/// Format of the converted document
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Kdl,
+    /// JSON-in-KDL, the document must have a single node
+    Json,
+    /// Syntax tree of the document encoded with `minicbor`
+    Cbor,
+}
+
+/// Converts the document between the formats
+///
+/// Formats default to the ones implied by the file extensions.
+pub fn run(args: Vec<String>) -> Result<bool, Failure> {
+    let mut from = None;
+    let mut to = None;
+    let mut output = None;
+    let mut paths = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--from" => from = Some(Format::parse(&option_value(&arg, args.next())?)?),
+            "--to" => to = Some(Format::parse(&option_value(&arg, args.next())?)?),
+            "-o" | "--output" => output = Some(option_value(&arg, args.next())?),
+            _ => paths.push(positional(arg)?),
+        }
+    }
+    let path = match &paths[..] {
+        [] => None,
+        [path] => Some(path.as_str()),
+        _ => return Err(Failure::usage("convert accepts a single input")),
+    };
+    let from = from
+        .or_else(|| path.and_then(Format::from_path))
+        .ok_or_else(|| Failure::usage("cannot infer the input format, use --from"))?;
+    let to = to
+        .or_else(|| output.as_deref().and_then(Format::from_path))
+        .ok_or_else(|| Failure::usage("cannot infer the output format, use --to"))?;
+
+    let input = Input::read(path)?;
+    let data = match (from, to) {
+        // keeps the source code in the errors of the JiK document
+        (Format::Kdl, Format::Json) => json(&knus::jik::parse(&input.name, input.text()?)?)?,
+        _ => write(to, &read(from, &input)?)?,
+    };
+    match output {
+        Some(path) => {
+            fs::write(&path, data).map_err(|e| miette::miette!("cannot write {}: {}", path, e))?
+        }
+        None => io::stdout()
+            .write_all(&data)
+            .map_err(|e| miette::miette!("cannot write standard output: {}", e))?,
+    }
+    Ok(true)
+}
+
+impl Format {
+    fn parse(name: &str) -> Result<Format, Failure> {
+        match name {
+            "kdl" => Ok(Format::Kdl),
+            "json" => Ok(Format::Json),
+            "cbor" => Ok(Format::Cbor),
+            _ => Err(Failure::usage(format!(
+                "unknown format `{}`, expected `kdl`, `json` or `cbor`",
+                name
+            ))),
+        }
+    }
+
+    fn from_path(path: &str) -> Option<Format> {
+        match Path::new(path).extension()?.to_str()? {
+            "kdl" => Some(Format::Kdl),
+            "json" => Some(Format::Json),
+            "cbor" => Some(Format::Cbor),
+            _ => None,
+        }
+    }
+}
+
+fn read(format: Format, input: &Input) -> miette::Result<Document<Span>> {
+    match format {
+        Format::Kdl => Ok(knus::parse_ast(&input.name, input.text()?)?),
+        Format::Json => {
+            let value = serde_json::from_slice(&input.data)
+                .map_err(|e| miette::miette!("{}: invalid JSON: {}", input.name, e))?;
+            Ok(knus::jik::from_json(&value))
+        }
+        Format::Cbor => cbor::decode(input),
+    }
+}
+
+fn write(format: Format, document: &Document<Span>) -> miette::Result<Vec<u8>> {
+    match format {
+        Format::Kdl => Ok(PrintOptions::new().print_document(document).into_bytes()),
+        Format::Json => {
+            let value = knus::jik::to_json(document).map_err(|errors| {
+                let messages = errors.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+                miette::miette!("invalid JiK document: {}", messages.join("; "))
+            })?;
+            json(&value)
+        }
+        Format::Cbor => cbor::encode(document),
+    }
+}
+
+fn json(value: &serde_json::Value) -> miette::Result<Vec<u8>> {
+    let mut data = serde_json::to_vec_pretty(value)
+        .map_err(|e| miette::miette!("cannot serialize JSON: {}", e))?;
+    data.push(b'\n');
+    Ok(data)
+}
+
+#[cfg(feature = "cbor")]
+mod cbor {
+    use knus::ast::Document;
+    use knus::span::Span;
+
+    use crate::Input;
+
+    pub fn decode(input: &Input) -> miette::Result<Document<Span>> {
+        minicbor::decode(&input.data)
+            .map_err(|e| miette::miette!("{}: invalid CBOR: {}", input.name, e))
+    }
+
+    pub fn encode(document: &Document<Span>) -> miette::Result<Vec<u8>> {
+        minicbor::to_vec(document).map_err(|e| miette::miette!("cannot encode CBOR: {}", e))
+    }
+}
+
+#[cfg(not(feature = "cbor"))]
+mod cbor {
+    use knus::ast::Document;
+    use knus::span::Span;
+
+    use crate::Input;
+
+    pub fn decode(_input: &Input) -> miette::Result<Document<Span>> {
+        miette::bail!("CBOR support requires the `cbor` feature of knus-cli")
+    }
+
+    pub fn encode(_document: &Document<Span>) -> miette::Result<Vec<u8>> {
+        miette::bail!("CBOR support requires the `cbor` feature of knus-cli")
+    }
+}
@@ -0,0 +1,42 @@
+use crate::{option_value, positional, report, Failure, Input};
+
+/// Pretty-prints the files in place, keeping the comments
+///
+/// The standard input is printed to the standard output instead. With
+/// `--check` nothing is written, the files that would change are listed.
+pub fn run(args: Vec<String>) -> Result<bool, Failure> {
+    let mut check = false;
+    let mut indent = 4;
+    let mut paths = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--check" => check = true,
+            "--indent" => {
+                indent = option_value(&arg, args.next())?
+                    .parse()
+                    .map_err(|_| Failure::usage("--indent requires a number"))?;
+            }
+            _ => paths.push(positional(arg)?),
+        }
+    }
+    let mut ok = true;
+    for input in Input::read_all(&paths) {
+        let result = input.and_then(|input| format(&input, indent).map(|text| (input, text)));
+        match result {
+            Ok((input, formatted)) => ok &= input.update(&formatted, check, "reformat"),
+            Err(err) => {
+                report(err);
+                ok = false;
+            }
+        }
+    }
+    Ok(ok)
+}
+
+/// Formats the concrete syntax tree, so comments are kept and the version
+/// of the syntax is detected the same way as when parsing
+fn format(input: &Input, indent: usize) -> miette::Result<String> {
+    let cst = knus::cst::parse(&input.name, input.text()?)?;
+    Ok(cst.format(indent))
+}
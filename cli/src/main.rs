@@ -0,0 +1,192 @@
+//! Command-line tool for KDL documents
+//!
+//! ```shell
+//! knus check [--schema <schema>] [<file>...]
+//! knus fmt [--check] [--indent <width>] [<file>...]
+//! knus migrate [--check] [<file>...]
+//! knus convert [--from <format>] [--to <format>] [-o <output>] [<input>]
+//! knus query <query> [<file>...]
+//! ```
+//!
+//! Standard input is read when no files are given, or for the `-` file.
+#![warn(missing_debug_implementations)]
+
+mod check;
+mod convert;
+mod fmt;
+mod migrate;
+mod query;
+
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::process::exit;
+
+const USAGE: &str = "\
+Usage:
+    knus check [--schema <schema>] [<file>...]
+    knus fmt [--check] [--indent <width>] [<file>...]
+    knus migrate [--check] [<file>...]
+    knus convert [--from <format>] [--to <format>] [-o <output>] [<input>]
+    knus query <query> [<file>...]
+
+Formats for `convert` are `kdl`, `json` (JiK) and `cbor`. Standard input is
+read when no files are given, or for the `-` file.";
+
+/// Error that stops the command
+#[derive(Debug)]
+enum Failure {
+    /// Invalid command line, exits with status 2
+    Usage(String),
+    /// Error that is reported as is, exits with status 1
+    Error(miette::Report),
+}
+
+/// Contents of the input file or of the standard input
+#[derive(Debug)]
+struct Input {
+    /// Path of the file or `<stdin>`, used in the error reports
+    name: String,
+    path: Option<String>,
+    data: Vec<u8>,
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let command = args.next();
+    let args = args.collect::<Vec<_>>();
+    let result = match command.as_deref() {
+        Some("check") => check::run(args),
+        Some("fmt") => fmt::run(args),
+        Some("migrate") => migrate::run(args),
+        Some("convert") => convert::run(args),
+        Some("query") => query::run(args),
+        Some("help" | "-h" | "--help") => {
+            println!("{}", USAGE);
+            return;
+        }
+        Some(command) => Err(Failure::usage(format!("unknown command `{}`", command))),
+        None => Err(Failure::usage("command is required")),
+    };
+    match result {
+        Ok(true) => {}
+        Ok(false) => exit(1),
+        Err(Failure::Usage(message)) => {
+            eprintln!("knus: {}\n\n{}", message, USAGE);
+            exit(2);
+        }
+        Err(Failure::Error(report)) => {
+            eprintln!("{:?}", report);
+            exit(1);
+        }
+    }
+}
+
+impl Failure {
+    fn usage(message: impl Into<String>) -> Failure {
+        Failure::Usage(message.into())
+    }
+}
+
+impl From<miette::Report> for Failure {
+    fn from(report: miette::Report) -> Failure {
+        Failure::Error(report)
+    }
+}
+
+impl From<knus::Error> for Failure {
+    fn from(err: knus::Error) -> Failure {
+        Failure::Error(err.into())
+    }
+}
+
+impl Input {
+    /// Reads the file, `None` and `-` stand for the standard input
+    fn read(path: Option<&str>) -> Result<Input, miette::Report> {
+        match path {
+            None | Some("-") => {
+                let mut data = Vec::new();
+                io::stdin()
+                    .read_to_end(&mut data)
+                    .map_err(|e| miette::miette!("cannot read standard input: {}", e))?;
+                Ok(Input {
+                    name: "<stdin>".into(),
+                    path: None,
+                    data,
+                })
+            }
+            Some(path) => {
+                let data =
+                    fs::read(path).map_err(|e| miette::miette!("cannot read {}: {}", path, e))?;
+                Ok(Input {
+                    name: path.into(),
+                    path: Some(path.into()),
+                    data,
+                })
+            }
+        }
+    }
+
+    /// Reads all the files, or the standard input if there are none
+    ///
+    /// Errors are reported per file, so that the command can continue with
+    /// the other files.
+    fn read_all(paths: &[String]) -> Vec<Result<Input, miette::Report>> {
+        if paths.is_empty() {
+            vec![Input::read(None)]
+        } else {
+            paths.iter().map(|path| Input::read(Some(path))).collect()
+        }
+    }
+
+    /// Writes the new contents of the file, or prints them for the
+    /// standard input
+    ///
+    /// With `check` nothing is written, the file is listed as the one that
+    /// would `action` instead. Returns `false` if the file would change or
+    /// cannot be written.
+    fn update(&self, output: &str, check: bool, action: &str) -> bool {
+        let changed = output.as_bytes() != self.data;
+        match (&self.path, check) {
+            (_, true) if changed => {
+                println!("would {} {}", action, self.name);
+                false
+            }
+            (None, false) => {
+                print!("{}", output);
+                true
+            }
+            (Some(path), false) if changed => match fs::write(path, output) {
+                Ok(()) => true,
+                Err(e) => {
+                    report(miette::miette!("cannot write {}: {}", path, e));
+                    false
+                }
+            },
+            _ => true,
+        }
+    }
+
+    fn text(&self) -> Result<&str, miette::Report> {
+        std::str::from_utf8(&self.data)
+            .map_err(|e| miette::miette!("{} is not valid UTF-8: {}", self.name, e))
+    }
+}
+
+/// Checks that the argument isn't an unknown option
+fn positional(arg: String) -> Result<String, Failure> {
+    if arg.starts_with('-') && arg != "-" {
+        Err(Failure::usage(format!("unknown option `{}`", arg)))
+    } else {
+        Ok(arg)
+    }
+}
+
+/// Returns the value of the option
+fn option_value(option: &str, value: Option<String>) -> Result<String, Failure> {
+    value.ok_or_else(|| Failure::usage(format!("{} requires a value", option)))
+}
+
+fn report(report: impl Into<miette::Report>) {
+    eprintln!("{:?}", report.into());
+}
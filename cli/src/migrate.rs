@@ -0,0 +1,56 @@
+use knus::migrate::Issue;
+
+use crate::{positional, report, Failure, Input};
+
+/// Converts KDL 1.0 files into KDL 2.0 in place
+///
+/// The standard input is printed to the standard output instead. With
+/// `--check` nothing is written, the files that would change are listed.
+/// Files with parts that can't be converted safely are left unchanged and
+/// the parts are reported.
+pub fn run(args: Vec<String>) -> Result<bool, Failure> {
+    let mut check = false;
+    let mut paths = Vec::new();
+    for arg in args {
+        match &arg[..] {
+            "--check" => check = true,
+            _ => paths.push(positional(arg)?),
+        }
+    }
+    let mut ok = true;
+    for input in Input::read_all(&paths) {
+        let result = input.and_then(|input| migrate(&input).map(|text| (input, text)));
+        match result {
+            Ok((input, migrated)) => ok &= input.update(&migrated, check, "migrate"),
+            Err(err) => {
+                report(err);
+                ok = false;
+            }
+        }
+    }
+    Ok(ok)
+}
+
+fn migrate(input: &Input) -> miette::Result<String> {
+    let text = input.text()?;
+    let migration = knus::migrate::v1_to_v2(&input.name, text)?;
+    let mut issues = migration.issues.into_iter();
+    let first = match issues.next() {
+        Some(issue) => issue,
+        None => return Ok(migration.text),
+    };
+    for issue in issues {
+        report(issue_report(input, text, issue));
+    }
+    Err(issue_report(input, text, first))
+}
+
+fn issue_report(input: &Input, text: &str, issue: Issue) -> miette::Report {
+    let span = issue.span.0..issue.span.1;
+    miette::miette!(
+        labels = vec![miette::LabeledSpan::at(span, "cannot be converted")],
+        "{}",
+        issue.message
+    )
+    .with_source_code(miette::NamedSource::new(&input.name, text.to_string()))
+}
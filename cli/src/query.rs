@@ -0,0 +1,57 @@
+use knus::query::{Extracted, Query};
+use knus::span::Span;
+
+use crate::{positional, report, Failure, Input};
+
+/// Prints the nodes, or the values extracted with `=>`, matching the query
+///
+/// Every match is printed on its own line, prefixed by the file name when
+/// there are several files. Parts of tuples are separated by tabs.
+pub fn run(args: Vec<String>) -> Result<bool, Failure> {
+    let mut args = args.into_iter();
+    let query = args
+        .next()
+        .ok_or_else(|| Failure::usage("query is required"))?;
+    let query = Query::parse(&query).map_err(miette::Report::new)?;
+    let paths = args.map(positional).collect::<Result<Vec<_>, _>>()?;
+    let mut ok = true;
+    for input in Input::read_all(&paths) {
+        let result = input.and_then(|input| {
+            let document = knus::parse_ast::<Span>(&input.name, input.text()?)?;
+            for item in query.extract(&document) {
+                if paths.len() > 1 {
+                    print!("{}:", input.name);
+                }
+                println!("{}", render(&item));
+            }
+            Ok(())
+        });
+        if let Err(err) = result {
+            report(err);
+            ok = false;
+        }
+    }
+    Ok(ok)
+}
+
+/// Renders the extracted item, missing values are rendered as empty string
+fn render(item: &Extracted<Span>) -> String {
+    match item {
+        Extracted::Node(node) => node.to_string(),
+        Extracted::Name(name) => name.to_string(),
+        Extracted::TypeName(type_name) => type_name.map(|t| t.to_string()).unwrap_or_default(),
+        Extracted::Value(value) => value.map(|v| v.to_string()).unwrap_or_default(),
+        Extracted::Values(values) => values
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>()
+            .join(" "),
+        Extracted::Props(props) => props
+            .iter()
+            .map(|(name, value)| format!("{}={}", &***name, value))
+            .collect::<Vec<_>>()
+            .join(" "),
+        Extracted::Tuple(items) => items.iter().map(render).collect::<Vec<_>>().join("\t"),
+        _ => String::new(),
+    }
+}
@@ -0,0 +1,192 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+fn knus(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_knus"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // the input is not read when files are given, so the pipe may be closed
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).ok();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> &str {
+    std::str::from_utf8(&output.stdout).unwrap()
+}
+
+fn stderr(output: &Output) -> &str {
+    std::str::from_utf8(&output.stderr).unwrap()
+}
+
+fn temp_file(name: &str, text: &str) -> String {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    fs::write(&path, text).unwrap();
+    path.to_str().unwrap().into()
+}
+
+#[test]
+fn check() {
+    let valid = temp_file("check_valid.kdl", "node 1\n");
+    let invalid = temp_file("check_invalid.kdl", "node {\n");
+    let output = knus(&["check", &valid], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stderr(&output), "");
+
+    let output = knus(&["check", &valid, &invalid], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("unclosed curly braces"));
+    assert!(stderr(&output).contains("check_invalid.kdl"));
+
+    let output = knus(&["check"], "node \"a\" )");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("<stdin>"));
+}
+
+#[test]
+fn check_schema() {
+    let schema = temp_file(
+        "check.schema.kdl",
+        r#"document { node "server" { prop "port" { type "number"; }; }; }"#,
+    );
+    let output = knus(&["check", "--schema", &schema], "server port=80\n");
+    assert_eq!(output.status.code(), Some(0));
+    let output = knus(&["check", "--schema", &schema], "server port=\"x\"\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("expected number, found string"));
+}
+
+#[test]
+fn fmt() {
+    let output = knus(&["fmt", "--indent", "2"], "a   1 {b;}\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "a 1 {\n  b\n}\n");
+
+    let path = temp_file("fmt.kdl", "a   1 {b;}\n");
+    let output = knus(&["fmt", "--check", &path], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), format!("would reformat {}\n", path));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "a   1 {b;}\n");
+
+    let output = knus(&["fmt", &path], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "a 1 {\n    b\n}\n");
+    let output = knus(&["fmt", "--check", &path], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "");
+
+    let output = knus(&["fmt", "--indent", "2"], "a  1 { // comment\nb;}\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "a 1 { // comment\n  b\n}\n");
+
+    let output = knus(&["fmt"], "/- kdl-version 2\nnode   #true\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "/- kdl-version 2\nnode #true\n");
+}
+
+#[test]
+fn migrate() {
+    let output = knus(&["migrate"], "a true \"x\" // comment\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output),
+        "/- kdl-version 2\na #true \"x\" // comment\n"
+    );
+
+    let path = temp_file("migrate.kdl", "a null\n");
+    let output = knus(&["migrate", "--check", &path], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), format!("would migrate {}\n", path));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "a null\n");
+
+    let output = knus(&["migrate", &path], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "/- kdl-version 2\na #null\n"
+    );
+    let output = knus(&["migrate", "--check", &path], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "");
+
+    let output = knus(&["migrate"], "a 1e 2\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+    assert!(stderr(&output).contains("exponent without digits is not allowed in KDL 2.0"));
+}
+
+#[test]
+fn convert() {
+    let output = knus(
+        &["convert", "--from", "kdl", "--to", "json"],
+        "- { a 1; b true; }",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    let value: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(value, serde_json::json!({"a": 1, "b": true}));
+
+    let output = knus(&["convert", "--from", "json", "--to", "kdl"], "[1, 2]");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "- 1 2\n");
+
+    let path = temp_file("convert.kdl", "- 1\n- 2\n");
+    let output = knus(&["convert", &path, "--to", "json"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("JiK document must contain a single node"));
+
+    let output = knus(&["convert", "--to", "json"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("cannot infer the input format"));
+}
+
+#[cfg(feature = "cbor")]
+#[test]
+fn convert_cbor() {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("convert.cbor");
+    let path = path.to_str().unwrap();
+    let output = knus(&["convert", "--from", "kdl", "-o", path], "a 1 x=\"y\"\n");
+    assert_eq!(output.status.code(), Some(0));
+    let output = knus(&["convert", path, "--to", "kdl"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "a 1 x=\"y\"\n");
+}
+
+#[test]
+fn query() {
+    let text = "server \"a\" port=80 {\n    listen 1\n}\nclient (u8)5\n";
+    let output = knus(&["query", "server > listen"], text);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "listen 1\n");
+
+    let output = knus(
+        &["query", "[port] || client => (name(), val(), prop(port))"],
+        text,
+    );
+    assert_eq!(stdout(&output), "server\t\"a\"\t80\nclient\t(u8)5\t\n");
+
+    let first = temp_file("query1.kdl", text);
+    let second = temp_file("query2.kdl", "server\n");
+    let output = knus(&["query", "server => name()", &first, &second], "");
+    assert_eq!(
+        stdout(&output),
+        format!("{}:server\n{}:server\n", first, second)
+    );
+
+    let output = knus(&["query", "server >"], text);
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn usage() {
+    let output = knus(&[], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("Usage:"));
+    let output = knus(&["check", "--verbose"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("unknown option `--verbose`"));
+}
@@ -21,8 +21,8 @@ pub enum Definition {
 
 pub enum VariantKind {
     Unit,
-    Nested { option: bool },
-    Tuple(Struct),
+    Nested { option: bool, ty: Box<syn::Type> },
+    Tuple(Box<Struct>),
     Named,
 }
 
@@ -62,6 +62,23 @@ pub enum DecodeMode {
     Bytes,
 }
 
+/// Case of the names derived from the identifiers, `rename_all`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenameAll {
+    #[default]
+    KebabCase,
+    SnakeCase,
+    CamelCase,
+    PascalCase,
+    Verbatim,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeMode {
+    Append,
+    Replace,
+}
+
 #[derive(Debug)]
 pub enum Attr {
     Skip,
@@ -69,7 +86,12 @@ pub enum Attr {
     FieldMode(FieldMode),
     Unwrap(FieldAttrs),
     Default(Option<syn::Expr>),
+    Merge(MergeMode),
     SpanType(syn::Type),
+    RenameAll(RenameAll),
+    AllowUnknown,
+    Rename(String),
+    Alias(String),
 }
 
 #[derive(Debug, Clone)]
@@ -78,11 +100,16 @@ pub struct FieldAttrs {
     pub decode: Option<(DecodeMode, Span)>,
     pub unwrap: Option<Box<FieldAttrs>>,
     pub default: Option<Option<syn::Expr>>,
+    pub merge: Option<(MergeMode, Span)>,
+    pub rename: Option<(String, Span)>,
+    pub aliases: Vec<(String, Span)>,
 }
 
 #[derive(Debug, Clone)]
 pub struct VariantAttrs {
     pub skip: bool,
+    pub rename: Option<String>,
+    pub aliases: Vec<String>,
 }
 
 #[derive(Clone)]
@@ -96,6 +123,8 @@ pub struct Field {
     pub span: Span,
     pub attr: AttrAccess,
     pub tmp_name: syn::Ident,
+    /// Type of the field, `_` if unknown (i.e. for unwrapped fields)
+    pub ty: syn::Type,
 }
 
 pub struct SpanField {
@@ -127,6 +156,7 @@ pub struct VarArgs {
 pub struct Prop {
     pub field: Field,
     pub name: String,
+    pub aliases: Vec<String>,
     pub option: bool,
     pub decode: DecodeMode,
     pub flatten: bool,
@@ -148,15 +178,18 @@ pub enum ChildMode {
 pub struct Child {
     pub field: Field,
     pub name: String,
+    pub aliases: Vec<String>,
     pub option: bool,
     pub mode: ChildMode,
     pub unwrap: Option<Box<FieldAttrs>>,
     pub default: Option<Option<syn::Expr>>,
+    pub merge: Option<MergeMode>,
 }
 
 pub struct VarChildren {
     pub field: Field,
     pub unwrap: Option<Box<FieldAttrs>>,
+    pub merge: Option<MergeMode>,
 }
 
 pub enum ExtraKind {
@@ -172,6 +205,8 @@ pub struct ExtraField {
 #[derive(Clone)]
 pub struct TraitProps {
     pub span_type: Option<syn::Type>,
+    pub rename_all: RenameAll,
+    pub allow_unknown: bool,
 }
 
 pub struct Struct {
@@ -210,11 +245,13 @@ pub struct StructBuilder {
 
 pub struct NewType {
     pub ident: syn::Ident,
+    pub ty: syn::Type,
 }
 
 pub struct Variant {
     pub ident: syn::Ident,
     pub name: String,
+    pub aliases: Vec<String>,
     pub kind: VariantKind,
 }
 
@@ -223,16 +260,27 @@ pub struct Enum {
     pub trait_props: TraitProps,
     pub generics: syn::Generics,
     pub variants: Vec<Variant>,
+    pub has_skipped: bool,
 }
 
 impl TraitProps {
     fn pick_from(attrs: &mut Vec<(Attr, Span)>) -> TraitProps {
-        let mut props = TraitProps { span_type: None };
+        let mut props = TraitProps {
+            span_type: None,
+            rename_all: RenameAll::default(),
+            allow_unknown: false,
+        };
         for attr in mem::take(attrs) {
             match attr.0 {
                 Attr::SpanType(ty) => {
                     props.span_type = Some(ty);
                 }
+                Attr::RenameAll(case) => {
+                    props.rename_all = case;
+                }
+                Attr::AllowUnknown => {
+                    props.allow_unknown = true;
+                }
                 _ => attrs.push(attr),
             }
         }
@@ -246,7 +294,7 @@ fn err_pair(s1: &Field, s2: &Field, t1: &str, t2: &str) -> syn::Error {
     err
 }
 
-fn is_option(ty: &syn::Type) -> bool {
+pub fn is_option(ty: &syn::Type) -> bool {
     matches!(ty,
         syn::Type::Path(syn::TypePath {
             qself: None,
@@ -266,10 +314,34 @@ fn is_bool(ty: &syn::Type) -> bool {
     )
 }
 
+impl RenameAll {
+    /// Converts the field or variant identifier into the KDL name
+    pub fn apply(&self, ident: &syn::Ident) -> String {
+        let name = ident.unraw().to_string();
+        match self {
+            RenameAll::KebabCase => heck::ToKebabCase::to_kebab_case(&name[..]),
+            RenameAll::SnakeCase => heck::ToSnakeCase::to_snake_case(&name[..]),
+            RenameAll::CamelCase => heck::ToLowerCamelCase::to_lower_camel_case(&name[..]),
+            RenameAll::PascalCase => heck::ToUpperCamelCase::to_upper_camel_case(&name[..]),
+            RenameAll::Verbatim => name,
+        }
+    }
+}
+
 impl Variant {
-    fn new(ident: syn::Ident, _attrs: VariantAttrs, kind: VariantKind) -> syn::Result<Self> {
-        let name = heck::ToKebabCase::to_kebab_case(&ident.unraw().to_string()[..]);
-        Ok(Variant { ident, name, kind })
+    fn new(
+        ident: syn::Ident,
+        attrs: VariantAttrs,
+        kind: VariantKind,
+        rename_all: RenameAll,
+    ) -> syn::Result<Self> {
+        let name = attrs.rename.unwrap_or_else(|| rename_all.apply(&ident));
+        Ok(Variant {
+            ident,
+            name,
+            aliases: attrs.aliases,
+            kind,
+        })
     }
 }
 
@@ -289,10 +361,12 @@ impl Enum {
         }
 
         let mut variants = Vec::new();
+        let mut has_skipped = false;
         for var in src_variants {
             let mut attrs = VariantAttrs::new();
             attrs.update(parse_attr_list(&var.attrs));
             if attrs.skip {
+                has_skipped = true;
                 continue;
             }
             let kind = match var.fields {
@@ -313,20 +387,27 @@ impl Enum {
                         // parser.
                         VariantKind::Nested {
                             option: tup.extra_fields[0].option,
+                            ty: Box::new(tup.extra_fields[0].field.ty.clone()),
                         }
                     } else {
-                        VariantKind::Tuple(tup)
+                        VariantKind::Tuple(Box::new(tup))
                     }
                 }
                 syn::Fields::Unit => VariantKind::Unit,
             };
-            variants.push(Variant::new(var.ident, attrs, kind)?);
+            variants.push(Variant::new(
+                var.ident,
+                attrs,
+                kind,
+                trait_props.rename_all,
+            )?);
         }
         Ok(Enum {
             ident,
             trait_props,
             generics,
             variants,
+            has_skipped,
         })
     }
 }
@@ -375,6 +456,34 @@ impl StructBuilder {
         is_bool: bool,
         attrs: &FieldAttrs,
     ) -> syn::Result<&mut Self> {
+        if let Some((_, span)) = attrs.merge {
+            if !matches!(
+                attrs.mode,
+                Some(FieldMode::Child | FieldMode::Children { .. })
+            ) {
+                return Err(syn::Error::new(
+                    span,
+                    "merge modes are only supported on `child` and `children`",
+                ));
+            }
+        }
+        if !matches!(
+            attrs.mode,
+            Some(
+                FieldMode::Property { .. }
+                    | FieldMode::Child
+                    | FieldMode::Children { name: Some(_) }
+            )
+        ) {
+            let renamed = attrs.rename.as_ref().or_else(|| attrs.aliases.first());
+            if let Some((_, span)) = renamed {
+                return Err(syn::Error::new(
+                    *span,
+                    "`rename` and `alias` are only supported on `property`, \
+                     `child` and `children(name=...)`",
+                ));
+            }
+        }
         match &attrs.mode {
             Some(FieldMode::Argument) => {
                 if let Some(prev) = &self.var_args {
@@ -424,12 +533,18 @@ impl StructBuilder {
                         "capture all `properties` is defined here",
                     ));
                 }
-                let name = match (name, &field.attr) {
-                    (Some(name), _) => name.clone(),
-                    (None, AttrAccess::Named(name)) => {
-                        heck::ToKebabCase::to_kebab_case(&name.unraw().to_string()[..])
+                let name = match (name, &attrs.rename, &field.attr) {
+                    (Some(_), Some((_, span)), _) => {
+                        return Err(syn::Error::new(
+                            *span,
+                            "`rename` conflicts with `property(name=...)`",
+                        ));
                     }
-                    (None, AttrAccess::Indexed(_)) => {
+                    (Some(name), None, _) | (None, Some((name, _)), _) => name.clone(),
+                    (None, None, AttrAccess::Named(name)) => {
+                        self.trait_props.rename_all.apply(name)
+                    }
+                    (None, None, AttrAccess::Indexed(_)) => {
                         return Err(syn::Error::new(
                             field.span,
                             "property must be named, try \
@@ -440,6 +555,7 @@ impl StructBuilder {
                 self.properties.push(Prop {
                     field,
                     name,
+                    aliases: attrs.alias_names(),
                     option: is_option,
                     decode: attrs
                         .decode
@@ -479,11 +595,17 @@ impl StructBuilder {
                         "capture all `children` is defined here",
                     ));
                 }
-                let name = match &field.attr {
-                    AttrAccess::Named(n) => {
-                        heck::ToKebabCase::to_kebab_case(&n.unraw().to_string()[..])
-                    }
-                    AttrAccess::Indexed(_) => {
+                if let Some((MergeMode::Append, span)) = attrs.merge {
+                    return Err(syn::Error::new(
+                        span,
+                        "single `child` can't be appended, \
+                         try `merge = \"replace\"`",
+                    ));
+                }
+                let name = match (&attrs.rename, &field.attr) {
+                    (Some((name, _)), _) => name.clone(),
+                    (None, AttrAccess::Named(n)) => self.trait_props.rename_all.apply(n),
+                    (None, AttrAccess::Indexed(_)) => {
                         return Err(syn::Error::new(
                             field.span,
                             "`child` is not allowed for tuple structs",
@@ -492,6 +614,7 @@ impl StructBuilder {
                 };
                 self.children.push(Child {
                     name,
+                    aliases: attrs.alias_names(),
                     field,
                     option: is_option,
                     mode: if attrs.unwrap.is_none() && is_bool {
@@ -501,6 +624,7 @@ impl StructBuilder {
                     },
                     unwrap: attrs.unwrap.clone(),
                     default: attrs.default.clone(),
+                    merge: attrs.merge.map(|(mode, _)| mode),
                 });
             }
             Some(FieldMode::Children { name: Some(name) }) => {
@@ -513,13 +637,21 @@ impl StructBuilder {
                         "capture all `children` is defined here",
                     ));
                 }
+                if let Some((_, span)) = &attrs.rename {
+                    return Err(syn::Error::new(
+                        *span,
+                        "`rename` conflicts with `children(name=...)`",
+                    ));
+                }
                 self.children.push(Child {
                     name: name.clone(),
+                    aliases: attrs.alias_names(),
                     field,
                     option: is_option,
                     mode: ChildMode::Multi,
                     unwrap: attrs.unwrap.clone(),
                     default: attrs.default.clone(),
+                    merge: attrs.merge.map(|(mode, _)| mode),
                 });
             }
             Some(FieldMode::Children { name: None }) => {
@@ -535,6 +667,7 @@ impl StructBuilder {
                 self.var_children = Some(VarChildren {
                     field,
                     unwrap: attrs.unwrap.clone(),
+                    merge: attrs.merge.map(|(mode, _)| mode),
                 });
             }
             Some(FieldMode::Flatten(flatten)) => {
@@ -558,6 +691,7 @@ impl StructBuilder {
                     self.properties.push(Prop {
                         field: field.clone(),
                         name: "".into(), // irrelevant
+                        aliases: Vec::new(),
                         option: is_option,
                         decode: DecodeMode::Normal,
                         flatten: true,
@@ -576,11 +710,13 @@ impl StructBuilder {
                     }
                     self.children.push(Child {
                         name: "".into(), // unused
+                        aliases: Vec::new(),
                         field: field.clone(),
                         option: is_option,
                         mode: ChildMode::Flatten,
                         unwrap: None,
                         default: None,
+                        merge: None,
                     });
                 }
             }
@@ -679,7 +815,10 @@ impl Parse for Definition {
                         && tup.extra_fields.len() == 1
                         && matches!(tup.extra_fields[0].kind, ExtraKind::Auto)
                     {
-                        Ok(Definition::NewType(NewType { ident: item.ident }))
+                        Ok(Definition::NewType(NewType {
+                            ident: item.ident,
+                            ty: tup.extra_fields[0].field.ty.clone(),
+                        }))
                     } else {
                         Ok(Definition::TupleStruct(tup))
                     }
@@ -710,6 +849,9 @@ impl FieldAttrs {
             decode: None,
             unwrap: None,
             default: None,
+            merge: None,
+            rename: None,
+            aliases: Vec::new(),
         }
     }
     fn update(&mut self, attrs: impl IntoIterator<Item = (Attr, Span)>) {
@@ -749,11 +891,28 @@ impl FieldAttrs {
                     }
                     self.default = Some(value);
                 }
+                Merge(mode) => {
+                    if self.merge.is_some() {
+                        emit_error!(span, "only single merge mode is allowed");
+                    }
+                    self.merge = Some((mode, span));
+                }
+                Rename(name) => {
+                    if self.rename.is_some() {
+                        emit_error!(span, "only single `rename` is allowed");
+                    }
+                    self.rename = Some((name, span));
+                }
+                Alias(name) => self.aliases.push((name, span)),
                 _ => emit_error!(span, "this attribute is not supported on fields"),
             }
         }
     }
 
+    fn alias_names(&self) -> Vec<String> {
+        self.aliases.iter().map(|(name, _)| name.clone()).collect()
+    }
+
     fn no_decode(&self, element: &str) {
         if let Some((mode, span)) = self.decode.as_ref() {
             if self.unwrap.is_some() {
@@ -770,22 +929,33 @@ impl FieldAttrs {
 }
 
 impl VariantAttrs {
-    fn new() -> VariantAttrs {
-        VariantAttrs { skip: false }
+    pub fn new() -> VariantAttrs {
+        VariantAttrs {
+            skip: false,
+            rename: None,
+            aliases: Vec::new(),
+        }
     }
-    fn update(&mut self, attrs: impl IntoIterator<Item = (Attr, Span)>) {
+    pub fn update(&mut self, attrs: impl IntoIterator<Item = (Attr, Span)>) {
         use Attr::*;
 
         for (attr, span) in attrs {
             match attr {
                 Skip => self.skip = true,
+                Rename(name) => {
+                    if self.rename.is_some() {
+                        emit_error!(span, "only single `rename` is allowed");
+                    }
+                    self.rename = Some(name);
+                }
+                Alias(name) => self.aliases.push(name),
                 _ => emit_error!(span, "not supported on enum variants"),
             }
         }
     }
 }
 
-fn parse_attr_list(attrs: &[syn::Attribute]) -> Vec<(Attr, Span)> {
+pub fn parse_attr_list(attrs: &[syn::Attribute]) -> Vec<(Attr, Span)> {
     let mut all = Vec::new();
     for attr in attrs {
         if matches!(attr.style, syn::AttrStyle::Outer) && attr.path().is_ident("knus") {
@@ -906,6 +1076,47 @@ impl Attr {
         } else if lookahead.peek(kw::type_name) {
             let _kw: kw::type_name = input.parse()?;
             Ok(Attr::FieldMode(FieldMode::TypeName))
+        } else if lookahead.peek(kw::merge) {
+            let _kw: kw::merge = input.parse()?;
+            let _eq: syn::Token![=] = input.parse()?;
+            let mode: syn::LitStr = input.parse()?;
+            match &mode.value()[..] {
+                "append" => Ok(Attr::Merge(MergeMode::Append)),
+                "replace" => Ok(Attr::Merge(MergeMode::Replace)),
+                _ => Err(syn::Error::new(
+                    mode.span(),
+                    "expected `\"append\"` or `\"replace\"`",
+                )),
+            }
+        } else if lookahead.peek(kw::allow_unknown) {
+            let _kw: kw::allow_unknown = input.parse()?;
+            Ok(Attr::AllowUnknown)
+        } else if lookahead.peek(kw::rename_all) {
+            let _kw: kw::rename_all = input.parse()?;
+            let _eq: syn::Token![=] = input.parse()?;
+            let case: syn::LitStr = input.parse()?;
+            match &case.value()[..] {
+                "kebab-case" => Ok(Attr::RenameAll(RenameAll::KebabCase)),
+                "snake_case" => Ok(Attr::RenameAll(RenameAll::SnakeCase)),
+                "camelCase" => Ok(Attr::RenameAll(RenameAll::CamelCase)),
+                "PascalCase" => Ok(Attr::RenameAll(RenameAll::PascalCase)),
+                "verbatim" => Ok(Attr::RenameAll(RenameAll::Verbatim)),
+                _ => Err(syn::Error::new(
+                    case.span(),
+                    "expected `\"kebab-case\"`, `\"snake_case\"`, `\"camelCase\"`, \
+                     `\"PascalCase\"` or `\"verbatim\"`",
+                )),
+            }
+        } else if lookahead.peek(kw::rename) {
+            let _kw: kw::rename = input.parse()?;
+            let _eq: syn::Token![=] = input.parse()?;
+            let name: syn::LitStr = input.parse()?;
+            Ok(Attr::Rename(name.value()))
+        } else if lookahead.peek(kw::alias) {
+            let _kw: kw::alias = input.parse()?;
+            let _eq: syn::Token![=] = input.parse()?;
+            let name: syn::LitStr = input.parse()?;
+            Ok(Attr::Alias(name.value()))
         } else if lookahead.peek(kw::span_type) {
             let _kw: kw::span_type = input.parse()?;
             let _eq: syn::Token![=] = input.parse()?;
@@ -938,6 +1149,9 @@ impl Field {
             span: name.span(),
             attr: AttrAccess::Named(name.clone()),
             tmp_name: name.clone(),
+            ty: syn::Type::Infer(syn::TypeInfer {
+                underscore_token: Default::default(),
+            }),
         }
     }
     fn new(field: &syn::Field, idx: usize) -> Field {
@@ -948,11 +1162,13 @@ impl Field {
                 span: field.span(),
                 attr: AttrAccess::Named(id.clone()),
                 tmp_name: id.clone(),
+                ty: field.ty.clone(),
             })
             .unwrap_or_else(|| Field {
                 span: field.span(),
                 attr: AttrAccess::Indexed(idx),
                 tmp_name: syn::Ident::new(&format!("field{}", idx), Span::mixed_site()),
+                ty: field.ty.clone(),
             })
     }
     pub fn as_token_stream(&self) -> TokenStream {
@@ -0,0 +1,527 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+
+use crate::definition::{ChildMode, DecodeMode, Enum, Field, FieldAttrs, NewType};
+use crate::definition::{Struct, StructBuilder, VariantKind};
+
+pub(crate) struct Common<'a> {
+    pub object: &'a Struct,
+    pub node: &'a syn::Ident,
+    pub ctx: &'a syn::Ident,
+}
+
+fn used_fields(s: &Struct) -> Vec<&Field> {
+    let mut res = Vec::new();
+    res.extend(s.node_names.iter().map(|f| &f.field));
+    res.extend(s.type_names.iter().map(|f| &f.field));
+    res.extend(s.arguments.iter().map(|a| &a.field));
+    res.extend(s.var_args.iter().map(|a| &a.field));
+    res.extend(s.properties.iter().map(|p| &p.field));
+    res.extend(s.var_props.iter().map(|p| &p.field));
+    res.extend(s.children.iter().map(|c| &c.field));
+    res.extend(s.var_children.iter().map(|c| &c.field));
+    let mut seen = Vec::<&syn::Ident>::new();
+    res.retain(|f| {
+        if seen.contains(&&f.tmp_name) {
+            false
+        } else {
+            seen.push(&f.tmp_name);
+            true
+        }
+    });
+    res
+}
+
+/// Pattern that binds fields of the struct (or enum variant) to temporary
+/// names by reference
+fn bind_fields(s: &Struct, path: TokenStream, named: bool) -> TokenStream {
+    let used = used_fields(s);
+    if named {
+        let pairs = used.iter().map(|f| f.as_assign_pair().unwrap());
+        quote!(#path { #(#pairs,)* .. })
+    } else {
+        let total = s
+            .all_fields()
+            .iter()
+            .filter_map(|f| f.as_index())
+            .max()
+            .map(|idx| idx + 1)
+            .unwrap_or(0);
+        let items = (0..total).map(|idx| {
+            if let Some(f) = used.iter().find(|f| f.as_index() == Some(idx)) {
+                let name = &f.tmp_name;
+                quote!(#name)
+            } else {
+                quote!(_)
+            }
+        });
+        quote!(#path(#(#items),*))
+    }
+}
+
+pub fn emit_struct(s: &Struct, named: bool) -> syn::Result<TokenStream> {
+    let s_name = &s.ident;
+    let node = syn::Ident::new("node", Span::mixed_site());
+    let ctx = syn::Ident::new("ctx", Span::mixed_site());
+    let (impl_gen, type_gen, bounds) = s.generics.split_for_impl();
+
+    let common = Common {
+        object: s,
+        node: &node,
+        ctx: &ctx,
+    };
+    let pattern = bind_fields(s, quote!(#s_name), named);
+    let encode_fields = encode_fields(&common)?;
+
+    let mut extra_traits = Vec::new();
+    if !s.has_arguments
+        && !s.has_properties
+        && s.spans.is_empty()
+        && s.node_names.is_empty()
+        && s.type_names.is_empty()
+    {
+        let nodes = syn::Ident::new("nodes", Span::mixed_site());
+        extra_traits.push(quote! {
+            impl #impl_gen ::knus::traits::EncodeChildren for #s_name #type_gen
+                #bounds
+            {
+                fn encode_children<S>(&self,
+                    #nodes: &mut Vec<::knus::ast::SpannedNode<S>>,
+                    #ctx: &mut ::knus::encode::Context)
+                    -> ::std::result::Result<(), ::knus::errors::EncodeError>
+                    where S: ::knus::traits::ErrorSpan + ::std::default::Default
+                {
+                    let mut #node = ::knus::ast::Node::new("");
+                    ::knus::Encode::encode_node(self, &mut #node, #ctx)?;
+                    if let Some(children) = #node.children {
+                        #nodes.extend(children.into_inner());
+                    }
+                    Ok(())
+                }
+            }
+        });
+    }
+    Ok(quote! {
+        #(#extra_traits)*
+        impl #impl_gen ::knus::Encode for #s_name #type_gen
+            #bounds
+        {
+            fn encode_node<S>(&self,
+                #node: &mut ::knus::ast::Node<S>,
+                #ctx: &mut ::knus::encode::Context)
+                -> ::std::result::Result<(), ::knus::errors::EncodeError>
+                where S: ::knus::traits::ErrorSpan + ::std::default::Default
+            {
+                let #pattern = self;
+                #encode_fields
+                Ok(())
+            }
+        }
+    })
+}
+
+pub fn emit_new_type(s: &NewType) -> syn::Result<TokenStream> {
+    let s_name = &s.ident;
+    let node = syn::Ident::new("node", Span::mixed_site());
+    let ctx = syn::Ident::new("ctx", Span::mixed_site());
+    Ok(quote! {
+        impl ::knus::Encode for #s_name {
+            fn encode_node<S>(&self,
+                #node: &mut ::knus::ast::Node<S>,
+                #ctx: &mut ::knus::encode::Context)
+                -> ::std::result::Result<(), ::knus::errors::EncodeError>
+                where S: ::knus::traits::ErrorSpan + ::std::default::Default
+            {
+                if let Some(value) = &self.0 {
+                    ::knus::Encode::encode_node(value, #node, #ctx)?;
+                }
+                Ok(())
+            }
+        }
+    })
+}
+
+pub fn emit_enum(e: &Enum) -> syn::Result<TokenStream> {
+    let e_name = &e.ident;
+    let node = syn::Ident::new("node", Span::mixed_site());
+    let ctx = syn::Ident::new("ctx", Span::mixed_site());
+    let value = syn::Ident::new("value", Span::mixed_site());
+    let (impl_gen, type_gen, bounds) = e.generics.split_for_impl();
+
+    let mut branches = Vec::with_capacity(e.variants.len());
+    for var in &e.variants {
+        let name = &var.name;
+        let variant_name = &var.ident;
+        let set_name = quote! {
+            #node.node_name = ::knus::span::Spanned::new(
+                #name.into(), ::std::default::Default::default());
+        };
+        match &var.kind {
+            VariantKind::Unit => {
+                branches.push(quote! {
+                    #e_name::#variant_name => {
+                        #set_name
+                        Ok(())
+                    }
+                });
+            }
+            VariantKind::Nested { option: false, .. } => {
+                branches.push(quote! {
+                    #e_name::#variant_name(#value) => {
+                        #set_name
+                        ::knus::Encode::encode_node(#value, #node, #ctx)
+                    }
+                });
+            }
+            VariantKind::Nested { option: true, .. } => {
+                branches.push(quote! {
+                    #e_name::#variant_name(#value) => {
+                        #set_name
+                        if let Some(#value) = #value {
+                            ::knus::Encode::encode_node(#value, #node, #ctx)?;
+                        }
+                        Ok(())
+                    }
+                });
+            }
+            VariantKind::Tuple(s) => {
+                let common = Common {
+                    object: s,
+                    node: &node,
+                    ctx: &ctx,
+                };
+                let pattern = bind_fields(s, quote!(#e_name::#variant_name), false);
+                let encode_fields = encode_fields(&common)?;
+                branches.push(quote! {
+                    #pattern => {
+                        #set_name
+                        #encode_fields
+                        Ok(())
+                    }
+                });
+            }
+            VariantKind::Named => unimplemented!(),
+        }
+    }
+    if e.has_skipped {
+        branches.push(quote! {
+            _ => Err(::knus::errors::EncodeError::unsupported(
+                "skipped enum variant cannot be encoded")),
+        });
+    }
+    Ok(quote! {
+        impl #impl_gen ::knus::Encode for #e_name #type_gen
+            #bounds
+        {
+            fn encode_node<S>(&self,
+                #node: &mut ::knus::ast::Node<S>,
+                #ctx: &mut ::knus::encode::Context)
+                -> ::std::result::Result<(), ::knus::errors::EncodeError>
+                where S: ::knus::traits::ErrorSpan + ::std::default::Default
+            {
+                match self {
+                    #(#branches)*
+                }
+            }
+        }
+    })
+}
+
+/// Expression that encodes a reference to the value into `Value<S>`
+fn encode_value(val: &syn::Ident, ctx: &syn::Ident, mode: &DecodeMode) -> TokenStream {
+    match mode {
+        DecodeMode::Normal => quote! {
+            ::knus::traits::EncodeScalar::encode(#val, #ctx)?
+        },
+        DecodeMode::Str => quote! {
+            ::knus::ast::Value::new(::knus::ast::Literal::String(
+                ::std::string::ToString::to_string(#val).into()))
+        },
+        DecodeMode::Bytes => quote! {
+            ::knus::encode::bytes(::std::convert::AsRef::<[u8]>::as_ref(#val), #ctx)?
+        },
+    }
+}
+
+fn null_value() -> TokenStream {
+    quote!(::knus::ast::Value::new(::knus::ast::Literal::Null))
+}
+
+fn encode_fields(s: &Common) -> syn::Result<TokenStream> {
+    let specials = encode_specials(s);
+    let args = encode_args(s);
+    let props = encode_props(s);
+    let children = encode_children(s)?;
+    Ok(quote! {
+        #specials
+        #args
+        #props
+        #children
+    })
+}
+
+fn encode_specials(s: &Common) -> TokenStream {
+    let node = s.node;
+    let node_names = s.object.node_names.iter().map(|node_name| {
+        let fld = &node_name.field.tmp_name;
+        quote! {
+            #node.node_name = ::knus::span::Spanned::new(
+                ::std::string::ToString::to_string(#fld).into(),
+                ::std::default::Default::default());
+        }
+    });
+    let type_names = s.object.type_names.iter().map(|type_name| {
+        let fld = &type_name.field.tmp_name;
+        if type_name.option {
+            quote! {
+                #node.type_name = #fld.as_ref().map(|tn| {
+                    ::knus::encode::type_name(
+                        &::std::string::ToString::to_string(tn))
+                });
+            }
+        } else {
+            quote! {
+                #node.type_name = Some(::knus::encode::type_name(
+                    &::std::string::ToString::to_string(#fld)));
+            }
+        }
+    });
+    quote! {
+        #(#node_names)*
+        #(#type_names)*
+    }
+}
+
+fn encode_args(s: &Common) -> TokenStream {
+    let node = s.node;
+    let ctx = s.ctx;
+    let val = syn::Ident::new("val", Span::mixed_site());
+    let keep = syn::Ident::new("keep_args", Span::mixed_site());
+    // Trailing optional arguments that are `None` are omitted, but `null` is
+    // written if there is something after them to keep positions
+    let trim = s
+        .object
+        .arguments
+        .iter()
+        .any(|arg| arg.option && arg.default.is_none());
+    let mut encoder = Vec::new();
+    for arg in &s.object.arguments {
+        let fld = &arg.field.tmp_name;
+        let value = encode_value(&val, ctx, &arg.decode);
+        let update_keep = trim.then(|| quote!(#keep = #node.arguments.len();));
+        if arg.option {
+            let null_value = null_value();
+            // `null` written in place of the default value must be kept
+            let on_none_keep = arg.default.as_ref().and(update_keep.as_ref());
+            let on_none = quote! {
+                #node.arguments.push(#null_value);
+                #on_none_keep
+            };
+            encoder.push(quote! {
+                if let Some(#val) = #fld {
+                    #node.arguments.push(#value);
+                    #update_keep
+                } else {
+                    #on_none
+                }
+            });
+        } else {
+            encoder.push(quote! {
+                let #val = #fld;
+                #node.arguments.push(#value);
+                #update_keep
+            });
+        }
+    }
+    if let Some(var_args) = &s.object.var_args {
+        let fld = &var_args.field.tmp_name;
+        let value = encode_value(&val, ctx, &var_args.decode);
+        let update_keep = trim.then(|| quote!(#keep = #node.arguments.len();));
+        encoder.push(quote! {
+            for #val in #fld {
+                #node.arguments.push(#value);
+                #update_keep
+            }
+        });
+    }
+    if trim {
+        quote! {
+            let mut #keep = #node.arguments.len();
+            #(#encoder)*
+            #node.arguments.truncate(#keep);
+        }
+    } else {
+        quote!(#(#encoder)*)
+    }
+}
+
+fn encode_props(s: &Common) -> TokenStream {
+    let node = s.node;
+    let ctx = s.ctx;
+    let val = syn::Ident::new("val", Span::mixed_site());
+    let key = syn::Ident::new("key", Span::mixed_site());
+    let mut encoder = Vec::new();
+    for prop in &s.object.properties {
+        let fld = &prop.field.tmp_name;
+        let prop_name = &prop.name;
+        if prop.flatten {
+            encoder.push(quote! {
+                ::knus::Encode::encode_node(#fld, #node, #ctx)?;
+            });
+            continue;
+        }
+        let value = encode_value(&val, ctx, &prop.decode);
+        let insert = |value: TokenStream| {
+            quote! {
+                #node.properties.insert(
+                    ::knus::span::Spanned::new(
+                        #prop_name.into(),
+                        ::std::default::Default::default()),
+                    #value);
+            }
+        };
+        if prop.option {
+            let insert_value = insert(value);
+            // `null` is only written when needed to override the default
+            let on_none = prop.default.as_ref().map(|_| insert(null_value()));
+            encoder.push(quote! {
+                if let Some(#val) = #fld {
+                    #insert_value
+                } else {
+                    #on_none
+                }
+            });
+        } else {
+            let insert_value = insert(value);
+            encoder.push(quote! {
+                let #val = #fld;
+                #insert_value
+            });
+        }
+    }
+    if let Some(var_props) = &s.object.var_props {
+        let fld = &var_props.field.tmp_name;
+        let value = encode_value(&val, ctx, &var_props.decode);
+        encoder.push(quote! {
+            for (#key, #val) in #fld {
+                #node.properties.insert(
+                    ::knus::span::Spanned::new(
+                        ::std::string::ToString::to_string(#key).into(),
+                        ::std::default::Default::default()),
+                    #value);
+            }
+        });
+    }
+    quote!(#(#encoder)*)
+}
+
+fn encode_child(
+    s: &Common,
+    fld: &syn::Ident,
+    name: &str,
+    unwrap: Option<&FieldAttrs>,
+) -> syn::Result<TokenStream> {
+    let node = s.node;
+    let ctx = s.ctx;
+    let child = syn::Ident::new("child", Span::mixed_site());
+    let encode = if let Some(attrs) = unwrap {
+        let mut bld = StructBuilder::new(
+            fld.clone(),
+            s.object.trait_props.clone(),
+            s.object.generics.clone(),
+        );
+        bld.add_field(Field::new_named(fld), false, false, attrs)?;
+        let object = bld.build();
+        let common = Common {
+            object: &object,
+            node: &child,
+            ctx,
+        };
+        encode_fields(&common)?
+    } else {
+        quote! {
+            ::knus::Encode::encode_node(#fld, &mut #child, #ctx)?;
+        }
+    };
+    Ok(quote! {
+        let mut #child = ::knus::ast::Node::new(#name);
+        #encode
+        #node.push_child(#child);
+    })
+}
+
+fn encode_children(s: &Common) -> syn::Result<TokenStream> {
+    let node = s.node;
+    let ctx = s.ctx;
+    let mut encoder = Vec::new();
+    for child_def in &s.object.children {
+        let fld = &child_def.field.tmp_name;
+        let child_name = &child_def.name;
+        let unwrap = child_def.unwrap.as_deref();
+        match child_def.mode {
+            ChildMode::Flatten => {
+                let done_as_property = s
+                    .object
+                    .properties
+                    .iter()
+                    .any(|p| p.flatten && p.field.tmp_name == *fld);
+                if !done_as_property {
+                    encoder.push(quote! {
+                        ::knus::Encode::encode_node(#fld, #node, #ctx)?;
+                    });
+                }
+            }
+            ChildMode::Bool => {
+                encoder.push(quote! {
+                    if *#fld {
+                        #node.push_child(::knus::ast::Node::new(#child_name));
+                    }
+                });
+            }
+            ChildMode::Normal => {
+                let encode = encode_child(s, fld, child_name, unwrap)?;
+                if child_def.option {
+                    encoder.push(quote! {
+                        if let Some(#fld) = #fld {
+                            #encode
+                        }
+                    });
+                } else {
+                    encoder.push(quote! {
+                        {
+                            #encode
+                        }
+                    });
+                }
+            }
+            ChildMode::Multi => {
+                let encode = encode_child(s, fld, child_name, unwrap)?;
+                if child_def.option {
+                    encoder.push(quote! {
+                        if let Some(#fld) = #fld {
+                            for #fld in #fld {
+                                #encode
+                            }
+                        }
+                    });
+                } else {
+                    encoder.push(quote! {
+                        for #fld in #fld {
+                            #encode
+                        }
+                    });
+                }
+            }
+        }
+    }
+    if let Some(var_children) = &s.object.var_children {
+        let fld = &var_children.field.tmp_name;
+        let encode = encode_child(s, fld, "-", var_children.unwrap.as_deref())?;
+        encoder.push(quote! {
+            for #fld in #fld {
+                #encode
+            }
+        });
+    }
+    Ok(quote!(#(#encoder)*))
+}
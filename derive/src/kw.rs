@@ -1,3 +1,5 @@
+syn::custom_keyword!(alias);
+syn::custom_keyword!(allow_unknown);
 syn::custom_keyword!(argument);
 syn::custom_keyword!(arguments);
 syn::custom_keyword!(bytes);
@@ -5,10 +7,13 @@ syn::custom_keyword!(child);
 syn::custom_keyword!(children);
 syn::custom_keyword!(default);
 syn::custom_keyword!(flatten);
+syn::custom_keyword!(merge);
 syn::custom_keyword!(name);
 syn::custom_keyword!(node_name);
 syn::custom_keyword!(properties);
 syn::custom_keyword!(property);
+syn::custom_keyword!(rename);
+syn::custom_keyword!(rename_all);
 syn::custom_keyword!(skip);
 syn::custom_keyword!(span);
 syn::custom_keyword!(span_type);
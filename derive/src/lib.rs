@@ -1,13 +1,16 @@
 use proc_macro2::TokenStream;
 
 mod definition;
+mod encode;
 mod kw;
+mod merge;
 mod node;
 mod scalar;
+mod schema;
 mod variants;
 
 use definition::Definition;
-use scalar::{emit_scalar, Scalar};
+use scalar::{emit_encode_scalar, emit_scalar, Scalar};
 
 fn emit_decoder(def: &Definition) -> syn::Result<TokenStream> {
     match def {
@@ -19,6 +22,16 @@ fn emit_decoder(def: &Definition) -> syn::Result<TokenStream> {
     }
 }
 
+fn emit_encoder(def: &Definition) -> syn::Result<TokenStream> {
+    match def {
+        Definition::Struct(s) => encode::emit_struct(s, true),
+        Definition::NewType(s) => encode::emit_new_type(s),
+        Definition::TupleStruct(s) => encode::emit_struct(s, false),
+        Definition::UnitStruct(s) => encode::emit_struct(s, true),
+        Definition::Enum(e) => encode::emit_enum(e),
+    }
+}
+
 #[proc_macro_error2::proc_macro_error]
 #[proc_macro_derive(Decode, attributes(knus))]
 #[doc = include_str!("../derive_decode.md")]
@@ -40,3 +53,67 @@ pub fn decode_scalar_derive(input: proc_macro::TokenStream) -> proc_macro::Token
         Err(e) => e.to_compile_error().into(),
     }
 }
+
+fn emit_schema(def: &Definition) -> syn::Result<TokenStream> {
+    match def {
+        Definition::Struct(s) => schema::emit_struct(s),
+        Definition::NewType(s) => schema::emit_new_type(s),
+        Definition::TupleStruct(s) => schema::emit_struct(s),
+        Definition::UnitStruct(s) => schema::emit_struct(s),
+        Definition::Enum(e) => schema::emit_enum(e),
+    }
+}
+
+#[proc_macro_error2::proc_macro_error]
+#[proc_macro_derive(DecodeSchema, attributes(knus))]
+#[doc = include_str!("../derive_decode_schema.md")]
+pub fn decode_schema_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let item = syn::parse_macro_input!(input as Definition);
+    match emit_schema(&item) {
+        Ok(stream) => stream.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn emit_merge(def: &Definition) -> syn::Result<TokenStream> {
+    match def {
+        Definition::Struct(s) => merge::emit_struct(s),
+        Definition::NewType(s) => merge::emit_new_type(s),
+        Definition::TupleStruct(s) => merge::emit_struct(s),
+        Definition::UnitStruct(s) => merge::emit_struct(s),
+        Definition::Enum(e) => merge::emit_enum(e),
+    }
+}
+
+#[proc_macro_error2::proc_macro_error]
+#[proc_macro_derive(Merge, attributes(knus))]
+#[doc = include_str!("../derive_merge.md")]
+pub fn merge_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let item = syn::parse_macro_input!(input as Definition);
+    match emit_merge(&item) {
+        Ok(stream) => stream.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+#[proc_macro_error2::proc_macro_error]
+#[proc_macro_derive(Encode, attributes(knus))]
+#[doc = include_str!("../derive_encode.md")]
+pub fn encode_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let item = syn::parse_macro_input!(input as Definition);
+    match emit_encoder(&item) {
+        Ok(stream) => stream.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+#[proc_macro_error2::proc_macro_error]
+#[proc_macro_derive(EncodeScalar, attributes(knus))]
+#[doc = include_str!("../derive_encode_scalar.md")]
+pub fn encode_scalar_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let item = syn::parse_macro_input!(input as Scalar);
+    match emit_encode_scalar(&item) {
+        Ok(stream) => stream.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
@@ -0,0 +1,175 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+
+use crate::definition::{ChildMode, Enum, MergeMode, NewType, Struct};
+use crate::node;
+use crate::schema::strip_option;
+
+/// Returns generics of the impl with the span type `S` added
+fn impl_generics(generics: &syn::Generics) -> syn::Generics {
+    let mut generics = generics.clone();
+    if generics.params.is_empty() {
+        generics.lt_token = Some(Default::default());
+        generics.gt_token = Some(Default::default());
+    }
+    generics.params.push(syn::parse2(quote!(S)).unwrap());
+    generics
+}
+
+pub fn emit_struct(s: &Struct) -> syn::Result<TokenStream> {
+    let s_name = &s.ident;
+    let (_, type_gen, _) = s.generics.split_for_impl();
+    let generics = impl_generics(&s.generics);
+    let (impl_gen, _, bounds) = generics.split_for_impl();
+    let base = syn::Ident::new("base", Span::mixed_site());
+    let child = syn::Ident::new("child", Span::mixed_site());
+    let layer = syn::Ident::new("layer", Span::mixed_site());
+
+    let mut arms = Vec::new();
+    let mut flatten = Vec::new();
+    let mut replaced = Vec::new();
+    let mut names = Vec::new();
+    for ch in &s.children {
+        let name = node::name_pattern(&ch.name, &ch.aliases);
+        let all_names = node::name_list(&ch.name, &ch.aliases);
+        let merge = match ch.mode {
+            ChildMode::Flatten => {
+                flatten.push(&ch.field.ty);
+                continue;
+            }
+            ChildMode::Normal if ch.unwrap.is_none() && ch.merge.is_none() => {
+                let ty = strip_option(&ch.field.ty);
+                quote!(::knus::merge::merge_named::<#ty, S>(#base, #child, #all_names))
+            }
+            ChildMode::Normal | ChildMode::Bool => {
+                quote!(::knus::merge::replace_named(#base, #child, #all_names))
+            }
+            ChildMode::Multi => {
+                if ch.merge == Some(MergeMode::Replace) {
+                    replaced.push(quote! {
+                        ::knus::merge::replace_collection(#base, #layer, |name| matches!(name, #name));
+                    });
+                }
+                quote!(Some(#child))
+            }
+        };
+        arms.push(quote!(#name => #merge,));
+        names.push(name);
+    }
+    if let Some(var_children) = &s.var_children {
+        if var_children.merge == Some(MergeMode::Replace) {
+            if !flatten.is_empty() {
+                return Err(syn::Error::new(
+                    var_children.field.span,
+                    "`merge = \"replace\"` on `children` is not supported \
+                     together with `flatten(child)`",
+                ));
+            }
+            let contains = if names.is_empty() {
+                quote!(|_| true)
+            } else {
+                quote!(|name| !matches!(name, #(#names)|*))
+            };
+            replaced.push(quote! {
+                ::knus::merge::replace_collection(#base, #layer, #contains);
+            });
+        }
+    }
+    let mut merge_child = Vec::new();
+    let mut remove_replaced = replaced;
+    for ty in flatten {
+        merge_child.push(quote! {
+            let #child = <#ty as ::knus::traits::Merge<S>>::merge_child(#base, #child)?;
+        });
+        remove_replaced.push(quote! {
+            <#ty as ::knus::traits::Merge<S>>::remove_replaced(#base, #layer);
+        });
+    }
+    let mut methods = Vec::new();
+    if !arms.is_empty() || !merge_child.is_empty() {
+        methods.push(quote! {
+            fn merge_child(
+                #base: &mut Vec<::knus::ast::SpannedNode<S>>,
+                #child: ::knus::ast::SpannedNode<S>,
+            ) -> Option<::knus::ast::SpannedNode<S>> {
+                match &**#child.node_name {
+                    #(#arms)*
+                    _ => {
+                        #(#merge_child)*
+                        Some(#child)
+                    }
+                }
+            }
+        });
+    }
+    if !remove_replaced.is_empty() {
+        methods.push(quote! {
+            fn remove_replaced(
+                #base: &mut Vec<::knus::ast::SpannedNode<S>>,
+                #layer: &[::knus::ast::SpannedNode<S>],
+            ) {
+                #(#remove_replaced)*
+            }
+        });
+    }
+    Ok(quote! {
+        impl #impl_gen ::knus::traits::Merge<S> for #s_name #type_gen
+            #bounds
+        {
+            #(#methods)*
+        }
+    })
+}
+
+pub fn emit_new_type(s: &NewType) -> syn::Result<TokenStream> {
+    let s_name = &s.ident;
+    let ty = strip_option(&s.ty);
+    Ok(quote! {
+        impl<S> ::knus::traits::Merge<S> for #s_name {
+            fn merge_node(
+                base: &mut ::knus::ast::SpannedNode<S>,
+                layer: ::knus::ast::SpannedNode<S>,
+            ) {
+                <#ty as ::knus::traits::Merge<S>>::merge_node(base, layer)
+            }
+            fn merge_children(
+                base: &mut Vec<::knus::ast::SpannedNode<S>>,
+                layer: Vec<::knus::ast::SpannedNode<S>>,
+            ) {
+                <#ty as ::knus::traits::Merge<S>>::merge_children(base, layer)
+            }
+            fn merge_child(
+                base: &mut Vec<::knus::ast::SpannedNode<S>>,
+                child: ::knus::ast::SpannedNode<S>,
+            ) -> Option<::knus::ast::SpannedNode<S>> {
+                <#ty as ::knus::traits::Merge<S>>::merge_child(base, child)
+            }
+            fn remove_replaced(
+                base: &mut Vec<::knus::ast::SpannedNode<S>>,
+                layer: &[::knus::ast::SpannedNode<S>],
+            ) {
+                <#ty as ::knus::traits::Merge<S>>::remove_replaced(base, layer)
+            }
+        }
+    })
+}
+
+pub fn emit_enum(e: &Enum) -> syn::Result<TokenStream> {
+    let e_name = &e.ident;
+    let (_, type_gen, _) = e.generics.split_for_impl();
+    let generics = impl_generics(&e.generics);
+    let (impl_gen, _, bounds) = generics.split_for_impl();
+    // variant of the node may change, so it's never merged
+    Ok(quote! {
+        impl #impl_gen ::knus::traits::Merge<S> for #e_name #type_gen
+            #bounds
+        {
+            fn merge_node(
+                base: &mut ::knus::ast::SpannedNode<S>,
+                layer: ::knus::ast::SpannedNode<S>,
+            ) {
+                ::knus::merge::replace_node(base, layer)
+            }
+        }
+    })
+}
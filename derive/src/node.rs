@@ -9,6 +9,50 @@ pub(crate) struct Common<'a> {
     pub object: &'a Struct,
     pub ctx: &'a syn::Ident,
     pub span_type: &'a TokenStream,
+    pub ast: &'a Ast,
+}
+
+/// Paths to the syntax tree and the traits the decoder is generated for
+///
+/// Types having lifetime parameters are decoded from the
+/// `::knus::borrowed` tree, others from `::knus::ast`.
+pub(crate) struct Ast {
+    /// Module of the tree types
+    pub module: TokenStream,
+    /// Module of the `Decode*` traits
+    pub traits: TokenStream,
+    /// Module of the `bytes` and `check_flag_node` helpers
+    pub helpers: TokenStream,
+    /// Lifetime argument of the tree types and traits (with a comma)
+    pub lifetime: TokenStream,
+}
+
+impl Ast {
+    /// Returns the tree for the type and adds the lifetime of the tree to
+    /// the `generics` of the impl if needed
+    pub fn for_generics(generics: &mut syn::Generics) -> Ast {
+        let lifetimes = generics
+            .lifetimes()
+            .map(|l| l.lifetime.clone())
+            .collect::<Vec<_>>();
+        if lifetimes.is_empty() {
+            return Ast {
+                module: quote!(::knus::ast),
+                traits: quote!(::knus::traits),
+                helpers: quote!(::knus::decode),
+                lifetime: quote!(),
+            };
+        }
+        generics
+            .params
+            .insert(0, syn::parse2(quote!('__knus: #(#lifetimes)+*)).unwrap());
+        Ast {
+            module: quote!(::knus::borrowed),
+            traits: quote!(::knus::borrowed),
+            helpers: quote!(::knus::borrowed),
+            lifetime: quote!('__knus,),
+        }
+    }
 }
 
 fn child_can_partial(child: &Child) -> bool {
@@ -40,13 +84,21 @@ pub fn emit_struct(s: &Struct, named: bool) -> syn::Result<TokenStream> {
             .predicates
             .push(syn::parse2(quote!(S: ::knus::traits::ErrorSpan)).unwrap());
     };
-    let trait_gen = quote!(<#span_ty>);
+    let ast = Ast::for_generics(&mut common_generics);
+    let Ast {
+        module,
+        traits,
+        lifetime,
+        ..
+    } = &ast;
+    let trait_gen = quote!(<#lifetime #span_ty>);
     let (impl_gen, _, bounds) = common_generics.split_for_impl();
 
     let common = Common {
         object: s,
         ctx: &ctx,
         span_type: &span_ty,
+        ast: &ast,
     };
 
     let decode_specials = decode_specials(&common, &node)?;
@@ -87,20 +139,20 @@ pub fn emit_struct(s: &Struct, named: bool) -> syn::Result<TokenStream> {
         let insert_child = insert_child(&common, &node)?;
         let insert_property = insert_property(&common, &name, &value)?;
         extra_traits.push(quote! {
-            impl #impl_gen ::knus::traits::DecodePartial #trait_gen
+            impl #impl_gen #traits::DecodePartial #trait_gen
                 for #s_name #type_gen
                 #bounds
             {
                 fn insert_child(&mut self,
-                    #node: &::knus::ast::SpannedNode<#span_ty>,
+                    #node: &#module::SpannedNode<#lifetime #span_ty>,
                     #ctx: &mut ::knus::decode::Context<#span_ty>)
                     -> ::std::result::Result<bool, ::knus::errors::DecodeError<#span_ty>>
                 {
                     #insert_child
                 }
                 fn insert_property(&mut self,
-                    #name: &::knus::span::Spanned<Box<str>, #span_ty>,
-                    #value: &::knus::ast::Value<#span_ty>,
+                    #name: &#module::SpannedName<#lifetime #span_ty>,
+                    #value: &#module::Value<#lifetime #span_ty>,
                     #ctx: &mut ::knus::decode::Context<#span_ty>)
                     -> ::std::result::Result<bool, ::knus::errors::DecodeError<#span_ty>>
                 {
@@ -117,12 +169,12 @@ pub fn emit_struct(s: &Struct, named: bool) -> syn::Result<TokenStream> {
     {
         let decode_children = decode_children(&common, &children, None)?;
         extra_traits.push(quote! {
-            impl #impl_gen ::knus::traits::DecodeChildren #trait_gen
+            impl #impl_gen #traits::DecodeChildren #trait_gen
                 for #s_name #type_gen
                 #bounds
             {
                 fn decode_children(
-                    #children: &[::knus::ast::SpannedNode<#span_ty>],
+                    #children: &[#module::SpannedNode<#lifetime #span_ty>],
                     #ctx: &mut ::knus::decode::Context<#span_ty>)
                     -> ::std::result::Result<Self, ::knus::errors::DecodeError<#span_ty>>
                 {
@@ -135,10 +187,10 @@ pub fn emit_struct(s: &Struct, named: bool) -> syn::Result<TokenStream> {
     }
     Ok(quote! {
         #(#extra_traits)*
-        impl #impl_gen ::knus::Decode #trait_gen for #s_name #type_gen
+        impl #impl_gen #traits::Decode #trait_gen for #s_name #type_gen
             #bounds
         {
-            fn decode_node(#node: &::knus::ast::SpannedNode<#span_ty>,
+            fn decode_node(#node: &#module::SpannedNode<#lifetime #span_ty>,
                            #ctx: &mut ::knus::decode::Context<#span_ty>)
                 -> ::std::result::Result<Self, ::knus::errors::DecodeError<#span_ty>>
             {
@@ -224,12 +276,19 @@ pub(crate) fn decode_enum_item(
 fn decode_value(
     val: &syn::Ident,
     ctx: &syn::Ident,
+    ast: &Ast,
     mode: &DecodeMode,
     optional: bool,
 ) -> syn::Result<TokenStream> {
+    let Ast {
+        module,
+        traits,
+        helpers,
+        ..
+    } = ast;
     match mode {
         DecodeMode::Normal => Ok(quote! {
-            ::knus::traits::DecodeScalar::decode(#val, #ctx)
+            #traits::DecodeScalar::decode(#val, #ctx)
         }),
         DecodeMode::Str if optional => {
             Ok(quote![{
@@ -242,14 +301,14 @@ fn decode_value(
                     });
                 }
                 match *#val.literal {
-                    ::knus::ast::Literal::String(ref s) => {
+                    #module::Literal::String(ref s) => {
                         ::std::str::FromStr::from_str(s).map_err(|e| {
                             ::knus::errors::DecodeError::conversion(
                                 &#val.literal, e)
                         })
                         .map(Some)
                     }
-                    ::knus::ast::Literal::Null => Ok(None),
+                    #module::Literal::Null => Ok(None),
                     _ => {
                         #ctx.emit_error(
                             ::knus::errors::DecodeError::scalar_kind(
@@ -273,7 +332,7 @@ fn decode_value(
                     });
                 }
                 match *#val.literal {
-                    ::knus::ast::Literal::String(ref s) => {
+                    #module::Literal::String(ref s) => {
                         ::std::str::FromStr::from_str(s).map_err(|e| {
                             ::knus::errors::DecodeError::conversion(
                                 &#val.literal, e)
@@ -287,10 +346,10 @@ fn decode_value(
             }])
         }
         DecodeMode::Bytes if optional => Ok(quote! {
-            if matches!(&*#val.literal, ::knus::ast::Literal::Null) {
+            if matches!(&*#val.literal, #module::Literal::Null) {
                 Ok(None)
             } else {
-                match ::knus::decode::bytes(#val, #ctx).try_into() {
+                match #helpers::bytes(#val, #ctx).try_into() {
                     Ok(v) => Ok(Some(v)),
                     Err(e) => {
                         #ctx.emit_error(
@@ -302,7 +361,7 @@ fn decode_value(
             }
         }),
         DecodeMode::Bytes => Ok(quote! {
-            ::knus::decode::bytes(#val, #ctx).try_into()
+            #helpers::bytes(#val, #ctx).try_into()
             .map_err(|e| ::knus::errors::DecodeError::conversion(
                     &#val.literal, e))
         }),
@@ -351,8 +410,10 @@ fn decode_specials(s: &Common, node: &syn::Ident) -> syn::Result<TokenStream> {
                             ::knus::errors::DecodeError::conversion(tn, e)
                         })?
                 } else {
-                    return Err(::knus::errors::DecodeError::missing(
-                        #node, "type name required"));
+                    return Err(::knus::errors::DecodeError::Missing {
+                        span: #node.node_name.span().clone(),
+                        message: "type name required".into(),
+                    });
                 };
             }
         }
@@ -386,7 +447,7 @@ fn decode_args(s: &Common, node: &syn::Ident) -> syn::Result<TokenStream> {
     for arg in &s.object.arguments {
         let fld = &arg.field.tmp_name;
         let val = syn::Ident::new("val", Span::mixed_site());
-        let decode_value = decode_value(&val, ctx, &arg.decode, arg.option)?;
+        let decode_value = decode_value(&val, ctx, s.ast, &arg.decode, arg.option)?;
         match (&arg.default, &arg.kind) {
             (None, ArgKind::Value { option: true }) => {
                 decoder.push(quote! {
@@ -404,8 +465,10 @@ fn decode_args(s: &Common, node: &syn::Ident) -> syn::Result<TokenStream> {
                 decoder.push(quote! {
                     let #val =
                         #iter_args.next().ok_or_else(|| {
-                            ::knus::errors::DecodeError::missing(
-                                #node, #error)
+                            ::knus::errors::DecodeError::Missing {
+                                span: #node.node_name.span().clone(),
+                                message: #error.into(),
+                            }
                         })?;
                     let #fld = #decode_value?;
                 });
@@ -429,18 +492,27 @@ fn decode_args(s: &Common, node: &syn::Ident) -> syn::Result<TokenStream> {
     if let Some(var_args) = &s.object.var_args {
         let fld = &var_args.field.tmp_name;
         let val = syn::Ident::new("val", Span::mixed_site());
-        let decode_value = decode_value(&val, ctx, &var_args.decode, false)?;
+        let decode_value = decode_value(&val, ctx, s.ast, &var_args.decode, false)?;
         decoder.push(quote! {
             let #fld = #iter_args.map(|#val| {
                 #decode_value
             }).collect::<std::result::Result<_, _>>()?;
         });
     } else {
+        let val = syn::Ident::new("val", Span::mixed_site());
+        let handle = unknown(
+            ctx,
+            s.object.trait_props.allow_unknown,
+            quote! {
+                ::knus::errors::DecodeError::unexpected(
+                    &#val.literal, "argument",
+                    "unexpected argument")
+            },
+            |err| quote!(return Err(#err);),
+        );
         decoder.push(quote! {
-            if let Some(val) = #iter_args.next() {
-                return Err(::knus::errors::DecodeError::unexpected(
-                        &val.literal, "argument",
-                        "unexpected argument"));
+            for #val in #iter_args {
+                #handle
             }
         });
     }
@@ -453,25 +525,26 @@ fn decode_props(s: &Common, node: &syn::Ident) -> syn::Result<TokenStream> {
     let mut postprocess = Vec::new();
 
     let ctx = s.ctx;
+    let traits = &s.ast.traits;
     let val = syn::Ident::new("val", Span::mixed_site());
     let name = syn::Ident::new("name", Span::mixed_site());
     let name_str = syn::Ident::new("name_str", Span::mixed_site());
 
     for prop in &s.object.properties {
         let fld = &prop.field.tmp_name;
-        let prop_name = &prop.name;
+        let prop_name = name_pattern(&prop.name, &prop.aliases);
         let seen_name = format_ident!("seen_{}", fld, span = Span::mixed_site());
         if prop.flatten {
             declare_empty.push(quote! {
                 let mut #fld = ::std::default::Default::default();
             });
             match_branches.push(quote! {
-                _ if ::knus::traits::DecodePartial::
+                _ if #traits::DecodePartial::
                     insert_property(&mut #fld, #name, #val, #ctx)?
                 => {}
             });
         } else {
-            let decode_value = decode_value(&val, ctx, &prop.decode, prop.option)?;
+            let decode_value = decode_value(&val, ctx, s.ast, &prop.decode, prop.option)?;
             declare_empty.push(quote! {
                 let mut #fld = None;
                 let mut #seen_name = false;
@@ -490,7 +563,7 @@ fn decode_props(s: &Common, node: &syn::Ident) -> syn::Result<TokenStream> {
                     }
                 });
             }
-            let req_msg = format!("property `{}` is required", prop_name);
+            let req_msg = format!("property `{}` is required", prop.name);
             if let Some(value) = &prop.default {
                 let default = if let Some(expr) = value {
                     quote!(#expr)
@@ -511,8 +584,10 @@ fn decode_props(s: &Common, node: &syn::Ident) -> syn::Result<TokenStream> {
             } else if !prop.option {
                 postprocess.push(quote! {
                     let #fld = #fld.ok_or_else(|| {
-                        ::knus::errors::DecodeError::missing(
-                            #node, #req_msg)
+                        ::knus::errors::DecodeError::Missing {
+                            span: #node.node_name.span().clone(),
+                            message: #req_msg.into(),
+                        }
                     })?;
                 });
             }
@@ -520,7 +595,7 @@ fn decode_props(s: &Common, node: &syn::Ident) -> syn::Result<TokenStream> {
     }
     if let Some(var_props) = &s.object.var_props {
         let fld = &var_props.field.tmp_name;
-        let decode_value = decode_value(&val, ctx, &var_props.decode, false)?;
+        let decode_value = decode_value(&val, ctx, s.ast, &var_props.decode, false)?;
         declare_empty.push(quote! {
             let mut #fld = Vec::new();
         });
@@ -540,12 +615,33 @@ fn decode_props(s: &Common, node: &syn::Ident) -> syn::Result<TokenStream> {
             let #fld = #fld.into_iter().collect();
         });
     } else {
-        match_branches.push(quote! {
-            #name_str => {
-                return Err(::knus::errors::DecodeError::unexpected(
+        let names = s
+            .object
+            .properties
+            .iter()
+            .filter(|prop| !prop.flatten)
+            .map(|prop| &prop.name[..])
+            .collect::<Vec<_>>();
+        let err = with_names_help(
+            quote! {
+                ::knus::errors::DecodeError::unexpected(
                     #name, "property",
                     format!("unexpected property `{}`",
-                            #name_str.escape_default())));
+                            #name_str.escape_default()))
+            },
+            quote!(#name_str),
+            &names,
+            false,
+        );
+        let handle = unknown(
+            ctx,
+            s.object.trait_props.allow_unknown,
+            err,
+            |err| quote!(return Err(#err);),
+        );
+        match_branches.push(quote! {
+            #name_str => {
+                #handle
             }
         });
     };
@@ -560,6 +656,71 @@ fn decode_props(s: &Common, node: &syn::Ident) -> syn::Result<TokenStream> {
     })
 }
 
+/// Handles the unknown argument, property or child node
+///
+/// The entity described by the `err` expression is recorded as ignored if
+/// the container has `allow_unknown` or the context allows unknown entities.
+/// Otherwise `fail` generates the code handling the error.
+pub(crate) fn unknown(
+    ctx: &syn::Ident,
+    allow: bool,
+    err: TokenStream,
+    fail: impl FnOnce(&TokenStream) -> TokenStream,
+) -> TokenStream {
+    if allow {
+        return quote!(#ctx.emit_ignored(#err););
+    }
+    let fail = fail(&err);
+    quote! {
+        if #ctx.allow_unknown() {
+            #ctx.emit_ignored(#err);
+        } else {
+            #fail
+        }
+    }
+}
+
+/// Match pattern accepting the `name` and all its `aliases`
+pub(crate) fn name_pattern(name: &str, aliases: &[String]) -> TokenStream {
+    quote!(#name #(| #aliases)*)
+}
+
+/// Slice expression of the `name` followed by all its `aliases`
+pub(crate) fn name_list(name: &str, aliases: &[String]) -> TokenStream {
+    quote!(&[#name #(, #aliases)*])
+}
+
+/// Wraps the error expression to attach a help listing the valid `names`
+///
+/// `found` is the `&str` expression holding the name that didn't match. If
+/// the error message already `listed` all the names, the help only suggests
+/// the similar one, and is not attached when there is none.
+pub(crate) fn with_names_help(
+    err: TokenStream,
+    found: TokenStream,
+    names: &[&str],
+    listed: bool,
+) -> TokenStream {
+    if names.is_empty() {
+        return err;
+    }
+    if listed {
+        return quote! {
+            match ::knus::decode::similar_name(#found, &[#(#names),*]) {
+                Some(similar) => #err.with_help(
+                    format!("did you mean `{}`?", similar.escape_default())),
+                None => #err,
+            }
+        };
+    }
+    quote! {
+        match ::knus::decode::names_help(#found, &[#(#names),*]) {
+            Some(help) => #err.with_help(help),
+            None => #err,
+        }
+    }
+}
+
 fn unwrap_fn(
     parent: &Common,
     func: &syn::Ident,
@@ -579,7 +740,11 @@ fn unwrap_fn(
         object: &object,
         ctx: parent.ctx,
         span_type: parent.span_type,
+        ast: parent.ast,
     };
+    let Ast {
+        module, lifetime, ..
+    } = parent.ast;
 
     let node = syn::Ident::new("node", Span::mixed_site());
     let children = syn::Ident::new("children", Span::mixed_site());
@@ -587,7 +752,7 @@ fn unwrap_fn(
     let decode_props = decode_props(&common, &node)?;
     let decode_children = decode_children(&common, &children, Some(quote!(#node.span())))?;
     Ok(quote! {
-        let mut #func = |#node: &::knus::ast::SpannedNode<#span_ty>,
+        let mut #func = |#node: &#module::SpannedNode<#lifetime #span_ty>,
                          #ctx: &mut ::knus::decode::Context<#span_ty>|
         {
             #decode_args
@@ -608,6 +773,7 @@ fn decode_node(
     child: &syn::Ident,
 ) -> syn::Result<TokenStream> {
     let ctx = common.ctx;
+    let traits = &common.ast.traits;
     let fld = &child_def.field.tmp_name;
     let dest = if in_partial {
         child_def.field.as_token_stream()
@@ -619,7 +785,7 @@ fn decode_node(
         let unwrap_fn = unwrap_fn(common, &func, fld, unwrap)?;
         (unwrap_fn, quote!(#func))
     } else {
-        (quote!(), quote!(::knus::Decode::decode_node))
+        (quote!(), quote!(#traits::Decode::decode_node))
     };
     let value = syn::Ident::new("value", Span::mixed_site());
     let assign = if matches!(child_def.mode, ChildMode::Multi) {
@@ -654,24 +820,27 @@ fn decode_node(
 
 fn insert_child(s: &Common, node: &syn::Ident) -> syn::Result<TokenStream> {
     let ctx = s.ctx;
+    let Ast {
+        traits, helpers, ..
+    } = s.ast;
     let mut match_branches = Vec::with_capacity(s.object.children.len());
     for child_def in &s.object.children {
         let dest = &child_def.field.as_token_stream();
-        let child_name = &child_def.name;
+        let child_name = name_pattern(&child_def.name, &child_def.aliases);
         if matches!(child_def.mode, ChildMode::Flatten) {
             match_branches.push(quote! {
-                _ if ::knus::traits::DecodePartial
+                _ if #traits::DecodePartial
                     ::insert_child(&mut #dest, #node, #ctx)?
                 => Ok(true),
             })
         } else if matches!(child_def.mode, ChildMode::Bool) {
             let dup_err = format!(
                 "duplicate node `{}`, single node expected",
-                child_name.escape_default()
+                child_def.name.escape_default()
             );
             match_branches.push(quote! {
                 #child_name => {
-                    ::knus::decode::check_flag_node(#node, #ctx);
+                    #helpers::check_flag_node(#node, #ctx);
                     if #dest {
                         #ctx.emit_error(
                             ::knus::errors::DecodeError::unexpected(
@@ -685,7 +854,7 @@ fn insert_child(s: &Common, node: &syn::Ident) -> syn::Result<TokenStream> {
         } else {
             let dup_err = format!(
                 "duplicate node `{}`, single node expected",
-                child_name.escape_default()
+                child_def.name.escape_default()
             );
             let decode = decode_node(s, child_def, true, node)?;
             match_branches.push(quote! {
@@ -710,18 +879,19 @@ fn insert_child(s: &Common, node: &syn::Ident) -> syn::Result<TokenStream> {
 
 fn insert_property(s: &Common, name: &syn::Ident, value: &syn::Ident) -> syn::Result<TokenStream> {
     let ctx = s.ctx;
+    let traits = &s.ast.traits;
     let mut match_branches = Vec::with_capacity(s.object.children.len());
     for prop in &s.object.properties {
         let dest = &prop.field.as_token_stream();
-        let prop_name = &prop.name;
+        let prop_name = name_pattern(&prop.name, &prop.aliases);
         if prop.flatten {
             match_branches.push(quote! {
-                _ if ::knus::traits::DecodePartial
+                _ if #traits::DecodePartial
                     ::insert_property(&mut #dest, #name, #value, #ctx)?
                 => Ok(true),
             });
         } else {
-            let decode_value = decode_value(value, ctx, &prop.decode, prop.option)?;
+            let decode_value = decode_value(value, ctx, s.ast, &prop.decode, prop.option)?;
             if prop.option {
                 match_branches.push(quote! {
                     #prop_name => {
@@ -757,11 +927,14 @@ fn decode_children(
     let mut postprocess = Vec::new();
 
     let ctx = s.ctx;
+    let Ast {
+        traits, helpers, ..
+    } = s.ast;
     let child = syn::Ident::new("child", Span::mixed_site());
     let name_str = syn::Ident::new("name_str", Span::mixed_site());
     for child_def in &s.object.children {
         let fld = &child_def.field.tmp_name;
-        let child_name = &child_def.name;
+        let child_name = name_pattern(&child_def.name, &child_def.aliases);
         match child_def.mode {
             ChildMode::Flatten => {
                 declare_empty.push(quote! {
@@ -769,7 +942,7 @@ fn decode_children(
                 });
                 match_branches.push(quote! {
                     _ if (
-                        match ::knus::traits::DecodePartial
+                        match #traits::DecodePartial
                             ::insert_child(&mut #fld, #child, #ctx)
                         {
                             Ok(true) => return None,
@@ -830,7 +1003,7 @@ fn decode_children(
                 });
                 let dup_err = format!(
                     "duplicate node `{}`, single node expected",
-                    child_name.escape_default()
+                    child_def.name.escape_default()
                 );
                 let decode = decode_node(s, child_def, false, &child)?;
                 match_branches.push(quote! {
@@ -844,7 +1017,7 @@ fn decode_children(
                         }
                     }
                 });
-                let req_msg = format!("child node `{}` is required", child_name);
+                let req_msg = format!("child node `{}` is required", child_def.name);
                 if let Some(default_value) = &child_def.default {
                     let default = if let Some(expr) = default_value {
                         quote!(#expr)
@@ -878,14 +1051,14 @@ fn decode_children(
             ChildMode::Bool => {
                 let dup_err = format!(
                     "duplicate node `{}`, single node expected",
-                    child_name.escape_default()
+                    child_def.name.escape_default()
                 );
                 declare_empty.push(quote! {
                     let mut #fld = false;
                 });
                 match_branches.push(quote! {
                     #child_name => {
-                        ::knus::decode::check_flag_node(#child, #ctx);
+                        #helpers::check_flag_node(#child, #ctx);
                         if #fld {
                             #ctx.emit_error(
                                 ::knus::errors::DecodeError::unexpected(
@@ -907,7 +1080,7 @@ fn decode_children(
             let unwrap_fn = unwrap_fn(s, &func, fld, unwrap)?;
             (unwrap_fn, quote!(#func))
         } else {
-            (quote!(), quote!(::knus::Decode::decode_node))
+            (quote!(), quote!(#traits::Decode::decode_node))
         };
 
         match_branches.push(quote! {
@@ -929,12 +1102,33 @@ fn decode_children(
             #(#postprocess)*
         })
     } else {
-        match_branches.push(quote! {
-            #name_str => {
-                #ctx.emit_error(::knus::errors::DecodeError::unexpected(
+        let names = s
+            .object
+            .children
+            .iter()
+            .filter(|child| !matches!(child.mode, ChildMode::Flatten))
+            .map(|child| &child.name[..])
+            .collect::<Vec<_>>();
+        let err = with_names_help(
+            quote! {
+                ::knus::errors::DecodeError::unexpected(
                     #child, "node",
                     format!("unexpected node `{}`",
-                            #name_str.escape_default())));
+                            #name_str.escape_default()))
+            },
+            quote!(#name_str),
+            &names,
+            false,
+        );
+        let handle = unknown(
+            ctx,
+            s.object.trait_props.allow_unknown,
+            err,
+            |err| quote!(#ctx.emit_error(#err);),
+        );
+        match_branches.push(quote! {
+            #name_str => {
+                #handle
                 None
             }
         });
@@ -1,9 +1,12 @@
 use proc_macro2::TokenStream;
+use proc_macro_error2::emit_error;
 use quote::quote;
-use syn::ext::IdentExt;
 use syn::parse::{Parse, ParseStream};
 use syn::spanned::Spanned;
 
+use crate::definition::{parse_attr_list, Attr, RenameAll, VariantAttrs};
+use crate::node;
+
 pub enum Scalar {
     Enum(Enum),
 }
@@ -16,22 +19,35 @@ pub struct Enum {
 pub struct Variant {
     pub ident: syn::Ident,
     pub name: String,
+    pub aliases: Vec<String>,
 }
 
 impl Enum {
     fn new(
         ident: syn::Ident,
-        _attrs: Vec<syn::Attribute>,
+        attrs: Vec<syn::Attribute>,
         src_variants: impl Iterator<Item = syn::Variant>,
     ) -> syn::Result<Self> {
+        let mut rename_all = RenameAll::default();
+        for (attr, span) in parse_attr_list(&attrs) {
+            match attr {
+                Attr::RenameAll(case) => rename_all = case,
+                _ => emit_error!(span, "unexpected container attribute"),
+            }
+        }
         let mut variants = Vec::new();
         for var in src_variants {
             match var.fields {
                 syn::Fields::Unit => {
-                    let name = heck::ToKebabCase::to_kebab_case(&var.ident.unraw().to_string()[..]);
+                    let mut attrs = VariantAttrs::new();
+                    attrs.update(parse_attr_list(&var.attrs));
+                    if attrs.skip {
+                        emit_error!(var.span(), "`skip` is not supported for scalars");
+                    }
                     variants.push(Variant {
+                        name: attrs.rename.unwrap_or_else(|| rename_all.apply(&var.ident)),
+                        aliases: attrs.aliases,
                         ident: var.ident,
-                        name,
                     });
                 }
                 _ => {
@@ -71,7 +87,8 @@ pub fn emit_scalar(s: &Scalar) -> syn::Result<TokenStream> {
 
 pub fn emit_enum(e: &Enum) -> syn::Result<TokenStream> {
     let e_name = &e.ident;
-    let value_err = if e.variants.len() <= 3 {
+    let listed = e.variants.len() <= 3;
+    let value_err = if listed {
         format!(
             "expected one of {}",
             e.variants
@@ -88,11 +105,28 @@ pub fn emit_enum(e: &Enum) -> syn::Result<TokenStream> {
             e.variants.len() - 2
         )
     };
-    let match_branches = e.variants.iter().map(|var| {
-        let name = &var.name;
-        let ident = &var.ident;
-        quote!(#name => Ok(#e_name::#ident))
-    });
+    let valid = e.variants.iter().map(|v| &v.name[..]).collect::<Vec<_>>();
+    let value_err = node::with_names_help(
+        quote! {
+            ::knus::errors::DecodeError::conversion(val, #value_err)
+        },
+        quote!(name_str),
+        &valid,
+        listed,
+    );
+    let match_branches = e
+        .variants
+        .iter()
+        .map(|var| {
+            let name = node::name_pattern(&var.name, &var.aliases);
+            let ident = &var.ident;
+            quote!(#name => Ok(#e_name::#ident))
+        })
+        .collect::<Vec<_>>();
+    let names = e
+        .variants
+        .iter()
+        .flat_map(|var| std::iter::once(&var.name).chain(&var.aliases));
     Ok(quote! {
         impl<S: ::knus::traits::ErrorSpan> ::knus::DecodeScalar<S>
                 for #e_name {
@@ -105,10 +139,7 @@ pub fn emit_enum(e: &Enum) -> syn::Result<TokenStream> {
                     ::knus::ast::Literal::String(ref s) => {
                         match &s[..] {
                             #(#match_branches,)*
-                            _ => {
-                                Err(::knus::errors::DecodeError::conversion(
-                                        val, #value_err))
-                            }
+                            name_str => Err(#value_err),
                         }
                     }
                     _ => {
@@ -133,5 +164,76 @@ pub fn emit_enum(e: &Enum) -> syn::Result<TokenStream> {
                 }
             }
         }
+
+        impl<'__knus, S: ::knus::traits::ErrorSpan>
+            ::knus::borrowed::DecodeScalar<'__knus, S> for #e_name
+        {
+            fn raw_decode(val: &::knus::span::Spanned<
+                          ::knus::borrowed::Literal<'__knus>, S>,
+                          ctx: &mut ::knus::decode::Context<S>)
+                -> ::std::result::Result<#e_name, ::knus::errors::DecodeError<S>>
+            {
+                match &**val {
+                    ::knus::borrowed::Literal::String(ref s) => {
+                        match &s[..] {
+                            #(#match_branches,)*
+                            name_str => Err(#value_err),
+                        }
+                    }
+                    _ => {
+                        Err(::knus::errors::DecodeError::scalar_kind(
+                            ::knus::decode::Kind::String,
+                            &val,
+                        ))
+                    }
+                }
+            }
+            fn type_check(type_name: &Option<::knus::span::Spanned<
+                          ::knus::ast::TypeName, S>>,
+                          ctx: &mut ::knus::decode::Context<S>)
+            {
+                <#e_name as ::knus::DecodeScalar<S>>::type_check(type_name, ctx)
+            }
+        }
+
+        impl ::knus::traits::ScalarSchema for #e_name {
+            fn validations() -> ::knus::schema::Validations {
+                ::knus::schema::Validations {
+                    enum_values: vec![
+                        #(::knus::ast::Literal::String(#names.into()),)*
+                    ],
+                    ..::knus::schema::Validations::of_type(
+                        ::knus::schema::ValueType::String)
+                }
+            }
+        }
+    })
+}
+
+pub fn emit_encode_scalar(s: &Scalar) -> syn::Result<TokenStream> {
+    match s {
+        Scalar::Enum(e) => emit_encode_enum(e),
+    }
+}
+
+pub fn emit_encode_enum(e: &Enum) -> syn::Result<TokenStream> {
+    let e_name = &e.ident;
+    let match_branches = e.variants.iter().map(|var| {
+        let name = &var.name;
+        let ident = &var.ident;
+        quote!(#e_name::#ident => #name)
+    });
+    Ok(quote! {
+        impl ::knus::EncodeScalar for #e_name {
+            fn raw_encode(&self, _ctx: &mut ::knus::encode::Context)
+                -> ::std::result::Result<
+                    ::knus::ast::Literal, ::knus::errors::EncodeError>
+            {
+                let name = match self {
+                    #(#match_branches,)*
+                };
+                Ok(::knus::ast::Literal::String(name.into()))
+            }
+        }
     })
 }
@@ -0,0 +1,299 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+
+use crate::definition::{is_option, ChildMode, DecodeMode, Enum, Field, FieldAttrs};
+use crate::definition::{NewType, Struct, StructBuilder, VariantKind};
+
+/// Returns the first type argument, i.e. `T` for `Option<T>` or `Vec<T>`
+fn element_type(ty: &syn::Type) -> Option<&syn::Type> {
+    let path = match ty {
+        syn::Type::Path(syn::TypePath { qself: None, path }) => path,
+        _ => return None,
+    };
+    match &path.segments.last()?.arguments {
+        syn::PathArguments::AngleBracketed(args) => args.args.iter().find_map(|arg| match arg {
+            syn::GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+pub(crate) fn strip_option(ty: &syn::Type) -> &syn::Type {
+    if is_option(ty) {
+        element_type(ty).unwrap_or(ty)
+    } else {
+        ty
+    }
+}
+
+pub fn emit_struct(s: &Struct) -> syn::Result<TokenStream> {
+    let s_name = &s.ident;
+    let (impl_gen, type_gen, bounds) = s.generics.split_for_impl();
+    let schema = struct_schema(s)?;
+    Ok(quote! {
+        impl #impl_gen ::knus::traits::DecodeSchema for #s_name #type_gen
+            #bounds
+        {
+            fn schema() -> ::knus::schema::NodeSchema {
+                ::knus::schema::guard_recursion::<Self, _>(
+                    ::knus::schema::NodeSchema::any(),
+                    || #schema,
+                )
+            }
+        }
+    })
+}
+
+pub fn emit_new_type(s: &NewType) -> syn::Result<TokenStream> {
+    let s_name = &s.ident;
+    let ty = strip_option(&s.ty);
+    Ok(quote! {
+        impl ::knus::traits::DecodeSchema for #s_name {
+            fn schema() -> ::knus::schema::NodeSchema {
+                <#ty as ::knus::traits::DecodeSchema>::schema()
+            }
+        }
+    })
+}
+
+pub fn emit_enum(e: &Enum) -> syn::Result<TokenStream> {
+    let e_name = &e.ident;
+    let (impl_gen, type_gen, bounds) = e.generics.split_for_impl();
+    let node = syn::Ident::new("node", Span::mixed_site());
+    let mut variants = Vec::with_capacity(e.variants.len());
+    for var in &e.variants {
+        let name = &var.name;
+        let aliases = &var.aliases;
+        let schema = match &var.kind {
+            VariantKind::Unit => quote!(::knus::schema::NodeSchema::default()),
+            VariantKind::Nested { option, ty } => {
+                let ty = if *option { strip_option(ty) } else { ty };
+                quote!(<#ty as ::knus::traits::DecodeSchema>::schema())
+            }
+            VariantKind::Tuple(s) => struct_schema(s)?,
+            VariantKind::Named => {
+                return Err(syn::Error::new(
+                    var.ident.span(),
+                    "named variants are not supported",
+                ))
+            }
+        };
+        variants.push(quote! {{
+            let mut #node = #schema;
+            #node.name = Some(#name.into());
+            #node.aliases = vec![#(#aliases.into()),*];
+            #node
+        }});
+    }
+    Ok(quote! {
+        impl #impl_gen ::knus::traits::DecodeSchema for #e_name #type_gen
+            #bounds
+        {
+            fn schema() -> ::knus::schema::NodeSchema {
+                ::knus::schema::NodeSchema::any()
+            }
+            fn children_schema() -> Vec<::knus::schema::NodeSchema> {
+                ::knus::schema::guard_recursion::<Self, _>(
+                    vec![::knus::schema::NodeSchema::any()],
+                    || vec![#(#variants),*],
+                )
+            }
+        }
+    })
+}
+
+fn scalar_validations(ty: &syn::Type, decode: &DecodeMode, option: bool) -> TokenStream {
+    match decode {
+        DecodeMode::Normal => quote!(<#ty as ::knus::traits::ScalarSchema>::validations()),
+        DecodeMode::Str | DecodeMode::Bytes => {
+            let nullable = if option {
+                quote!(.nullable())
+            } else {
+                quote!()
+            };
+            quote! {
+                ::knus::schema::Validations::of_type(
+                    ::knus::schema::ValueType::String)#nullable
+            }
+        }
+    }
+}
+
+/// Returns the schema of the child node, or of the node unwrapped into the
+/// field
+fn node_schema(
+    s: &Struct,
+    field: &Field,
+    ty: Option<&syn::Type>,
+    unwrap: Option<&FieldAttrs>,
+) -> syn::Result<TokenStream> {
+    let ty = match ty {
+        Some(ty) => ty,
+        None => return Ok(quote!(::knus::schema::NodeSchema::any())),
+    };
+    let attrs = match unwrap {
+        Some(attrs) => attrs,
+        None => return Ok(quote!(<#ty as ::knus::traits::DecodeSchema>::schema())),
+    };
+    let mut bld = StructBuilder::new(
+        field.tmp_name.clone(),
+        s.trait_props.clone(),
+        s.generics.clone(),
+    );
+    let field = Field {
+        ty: ty.clone(),
+        ..Field::new_named(&field.tmp_name)
+    };
+    bld.add_field(field, false, false, attrs)?;
+    struct_schema(&bld.build())
+}
+
+fn struct_schema(s: &Struct) -> syn::Result<TokenStream> {
+    let schema = syn::Ident::new("schema", Span::mixed_site());
+    let children = syn::Ident::new("children", Span::mixed_site());
+    let node = syn::Ident::new("node", Span::mixed_site());
+    let mut items = Vec::new();
+
+    if s.has_arguments {
+        let required = s
+            .arguments
+            .iter()
+            .rposition(|a| !a.option && a.default.is_none())
+            .map_or(0, |idx| idx + 1);
+        let arguments = s
+            .arguments
+            .iter()
+            .map(|a| scalar_validations(&a.field.ty, &a.decode, a.option));
+        let rest = match &s.var_args {
+            Some(var_args) => {
+                let validations = match element_type(&var_args.field.ty) {
+                    Some(ty) => scalar_validations(ty, &var_args.decode, false),
+                    None => quote!(::std::default::Default::default()),
+                };
+                quote!(Some(#validations))
+            }
+            None => quote!(None),
+        };
+        items.push(quote! {
+            #schema.values = ::knus::schema::ValueSchema::for_arguments(
+                #required, vec![#(#arguments),*], #rest);
+        });
+    }
+    for prop in &s.properties {
+        let ty = &prop.field.ty;
+        if prop.flatten {
+            items.push(quote! {
+                #schema.flatten(<#ty as ::knus::traits::DecodeSchema>::schema());
+            });
+            continue;
+        }
+        let name = &prop.name;
+        let aliases = &prop.aliases;
+        let required = !prop.option && prop.default.is_none();
+        let validations = scalar_validations(ty, &prop.decode, prop.option);
+        items.push(quote! {
+            #schema.props.push(::knus::schema::PropSchema {
+                name: #name.into(),
+                aliases: vec![#(#aliases.into()),*],
+                description: None,
+                required: #required,
+                validations: #validations,
+            });
+        });
+    }
+    if s.var_props.is_some() {
+        items.push(quote!(#schema.other_props_allowed = true;));
+    }
+
+    let mut child_items = Vec::new();
+    for child in &s.children {
+        let name = &child.name;
+        let aliases = &child.aliases;
+        let ty = if child.option {
+            strip_option(&child.field.ty)
+        } else {
+            &child.field.ty
+        };
+        match child.mode {
+            ChildMode::Flatten => {
+                let flattened = s
+                    .properties
+                    .iter()
+                    .any(|p| p.flatten && p.field.tmp_name == child.field.tmp_name);
+                if !flattened {
+                    items.push(quote! {
+                        #schema.flatten(<#ty as ::knus::traits::DecodeSchema>::schema());
+                    });
+                }
+            }
+            ChildMode::Bool => {
+                child_items.push(quote! {
+                    #children.nodes.push(::knus::schema::NodeSchema {
+                        name: Some(#name.into()),
+                        aliases: vec![#(#aliases.into()),*],
+                        max: Some(1),
+                        ..::std::default::Default::default()
+                    });
+                });
+            }
+            ChildMode::Normal => {
+                let node_schema = node_schema(s, &child.field, Some(ty), child.unwrap.as_deref())?;
+                let min = if child.option || child.default.is_some() {
+                    quote!(None)
+                } else {
+                    quote!(Some(1))
+                };
+                child_items.push(quote! {
+                    let mut #node = #node_schema;
+                    #node.name = Some(#name.into());
+                    #node.aliases = vec![#(#aliases.into()),*];
+                    #node.min = #min;
+                    #node.max = Some(1);
+                    #children.nodes.push(#node);
+                });
+            }
+            ChildMode::Multi => {
+                let node_schema =
+                    node_schema(s, &child.field, element_type(ty), child.unwrap.as_deref())?;
+                child_items.push(quote! {
+                    let mut #node = #node_schema;
+                    #node.name = Some(#name.into());
+                    #node.aliases = vec![#(#aliases.into()),*];
+                    #children.nodes.push(#node);
+                });
+            }
+        }
+    }
+    if let Some(var_children) = &s.var_children {
+        match (element_type(&var_children.field.ty), &var_children.unwrap) {
+            (Some(ty), None) => child_items.push(quote! {
+                #children.nodes.extend(
+                    <#ty as ::knus::traits::DecodeSchema>::children_schema());
+            }),
+            (Some(ty), Some(unwrap)) => {
+                let node_schema = node_schema(s, &var_children.field, Some(ty), Some(unwrap))?;
+                child_items.push(quote!(#children.nodes.push(#node_schema);));
+            }
+            (None, _) => child_items.push(quote!(#children.other_nodes_allowed = true;)),
+        }
+    }
+    if !child_items.is_empty() {
+        items.push(quote! {
+            let #children = #schema.children.get_or_insert_with(
+                ::std::default::Default::default);
+            #(#child_items)*
+        });
+    }
+
+    let mutability = if items.is_empty() {
+        quote!()
+    } else {
+        quote!(mut)
+    };
+    Ok(quote! {{
+        let #mutability #schema = ::knus::schema::NodeSchema::default();
+        #(#items)*
+        #schema
+    }})
+}
@@ -2,12 +2,13 @@ use proc_macro2::{Span, TokenStream};
 use quote::quote;
 
 use crate::definition::{Enum, VariantKind};
-use crate::node;
+use crate::node::{self, Ast};
 
 pub(crate) struct Common<'a> {
     pub object: &'a Enum,
     pub ctx: &'a syn::Ident,
     pub span_type: &'a TokenStream,
+    pub ast: &'a Ast,
 }
 
 pub fn emit_enum(e: &Enum) -> syn::Result<TokenStream> {
@@ -32,21 +33,29 @@ pub fn emit_enum(e: &Enum) -> syn::Result<TokenStream> {
             .predicates
             .push(syn::parse2(quote!(S: ::knus::traits::ErrorSpan)).unwrap());
     };
-    let trait_gen = quote!(<#span_ty>);
+    let ast = Ast::for_generics(&mut common_generics);
+    let Ast {
+        module,
+        traits,
+        lifetime,
+        ..
+    } = &ast;
+    let trait_gen = quote!(<#lifetime #span_ty>);
     let (impl_gen, _, bounds) = common_generics.split_for_impl();
 
     let common = Common {
         object: e,
         ctx: &ctx,
         span_type: &span_ty,
+        ast: &ast,
     };
 
     let decode = decode(&common, &node)?;
     Ok(quote! {
-        impl #impl_gen ::knus::Decode #trait_gen for #name #type_gen
+        impl #impl_gen #traits::Decode #trait_gen for #name #type_gen
             #bounds
         {
-            fn decode_node(#node: &::knus::ast::SpannedNode<#span_ty>,
+            fn decode_node(#node: &#module::SpannedNode<#lifetime #span_ty>,
                            #ctx: &mut ::knus::decode::Context<#span_ty>)
                 -> ::std::result::Result<Self, ::knus::errors::DecodeError<#span_ty>>
             {
@@ -58,56 +67,79 @@ pub fn emit_enum(e: &Enum) -> syn::Result<TokenStream> {
 
 fn decode(e: &Common, node: &syn::Ident) -> syn::Result<TokenStream> {
     let ctx = e.ctx;
+    let traits = &e.ast.traits;
     let mut branches = Vec::with_capacity(e.object.variants.len());
     let enum_name = &e.object.ident;
     for var in &e.object.variants {
-        let name = &var.name;
+        let name = node::name_pattern(&var.name, &var.aliases);
         let variant_name = &var.ident;
         match &var.kind {
             VariantKind::Unit => {
+                let allow = e.object.trait_props.allow_unknown;
+                let emit = |err: &TokenStream| quote!(#ctx.emit_error(#err););
+                let arg = node::unknown(
+                    ctx,
+                    allow,
+                    quote! {
+                        ::knus::errors::DecodeError::unexpected(
+                            &arg.literal, "argument",
+                            "unexpected argument")
+                    },
+                    emit,
+                );
+                let prop = node::unknown(
+                    ctx,
+                    allow,
+                    quote! {
+                        ::knus::errors::DecodeError::unexpected(
+                            name, "property",
+                            format!("unexpected property `{}`",
+                                    name.escape_default()))
+                    },
+                    emit,
+                );
+                let child = node::unknown(
+                    ctx,
+                    allow,
+                    quote! {
+                        ::knus::errors::DecodeError::unexpected(
+                            child, "node",
+                            format!("unexpected node `{}`",
+                                child.node_name.escape_default()))
+                    },
+                    emit,
+                );
                 branches.push(quote! {
                     #name => {
                         for arg in &#node.arguments {
-                            #ctx.emit_error(
-                                ::knus::errors::DecodeError::unexpected(
-                                    &arg.literal, "argument",
-                                    "unexpected argument"));
+                            #arg
                         }
                         for (name, _) in &#node.properties {
-                            #ctx.emit_error(
-                                ::knus::errors::DecodeError::unexpected(
-                                    name, "property",
-                                    format!("unexpected property `{}`",
-                                            name.escape_default())));
+                            #prop
                         }
                         if let Some(children) = &#node.children {
                             for child in children.iter() {
-                                #ctx.emit_error(
-                                    ::knus::errors::DecodeError::unexpected(
-                                        child, "node",
-                                        format!("unexpected node `{}`",
-                                            child.node_name.escape_default())
-                                    ));
+                                #child
                             }
                         }
                         Ok(#enum_name::#variant_name)
                     }
                 });
             }
-            VariantKind::Nested { option: false } => {
+            VariantKind::Nested { option: false, .. } => {
                 branches.push(quote! {
-                    #name => ::knus::Decode::decode_node(#node, #ctx)
+                    #name => #traits::Decode::decode_node(#node, #ctx)
                         .map(#enum_name::#variant_name),
                 });
             }
-            VariantKind::Nested { option: true } => {
+            VariantKind::Nested { option: true, .. } => {
                 branches.push(quote! {
                     #name => {
                         if #node.arguments.len() > 0 ||
                             #node.properties.len() > 0 ||
                             #node.children.is_some()
                         {
-                            ::knus::Decode::decode_node(#node, #ctx)
+                            #traits::Decode::decode_node(#node, #ctx)
                                 .map(Some)
                                 .map(#enum_name::#variant_name)
                         } else {
@@ -121,6 +153,7 @@ fn decode(e: &Common, node: &syn::Ident) -> syn::Result<TokenStream> {
                     object: s,
                     ctx,
                     span_type: e.span_type,
+                    ast: e.ast,
                 };
                 let decode = node::decode_enum_item(
                     &common,
@@ -135,8 +168,8 @@ fn decode(e: &Common, node: &syn::Ident) -> syn::Result<TokenStream> {
             VariantKind::Named => unimplemented!(),
         }
     }
-    // TODO(tailhook) use strsim to find similar names
-    let err = if e.object.variants.len() <= 3 {
+    let listed = e.object.variants.len() <= 3;
+    let err = if listed {
         format!(
             "expected one of {}",
             e.object
@@ -154,13 +187,24 @@ fn decode(e: &Common, node: &syn::Ident) -> syn::Result<TokenStream> {
             e.object.variants.len() - 2
         )
     };
+    let names = e
+        .object
+        .variants
+        .iter()
+        .map(|v| &v.name[..])
+        .collect::<Vec<_>>();
+    let err = node::with_names_help(
+        quote! {
+            ::knus::errors::DecodeError::conversion(&#node.node_name, #err)
+        },
+        quote!(name_str),
+        &names,
+        listed,
+    );
     Ok(quote! {
         match &**#node.node_name {
             #(#branches)*
-            name_str => {
-                Err(::knus::errors::DecodeError::conversion(
-                        &#node.node_name, #err))
-            }
+            name_str => Err(#err),
         }
     })
 }
@@ -0,0 +1,184 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use miette::Diagnostic;
+
+use knus::borrowed::{Decode, DecodeChildren};
+use knus::span::Span;
+
+#[derive(knus_derive::Decode, Debug, PartialEq)]
+struct Server<'a> {
+    #[knus(argument)]
+    host: &'a str,
+    #[knus(property)]
+    port: Option<u16>,
+    #[knus(property)]
+    comment: Option<Cow<'a, str>>,
+    #[knus(children(name = "alias"), unwrap(argument))]
+    aliases: Vec<&'a str>,
+    #[knus(child)]
+    disabled: bool,
+}
+
+#[derive(knus_derive::Decode, Debug, PartialEq)]
+struct Document<'a> {
+    #[knus(children(name = "server"))]
+    servers: Vec<Server<'a>>,
+    #[knus(child, unwrap(argument, bytes))]
+    key: Option<Vec<u8>>,
+}
+
+#[derive(knus_derive::Decode, Debug, PartialEq)]
+enum Command<'a> {
+    Echo(#[knus(argument)] &'a str),
+    Level(#[knus(argument)] Level),
+    Nested(Server<'a>),
+}
+
+#[derive(knus_derive::DecodeScalar, Debug, PartialEq)]
+enum Level {
+    Low,
+    High,
+}
+
+#[derive(knus_derive::Decode, Debug, PartialEq)]
+struct Note<'a> {
+    #[knus(argument)]
+    text: Cow<'a, str>,
+}
+
+#[derive(knus_derive::Decode, Debug, PartialEq)]
+struct Owned {
+    #[knus(argument)]
+    name: String,
+}
+
+fn parse<'a, T: Decode<'a, Span>>(text: &'a str) -> T {
+    let mut nodes: Vec<T> = knus::borrowed::parse("<test>", text).unwrap();
+    assert_eq!(nodes.len(), 1);
+    nodes.remove(0)
+}
+
+fn parse_err<'a, T: Decode<'a, Span> + fmt::Debug>(text: &'a str) -> String {
+    let err = knus::borrowed::parse::<Vec<T>>("<test>", text).unwrap_err();
+    err.related()
+        .unwrap()
+        .map(|e| e.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn parse_doc<'a, T: DecodeChildren<'a, Span>>(text: &'a str) -> T {
+    knus::borrowed::parse("<test>", text).unwrap()
+}
+
+#[test]
+fn parse_server() {
+    let text = r#"server "example.org" port=80 comment="main" { alias "www"; }"#;
+    let server = parse::<Server>(text);
+    assert_eq!(
+        server,
+        Server {
+            host: "example.org",
+            port: Some(80),
+            comment: Some("main".into()),
+            aliases: vec!["www"],
+            disabled: false,
+        }
+    );
+    assert!(matches!(server.comment, Some(Cow::Borrowed(_))));
+    let server = parse::<Server>(r#"server "a" comment="x\ty" { disabled; }"#);
+    assert!(matches!(server.comment, Some(Cow::Owned(ref s)) if s == "x\ty"));
+    assert!(server.disabled);
+    assert_eq!(
+        parse_err::<Server>(r#"server "a\nb""#),
+        "string with escapes can't be borrowed, use `Cow<str>` or `String`"
+    );
+    assert_eq!(
+        parse_err::<Server>(r#"server"#),
+        "additional argument `host` is required"
+    );
+}
+
+#[test]
+fn parse_document() {
+    assert_eq!(
+        parse_doc::<Document>(
+            r#"
+            server "a"
+            server "b" port=8080
+            key "abc"
+        "#
+        ),
+        Document {
+            servers: vec![
+                Server {
+                    host: "a",
+                    port: None,
+                    comment: None,
+                    aliases: vec![],
+                    disabled: false,
+                },
+                Server {
+                    host: "b",
+                    port: Some(8080),
+                    comment: None,
+                    aliases: vec![],
+                    disabled: false,
+                },
+            ],
+            key: Some(b"abc".to_vec()),
+        }
+    );
+}
+
+#[test]
+fn parse_enum() {
+    assert_eq!(parse::<Command>(r#"echo "hello""#), Command::Echo("hello"));
+    assert_eq!(
+        parse::<Command>(r#"level "high""#),
+        Command::Level(Level::High)
+    );
+    assert_eq!(
+        parse::<Command>(r#"nested "x""#),
+        Command::Nested(Server {
+            host: "x",
+            port: None,
+            comment: None,
+            aliases: vec![],
+            disabled: false,
+        })
+    );
+    assert_eq!(
+        parse_err::<Command>(r#"level "medium""#),
+        "expected one of `low`, `high`"
+    );
+}
+
+#[test]
+fn parse_v2() {
+    let note = parse::<Note>("/- kdl-version 2\nnote hello");
+    assert!(matches!(note.text, Cow::Borrowed("hello")));
+    assert_eq!(
+        parse::<Server>("/- kdl-version 2\nserver example.com port=80 { disabled; }"),
+        Server {
+            host: "example.com",
+            port: Some(80),
+            comment: None,
+            aliases: vec![],
+            disabled: true,
+        }
+    );
+}
+
+#[test]
+fn parse_owned() {
+    let text = r#"node "hello""#;
+    let nodes: Vec<Owned> = knus::parse("<test>", text).unwrap();
+    assert_eq!(
+        nodes,
+        vec![Owned {
+            name: "hello".into()
+        }]
+    );
+}
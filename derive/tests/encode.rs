@@ -0,0 +1,320 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use knus::ast::{BuiltinType, TypeName};
+use knus::traits::{DecodeChildren, EncodeChildren};
+use knus::{span::Span, Decode, Encode};
+
+#[derive(knus_derive::Decode, knus_derive::Encode, Debug, PartialEq)]
+struct Args {
+    #[knus(argument)]
+    name: String,
+    #[knus(argument)]
+    alias: Option<String>,
+    #[knus(arguments)]
+    rest: Vec<u32>,
+}
+
+#[derive(knus_derive::Decode, knus_derive::Encode, Debug, PartialEq)]
+struct OptArgs {
+    #[knus(argument)]
+    first: Option<String>,
+    #[knus(argument)]
+    second: Option<String>,
+}
+
+#[derive(knus_derive::Decode, knus_derive::Encode, Debug, PartialEq)]
+struct Props {
+    #[knus(property)]
+    label: String,
+    #[knus(property)]
+    note: Option<String>,
+    #[knus(property, default=Some(1))]
+    level: Option<u8>,
+    #[knus(properties)]
+    extra: BTreeMap<String, i64>,
+}
+
+#[derive(knus_derive::Decode, knus_derive::Encode, Debug, PartialEq)]
+struct Children {
+    #[knus(child)]
+    main: Args,
+    #[knus(child)]
+    other: Option<Props>,
+    #[knus(child)]
+    enabled: bool,
+    #[knus(children(name = "item"))]
+    items: Vec<OptArgs>,
+    #[knus(child, unwrap(argument))]
+    title: String,
+    #[knus(children(name = "tag"), unwrap(argument))]
+    tags: Vec<String>,
+}
+
+#[derive(knus_derive::Decode, knus_derive::Encode, Debug, PartialEq)]
+struct Document {
+    #[knus(children)]
+    nodes: Vec<Variant>,
+}
+
+#[derive(knus_derive::Decode, knus_derive::Encode, Debug, PartialEq)]
+enum Variant {
+    Unit,
+    Nested(Args),
+    Tuple(#[knus(argument)] u32, #[knus(property(name = "x"))] bool),
+}
+
+#[derive(knus_derive::Decode, knus_derive::Encode, Debug, PartialEq)]
+enum WithSkip {
+    Plain,
+    #[knus(skip)]
+    #[allow(dead_code)]
+    Hidden,
+}
+
+#[derive(knus_derive::Decode, knus_derive::Encode, Debug, PartialEq, Default)]
+struct InnerProp {
+    #[knus(property)]
+    label: Option<String>,
+}
+
+#[derive(knus_derive::Decode, knus_derive::Encode, Debug, PartialEq, Default)]
+struct InnerChild {
+    #[knus(child, unwrap(argument))]
+    value: Option<u32>,
+}
+
+#[derive(knus_derive::Decode, knus_derive::Encode, Debug, PartialEq)]
+struct Flat {
+    #[knus(flatten(property))]
+    props: InnerProp,
+    #[knus(flatten(child))]
+    children: InnerChild,
+}
+
+#[derive(knus_derive::Decode, knus_derive::Encode, Debug, PartialEq)]
+#[knus(span_type=Span)]
+struct Special {
+    #[knus(node_name)]
+    node_name: String,
+    #[knus(type_name)]
+    type_name: Option<TypeName>,
+    #[knus(span)]
+    span: Span,
+    unused: u32,
+}
+
+#[derive(knus_derive::Decode, knus_derive::Encode, Debug, PartialEq)]
+struct Modes {
+    #[knus(argument, str)]
+    addr: std::net::SocketAddr,
+    #[knus(property, bytes)]
+    data: Vec<u8>,
+}
+
+#[derive(knus_derive::DecodeScalar, knus_derive::EncodeScalar, Debug, PartialEq)]
+enum Color {
+    Red,
+    InfraRed,
+}
+
+#[derive(knus_derive::Decode, knus_derive::Encode, Debug, PartialEq)]
+struct Scalars {
+    #[knus(arguments)]
+    colors: Vec<Color>,
+}
+
+fn print<T: Encode>(value: &T) -> String {
+    knus::to_string(std::slice::from_ref(value)).unwrap()
+}
+
+fn roundtrip<T: Decode<Span> + Encode + fmt::Debug + PartialEq>(value: T) -> String {
+    let text = print(&value);
+    let mut nodes: Vec<T> = knus::parse("<test>", &text).unwrap();
+    assert_eq!(nodes.len(), 1);
+    assert_eq!(nodes.remove(0), value);
+    text
+}
+
+fn roundtrip_doc<T>(value: T) -> String
+where
+    T: DecodeChildren<Span> + EncodeChildren + fmt::Debug + PartialEq,
+{
+    let text = knus::to_string(&value).unwrap();
+    assert_eq!(knus::parse::<T>("<test>", &text).unwrap(), value);
+    text
+}
+
+#[test]
+fn encode_args() {
+    assert_eq!(
+        roundtrip(Args {
+            name: "hello".into(),
+            alias: None,
+            rest: vec![],
+        }),
+        "- \"hello\"\n"
+    );
+    assert_eq!(
+        roundtrip(Args {
+            name: "hello".into(),
+            alias: None,
+            rest: vec![1, 2],
+        }),
+        "- \"hello\" null 1 2\n"
+    );
+    assert_eq!(
+        roundtrip(Args {
+            name: "a b".into(),
+            alias: Some("x".into()),
+            rest: vec![3],
+        }),
+        "- \"a b\" \"x\" 3\n"
+    );
+}
+
+#[test]
+fn encode_opt_args() {
+    assert_eq!(
+        roundtrip(OptArgs {
+            first: None,
+            second: None,
+        }),
+        "-\n"
+    );
+    assert_eq!(
+        roundtrip(OptArgs {
+            first: None,
+            second: Some("2".into()),
+        }),
+        "- null \"2\"\n"
+    );
+}
+
+#[test]
+fn encode_props() {
+    assert_eq!(
+        roundtrip(Props {
+            label: "x".into(),
+            note: None,
+            level: Some(1),
+            extra: BTreeMap::new(),
+        }),
+        "- label=\"x\" level=1\n"
+    );
+    assert_eq!(
+        roundtrip(Props {
+            label: "x".into(),
+            note: Some("y".into()),
+            level: None,
+            extra: [("z".to_string(), -5)].into_iter().collect(),
+        }),
+        "- label=\"x\" level=null note=\"y\" z=-5\n"
+    );
+}
+
+#[test]
+fn encode_children() {
+    assert_eq!(
+        roundtrip(Children {
+            main: Args {
+                name: "m".into(),
+                alias: None,
+                rest: vec![],
+            },
+            other: None,
+            enabled: true,
+            items: vec![
+                OptArgs {
+                    first: Some("a".into()),
+                    second: None,
+                },
+                OptArgs {
+                    first: None,
+                    second: None,
+                },
+            ],
+            title: "t".into(),
+            tags: vec!["x".into(), "y".into()],
+        }),
+        "- {\n    main \"m\"\n    enabled\n    item \"a\"\n    item\n    \
+         title \"t\"\n    tag \"x\"\n    tag \"y\"\n}\n"
+    );
+}
+
+#[test]
+fn encode_enum() {
+    assert_eq!(
+        roundtrip_doc(Document {
+            nodes: vec![
+                Variant::Unit,
+                Variant::Nested(Args {
+                    name: "n".into(),
+                    alias: None,
+                    rest: vec![],
+                }),
+                Variant::Tuple(7, true),
+            ],
+        }),
+        "unit\nnested \"n\"\ntuple 7 x=true\n"
+    );
+    assert_eq!(print(&WithSkip::Plain), "plain\n");
+    assert_eq!(
+        knus::to_string(&[WithSkip::Hidden][..])
+            .unwrap_err()
+            .to_string(),
+        "skipped enum variant cannot be encoded"
+    );
+}
+
+#[test]
+fn encode_flatten() {
+    assert_eq!(
+        roundtrip(Flat {
+            props: InnerProp {
+                label: Some("l".into()),
+            },
+            children: InnerChild { value: Some(3) },
+        }),
+        "- label=\"l\" {\n    value 3\n}\n"
+    );
+    assert_eq!(
+        roundtrip(Flat {
+            props: InnerProp::default(),
+            children: InnerChild::default(),
+        }),
+        "-\n"
+    );
+}
+
+#[test]
+fn encode_special() {
+    let value = Special {
+        node_name: "my-node".into(),
+        type_name: Some(BuiltinType::U32.into()),
+        span: Span(10, 20),
+        unused: 5,
+    };
+    assert_eq!(print(&value), "(u32)my-node\n");
+}
+
+#[test]
+fn encode_modes() {
+    assert_eq!(
+        roundtrip(Modes {
+            addr: "127.0.0.1:80".parse().unwrap(),
+            data: b"hello".to_vec(),
+        }),
+        "- \"127.0.0.1:80\" data=(base64)\"aGVsbG8=\"\n"
+    );
+}
+
+#[test]
+fn encode_scalar() {
+    assert_eq!(
+        roundtrip(Scalars {
+            colors: vec![Color::Red, Color::InfraRed],
+        }),
+        "- \"red\" \"infra-red\"\n"
+    );
+}
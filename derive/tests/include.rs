@@ -0,0 +1,80 @@
+use knus::include::{MemoryLoader, Resolver};
+
+#[derive(knus_derive::Decode, Debug, PartialEq)]
+struct Config {
+    #[knus(children(name = "plugin"), unwrap(argument))]
+    plugins: Vec<String>,
+    #[knus(child)]
+    server: Option<Server>,
+}
+
+#[derive(knus_derive::Decode, Debug, PartialEq)]
+struct Server {
+    #[knus(child, unwrap(argument))]
+    port: u16,
+}
+
+fn resolver(files: &[(&str, &str)]) -> Resolver<MemoryLoader> {
+    let mut loader = MemoryLoader::new();
+    for (path, text) in files {
+        loader.insert(path, *text);
+    }
+    Resolver::new(loader)
+}
+
+#[test]
+fn decode() {
+    let resolver = resolver(&[
+        (
+            "conf/main.kdl",
+            r#"
+            plugin "a"
+            include "plugins.kdl"
+            server {
+                include "../shared/port.kdl"
+            }
+            "#,
+        ),
+        ("conf/plugins.kdl", r#"plugin "b"; plugin "c""#),
+        ("shared/port.kdl", "port 8080"),
+    ]);
+    let config: Config = resolver.parse("conf/main.kdl").unwrap();
+    assert_eq!(
+        config,
+        Config {
+            plugins: vec!["a".into(), "b".into(), "c".into()],
+            server: Some(Server { port: 8080 }),
+        }
+    );
+}
+
+#[test]
+fn decode_errors() {
+    let resolver = resolver(&[
+        ("main.kdl", r#"plugin 1; include "server.kdl""#),
+        ("server.kdl", "server { port 100000; }"),
+    ]);
+    let err = resolver.parse::<Config>("main.kdl").unwrap_err();
+    let messages = miette::Diagnostic::related(&err)
+        .unwrap()
+        .map(|e| e.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        [
+            "expected string scalar, found integer",
+            "number too large to fit in target type",
+        ]
+    );
+    let mut text = String::new();
+    miette::NarratableReportHandler::new()
+        .render_report(&mut text, &err)
+        .unwrap();
+    assert!(text.contains("snippet for main.kdl"), "{}", text);
+    assert!(text.contains("snippet for server.kdl"), "{}", text);
+    assert!(
+        text.contains("snippet line 1: server { port 100000; }"),
+        "{}",
+        text
+    );
+}
@@ -0,0 +1,107 @@
+use std::path::PathBuf;
+
+use knus::interpolate::{Interpolated, Variables};
+use knus::span::Span;
+
+#[derive(knus_derive::Decode, Debug, PartialEq)]
+struct Config {
+    #[knus(child, unwrap(argument))]
+    data_dir: Interpolated<PathBuf>,
+    #[knus(child, unwrap(argument))]
+    database: Option<Interpolated<String>>,
+    #[knus(child)]
+    server: Server,
+}
+
+#[derive(knus_derive::Decode, Debug, PartialEq)]
+struct Server {
+    #[knus(property)]
+    host: Interpolated<String>,
+    #[knus(property)]
+    port: Interpolated<u16>,
+}
+
+fn parse(text: &str) -> Result<Config, knus::Error> {
+    knus::parse_with_context::<Config, Span, _>("<test>", text, |ctx| {
+        ctx.set(
+            Variables::from_iter([
+                ("HOME", "/home/user"),
+                ("DATABASE_URL", "postgres://db"),
+                ("PORT", "8080"),
+            ])
+            .without_env(),
+        )
+    })
+    .map(|(config, _)| config)
+}
+
+#[test]
+fn decode() {
+    let config = parse(
+        r#"
+        data-dir "${HOME}/data"
+        database (env)"DATABASE_URL"
+        server host="localhost" port=(env)"PORT"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(
+        config,
+        Config {
+            data_dir: Interpolated("/home/user/data".into()),
+            database: Some(Interpolated("postgres://db".into())),
+            server: Server {
+                host: Interpolated("localhost".into()),
+                port: Interpolated(8080),
+            },
+        }
+    );
+    let config = parse(
+        r#"
+        data-dir "$$HOME"
+        server host="${HOME}" port=1
+        "#,
+    )
+    .unwrap();
+    assert_eq!(*config.data_dir, PathBuf::from("$HOME"));
+    assert_eq!(config.database, None);
+    assert_eq!(*config.server.port, 1);
+}
+
+fn error_label(text: &str) -> String {
+    let err = parse(text).unwrap_err();
+    let mut text = String::new();
+    miette::NarratableReportHandler::new()
+        .render_report(&mut text, &err)
+        .unwrap();
+    let message = text
+        .lines()
+        .find_map(|line| line.strip_prefix("    Caused by: "))
+        .unwrap();
+    let label = text
+        .lines()
+        .find_map(|line| line.strip_prefix("    label at "))
+        .unwrap();
+    format!("{} ({})", message, label)
+}
+
+#[test]
+fn errors() {
+    assert_eq!(
+        error_label(r#"data-dir "${HOME}/${DATA"; server host="" port=1"#),
+        "unclosed placeholder, expected `}` \
+         (line 1, columns 19 to 24: invalid value)"
+    );
+    assert_eq!(
+        error_label(r#"data-dir "x"; database (env)"NOPE"; server host="" port=1"#),
+        "variable `NOPE` is not defined (line 1, columns 29 to 34: invalid value)"
+    );
+    assert_eq!(
+        error_label(r#"data-dir "x"; server host="a ${HOST}" port=1"#),
+        "variable `HOST` is not defined (line 1, columns 30 to 36: invalid value)"
+    );
+    assert_eq!(
+        error_label(r#"data-dir "x"; server host="" port="x${PORT}""#),
+        "invalid digit found in string (line 1, columns 35 to 44: invalid value)"
+    );
+}
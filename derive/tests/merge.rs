@@ -0,0 +1,261 @@
+use std::collections::BTreeMap;
+
+#[derive(knus_derive::Decode, knus_derive::Merge, Debug, PartialEq)]
+struct Config {
+    #[knus(child)]
+    server: Option<Server>,
+    #[knus(child)]
+    verbose: bool,
+    #[knus(child, unwrap(argument))]
+    name: String,
+    #[knus(children(name = "plugin"))]
+    plugins: Vec<Plugin>,
+    #[knus(children(name = "path"), unwrap(argument), merge = "replace")]
+    paths: Vec<String>,
+    #[knus(flatten(child))]
+    logging: Logging,
+    #[knus(children)]
+    rules: Vec<Rule>,
+}
+
+#[derive(knus_derive::Decode, knus_derive::Merge, Debug, PartialEq)]
+struct Rules {
+    #[knus(child, unwrap(argument))]
+    default: Option<String>,
+    #[knus(children, merge = "replace")]
+    rules: Vec<Rule>,
+}
+
+#[derive(knus_derive::Decode, knus_derive::Merge, Debug, PartialEq)]
+struct Server {
+    #[knus(argument)]
+    host: String,
+    #[knus(properties)]
+    options: BTreeMap<String, u32>,
+    #[knus(child, unwrap(argument))]
+    port: u16,
+    #[knus(child)]
+    tls: Option<Tls>,
+}
+
+#[derive(knus_derive::Decode, knus_derive::Merge, Debug, PartialEq)]
+struct Tls(#[knus(argument)] String);
+
+#[derive(knus_derive::Decode, knus_derive::Merge, Debug, PartialEq)]
+struct Plugin {
+    #[knus(argument)]
+    name: String,
+    #[knus(property, default)]
+    enabled: bool,
+}
+
+#[derive(knus_derive::Decode, knus_derive::Merge, Default, Debug, PartialEq)]
+struct Logging {
+    #[knus(child, unwrap(argument))]
+    level: Option<String>,
+    #[knus(child)]
+    output: Option<Output>,
+}
+
+#[derive(knus_derive::Decode, knus_derive::Merge, Debug, PartialEq)]
+struct Output {
+    #[knus(argument)]
+    path: String,
+    #[knus(property)]
+    rotate: Option<u32>,
+}
+
+#[derive(knus_derive::Decode, knus_derive::Merge, Debug, PartialEq)]
+enum Rule {
+    Allow(#[knus(argument)] String),
+    Deny(#[knus(argument)] String),
+}
+
+const DEFAULTS: &str = r#"
+    name "app"
+    server "localhost" timeout=10 retries=3 {
+        port 8080
+        tls "default.pem"
+    }
+    plugin "fmt"
+    plugin "lint" enabled=true
+    path "/usr/lib"
+    level "info"
+    output "app.log"
+    allow "all"
+"#;
+
+fn parse(layers: &[&str]) -> Config {
+    let layers = layers
+        .iter()
+        .enumerate()
+        .map(|(idx, text)| (format!("layer{}.kdl", idx), *text));
+    knus::merge::parse(layers).unwrap()
+}
+
+#[test]
+fn single_layer() {
+    let config = parse(&[DEFAULTS]);
+    assert_eq!(config.name, "app");
+    assert!(!config.verbose);
+    let server = config.server.unwrap();
+    assert_eq!(server.port, 8080);
+    assert_eq!(server.tls, Some(Tls("default.pem".into())));
+    assert_eq!(config.plugins.len(), 2);
+    assert_eq!(config.paths, ["/usr/lib"]);
+    assert_eq!(config.rules, [Rule::Allow("all".into())]);
+}
+
+#[test]
+fn override_values() {
+    let config = parse(&[
+        DEFAULTS,
+        r#"
+        name "user-app"
+        verbose
+        server "example.com" retries=5 {
+            port 443
+        }
+        level "debug"
+        output rotate=7
+        "#,
+    ]);
+    assert_eq!(config.name, "user-app");
+    assert!(config.verbose);
+    let server = config.server.unwrap();
+    assert_eq!(server.host, "example.com");
+    assert_eq!(
+        server.options,
+        BTreeMap::from([("retries".into(), 5), ("timeout".into(), 10)])
+    );
+    assert_eq!(server.port, 443);
+    assert_eq!(server.tls, Some(Tls("default.pem".into())));
+    assert_eq!(config.logging.level.as_deref(), Some("debug"));
+    assert_eq!(
+        config.logging.output,
+        Some(Output {
+            path: "app.log".into(),
+            rotate: Some(7),
+        })
+    );
+}
+
+#[test]
+fn append_and_replace() {
+    let config = parse(&[
+        DEFAULTS,
+        r#"
+        plugin "test"
+        path "~/lib"
+        path "./lib"
+        deny "root"
+        "#,
+        r#"
+        plugin "bench"
+        "#,
+    ]);
+    let plugins = config
+        .plugins
+        .iter()
+        .map(|p| &p.name[..])
+        .collect::<Vec<_>>();
+    assert_eq!(plugins, ["fmt", "lint", "test", "bench"]);
+    assert_eq!(config.paths, ["~/lib", "./lib"]);
+    assert_eq!(
+        config.rules,
+        [Rule::Allow("all".into()), Rule::Deny("root".into())]
+    );
+
+    let rules: Rules = knus::merge::parse([
+        ("a.kdl", "default \"deny\"\nallow \"a\"\nallow \"b\""),
+        ("b.kdl", "default \"allow\""),
+        ("c.kdl", "deny \"c\""),
+    ])
+    .unwrap();
+    assert_eq!(
+        rules,
+        Rules {
+            default: Some("allow".into()),
+            rules: vec![Rule::Deny("c".into())],
+        }
+    );
+}
+
+#[test]
+fn delete() {
+    let config = parse(&[
+        DEFAULTS,
+        r#"
+        (delete)plugin "lint"
+        (delete)path
+        server timeout=(delete)null {
+            (delete)tls
+        }
+        "#,
+        "(delete)level",
+    ]);
+    assert_eq!(
+        config.plugins,
+        [Plugin {
+            name: "fmt".into(),
+            enabled: false,
+        }]
+    );
+    assert!(config.paths.is_empty());
+    assert_eq!(config.logging.level, None);
+    let server = config.server.unwrap();
+    assert_eq!(server.options, BTreeMap::from([("retries".into(), 3)]));
+    assert_eq!(server.tls, None);
+
+    let config = parse(&[DEFAULTS, "(delete)server"]);
+    assert_eq!(config.server, None);
+}
+
+#[test]
+fn errors_point_at_layer() {
+    let err = knus::merge::parse::<Config, _, _>([
+        ("defaults.kdl", DEFAULTS),
+        ("user.kdl", "server { port 100000; }\nplugin 1"),
+    ])
+    .unwrap_err();
+    let messages = miette::Diagnostic::related(&err)
+        .unwrap()
+        .map(|e| e.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        [
+            "number too large to fit in target type",
+            "expected string scalar, found integer",
+        ]
+    );
+    let mut text = String::new();
+    miette::NarratableReportHandler::new()
+        .render_report(&mut text, &err)
+        .unwrap();
+    assert!(text.contains("snippet for user.kdl"), "{}", text);
+    assert!(!text.contains("snippet for defaults.kdl"), "{}", text);
+
+    let err = knus::merge::parse::<Config, _, _>([
+        ("defaults.kdl", "server \"localhost\""),
+        ("user.kdl", "name \"x\""),
+    ])
+    .unwrap_err();
+    let mut text = String::new();
+    miette::NarratableReportHandler::new()
+        .render_report(&mut text, &err)
+        .unwrap();
+    assert!(text.contains("child node `port` is required"), "{}", text);
+    assert!(text.contains("snippet for defaults.kdl"), "{}", text);
+}
+
+#[test]
+fn parse_errors() {
+    let err = knus::merge::parse::<Config, _, _>([("defaults.kdl", DEFAULTS), ("user.kdl", "a {")])
+        .unwrap_err();
+    let mut text = String::new();
+    miette::NarratableReportHandler::new()
+        .render_report(&mut text, &err)
+        .unwrap();
+    assert!(text.contains("user.kdl"), "{}", text);
+}
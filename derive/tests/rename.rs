@@ -0,0 +1,206 @@
+use knus::span::Span;
+use miette::Diagnostic;
+
+#[derive(knus::DecodeScalar, knus::EncodeScalar, Debug, PartialEq)]
+#[knus(rename_all = "snake_case")]
+enum Level {
+    Debug,
+    #[knus(alias = "information")]
+    Info,
+    #[knus(rename = "warn", alias = "warning")]
+    Warning,
+}
+
+#[derive(knus::Decode, knus::Encode, knus::Merge, Debug, PartialEq)]
+#[knus(rename_all = "camelCase")]
+struct Server {
+    #[knus(property)]
+    max_connections: u32,
+    #[knus(property, rename = "host", alias = "hostname", alias = "addr")]
+    host_name: String,
+    #[knus(child, unwrap(argument))]
+    listen_port: u16,
+    #[knus(child, unwrap(argument), rename = "log", alias = "logging", default)]
+    log_level: Option<Level>,
+    #[knus(children(name = "plugin"), alias = "extension", unwrap(argument))]
+    plugins: Vec<String>,
+}
+
+#[derive(knus::Decode, knus::Encode, Debug, PartialEq)]
+#[knus(rename_all = "PascalCase")]
+enum Action {
+    AllowAll,
+    #[knus(rename = "deny", alias = "reject")]
+    DenyAll(#[knus(argument)] String),
+}
+
+#[derive(knus::DecodeScalar, Debug, PartialEq)]
+#[knus(rename_all = "verbatim")]
+enum Mode {
+    ReadOnly,
+    ReadWrite,
+}
+
+#[derive(knus::Decode, Debug, PartialEq)]
+#[knus(rename_all = "verbatim")]
+struct Verbatim {
+    #[knus(property)]
+    r#type: String,
+    #[knus(property)]
+    access_mode: Mode,
+}
+
+#[derive(knus::Decode, knus::Merge, Debug, PartialEq)]
+struct Config {
+    #[knus(child, alias = "server")]
+    main_server: Server,
+}
+
+fn parse<T: knus::Decode<Span>>(text: &str) -> T {
+    let mut nodes: Vec<T> = knus::parse("<test>", text).unwrap();
+    assert_eq!(nodes.len(), 1);
+    nodes.remove(0)
+}
+
+fn parse_err<T: knus::Decode<Span> + std::fmt::Debug>(text: &str) -> String {
+    let err = knus::parse::<Vec<T>>("<test>", text).unwrap_err();
+    err.related()
+        .unwrap()
+        .map(|e| e.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn rename_all_and_rename() {
+    let server = parse::<Server>(
+        r#"server maxConnections=10 host="localhost" {
+            listenPort 80
+            log "warn"
+            plugin "a"
+        }"#,
+    );
+    assert_eq!(
+        server,
+        Server {
+            max_connections: 10,
+            host_name: "localhost".into(),
+            listen_port: 80,
+            log_level: Some(Level::Warning),
+            plugins: vec!["a".into()],
+        }
+    );
+    assert_eq!(
+        parse_err::<Server>(r#"server max-connections=1 host="x" { listenPort 1; }"#),
+        "unexpected property `max-connections`"
+    );
+    assert_eq!(
+        parse_err::<Server>(r#"server maxConnections=1 hostName="x" { listenPort 1; }"#),
+        "unexpected property `hostName`"
+    );
+}
+
+#[test]
+fn aliases() {
+    let server = parse::<Server>(
+        r#"server maxConnections=1 addr="127.0.0.1" {
+            listenPort 8080
+            logging "information"
+            extension "a"
+            plugin "b"
+        }"#,
+    );
+    assert_eq!(server.host_name, "127.0.0.1");
+    assert_eq!(server.log_level, Some(Level::Info));
+    assert_eq!(server.plugins, vec!["a", "b"]);
+
+    let server =
+        parse::<Server>(r#"server maxConnections=1 hostname="h" { listenPort 1; log "warning"; }"#);
+    assert_eq!(server.host_name, "h");
+    assert_eq!(server.log_level, Some(Level::Warning));
+
+    let config: Config = knus::merge::parse([
+        (
+            "base.kdl",
+            r#"server maxConnections=1 host="h" { listenPort 1; }"#,
+        ),
+        ("layer.kdl", r#"server maxConnections=2 { listenPort 2; }"#),
+    ])
+    .unwrap();
+    assert_eq!(config.main_server.host_name, "h");
+    assert_eq!(config.main_server.listen_port, 2);
+}
+
+#[test]
+fn merge_aliases() {
+    let config: Config = knus::merge::parse([
+        (
+            "base.kdl",
+            r#"server maxConnections=1 host="h" { listenPort 1; log "warn"; }"#,
+        ),
+        (
+            "layer.kdl",
+            r#"main-server maxConnections=2 { logging "debug"; }"#,
+        ),
+    ])
+    .unwrap();
+    assert_eq!(config.main_server.max_connections, 2);
+    assert_eq!(config.main_server.host_name, "h");
+    assert_eq!(config.main_server.listen_port, 1);
+    assert_eq!(config.main_server.log_level, Some(Level::Debug));
+}
+
+#[test]
+fn variants() {
+    assert_eq!(parse::<Action>("AllowAll"), Action::AllowAll);
+    assert_eq!(parse::<Action>(r#"deny "x""#), Action::DenyAll("x".into()));
+    assert_eq!(
+        parse::<Action>(r#"reject "y""#),
+        Action::DenyAll("y".into())
+    );
+    assert_eq!(
+        parse_err::<Action>("allow-all"),
+        "expected one of `AllowAll`, `deny`"
+    );
+}
+
+#[test]
+fn scalar() {
+    let server =
+        parse::<Server>(r#"server maxConnections=1 host="h" { listenPort 1; log "debug"; }"#);
+    assert_eq!(server.log_level, Some(Level::Debug));
+    assert_eq!(
+        parse_err::<Server>(r#"server maxConnections=1 host="h" { listenPort 1; log "Info"; }"#),
+        "expected one of `debug`, `info`, `warn`"
+    );
+}
+
+#[test]
+fn verbatim() {
+    let node = parse::<Verbatim>(r#"node type="x" access_mode="ReadWrite""#);
+    assert_eq!(
+        node,
+        Verbatim {
+            r#type: "x".into(),
+            access_mode: Mode::ReadWrite,
+        }
+    );
+}
+
+#[test]
+fn encode_primary_name() {
+    let text = knus::to_string(&vec![Server {
+        max_connections: 2,
+        host_name: "h".into(),
+        listen_port: 1,
+        log_level: Some(Level::Warning),
+        plugins: vec!["p".into()],
+    }])
+    .unwrap();
+    assert_eq!(
+        text,
+        "- host=\"h\" maxConnections=2 {\n    listenPort 1\n    log \"warn\"\n    plugin \"p\"\n}\n"
+    );
+    let text = knus::to_string(&vec![Action::DenyAll("x".into())]).unwrap();
+    assert_eq!(text, "deny \"x\"\n");
+}
@@ -0,0 +1,302 @@
+use std::collections::BTreeMap;
+
+use knus::schema::{NodeSchema, Schema, ValueType};
+use knus::traits::DecodeSchema;
+
+#[derive(knus_derive::DecodeScalar, Debug, PartialEq)]
+enum Mode {
+    Fast,
+    Slow,
+}
+
+#[derive(knus_derive::Decode, knus_derive::DecodeSchema, Debug, PartialEq)]
+struct Args {
+    #[knus(argument)]
+    name: String,
+    #[knus(argument)]
+    alias: Option<String>,
+    #[knus(arguments)]
+    rest: Vec<u32>,
+}
+
+#[derive(knus_derive::Decode, knus_derive::DecodeSchema, Debug, PartialEq)]
+struct Props {
+    #[knus(property)]
+    label: String,
+    #[knus(property)]
+    mode: Option<Mode>,
+    #[knus(property, default = 1)]
+    level: u8,
+    #[knus(property, str)]
+    addr: std::net::SocketAddr,
+}
+
+#[derive(knus_derive::Decode, knus_derive::DecodeSchema, Debug, PartialEq)]
+struct Extra {
+    #[knus(properties)]
+    extra: BTreeMap<String, i64>,
+}
+
+#[derive(knus_derive::Decode, knus_derive::DecodeSchema, Debug, PartialEq)]
+struct Children {
+    #[knus(child)]
+    main: Args,
+    #[knus(child)]
+    other: Option<Props>,
+    #[knus(child)]
+    enabled: bool,
+    #[knus(children(name = "extra"))]
+    items: Vec<Extra>,
+    #[knus(child, unwrap(argument))]
+    title: String,
+    #[knus(children(name = "tag"), unwrap(argument))]
+    tags: Vec<String>,
+}
+
+#[derive(knus_derive::Decode, knus_derive::DecodeSchema, Debug, PartialEq)]
+struct Document {
+    #[knus(children)]
+    nodes: Vec<Variant>,
+}
+
+#[derive(knus_derive::Decode, knus_derive::DecodeSchema, Debug, PartialEq)]
+enum Variant {
+    Unit,
+    Nested(Args),
+    Tuple(
+        #[knus(argument)] u32,
+        #[knus(property(name = "x"))] Option<f64>,
+    ),
+}
+
+#[test]
+fn arguments() {
+    let schema = Args::schema();
+    let values = schema.values.unwrap();
+    assert_eq!(values.min, Some(1));
+    assert_eq!(values.max, None);
+    assert_eq!(
+        values.validations.types,
+        [ValueType::String, ValueType::Null, ValueType::Number]
+    );
+    assert!(schema.props.is_empty());
+    assert!(schema.children.is_none());
+}
+
+#[test]
+fn properties() {
+    let schema = Props::schema();
+    assert!(schema.values.is_none());
+    assert!(!schema.other_props_allowed);
+    let props = schema
+        .props
+        .iter()
+        .map(|p| (&p.name[..], p.required))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        props,
+        [
+            ("label", true),
+            ("mode", false),
+            ("level", false),
+            ("addr", true)
+        ]
+    );
+    assert_eq!(schema.props[1].validations.enum_values.len(), 2);
+    assert_eq!(
+        schema.props[1].validations.types,
+        [ValueType::String, ValueType::Null]
+    );
+    assert_eq!(schema.props[2].validations.format, ["u8"]);
+    assert_eq!(schema.props[3].validations.types, [ValueType::String]);
+    assert!(Extra::schema().other_props_allowed);
+}
+
+#[test]
+fn children() {
+    let schema = Children::schema();
+    let children = schema.children.unwrap();
+    let nodes = children
+        .nodes
+        .iter()
+        .map(|n| (n.name.as_deref().unwrap(), n.min, n.max))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        nodes,
+        [
+            ("main", Some(1), Some(1)),
+            ("other", None, Some(1)),
+            ("enabled", None, Some(1)),
+            ("extra", None, None),
+            ("title", Some(1), Some(1)),
+            ("tag", None, None),
+        ]
+    );
+    assert_eq!(children.nodes[0].values, Args::schema().values);
+    assert_eq!(
+        children.nodes[2],
+        NodeSchema {
+            name: Some("enabled".into()),
+            max: Some(1),
+            ..NodeSchema::default()
+        }
+    );
+    let title = children.nodes[4].values.as_ref().unwrap();
+    assert_eq!((title.min, title.max), (Some(1), Some(1)));
+    assert!(!children.other_nodes_allowed);
+}
+
+#[test]
+fn variants() {
+    let schema = Schema::for_document::<Document>();
+    let names = schema
+        .nodes
+        .iter()
+        .map(|n| n.name.as_deref().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["unit", "nested", "tuple"]);
+    assert_eq!(schema.nodes[0].values, None);
+    assert_eq!(schema.nodes[1].values, Args::schema().values);
+    assert_eq!(schema.nodes[2].props[0].name, "x");
+}
+
+#[test]
+fn validate() {
+    let schema = Schema::for_document::<Document>();
+    let text = r#"
+        unit
+        nested "a" null 1 2
+        tuple 1 x=2.5
+    "#;
+    schema.validate_text("<test>", text).unwrap();
+    knus::parse::<Document>("<test>", text).unwrap();
+
+    let text = r#"
+        unit 1
+        nested
+        tuple "x"
+        other
+    "#;
+    let err = schema.validate_text("<test>", text).unwrap_err();
+    assert_eq!(miette::Diagnostic::related(&err).unwrap().count(), 4);
+    assert!(knus::parse::<Document>("<test>", text).is_err());
+}
+
+#[test]
+fn roundtrip() {
+    let schema = Schema::for_document::<Children>();
+    let text = knus::to_string(&schema).unwrap();
+    assert_eq!(Schema::parse("<schema>", &text).unwrap(), schema);
+    assert!(text.starts_with("document {\n    node \"main\" {\n        min 1\n"));
+}
+
+#[derive(knus_derive::Decode, knus_derive::DecodeSchema, Debug, PartialEq)]
+struct Item {
+    #[knus(argument)]
+    name: String,
+    #[knus(children(name = "item"))]
+    items: Vec<Item>,
+}
+
+#[derive(knus_derive::Decode, knus_derive::DecodeSchema, Debug, PartialEq)]
+enum Expr {
+    Value(#[knus(argument)] i64),
+    Sum(#[knus(children)] Vec<Expr>),
+}
+
+#[derive(knus_derive::Decode, knus_derive::DecodeSchema, Debug, PartialEq)]
+struct Exprs {
+    #[knus(children)]
+    exprs: Vec<Expr>,
+}
+
+#[test]
+fn recursive() {
+    let schema = Item::schema();
+    let inner = &schema.children.as_ref().unwrap().nodes[0];
+    assert_eq!(inner.name.as_deref(), Some("item"));
+    assert!(inner.other_props_allowed);
+    let schema = Schema {
+        nodes: Item::children_schema(),
+        other_nodes_allowed: false,
+    };
+    schema
+        .validate_text("<test>", r#"item "a" { item "b" { item "c" x=1; }; }"#)
+        .unwrap();
+    assert!(schema.validate_text("<test>", "item").is_err());
+
+    let schema = Schema::for_document::<Exprs>();
+    let names = schema
+        .nodes
+        .iter()
+        .map(|n| n.name.as_deref().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["value", "sum"]);
+    schema
+        .validate_text("<test>", "sum { value 1; sum { value 2; }; }")
+        .unwrap();
+    assert!(schema.validate_text("<test>", "value; sum").is_err());
+    // the recursive occurrence allows any node
+    schema.validate_text("<test>", "sum { other; }").unwrap();
+}
+
+#[derive(knus_derive::DecodeScalar, Debug, PartialEq)]
+enum Level {
+    #[knus(alias = "information")]
+    Info,
+    Debug,
+}
+
+#[derive(knus_derive::Decode, knus_derive::DecodeSchema, Debug, PartialEq)]
+struct Server {
+    #[knus(property, alias = "max")]
+    max_connections: u32,
+    #[knus(property, default)]
+    level: Option<Level>,
+    #[knus(child, alias = "flag")]
+    enabled: bool,
+}
+
+#[derive(knus_derive::Decode, knus_derive::DecodeSchema, Debug, PartialEq)]
+enum Action {
+    #[knus(rename = "new-name", alias = "old-name")]
+    Renamed,
+}
+
+#[derive(knus_derive::Decode, knus_derive::DecodeSchema, Debug, PartialEq)]
+struct Aliased {
+    #[knus(child, alias = "srv")]
+    server: Server,
+    #[knus(children(name = "user"), alias = "member", unwrap(argument))]
+    users: Vec<String>,
+    #[knus(children)]
+    actions: Vec<Action>,
+}
+
+#[test]
+fn aliases() {
+    let schema = Schema::for_document::<Aliased>();
+    assert_eq!(schema.nodes[0].aliases, ["srv"]);
+    assert_eq!(schema.nodes[0].props[0].aliases, ["max"]);
+    let text = r#"
+        srv max=10 level="information" { flag; }
+        user "a"
+        member "b"
+        old-name
+    "#;
+    schema.validate_text("<test>", text).unwrap();
+    knus::parse::<Aliased>("<test>", text).unwrap();
+
+    let text = "server max-connections=1; srv max=2";
+    assert!(schema.validate_text("<test>", text).is_err());
+    assert!(knus::parse::<Aliased>("<test>", text).is_err());
+
+    let text = knus::to_string(&schema).unwrap();
+    assert!(!text.contains("srv"), "{}", text);
+    let text = knus::to_document_with_context::<_, knus::span::Span, _>(&schema, |ctx| {
+        ctx.set(knus::schema::EncodeAliases)
+    })
+    .unwrap()
+    .to_string();
+    assert_eq!(Schema::parse("<schema>", &text).unwrap(), schema);
+}
@@ -0,0 +1,156 @@
+use std::fmt;
+
+use knus::span::Span;
+use knus::Decode;
+use miette::Diagnostic;
+
+#[derive(knus::DecodeScalar, Debug, PartialEq)]
+enum Level {
+    Debug,
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(knus::Decode, Debug, PartialEq)]
+struct Server {
+    #[knus(argument)]
+    host: String,
+    #[knus(property, default)]
+    timeout: u32,
+    #[knus(property, default)]
+    retries: u32,
+    #[knus(child, unwrap(argument))]
+    listen: Option<String>,
+    #[knus(child, unwrap(argument))]
+    level: Option<Level>,
+    #[knus(children(name = "plugin"), unwrap(argument))]
+    plugins: Vec<String>,
+}
+
+#[derive(knus::Decode, Debug, PartialEq)]
+struct Flag {
+    #[knus(child)]
+    verbose: bool,
+}
+
+#[derive(knus::Decode, Debug, PartialEq)]
+enum Action {
+    Allow(#[knus(argument)] String),
+    Deny(#[knus(argument)] String),
+    RateLimit(#[knus(argument)] u32),
+}
+
+#[derive(knus::Decode, Debug, PartialEq)]
+struct Borrowed<'a> {
+    #[knus(property)]
+    name: &'a str,
+}
+
+fn help<T: Decode<Span> + fmt::Debug>(text: &str) -> Vec<(String, Option<String>)> {
+    let err = knus::parse::<Vec<T>>("<test>", text).unwrap_err();
+    err.related()
+        .unwrap()
+        .map(|e| (e.to_string(), e.help().map(|h| h.to_string())))
+        .collect()
+}
+
+fn pair(message: &str, help: &str) -> (String, Option<String>) {
+    (message.into(), Some(help.into()))
+}
+
+#[test]
+fn unknown_property() {
+    assert_eq!(
+        help::<Server>(r#"server "a" timout=1"#),
+        [pair(
+            "unexpected property `timout`",
+            "did you mean `timeout`? expected one of: `timeout`, `retries`"
+        )]
+    );
+    assert_eq!(
+        help::<Server>(r#"server "a" port=1"#),
+        [pair(
+            "unexpected property `port`",
+            "expected one of: `timeout`, `retries`"
+        )]
+    );
+}
+
+#[test]
+fn unknown_node() {
+    assert_eq!(
+        help::<Server>(r#"server "a" { lisen "x"; plugins "y"; }"#),
+        [
+            pair(
+                "unexpected node `lisen`",
+                "did you mean `listen`? expected one of: \
+                 `listen`, `level`, `plugin`"
+            ),
+            pair(
+                "unexpected node `plugins`",
+                "did you mean `plugin`? expected one of: \
+                 `listen`, `level`, `plugin`"
+            ),
+        ]
+    );
+    assert_eq!(
+        help::<Flag>("flag { verbose; quiet; }"),
+        [pair(
+            "unexpected node `quiet`",
+            "expected one of: `verbose`"
+        )]
+    );
+}
+
+#[test]
+fn unknown_variant() {
+    assert_eq!(
+        help::<Action>(r#"rate-limt 10"#),
+        [pair(
+            "expected one of `allow`, `deny`, `rate-limit`",
+            "did you mean `rate-limit`?"
+        )]
+    );
+    // the message lists the variants, so there is no help without suggestion
+    assert_eq!(
+        help::<Action>(r#"run 10"#),
+        [(
+            "expected one of `allow`, `deny`, `rate-limit`".to_string(),
+            None
+        )]
+    );
+}
+
+#[test]
+fn unknown_scalar() {
+    assert_eq!(
+        help::<Server>(r#"server "a" { level "fatal"; }"#),
+        [pair(
+            "expected `debug`, `info`, or one of 2 others",
+            "expected one of: `debug`, `info`, `warning`, `error`"
+        )]
+    );
+    assert_eq!(
+        help::<Server>(r#"server "a" { level "eror"; }"#),
+        [pair(
+            "expected `debug`, `info`, or one of 2 others",
+            "did you mean `error`? expected one of: \
+             `debug`, `info`, `warning`, `error`"
+        )]
+    );
+}
+
+#[test]
+fn borrowed() {
+    let err = knus::borrowed::parse::<Vec<Borrowed>>("<test>", r#"node nme="x""#).unwrap_err();
+    let helps = err
+        .related()
+        .unwrap()
+        .map(|e| e.help().map(|h| h.to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        helps,
+        [Some("did you mean `name`? expected one of: `name`".into())]
+    );
+}
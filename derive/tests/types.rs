@@ -42,3 +42,25 @@ fn parse_enum() {
         }
     );
 }
+
+#[test]
+fn parse_v2() {
+    assert_eq!(
+        parse::<Scalars>(
+            r#"/- kdl-version 2
+            str hello
+            u64 1234
+            f64 #inf
+            path "/hello/world"
+            boolean #true
+        "#
+        ),
+        Scalars {
+            str: "hello".into(),
+            u64: 1234,
+            f64: f64::INFINITY,
+            path: PathBuf::from("/hello/world"),
+            boolean: true,
+        }
+    );
+}
@@ -0,0 +1,109 @@
+use knus::decode::Context;
+use knus::span::Span;
+use knus::traits::DecodeChildren;
+use miette::Diagnostic;
+
+#[derive(knus::Decode, Debug, PartialEq)]
+#[knus(allow_unknown)]
+struct Plugin {
+    #[knus(argument)]
+    name: String,
+    #[knus(property, default)]
+    enabled: bool,
+    #[knus(child, unwrap(argument), default)]
+    priority: u32,
+}
+
+#[derive(knus::Decode, Debug, PartialEq)]
+struct Strict {
+    #[knus(argument)]
+    name: String,
+    #[knus(children(name = "plugin"))]
+    plugins: Vec<Plugin>,
+}
+
+#[derive(knus::Decode, Debug, PartialEq)]
+#[knus(allow_unknown)]
+enum Action {
+    Stop,
+    Run(#[knus(argument)] String),
+}
+
+fn decode<T: DecodeChildren<Span>>(text: &str, allow_unknown: bool) -> (T, Vec<String>) {
+    let doc = knus::parse_ast::<Span>("<test>", text).unwrap();
+    let mut ctx = Context::default();
+    ctx.set_allow_unknown(allow_unknown);
+    let value = T::decode_children(&doc.nodes, &mut ctx).unwrap();
+    assert!(!ctx.has_errors());
+    let ignored = ctx.ignored().iter().map(|e| e.to_string()).collect();
+    (value, ignored)
+}
+
+fn parse_err<T: DecodeChildren<Span> + std::fmt::Debug>(text: &str) -> String {
+    let err = knus::parse::<T>("<test>", text).unwrap_err();
+    err.related()
+        .unwrap()
+        .map(|e| e.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn container_attribute() {
+    let (plugins, ignored) = decode::<Vec<Plugin>>(
+        r#"plugin "a" 1 enabled=true version=2 { priority 3; config; }"#,
+        false,
+    );
+    assert_eq!(
+        plugins,
+        vec![Plugin {
+            name: "a".into(),
+            enabled: true,
+            priority: 3,
+        }]
+    );
+    assert_eq!(
+        ignored,
+        vec![
+            "unexpected argument",
+            "unexpected property `version`",
+            "unexpected node `config`",
+        ]
+    );
+    // nested types don't inherit the attribute
+    assert_eq!(
+        parse_err::<Vec<Strict>>(r#"strict "x" extra=1 { plugin "a" x=1; }"#),
+        "unexpected property `extra`"
+    );
+}
+
+#[test]
+fn enum_variants() {
+    let (actions, ignored) = decode::<Vec<Action>>(r#"stop 1 x=2 { y; }"#, false);
+    assert_eq!(actions, vec![Action::Stop]);
+    assert_eq!(ignored.len(), 3);
+    assert_eq!(
+        parse_err::<Vec<Action>>(r#"jump"#),
+        "expected one of `stop`, `run`"
+    );
+}
+
+#[test]
+fn context() {
+    let (strict, ignored) =
+        decode::<Vec<Strict>>(r#"strict "x" "y" extra=1 { plugin "a"; other; }"#, true);
+    assert_eq!(strict[0].name, "x");
+    assert_eq!(strict[0].plugins.len(), 1);
+    assert_eq!(
+        ignored,
+        vec![
+            "unexpected argument",
+            "unexpected property `extra`",
+            "unexpected node `other`",
+        ]
+    );
+
+    let (strict, ignored) = decode::<Vec<Strict>>(r#"strict "x""#, true);
+    assert_eq!(strict[0].name, "x");
+    assert!(ignored.is_empty());
+}
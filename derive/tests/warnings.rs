@@ -0,0 +1,149 @@
+use knus::ast::{Literal, TypeName};
+use knus::decode::Context;
+use knus::errors::DecodeError;
+use knus::span::{Span, Spanned};
+use knus::traits::{DecodeScalar, ErrorSpan};
+use miette::{Diagnostic, Severity};
+
+#[derive(Debug, PartialEq)]
+struct Timeout(u64);
+
+impl<S: ErrorSpan> DecodeScalar<S> for Timeout {
+    fn type_check(type_name: &Option<Spanned<TypeName, S>>, ctx: &mut Context<S>) {
+        <u64 as DecodeScalar<S>>::type_check(type_name, ctx)
+    }
+    fn raw_decode(
+        value: &Spanned<Literal, S>,
+        ctx: &mut Context<S>,
+    ) -> Result<Self, DecodeError<S>> {
+        let seconds = <u64 as DecodeScalar<S>>::raw_decode(value, ctx)?;
+        if seconds == 0 {
+            ctx.emit_warning(DecodeError::conversion(value, "zero timeout waits forever"));
+        } else if seconds > 3600 {
+            ctx.emit_advice(DecodeError::conversion(value, "timeout is over an hour"));
+        }
+        Ok(Timeout(seconds))
+    }
+}
+
+#[derive(knus::Decode, Debug, PartialEq)]
+struct Job {
+    #[knus(argument)]
+    name: String,
+    #[knus(property)]
+    timeout: Timeout,
+}
+
+fn parse(text: &str) -> Result<(Vec<Job>, knus::Warnings), knus::Error> {
+    knus::parse_with_context::<Vec<Job>, Span, _>("<test>", text, |_| {})
+}
+
+fn severities(diagnostic: &dyn Diagnostic) -> Vec<(Option<Severity>, String)> {
+    diagnostic
+        .related()
+        .unwrap()
+        .map(|d| (d.severity(), d.to_string()))
+        .collect()
+}
+
+#[test]
+fn no_warnings() {
+    let (jobs, warnings) = parse(r#"job "a" timeout=10"#).unwrap();
+    assert_eq!(jobs[0].timeout, Timeout(10));
+    assert!(warnings.is_empty());
+}
+
+#[test]
+fn warnings() {
+    let (jobs, warnings) = parse(
+        r#"
+        job "a" timeout=0
+        job "b" timeout=7200
+        "#,
+    )
+    .unwrap();
+    assert_eq!(jobs.len(), 2);
+    assert_eq!(warnings.len(), 2);
+    assert_eq!(warnings.severity(), Some(Severity::Warning));
+    assert_eq!(
+        severities(&warnings),
+        vec![
+            (Some(Severity::Warning), "zero timeout waits forever".into()),
+            (Some(Severity::Advice), "timeout is over an hour".into()),
+        ]
+    );
+    let diagnostics = warnings.diagnostics();
+    assert_eq!(diagnostics[0].lsp_severity(), 2);
+    assert_eq!(diagnostics[1].lsp_severity(), 3);
+    assert_eq!(diagnostics[0].range, 25..26);
+
+    // plain `parse` drops the warnings
+    let jobs = knus::parse::<Vec<Job>>("<test>", r#"job "a" timeout=0"#).unwrap();
+    assert_eq!(jobs[0].timeout, Timeout(0));
+}
+
+#[test]
+fn warnings_with_errors() {
+    let err = parse(
+        r#"
+        job "a" timeout=0
+        job timeout=1
+        "#,
+    )
+    .unwrap_err();
+    assert_eq!(
+        severities(&err),
+        vec![
+            (None, "additional argument `name` is required".into()),
+            (Some(Severity::Warning), "zero timeout waits forever".into()),
+        ]
+    );
+}
+
+#[derive(knus::Decode, Debug, PartialEq)]
+#[knus(allow_unknown)]
+struct Plugin {
+    #[knus(argument)]
+    name: String,
+}
+
+#[test]
+fn ignored() {
+    let (plugins, warnings) = knus::parse_with_context::<Vec<Plugin>, Span, _>(
+        "<test>",
+        r#"plugin "a" "b" version=2 { config; }"#,
+        |_| {},
+    )
+    .unwrap();
+    assert_eq!(plugins, vec![Plugin { name: "a".into() }]);
+    assert_eq!(
+        severities(&warnings),
+        vec![
+            (Some(Severity::Warning), "unexpected argument".into()),
+            (
+                Some(Severity::Warning),
+                "unexpected property `version`".into()
+            ),
+            (Some(Severity::Warning), "unexpected node `config`".into()),
+        ]
+    );
+    assert_eq!(warnings.diagnostics()[1].range, 15..22);
+
+    let (jobs, warnings) = knus::parse_with_context::<Vec<Job>, Span, _>(
+        "<test>",
+        r#"job "a" timeout=0 retries=3"#,
+        |ctx| ctx.set_allow_unknown(true),
+    )
+    .unwrap();
+    assert_eq!(jobs.len(), 1);
+    assert_eq!(
+        severities(&warnings),
+        vec![
+            (Some(Severity::Warning), "zero timeout waits forever".into()),
+            (
+                Some(Severity::Warning),
+                "unexpected property `retries`".into()
+            ),
+        ]
+    );
+}
@@ -0,0 +1,205 @@
+use std::ops::Range;
+
+use knus::ast::{self, SpannedNode};
+use knus::diagnostic::LineIndex;
+use knus::schema::Schema;
+use knus::span::Span;
+use lsp_types::{
+    DiagnosticRelatedInformation, DiagnosticSeverity, DocumentSymbol, FoldingRange,
+    FoldingRangeKind, Location, NumberOrString, Position, SymbolKind, TextEdit, Uri,
+};
+
+/// Open text document and its syntax tree
+pub(crate) struct Document {
+    uri: Uri,
+    text: String,
+    lines: LineIndex,
+    ast: Option<ast::Document<Span>>,
+    errors: Vec<knus::errors::ParseError<Span>>,
+}
+
+impl Document {
+    pub fn new(uri: Uri, text: String) -> Document {
+        let (ast, errors) = knus::parse_ast_recovery(&text);
+        Document {
+            uri,
+            lines: LineIndex::new(&text),
+            text,
+            ast,
+            errors,
+        }
+    }
+
+    pub fn diagnostics(&self, schema: Option<&Schema>) -> Vec<lsp_types::Diagnostic> {
+        let mut result = self
+            .errors
+            .iter()
+            .map(|e| self.diagnostic(e))
+            .collect::<Vec<_>>();
+        if let (Some(schema), Some(ast)) = (schema, &self.ast) {
+            if let Err(errors) = schema.validate(ast) {
+                result.extend(errors.iter().map(|e| self.diagnostic(e)));
+            }
+        }
+        result
+    }
+
+    fn diagnostic(&self, err: &dyn miette::Diagnostic) -> lsp_types::Diagnostic {
+        let diag =
+            knus::diagnostic::Diagnostic::with_line_index(err, None, &self.text, &self.lines);
+        let severity = match diag.severity {
+            miette::Severity::Error => DiagnosticSeverity::ERROR,
+            miette::Severity::Warning => DiagnosticSeverity::WARNING,
+            miette::Severity::Advice => DiagnosticSeverity::INFORMATION,
+        };
+        let mut message = diag.message;
+        if let Some(help) = diag.help {
+            message.push_str("\nhelp: ");
+            message.push_str(&help);
+        }
+        let related = diag
+            .labels
+            .into_iter()
+            .filter_map(|label| {
+                Some(DiagnosticRelatedInformation {
+                    location: Location {
+                        uri: self.uri.clone(),
+                        range: lsp_range(label.start, label.end),
+                    },
+                    message: label.message?,
+                })
+            })
+            .collect::<Vec<_>>();
+        lsp_types::Diagnostic {
+            range: lsp_range(diag.start, diag.end),
+            severity: Some(severity),
+            code: diag.code.map(NumberOrString::String),
+            source: Some("knus".into()),
+            message,
+            related_information: if related.is_empty() {
+                None
+            } else {
+                Some(related)
+            },
+            ..Default::default()
+        }
+    }
+
+    pub fn symbols(&self) -> Vec<DocumentSymbol> {
+        match &self.ast {
+            Some(ast) => self.node_symbols(&ast.nodes),
+            None => Vec::new(),
+        }
+    }
+
+    fn node_symbols(&self, nodes: &[SpannedNode<Span>]) -> Vec<DocumentSymbol> {
+        nodes
+            .iter()
+            .map(|node| {
+                let detail = node
+                    .arguments
+                    .iter()
+                    .map(|arg| arg.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let children = node.children.as_ref().map(|c| self.node_symbols(c));
+                #[allow(deprecated)] // `deprecated` field must be initialized
+                DocumentSymbol {
+                    name: node.node_name.to_string(),
+                    detail: if detail.is_empty() {
+                        None
+                    } else {
+                        Some(detail)
+                    },
+                    kind: if children.is_some() {
+                        SymbolKind::OBJECT
+                    } else {
+                        SymbolKind::FIELD
+                    },
+                    tags: None,
+                    deprecated: None,
+                    range: self.node_range(node.span()),
+                    selection_range: self.span_range(node.node_name.span()),
+                    children,
+                }
+            })
+            .collect()
+    }
+
+    pub fn folding_ranges(&self) -> Vec<FoldingRange> {
+        let mut result = Vec::new();
+        if let Some(ast) = &self.ast {
+            self.fold_nodes(&ast.nodes, &mut result);
+        }
+        result
+    }
+
+    fn fold_nodes(&self, nodes: &[SpannedNode<Span>], result: &mut Vec<FoldingRange>) {
+        for node in nodes {
+            let children = match &node.children {
+                Some(children) => children,
+                None => continue,
+            };
+            let start = lsp_position(self.lines.position(&self.text, node.span().0));
+            let end = lsp_position(self.lines.position(&self.text, children.span().1));
+            // keep the line with the closing brace visible
+            if end.line > start.line + 1 {
+                result.push(FoldingRange {
+                    start_line: start.line,
+                    end_line: end.line - 1,
+                    kind: Some(FoldingRangeKind::Region),
+                    ..Default::default()
+                });
+            }
+            self.fold_nodes(children, result);
+        }
+    }
+
+    /// Pretty-prints the document keeping the comments
+    ///
+    /// Uses the same formatter as `knus fmt`, so the document is indented
+    /// by `indent` spaces per level and tabs are never emitted. Returns an
+    /// error message if the document has syntax errors.
+    pub fn format(&self, indent: usize) -> Result<Vec<TextEdit>, &'static str> {
+        let cst = knus::cst::parse("", &self.text).map_err(|_| "document has syntax errors")?;
+        let text = cst.format(indent);
+        if text == self.text {
+            return Ok(Vec::new());
+        }
+        Ok(vec![TextEdit {
+            range: self.range(0..self.text.len()),
+            new_text: text,
+        }])
+    }
+
+    /// Range of the node without the newline or semicolon terminating it
+    fn node_range(&self, span: &Span) -> lsp_types::Range {
+        let text = self.text.get(span.0..span.1).unwrap_or("");
+        let len = text
+            .trim_end_matches(|c: char| c.is_whitespace() || c == ';')
+            .len();
+        self.range(span.0..span.0 + len)
+    }
+
+    fn span_range(&self, span: &Span) -> lsp_types::Range {
+        self.range(span.0..span.1)
+    }
+
+    fn range(&self, range: Range<usize>) -> lsp_types::Range {
+        lsp_range(
+            self.lines.position(&self.text, range.start),
+            self.lines.position(&self.text, range.end),
+        )
+    }
+}
+
+fn lsp_range(
+    start: knus::diagnostic::Position,
+    end: knus::diagnostic::Position,
+) -> lsp_types::Range {
+    lsp_types::Range::new(lsp_position(start), lsp_position(end))
+}
+
+fn lsp_position(pos: knus::diagnostic::Position) -> Position {
+    Position::new(pos.line as u32, pos.character as u32)
+}
@@ -0,0 +1,254 @@
+//! Language server for KDL documents
+//!
+//! Provides diagnostics (syntax errors and, optionally, errors of validation
+//! against a [KDL Schema](knus::schema)), document symbols, folding ranges
+//! and formatting. Talks JSON-RPC over stdio when started as the `knus-lsp`
+//! binary:
+//!
+//! ```shell
+//! knus-lsp --schema config.schema.kdl
+//! ```
+//!
+//! The schema may also be set by the client in the `schema` field of the
+//! initialization options.
+//!
+//! Use [`run`] with an in-memory [`Connection`] to embed the server or to
+//! test it.
+#![warn(missing_docs)]
+#![warn(missing_debug_implementations)]
+
+mod document;
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+
+use knus::schema::Schema;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _,
+    PublishDiagnostics, ShowMessage,
+};
+use lsp_types::request::{DocumentSymbolRequest, FoldingRangeRequest, Formatting, Request as _};
+use lsp_types::{
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+    DocumentSymbolResponse, FoldingRangeProviderCapability, InitializeParams, MessageType, OneOf,
+    PublishDiagnosticsParams, ServerCapabilities, ShowMessageParams, TextDocumentSyncCapability,
+    TextDocumentSyncKind, Uri,
+};
+use serde::de::DeserializeOwned;
+
+use crate::document::Document;
+
+struct Server<'a> {
+    connection: &'a Connection,
+    schema: Option<Schema>,
+    documents: BTreeMap<Uri, Document>,
+}
+
+/// Returns the capabilities of the server
+pub fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        ..ServerCapabilities::default()
+    }
+}
+
+/// Reads and parses the KDL Schema file
+pub fn load_schema(path: &str) -> Result<Schema, Box<dyn Error + Send + Sync>> {
+    let text = fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+    Ok(Schema::parse(path, &text)?)
+}
+
+/// Runs the server until the client sends `shutdown` and `exit`
+///
+/// The `schema` is used to validate all the documents unless the client
+/// specifies another one in the initialization options.
+pub fn run(
+    connection: &Connection,
+    schema: Option<Schema>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let params = connection.initialize(serde_json::to_value(capabilities())?)?;
+    let params: InitializeParams = serde_json::from_value(params)?;
+    let mut server = Server {
+        connection,
+        schema,
+        documents: BTreeMap::new(),
+    };
+    let schema_path = params
+        .initialization_options
+        .as_ref()
+        .and_then(|options| options.get("schema"))
+        .and_then(|path| path.as_str());
+    if let Some(path) = schema_path {
+        match load_schema(path) {
+            Ok(schema) => server.schema = Some(schema),
+            Err(e) => server.show_error(format!("invalid schema: {}", e))?,
+        }
+    }
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    return Ok(());
+                }
+                let response = server.request(request);
+                connection.sender.send(response.into())?;
+            }
+            Message::Notification(notification) => {
+                let method = notification.method.clone();
+                if let Err(e) = server.notification(notification) {
+                    // if the client is gone, showing the error fails too
+                    server.show_error(format!("cannot handle {}: {}", method, e))?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+impl Server<'_> {
+    fn request(&mut self, request: Request) -> Response {
+        let id = request.id.clone();
+        let result = match &request.method[..] {
+            DocumentSymbolRequest::METHOD => self
+                .handle::<DocumentSymbolRequest, _>(request, |doc, _| {
+                    Ok(Some(DocumentSymbolResponse::Nested(doc.symbols())))
+                }),
+            FoldingRangeRequest::METHOD => self
+                .handle::<FoldingRangeRequest, _>(request, |doc, _| Ok(Some(doc.folding_ranges()))),
+            // `insert_spaces` is ignored, the document is always indented
+            // with spaces
+            Formatting::METHOD => self.handle::<Formatting, _>(request, |doc, params| {
+                let edits = doc.format(params.options.tab_size as usize)?;
+                Ok(Some(edits))
+            }),
+            _ => Err((
+                ErrorCode::MethodNotFound,
+                format!("unknown method {}", request.method),
+            )),
+        };
+        match result {
+            Ok(value) => Response::new_ok(id, value),
+            Err((code, message)) => Response::new_err(id, code as i32, message),
+        }
+    }
+
+    /// Finds the document of the request and calls `f` with it
+    fn handle<R, F>(&self, request: Request, f: F) -> Result<serde_json::Value, (ErrorCode, String)>
+    where
+        R: lsp_types::request::Request,
+        R::Params: TextDocumentParams,
+        F: FnOnce(&Document, R::Params) -> Result<R::Result, &'static str>,
+    {
+        let params = parse_params::<R::Params>(request.params)
+            .map_err(|e| (ErrorCode::InvalidParams, e.to_string()))?;
+        let document = self
+            .documents
+            .get(params.uri())
+            .ok_or_else(|| (ErrorCode::InvalidParams, "document is not open".into()))?;
+        let result = f(document, params).map_err(|e| (ErrorCode::RequestFailed, e.into()))?;
+        serde_json::to_value(result).map_err(|e| (ErrorCode::InternalError, e.to_string()))
+    }
+
+    fn notification(
+        &mut self,
+        notification: Notification,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match &notification.method[..] {
+            DidOpenTextDocument::METHOD => {
+                let params: DidOpenTextDocumentParams = parse_params(notification.params)?;
+                let doc = params.text_document;
+                self.update(doc.uri, doc.text, Some(doc.version))?;
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: DidChangeTextDocumentParams = parse_params(notification.params)?;
+                // full sync: the last change has the whole text
+                if let Some(change) = params.content_changes.into_iter().last() {
+                    let doc = params.text_document;
+                    self.update(doc.uri, change.text, Some(doc.version))?;
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: DidCloseTextDocumentParams = parse_params(notification.params)?;
+                let uri = params.text_document.uri;
+                self.documents.remove(&uri);
+                self.publish(uri, Vec::new(), None)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn update(
+        &mut self,
+        uri: Uri,
+        text: String,
+        version: Option<i32>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let document = Document::new(uri.clone(), text);
+        let diagnostics = document.diagnostics(self.schema.as_ref());
+        self.documents.insert(uri.clone(), document);
+        self.publish(uri, diagnostics, version)
+    }
+
+    fn publish(
+        &self,
+        uri: Uri,
+        diagnostics: Vec<lsp_types::Diagnostic>,
+        version: Option<i32>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.notify::<PublishDiagnostics>(PublishDiagnosticsParams {
+            uri,
+            diagnostics,
+            version,
+        })
+    }
+
+    fn show_error(&self, message: String) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.notify::<ShowMessage>(ShowMessageParams {
+            typ: MessageType::ERROR,
+            message,
+        })
+    }
+
+    fn notify<N: lsp_types::notification::Notification>(
+        &self,
+        params: N::Params,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let notification = Notification::new(N::METHOD.into(), params);
+        self.connection.sender.send(notification.into())?;
+        Ok(())
+    }
+}
+
+/// Parameters of the requests that refer to a text document
+trait TextDocumentParams {
+    fn uri(&self) -> &Uri;
+}
+
+impl TextDocumentParams for lsp_types::DocumentSymbolParams {
+    fn uri(&self) -> &Uri {
+        &self.text_document.uri
+    }
+}
+
+impl TextDocumentParams for lsp_types::FoldingRangeParams {
+    fn uri(&self) -> &Uri {
+        &self.text_document.uri
+    }
+}
+
+impl TextDocumentParams for lsp_types::DocumentFormattingParams {
+    fn uri(&self) -> &Uri {
+        &self.text_document.uri
+    }
+}
+
+fn parse_params<P: DeserializeOwned>(params: serde_json::Value) -> Result<P, serde_json::Error> {
+    serde_json::from_value(params)
+}
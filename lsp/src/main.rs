@@ -0,0 +1,42 @@
+use std::env;
+use std::process::exit;
+
+use lsp_server::Connection;
+
+fn main() {
+    let mut schema = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--schema" => match args.next() {
+                Some(path) => match knus_lsp::load_schema(&path) {
+                    Ok(value) => schema = Some(value),
+                    Err(e) => {
+                        eprintln!("knus-lsp: invalid schema: {}", e);
+                        exit(2);
+                    }
+                },
+                None => {
+                    eprintln!("knus-lsp: --schema requires a path");
+                    exit(2);
+                }
+            },
+            // editors often pass the transport explicitly
+            "--stdio" => {}
+            _ => {
+                eprintln!("Usage: knus-lsp [--stdio] [--schema <path>]");
+                exit(2);
+            }
+        }
+    }
+    let (connection, io_threads) = Connection::stdio();
+    if let Err(e) = knus_lsp::run(&connection, schema) {
+        eprintln!("knus-lsp: {}", e);
+        exit(1);
+    }
+    drop(connection);
+    if let Err(e) = io_threads.join() {
+        eprintln!("knus-lsp: {}", e);
+        exit(1);
+    }
+}
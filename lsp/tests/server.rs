@@ -0,0 +1,287 @@
+use std::thread;
+
+use lsp_server::{Connection, Message, Notification, Request, RequestId, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Exit, Initialized,
+    Notification as _, PublishDiagnostics,
+};
+use lsp_types::request::{
+    DocumentSymbolRequest, FoldingRangeRequest, Formatting, Initialize, Shutdown,
+};
+use lsp_types::{
+    DiagnosticSeverity, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, DocumentFormattingParams, DocumentSymbolParams,
+    DocumentSymbolResponse, FoldingRange, FoldingRangeParams, FormattingOptions, InitializeParams,
+    Position, PublishDiagnosticsParams, Range, SymbolKind, TextDocumentIdentifier,
+    TextDocumentItem, TextEdit, Uri, VersionedTextDocumentIdentifier,
+};
+use serde_json::{json, Value};
+
+const SCHEMA: &str = r#"
+document {
+    node "server" {
+        min 1
+        value { type "string"; }
+        prop "port" { type "number"; }
+    }
+}
+"#;
+
+struct Client {
+    connection: Connection,
+    server: Option<thread::JoinHandle<()>>,
+    next_id: i32,
+}
+
+impl Client {
+    fn start(schema: Option<&str>) -> Client {
+        let (connection, server_connection) = Connection::memory();
+        let schema = schema.map(|text| knus::schema::Schema::parse("schema.kdl", text).unwrap());
+        let server = thread::spawn(move || knus_lsp::run(&server_connection, schema).unwrap());
+        let mut client = Client {
+            connection,
+            server: Some(server),
+            next_id: 0,
+        };
+        let result = client.request::<Initialize>(InitializeParams::default());
+        assert_eq!(result["capabilities"]["documentSymbolProvider"], true);
+        client.notify::<Initialized>(json!({}));
+        client
+    }
+
+    fn request<R: lsp_types::request::Request>(&mut self, params: R::Params) -> Value {
+        match self.try_request::<R>(params).result {
+            Some(value) => value,
+            None => panic!("request {} failed", R::METHOD),
+        }
+    }
+
+    fn try_request<R: lsp_types::request::Request>(&mut self, params: R::Params) -> Response {
+        self.next_id += 1;
+        let id = RequestId::from(self.next_id);
+        let request = Request::new(id.clone(), R::METHOD.into(), params);
+        self.connection.sender.send(request.into()).unwrap();
+        loop {
+            match self.connection.receiver.recv().unwrap() {
+                Message::Response(response) if response.id == id => return response,
+                _ => {}
+            }
+        }
+    }
+
+    fn notify<N: lsp_types::notification::Notification>(&self, params: impl serde::Serialize) {
+        let notification = Notification::new(N::METHOD.into(), params);
+        self.connection.sender.send(notification.into()).unwrap();
+    }
+
+    fn diagnostics(&self) -> PublishDiagnosticsParams {
+        loop {
+            if let Message::Notification(n) = self.connection.receiver.recv().unwrap() {
+                if n.method == PublishDiagnostics::METHOD {
+                    return serde_json::from_value(n.params).unwrap();
+                }
+            }
+        }
+    }
+
+    fn open(&self, text: &str) -> PublishDiagnosticsParams {
+        self.notify::<DidOpenTextDocument>(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem {
+                uri: uri(),
+                language_id: "kdl".into(),
+                version: 1,
+                text: text.into(),
+            },
+        });
+        self.diagnostics()
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            return;
+        }
+        self.request::<Shutdown>(());
+        self.notify::<Exit>(());
+        self.server.take().unwrap().join().unwrap();
+    }
+}
+
+fn uri() -> Uri {
+    "file:///config.kdl".parse().unwrap()
+}
+
+fn range(start: (u32, u32), end: (u32, u32)) -> Range {
+    Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1))
+}
+
+fn formatting(client: &mut Client) -> Response {
+    client.try_request::<Formatting>(DocumentFormattingParams {
+        text_document: TextDocumentIdentifier { uri: uri() },
+        options: FormattingOptions {
+            tab_size: 2,
+            insert_spaces: true,
+            ..FormattingOptions::default()
+        },
+        work_done_progress_params: Default::default(),
+    })
+}
+
+#[test]
+fn diagnostics() {
+    let client = Client::start(None);
+    let published = client.open("node 1\u{2028}x\nчисло \"😀\" )\nother");
+    assert_eq!(published.uri, uri());
+    assert_eq!(published.version, Some(1));
+    assert_eq!(published.diagnostics.len(), 1);
+    let diag = &published.diagnostics[0];
+    assert_eq!(diag.range, range((1, 11), (1, 12)));
+    assert_eq!(diag.severity, Some(DiagnosticSeverity::ERROR));
+    assert_eq!(diag.source.as_deref(), Some("knus"));
+
+    client.notify::<DidChangeTextDocument>(DidChangeTextDocumentParams {
+        text_document: VersionedTextDocumentIdentifier {
+            uri: uri(),
+            version: 2,
+        },
+        content_changes: vec![lsp_types::TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: "node 1\n".into(),
+        }],
+    });
+    let published = client.diagnostics();
+    assert_eq!(published.version, Some(2));
+    assert!(published.diagnostics.is_empty());
+
+    client.notify::<DidCloseTextDocument>(DidCloseTextDocumentParams {
+        text_document: TextDocumentIdentifier { uri: uri() },
+    });
+    assert!(client.diagnostics().diagnostics.is_empty());
+}
+
+#[test]
+fn schema() {
+    let client = Client::start(Some(SCHEMA));
+    let published = client.open("server \"localhost\" port=\"x\"\nclient");
+    let messages = published
+        .diagnostics
+        .iter()
+        .map(|d| (d.range, d.message.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        [
+            (range((0, 24), (0, 27)), "expected number, found string"),
+            (range((1, 0), (1, 6)), "unexpected node `client`"),
+        ]
+    );
+}
+
+#[test]
+fn symbols_and_folding() {
+    let mut client = Client::start(None);
+    client.open("server \"a\" 1 {\n    listen 80\n    tls {\n        cert \"x\"\n    }\n}\nflag");
+    let params = DocumentSymbolParams {
+        text_document: TextDocumentIdentifier { uri: uri() },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    let symbols: DocumentSymbolResponse =
+        serde_json::from_value(client.request::<DocumentSymbolRequest>(params)).unwrap();
+    let symbols = match symbols {
+        DocumentSymbolResponse::Nested(symbols) => symbols,
+        _ => unreachable!(),
+    };
+    assert_eq!(symbols.len(), 2);
+    assert_eq!(symbols[0].name, "server");
+    assert_eq!(symbols[0].detail.as_deref(), Some("\"a\" 1"));
+    assert_eq!(symbols[0].kind, SymbolKind::OBJECT);
+    assert_eq!(symbols[0].selection_range, range((0, 0), (0, 6)));
+    assert_eq!(symbols[0].range, range((0, 0), (5, 1)));
+    let children = symbols[0].children.as_ref().unwrap();
+    assert_eq!(children[0].name, "listen");
+    assert_eq!(children[0].kind, SymbolKind::FIELD);
+    assert_eq!(children[1].name, "tls");
+    assert_eq!(children[1].children.as_ref().unwrap()[0].name, "cert");
+    assert_eq!(symbols[1].name, "flag");
+    assert_eq!(symbols[1].children, None);
+
+    let params = FoldingRangeParams {
+        text_document: TextDocumentIdentifier { uri: uri() },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    let ranges: Vec<FoldingRange> =
+        serde_json::from_value(client.request::<FoldingRangeRequest>(params)).unwrap();
+    let lines = ranges
+        .iter()
+        .map(|r| (r.start_line, r.end_line))
+        .collect::<Vec<_>>();
+    assert_eq!(lines, [(0, 4), (2, 3)]);
+}
+
+#[test]
+fn formatting_document() {
+    let mut client = Client::start(None);
+    client.open("server   \"a\" port=1 host=\"x\" {listen 80;}\n");
+    let edits: Vec<TextEdit> =
+        serde_json::from_value(formatting(&mut client).result.unwrap()).unwrap();
+    assert_eq!(
+        edits,
+        [TextEdit {
+            range: range((0, 0), (1, 0)),
+            new_text: "server \"a\" port=1 host=\"x\" {\n  listen 80\n}\n".into(),
+        }]
+    );
+
+    client.open("server \"a\" {\n  listen 80\n}\n");
+    assert_eq!(formatting(&mut client).result, Some(json!([])));
+
+    client.open("server   1 // main\n");
+    let edits: Vec<TextEdit> =
+        serde_json::from_value(formatting(&mut client).result.unwrap()).unwrap();
+    assert_eq!(edits[0].new_text, "server 1 // main\n");
+
+    client.open("/- kdl-version 2\nserver   #true\n");
+    let edits: Vec<TextEdit> =
+        serde_json::from_value(formatting(&mut client).result.unwrap()).unwrap();
+    assert_eq!(edits[0].new_text, "/- kdl-version 2\nserver #true\n");
+
+    client.open("server {\n");
+    let error = formatting(&mut client).error.unwrap();
+    assert_eq!(error.message, "document has syntax errors");
+}
+
+#[test]
+fn unknown_method() {
+    let mut client = Client::start(None);
+    let response = client.try_request::<lsp_types::request::HoverRequest>(
+        serde_json::from_value(json!({
+            "textDocument": {"uri": "file:///config.kdl"},
+            "position": {"line": 0, "character": 0},
+        }))
+        .unwrap(),
+    );
+    assert_eq!(
+        response.error.unwrap().code,
+        lsp_server::ErrorCode::MethodNotFound as i32
+    );
+}
+
+#[test]
+fn invalid_notification() {
+    let client = Client::start(None);
+    client.notify::<DidOpenTextDocument>(json!({"textDocument": 1}));
+    let message = loop {
+        if let Message::Notification(n) = client.connection.receiver.recv().unwrap() {
+            if n.method == lsp_types::notification::ShowMessage::METHOD {
+                break n.params["message"].as_str().unwrap().to_string();
+            }
+        }
+    };
+    assert!(message.starts_with("cannot handle textDocument/didOpen: "));
+    // the server keeps working
+    assert!(client.open("node 1").diagnostics.is_empty());
+}
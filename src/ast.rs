@@ -180,6 +180,51 @@ impl<S> Node<S> {
     }
 }
 
+/// Drops the nodes and their children without recursion
+///
+/// Dropping the nodes as usual recurses into the children. The documents
+/// owned by the library are dropped using this function instead, so that
+/// deeply nested documents don't overflow the stack.
+pub(crate) fn drop_nodes<S>(mut stack: Vec<SpannedNode<S>>) {
+    while let Some(mut node) = stack.pop() {
+        if let Some(children) = node.children.take() {
+            stack.extend(children.value);
+        }
+    }
+}
+
+impl<S: Default> Node<S> {
+    /// Creates a node with the specified name and nothing else
+    ///
+    /// All spans are set to default values, this is mostly useful for
+    /// building documents from scratch (i.e. when encoding).
+    pub fn new(name: impl Into<Box<str>>) -> Node<S> {
+        Node {
+            type_name: None,
+            node_name: Spanned::new(name.into(), S::default()),
+            arguments: Vec::new(),
+            properties: BTreeMap::new(),
+            children: None,
+        }
+    }
+    /// Appends a child node, adding braces `{..}` if there were none
+    pub fn push_child(&mut self, child: Node<S>) {
+        self.children
+            .get_or_insert_with(|| Spanned::new(Vec::new(), S::default()))
+            .push(Spanned::new(child, S::default()));
+    }
+}
+
+impl<S: Default> Value<S> {
+    /// Creates a value without type name and with default span
+    pub fn new(literal: Literal) -> Value<S> {
+        Value {
+            type_name: None,
+            literal: Spanned::new(literal, S::default()),
+        }
+    }
+}
+
 impl BuiltinType {
     /// Returns string representation of the builtin type as defined by KDL
     /// specification
@@ -0,0 +1,797 @@
+//! Abstract syntax tree borrowing from the source text
+//!
+//! This is the same tree as in [`ast`](crate::ast), except that names,
+//! strings and numbers are [`Cow`] values that borrow from the source text
+//! whenever possible, i.e. unless a string has escapes or a number has
+//! underscores. So parsing a large document doesn't allocate a string per
+//! token. Type names are kept as [`ast::TypeName`].
+//!
+//! Types that borrow from the document implement the traits of this module
+//! instead of the ones in [`traits`](crate::traits).
+//! [`Decode`](derive@crate::Decode) derive implements them for types that
+//! have a lifetime parameter:
+//!
+//! ```rust
+//! use std::borrow::Cow;
+//!
+//! #[derive(knus::Decode, Debug)]
+//! struct Server<'a> {
+//!     #[knus(argument)]
+//!     host: &'a str,
+//!     #[knus(property)]
+//!     comment: Option<Cow<'a, str>>,
+//! }
+//!
+//! let text = r#"server "example.org" comment="main\tserver""#;
+//! let servers: Vec<Server> = knus::borrowed::parse("config.kdl", text)?;
+//! assert_eq!(servers[0].host, "example.org");
+//! assert!(matches!(servers[0].comment, Some(Cow::Owned(_))));
+//! # Ok::<(), knus::Error>(())
+//! ```
+//!
+//! Strings containing escapes can't be decoded as `&str`, use [`Cow`] for
+//! the fields that may contain them.
+//!
+//! Both versions of the syntax are parsed without copying, except for the
+//! KDL 2.0 multi-line strings which are always dedented into a new string.
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use crate::ast::{self, Radix, TypeName};
+use crate::decode::{Context, Kind};
+use crate::errors::{DecodeError, Error, ParseErrorKind, Warnings};
+use crate::grammar::{Builder, Parsed, ParsedEntry, ParsedHead, ParsedType, ParsedValue};
+use crate::grammar::{Parser, Syntax};
+use crate::span::{Span, Spanned};
+use crate::traits::sealed::SpanTracker;
+use crate::traits::{self, ErrorSpan};
+use crate::wrappers::ParseOptions;
+
+/// A shortcut for nodes children that includes span of enclosing braces `{..}`
+pub type SpannedChildren<'a, S> = Spanned<Vec<SpannedNode<'a, S>>, S>;
+/// KDL names with span information are represented using this type
+pub type SpannedName<'a, S> = Spanned<Cow<'a, str>, S>;
+/// A KDL node with span of the whole node (including children)
+pub type SpannedNode<'a, S> = Spanned<Node<'a, S>, S>;
+
+/// Single node of the KDL document
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
+pub struct Node<'a, S> {
+    /// A type name if specified in parenthesis
+    pub type_name: Option<Spanned<TypeName, S>>,
+    /// A node name
+    pub node_name: SpannedName<'a, S>,
+    /// Positional arguments
+    pub arguments: Vec<Value<'a, S>>,
+    /// Named properties
+    pub properties: BTreeMap<SpannedName<'a, S>, Value<'a, S>>,
+    /// Node's children. This field is not none if there are braces `{..}`
+    pub children: Option<SpannedChildren<'a, S>>,
+}
+
+/// KDL document root
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
+pub struct Document<'a, S> {
+    /// Nodes of the document
+    pub nodes: Vec<SpannedNode<'a, S>>,
+}
+
+/// Potentially unlimited size integer value
+///
+/// Digits are borrowed if the number has no underscores and no sign before
+/// the radix prefix.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
+pub struct Integer<'a>(pub Radix, pub Cow<'a, str>);
+
+/// Potentially unlimited precision decimal value
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
+pub struct Decimal<'a>(pub Cow<'a, str>);
+
+/// Possibly typed KDL scalar value
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
+pub struct Value<'a, S> {
+    /// A type name if specified in parenthesis
+    pub type_name: Option<Spanned<TypeName, S>>,
+    /// The actual value literal
+    pub literal: Spanned<Literal<'a>, S>,
+}
+
+/// Scalar KDL value
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
+pub enum Literal<'a> {
+    /// Null value
+    #[default]
+    Null,
+    /// Boolean value
+    Bool(bool),
+    /// Integer value
+    Int(Integer<'a>),
+    /// Decimal (or floating point) value
+    Decimal(Decimal<'a>),
+    /// String value
+    String(Cow<'a, str>),
+}
+
+/// Trait to decode KDL node borrowing from the document
+///
+/// This is a counterpart of [`traits::Decode`] for the borrowed tree.
+pub trait Decode<'a, S: ErrorSpan>: Sized {
+    /// Decodes the node from the ast
+    fn decode_node(node: &SpannedNode<'a, S>, ctx: &mut Context<S>)
+        -> Result<Self, DecodeError<S>>;
+}
+
+/// Trait to decode children of the KDL node borrowing from the document
+///
+/// This is a counterpart of [`traits::DecodeChildren`] for the borrowed tree.
+pub trait DecodeChildren<'a, S: ErrorSpan>: Sized {
+    /// Decodes from a list of chidren ASTs
+    fn decode_children(
+        nodes: &[SpannedNode<'a, S>],
+        ctx: &mut Context<S>,
+    ) -> Result<Self, DecodeError<S>>;
+}
+
+/// The trait is implemented for structures that can be used as part of other
+/// structs
+///
+/// This is a counterpart of [`traits::DecodePartial`] for the borrowed tree.
+pub trait DecodePartial<'a, S: ErrorSpan>: Sized {
+    /// The method is called when unknown child is encountered by parent
+    /// structure
+    ///
+    /// Returns `Ok(true)` if the child is "consumed" (i.e. stored in this
+    /// structure).
+    fn insert_child(
+        &mut self,
+        node: &SpannedNode<'a, S>,
+        ctx: &mut Context<S>,
+    ) -> Result<bool, DecodeError<S>>;
+    /// The method is called when unknown property is encountered by parent
+    /// structure
+    ///
+    /// Returns `Ok(true)` if the property is "consumed" (i.e. stored in this
+    /// structure).
+    fn insert_property(
+        &mut self,
+        name: &SpannedName<'a, S>,
+        value: &Value<'a, S>,
+        ctx: &mut Context<S>,
+    ) -> Result<bool, DecodeError<S>>;
+}
+
+/// The trait that decodes scalar value borrowing from the document
+///
+/// This is a counterpart of [`traits::DecodeScalar`] for the borrowed tree.
+pub trait DecodeScalar<'a, S: ErrorSpan>: Sized {
+    /// Typecheck the value
+    ///
+    /// This method can only emit errors to the context in type mismatch case.
+    fn type_check(type_name: &Option<Spanned<TypeName, S>>, ctx: &mut Context<S>);
+    /// Decode value without typecheck
+    fn raw_decode(
+        value: &Spanned<Literal<'a>, S>,
+        ctx: &mut Context<S>,
+    ) -> Result<Self, DecodeError<S>>;
+    /// Decode the value and typecheck
+    ///
+    /// This should not be overriden and uses `type_check` in combination with
+    /// `raw_decode`.
+    fn decode(value: &Value<'a, S>, ctx: &mut Context<S>) -> Result<Self, DecodeError<S>> {
+        Self::type_check(&value.type_name, ctx);
+        Self::raw_decode(&value.literal, ctx)
+    }
+}
+
+/// Parse KDL text and return AST borrowing from the text
+///
+/// The version of the syntax is detected from the document, see
+/// [`ParseOptions::parse_ast_borrowed`] for choosing it explicitly.
+pub fn parse_ast<S: traits::Span>(
+    file_name: impl AsRef<str>,
+    text: &str,
+) -> Result<Document<'_, S>, Error> {
+    ParseOptions::new().parse_ast_borrowed(file_name, text)
+}
+
+/// Parse KDL text and decode Rust object borrowing from the text
+pub fn parse<'a, T>(file_name: impl AsRef<str>, text: &'a str) -> Result<T, Error>
+where
+    T: DecodeChildren<'a, Span>,
+{
+    ParseOptions::new().parse_borrowed(file_name, text)
+}
+
+/// Parse KDL text and decode Rust object borrowing from the text, providing
+/// extra context for the decoder
+///
+/// Returns the warnings emitted to the context along with the value.
+pub fn parse_with_context<'a, T, S, F>(
+    file_name: impl AsRef<str>,
+    text: &'a str,
+    set_ctx: F,
+) -> Result<(T, Warnings), Error>
+where
+    F: FnOnce(&mut Context<S>),
+    T: DecodeChildren<'a, S>,
+    S: traits::Span,
+{
+    ParseOptions::new().parse_borrowed_with_context(file_name, text, set_ctx)
+}
+
+/// Decodes KDL value as bytes
+///
+/// Used internally by `#[knus(..., bytes)]` attribute. This is a counterpart
+/// of [`decode::bytes`](crate::decode::bytes) for the borrowed tree.
+pub fn bytes<S: ErrorSpan>(value: &Value<'_, S>, ctx: &mut Context<S>) -> Vec<u8> {
+    let string = match &*value.literal {
+        Literal::String(s) => Some(&s[..]),
+        _ => None,
+    };
+    crate::decode::decode_bytes(&value.type_name, &value.literal, string, ctx)
+}
+
+/// Emits error(s) if node is not a flag node
+///
+/// Used internally by `#[knus(child)] x: bool,`. This is a counterpart of
+/// [`decode::check_flag_node`](crate::decode::check_flag_node) for the
+/// borrowed tree.
+pub fn check_flag_node<S: ErrorSpan>(node: &SpannedNode<'_, S>, ctx: &mut Context<S>) {
+    for arg in &node.arguments {
+        ctx.emit_error(DecodeError::unexpected(
+            &arg.literal,
+            "argument",
+            "unexpected argument",
+        ));
+    }
+    for name in node.properties.keys() {
+        ctx.emit_error(DecodeError::unexpected(
+            name,
+            "property",
+            format!("unexpected property `{}`", name.escape_default()),
+        ));
+    }
+    if let Some(children) = &node.children {
+        for child in children.iter() {
+            ctx.emit_error(DecodeError::unexpected(
+                child,
+                "node",
+                format!("unexpected node `{}`", child.node_name.escape_default()),
+            ));
+        }
+    }
+}
+
+impl<'a, S> Node<'a, S> {
+    /// Returns node children
+    pub fn children(&self) -> impl ExactSizeIterator<Item = &SpannedNode<'a, S>> {
+        self.children
+            .as_ref()
+            .map(|c| c.iter())
+            .unwrap_or_else(|| [].iter())
+    }
+    /// Converts the node into the one that doesn't borrow from the text
+    pub fn into_owned(mut self) -> ast::Node<S> {
+        let children = self.children.take();
+        let mut node = owned_head(self);
+        node.children = children.map(|c| c.map(into_owned_nodes));
+        node
+    }
+}
+
+impl<'a, S> Document<'a, S> {
+    /// Converts the document into the one that doesn't borrow from the text
+    pub fn into_owned(self) -> ast::Document<S> {
+        ast::Document {
+            nodes: into_owned_nodes(self.nodes),
+        }
+    }
+}
+
+/// Drops the nodes and their children without recursion, same as
+/// [`ast::drop_nodes`]
+pub(crate) fn drop_nodes<S>(mut stack: Vec<SpannedNode<S>>) {
+    while let Some(mut node) = stack.pop() {
+        if let Some(children) = node.children.take() {
+            stack.extend(children.value);
+        }
+    }
+}
+
+impl<'a, S> Value<'a, S> {
+    /// Converts the value into the one that doesn't borrow from the text
+    pub fn into_owned(self) -> ast::Value<S> {
+        ast::Value {
+            type_name: self.type_name,
+            literal: self.literal.map(Literal::into_owned),
+        }
+    }
+}
+
+impl<'a> Literal<'a> {
+    /// Converts the literal into the one that doesn't borrow from the text
+    pub fn into_owned(self) -> ast::Literal {
+        match self {
+            Literal::Null => ast::Literal::Null,
+            Literal::Bool(b) => ast::Literal::Bool(b),
+            Literal::Int(Integer(radix, value)) => {
+                ast::Literal::Int(ast::Integer(radix, value.into()))
+            }
+            Literal::Decimal(Decimal(value)) => ast::Literal::Decimal(ast::Decimal(value.into())),
+            Literal::String(s) => ast::Literal::String(s.into()),
+        }
+    }
+}
+
+/// Converts the node without its children
+fn owned_head<S>(node: Node<'_, S>) -> ast::Node<S> {
+    ast::Node {
+        type_name: node.type_name,
+        node_name: node.node_name.map(Cow::into),
+        arguments: node.arguments.into_iter().map(Value::into_owned).collect(),
+        properties: node
+            .properties
+            .into_iter()
+            .map(|(name, value)| (name.map(Cow::into), value.into_owned()))
+            .collect(),
+        children: None,
+    }
+}
+
+fn into_owned_nodes<S>(nodes: Vec<SpannedNode<'_, S>>) -> Vec<ast::SpannedNode<S>> {
+    convert_nodes(
+        nodes,
+        |mut node| {
+            let children = node.children.take();
+            (owned_head(node), children)
+        },
+        |node, children| node.children = Some(children),
+    )
+}
+
+/// Converts the node from the owned tree without its children
+fn borrowed_head<'a, S>(node: ast::Node<S>) -> Node<'a, S> {
+    Node {
+        type_name: node.type_name,
+        node_name: node.node_name.map(|n| Cow::Owned(n.into())),
+        arguments: node.arguments.into_iter().map(Value::from).collect(),
+        properties: node
+            .properties
+            .into_iter()
+            .map(|(name, value)| (name.map(|n| Cow::Owned(n.into())), value.into()))
+            .collect(),
+        children: None,
+    }
+}
+
+fn from_owned_nodes<'a, S>(nodes: Vec<ast::SpannedNode<S>>) -> Vec<SpannedNode<'a, S>> {
+    convert_nodes(
+        nodes,
+        |mut node| {
+            let children = node.children.take();
+            (borrowed_head(node), children)
+        },
+        |node, children| node.children = Some(children),
+    )
+}
+
+/// Node being converted whose children are converted
+struct Level<I, O, S> {
+    /// Converted node and the span of its children block
+    parent: Option<(Spanned<O, S>, S)>,
+    input: std::vec::IntoIter<Spanned<I, S>>,
+    output: Vec<Spanned<O, S>>,
+}
+
+/// Converts the nodes between the trees using an explicit stack, so that
+/// deeply nested documents don't overflow the stack
+///
+/// `head` converts the node and returns its children separately, `attach`
+/// puts the converted children back.
+fn convert_nodes<I, O, S>(
+    nodes: Vec<Spanned<I, S>>,
+    mut head: impl FnMut(I) -> (O, Option<Spanned<Vec<Spanned<I, S>>, S>>),
+    attach: impl Fn(&mut O, Spanned<Vec<Spanned<O, S>>, S>),
+) -> Vec<Spanned<O, S>> {
+    let mut stack = vec![Level {
+        parent: None,
+        input: nodes.into_iter(),
+        output: Vec::new(),
+    }];
+    loop {
+        let level = stack.last_mut().expect("stack is not empty");
+        match level.input.next() {
+            Some(Spanned { span, value }) => match head(value) {
+                (value, Some(children)) => stack.push(Level {
+                    parent: Some((Spanned { span, value }, children.span)),
+                    input: children.value.into_iter(),
+                    output: Vec::new(),
+                }),
+                (value, None) => level.output.push(Spanned { span, value }),
+            },
+            None => {
+                let level = stack.pop().expect("stack is not empty");
+                let (mut node, span) = match level.parent {
+                    Some(parent) => parent,
+                    None => return level.output,
+                };
+                let children = Spanned {
+                    span,
+                    value: level.output,
+                };
+                attach(&mut node.value, children);
+                stack
+                    .last_mut()
+                    .expect("parent is on the stack")
+                    .output
+                    .push(node);
+            }
+        }
+    }
+}
+
+impl<S> From<ast::Document<S>> for Document<'_, S> {
+    fn from(doc: ast::Document<S>) -> Self {
+        Document {
+            nodes: from_owned_nodes(doc.nodes),
+        }
+    }
+}
+
+impl<S> From<ast::Node<S>> for Node<'_, S> {
+    fn from(mut node: ast::Node<S>) -> Self {
+        let children = node.children.take();
+        let mut result = borrowed_head(node);
+        result.children = children.map(|c| c.map(from_owned_nodes));
+        result
+    }
+}
+
+impl<S> From<ast::Value<S>> for Value<'_, S> {
+    fn from(value: ast::Value<S>) -> Self {
+        Value {
+            type_name: value.type_name,
+            literal: value.literal.map(Literal::from),
+        }
+    }
+}
+
+impl From<ast::Literal> for Literal<'_> {
+    fn from(literal: ast::Literal) -> Self {
+        match literal {
+            ast::Literal::Null => Literal::Null,
+            ast::Literal::Bool(b) => Literal::Bool(b),
+            ast::Literal::Int(ast::Integer(radix, value)) => {
+                Literal::Int(Integer(radix, Cow::Owned(value.into())))
+            }
+            ast::Literal::Decimal(ast::Decimal(value)) => {
+                Literal::Decimal(Decimal(Cow::Owned(value.into())))
+            }
+            ast::Literal::String(s) => Literal::String(Cow::Owned(s.into())),
+        }
+    }
+}
+
+impl From<&'_ Literal<'_>> for Kind {
+    fn from(lit: &Literal) -> Kind {
+        use Kind as K;
+        use Literal as L;
+        match lit {
+            L::Int(_) => K::Int,
+            L::Decimal(_) => K::Decimal,
+            L::String(_) => K::String,
+            L::Bool(_) => K::Bool,
+            L::Null => K::Null,
+        }
+    }
+}
+
+/// Parses the document without copying the strings
+pub(crate) fn document<S: traits::Span>(
+    text: &str,
+    syntax: Syntax,
+) -> Result<Document<'_, S>, Vec<ParseErrorKind<S>>> {
+    Parser::new(text, syntax, Tree::new(text))
+        .document()
+        .map(|nodes| Document { nodes })
+}
+
+/// Builds the borrowed tree from the parts found by the parser
+struct Tree<'a, S: traits::Span> {
+    text: &'a str,
+    tracker: S::Tracker,
+}
+
+/// Node of the borrowed [`Tree`] that is being parsed
+pub(crate) struct TreeNode<'a, S: traits::Span> {
+    span_start: S::Offset,
+    commented: bool,
+    /// Start of the children block that is being parsed
+    open: Option<S::Offset>,
+    node: Node<'a, S>,
+}
+
+impl<'a, S: traits::Span> Tree<'a, S> {
+    fn new(text: &'a str) -> Self {
+        Tree {
+            text,
+            tracker: S::tracker(),
+        }
+    }
+
+    fn offset(&mut self, byte: usize) -> S::Offset {
+        self.tracker.offset(self.text, byte)
+    }
+
+    fn span(&mut self, range: Range<usize>) -> S {
+        let start = self.offset(range.start);
+        S::new(start, self.offset(range.end))
+    }
+
+    fn name(&mut self, name: Parsed<Cow<'a, str>>) -> SpannedName<'a, S> {
+        Spanned {
+            span: self.span(name.range),
+            value: name.value,
+        }
+    }
+
+    fn type_name(&mut self, type_name: ParsedType<'a>) -> Spanned<TypeName, S> {
+        Spanned {
+            span: self.span(type_name.open..type_name.close + 1),
+            value: TypeName::from_string(type_name.name.value.into()),
+        }
+    }
+
+    fn value(&mut self, value: ParsedValue<'a>) -> Value<'a, S> {
+        Value {
+            type_name: value.type_name.map(|t| self.type_name(t)),
+            literal: Spanned {
+                span: self.span(value.literal.range),
+                value: value.literal.value,
+            },
+        }
+    }
+}
+
+impl<'a, S: traits::Span> Builder<'a> for Tree<'a, S> {
+    type Node = TreeNode<'a, S>;
+    type Output = SpannedNode<'a, S>;
+
+    fn node(&mut self, head: ParsedHead<'a>) -> TreeNode<'a, S> {
+        let start = head
+            .type_name
+            .as_ref()
+            .map_or(head.name.range.start, |t| t.open);
+        let span_start = self.offset(start);
+        let type_name = head.type_name.map(|t| self.type_name(t));
+        let node_name = self.name(head.name);
+        TreeNode {
+            span_start,
+            commented: head.slashdash.is_some(),
+            open: None,
+            node: Node {
+                type_name,
+                node_name,
+                arguments: Vec::new(),
+                properties: BTreeMap::new(),
+                children: None,
+            },
+        }
+    }
+
+    fn entry(&mut self, node: &mut TreeNode<'a, S>, entry: ParsedEntry<'a>) {
+        if entry.slashdash.is_some() {
+            return;
+        }
+        match entry.name {
+            Some((name, _)) => {
+                let name = self.name(name);
+                let value = self.value(entry.value);
+                node.node.properties.insert(name, value);
+            }
+            None => {
+                let value = self.value(entry.value);
+                node.node.arguments.push(value);
+            }
+        }
+    }
+
+    fn open(&mut self, node: &mut TreeNode<'a, S>, slashdash: Option<usize>, open: usize) {
+        if slashdash.is_none() && node.node.children.is_none() {
+            node.open = Some(self.offset(open));
+        }
+    }
+
+    fn close(
+        &mut self,
+        node: &mut TreeNode<'a, S>,
+        children: Vec<SpannedNode<'a, S>>,
+        close: usize,
+    ) {
+        if let Some(open) = node.open.take() {
+            node.node.children = Some(Spanned {
+                span: S::new(open, self.offset(close + 1)),
+                value: children,
+            });
+        }
+    }
+
+    fn finish(
+        &mut self,
+        node: TreeNode<'a, S>,
+        terminator: Range<usize>,
+    ) -> Option<SpannedNode<'a, S>> {
+        if node.commented {
+            return None;
+        }
+        Some(Spanned {
+            span: S::new(node.span_start, self.offset(terminator.end)),
+            value: node.node,
+        })
+    }
+
+    fn discard(&mut self, nodes: Vec<SpannedNode<'a, S>>) {
+        drop_nodes(nodes);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::borrow::Cow;
+
+    use super::{Decimal, Document, Integer, Literal};
+    use crate::ast;
+    use crate::span::{LineSpan, Span};
+    use crate::{KdlVersion, ParseOptions};
+
+    const SAMPLE: &str = r#"
+        (ty)node 1 0x1F -0b1_0 1.5e3 "str" "esc\n" r"raw" null true {
+            child key="value" /- skipped=1 k2=(u8)2
+            /- commented { x; }
+            "quoted name" \
+                ; leaf {}
+        }
+        other; last
+    "#;
+
+    fn parse(text: &str) -> Document<'_, Span> {
+        super::parse_ast("<test>", text).unwrap()
+    }
+
+    fn spans(doc: &ast::Document<Span>) -> Vec<Span> {
+        fn collect(nodes: &[ast::SpannedNode<Span>], out: &mut Vec<Span>) {
+            for node in nodes {
+                out.push(*node.span());
+                out.push(*node.node_name.span());
+                out.extend(node.type_name.iter().map(|t| *t.span()));
+                for value in node.arguments.iter().chain(node.properties.values()) {
+                    out.extend(value.type_name.iter().map(|t| *t.span()));
+                    out.push(*value.literal.span());
+                }
+                out.extend(node.properties.keys().map(|k| *k.span()));
+                if let Some(children) = &node.children {
+                    out.push(*children.span());
+                    collect(children, out);
+                }
+            }
+        }
+        let mut out = Vec::new();
+        collect(&doc.nodes, &mut out);
+        out
+    }
+
+    #[test]
+    fn same_as_owned() {
+        let owned: ast::Document<Span> = crate::parse_ast("<test>", SAMPLE).unwrap();
+        let borrowed = parse(SAMPLE).into_owned();
+        assert_eq!(borrowed, owned);
+        assert_eq!(spans(&borrowed), spans(&owned));
+
+        let owned: ast::Document<LineSpan> = crate::parse_ast("<test>", SAMPLE).unwrap();
+        let borrowed = super::parse_ast::<LineSpan>("<test>", SAMPLE).unwrap();
+        assert_eq!(borrowed.into_owned(), owned);
+    }
+
+    #[test]
+    fn borrows() {
+        let doc = parse(SAMPLE);
+        let node = &doc.nodes[0];
+        assert!(matches!(*node.node_name, Cow::Borrowed("node")));
+        let args = node
+            .arguments
+            .iter()
+            .map(|v| &*v.literal)
+            .collect::<Vec<_>>();
+        assert!(matches!(
+            args[0],
+            Literal::Int(Integer(_, Cow::Borrowed("1")))
+        ));
+        assert!(matches!(
+            args[1],
+            Literal::Int(Integer(_, Cow::Borrowed("1F")))
+        ));
+        assert!(matches!(args[2], Literal::Int(Integer(_, Cow::Owned(_)))));
+        assert!(matches!(
+            args[3],
+            Literal::Decimal(Decimal(Cow::Borrowed(_)))
+        ));
+        assert!(matches!(args[4], Literal::String(Cow::Borrowed("str"))));
+        assert!(matches!(args[5], Literal::String(Cow::Owned(s)) if s == "esc\n"));
+        assert!(matches!(args[6], Literal::String(Cow::Borrowed("raw"))));
+        let children = node.children().collect::<Vec<_>>();
+        assert!(matches!(
+            *children[1].node_name,
+            Cow::Borrowed("quoted name")
+        ));
+    }
+
+    #[test]
+    fn errors() {
+        assert!(super::parse_ast::<Span>("<test>", "node {").is_err());
+        assert!(super::parse_ast::<Span>("<test>", "node \"a").is_err());
+        assert!(super::parse_ast::<Span>("<test>", "a=1").is_err());
+        assert_eq!(parse("").nodes.len(), 0);
+        for text in ["/-", "  /-", "\n\r\n/-", "/- ", "node /-"] {
+            assert!(
+                super::parse_ast::<Span>("<test>", text).is_err(),
+                "{text:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn deep_nesting() {
+        let depth = 20_000;
+        let text = "a {\n".repeat(depth) + &"}\n".repeat(depth);
+        let doc = parse(&text);
+        let mut node = &doc.nodes[0];
+        for _ in 1..depth {
+            assert_eq!(node.children().len(), 1);
+            node = node.children().next().unwrap();
+        }
+        assert!(matches!(node.node_name.value, Cow::Borrowed("a")));
+        super::drop_nodes(doc.nodes);
+    }
+
+    #[test]
+    fn deep_nesting_into_owned() {
+        let depth = 100_000;
+        let text = "a {\n".repeat(depth) + "b 1\n" + &"}\n".repeat(depth);
+        let owned = parse(&text).into_owned();
+        let mut node = &owned.nodes[0];
+        for _ in 0..depth {
+            assert_eq!(node.children().len(), 1);
+            node = node.children().next().unwrap();
+        }
+        assert_eq!(&**node.node_name, "b");
+        assert_eq!(
+            *node.arguments[0].literal,
+            ast::Literal::Int(ast::Integer(ast::Radix::Dec, "1".into()))
+        );
+        let borrowed = Document::from(owned);
+        let mut node = &borrowed.nodes[0];
+        for _ in 0..depth {
+            node = node.children().next().unwrap();
+        }
+        assert!(matches!(node.node_name.value, Cow::Owned(ref name) if name == "b"));
+        let mut doc = parse(&text);
+        let node = doc.nodes.pop().unwrap().value.into_owned();
+        ast::drop_nodes(node.children.unwrap().value);
+        super::drop_nodes(borrowed.nodes);
+    }
+
+    #[test]
+    fn v2() {
+        let text = "node #true key=value";
+        let doc = ParseOptions::new()
+            .version(KdlVersion::V2)
+            .parse_ast_borrowed::<Span>("<test>", text)
+            .unwrap();
+        let owned: ast::Document<Span> = ParseOptions::new()
+            .version(KdlVersion::V2)
+            .parse_ast("<test>", text)
+            .unwrap();
+        assert_eq!(doc.into_owned(), owned);
+    }
+}
@@ -1,12 +1,15 @@
 use std::rc::Rc;
 use std::sync::Arc;
 
-use crate::ast::{Literal, SpannedNode, TypeName, Value};
+use crate::ast::{Literal, Node, SpannedNode, TypeName, Value};
+use crate::borrowed;
 use crate::decode::Context;
-use crate::errors::DecodeError;
+use crate::encode;
+use crate::errors::{DecodeError, EncodeError};
 use crate::span::Spanned;
 use crate::traits::{Decode, DecodeChildren, DecodePartial, DecodeScalar};
 use crate::traits::{DecodeSpan, ErrorSpan, Span};
+use crate::traits::{Encode, EncodeChildren, EncodeScalar};
 
 impl<S: ErrorSpan, T: Decode<S>> Decode<S> for Box<T> {
     fn decode_node(node: &SpannedNode<S>, ctx: &mut Context<S>) -> Result<Self, DecodeError<S>> {
@@ -51,6 +54,9 @@ impl<S: ErrorSpan, T: DecodeScalar<S>> DecodeScalar<S> for Box<T> {
     ) -> Result<Self, DecodeError<S>> {
         DecodeScalar::raw_decode(value, ctx).map(Box::new)
     }
+    fn decode(value: &Value<S>, ctx: &mut Context<S>) -> Result<Self, DecodeError<S>> {
+        DecodeScalar::decode(value, ctx).map(Box::new)
+    }
 }
 
 impl<S: ErrorSpan, T: Decode<S>> Decode<S> for Arc<T> {
@@ -100,6 +106,9 @@ impl<S: ErrorSpan, T: DecodeScalar<S>> DecodeScalar<S> for Arc<T> {
     ) -> Result<Self, DecodeError<S>> {
         DecodeScalar::raw_decode(value, ctx).map(Arc::new)
     }
+    fn decode(value: &Value<S>, ctx: &mut Context<S>) -> Result<Self, DecodeError<S>> {
+        DecodeScalar::decode(value, ctx).map(Arc::new)
+    }
 }
 
 impl<S: ErrorSpan, T: Decode<S>> Decode<S> for Rc<T> {
@@ -149,6 +158,9 @@ impl<S: ErrorSpan, T: DecodeScalar<S>> DecodeScalar<S> for Rc<T> {
     ) -> Result<Self, DecodeError<S>> {
         DecodeScalar::raw_decode(value, ctx).map(Rc::new)
     }
+    fn decode(value: &Value<S>, ctx: &mut Context<S>) -> Result<Self, DecodeError<S>> {
+        DecodeScalar::decode(value, ctx).map(Rc::new)
+    }
 }
 
 impl<S: ErrorSpan, T: Decode<S>> DecodeChildren<S> for Vec<T> {
@@ -180,6 +192,15 @@ impl<S: ErrorSpan, T: DecodeScalar<S>> DecodeScalar<S> for Option<T> {
             _ => DecodeScalar::raw_decode(value, ctx).map(Some),
         }
     }
+    fn decode(value: &Value<S>, ctx: &mut Context<S>) -> Result<Self, DecodeError<S>> {
+        match &*value.literal {
+            Literal::Null => {
+                T::type_check(&value.type_name, ctx);
+                Ok(None)
+            }
+            _ => DecodeScalar::decode(value, ctx).map(Some),
+        }
+    }
 }
 
 impl<T: DecodeScalar<S>, S, Q> DecodeScalar<S> for Spanned<T, Q>
@@ -200,4 +221,233 @@ where
             value: decoded,
         })
     }
+    fn decode(value: &Value<S>, ctx: &mut Context<S>) -> Result<Self, DecodeError<S>> {
+        let decoded = T::decode(value, ctx)?;
+        Ok(Spanned {
+            span: DecodeSpan::decode_span(&value.literal.span, ctx),
+            value: decoded,
+        })
+    }
+}
+
+macro_rules! impl_decode_borrowed_pointer {
+    ($ptr: ident, |$this: ident| $get_mut: expr) => {
+        impl<'a, S: ErrorSpan, T: borrowed::Decode<'a, S>> borrowed::Decode<'a, S> for $ptr<T> {
+            fn decode_node(
+                node: &borrowed::SpannedNode<'a, S>,
+                ctx: &mut Context<S>,
+            ) -> Result<Self, DecodeError<S>> {
+                borrowed::Decode::decode_node(node, ctx).map($ptr::new)
+            }
+        }
+
+        impl<'a, S, T> borrowed::DecodeChildren<'a, S> for $ptr<T>
+        where
+            S: ErrorSpan,
+            T: borrowed::DecodeChildren<'a, S>,
+        {
+            fn decode_children(
+                nodes: &[borrowed::SpannedNode<'a, S>],
+                ctx: &mut Context<S>,
+            ) -> Result<Self, DecodeError<S>> {
+                borrowed::DecodeChildren::decode_children(nodes, ctx).map($ptr::new)
+            }
+        }
+
+        impl<'a, S, T> borrowed::DecodePartial<'a, S> for $ptr<T>
+        where
+            S: ErrorSpan,
+            T: borrowed::DecodePartial<'a, S>,
+        {
+            fn insert_child(
+                &mut self,
+                node: &borrowed::SpannedNode<'a, S>,
+                ctx: &mut Context<S>,
+            ) -> Result<bool, DecodeError<S>> {
+                let $this = self;
+                $get_mut.insert_child(node, ctx)
+            }
+            fn insert_property(
+                &mut self,
+                name: &borrowed::SpannedName<'a, S>,
+                value: &borrowed::Value<'a, S>,
+                ctx: &mut Context<S>,
+            ) -> Result<bool, DecodeError<S>> {
+                let $this = self;
+                $get_mut.insert_property(name, value, ctx)
+            }
+        }
+
+        impl<'a, S, T> borrowed::DecodeScalar<'a, S> for $ptr<T>
+        where
+            S: ErrorSpan,
+            T: borrowed::DecodeScalar<'a, S>,
+        {
+            fn type_check(type_name: &Option<Spanned<TypeName, S>>, ctx: &mut Context<S>) {
+                T::type_check(type_name, ctx)
+            }
+            fn raw_decode(
+                value: &Spanned<borrowed::Literal<'a>, S>,
+                ctx: &mut Context<S>,
+            ) -> Result<Self, DecodeError<S>> {
+                borrowed::DecodeScalar::raw_decode(value, ctx).map($ptr::new)
+            }
+        }
+    };
+}
+
+impl_decode_borrowed_pointer!(Box, |this| &mut **this);
+impl_decode_borrowed_pointer!(Arc, |this| Arc::get_mut(this).expect("no Arc clone yet"));
+impl_decode_borrowed_pointer!(Rc, |this| Rc::get_mut(this).expect("no Rc clone yet"));
+
+impl<'a, S: ErrorSpan, T: borrowed::Decode<'a, S>> borrowed::DecodeChildren<'a, S> for Vec<T> {
+    fn decode_children(
+        nodes: &[borrowed::SpannedNode<'a, S>],
+        ctx: &mut Context<S>,
+    ) -> Result<Self, DecodeError<S>> {
+        let mut result = Vec::with_capacity(nodes.len());
+        for node in nodes {
+            match borrowed::Decode::decode_node(node, ctx) {
+                Ok(node) => result.push(node),
+                Err(e) => ctx.emit_error(e),
+            }
+        }
+        Ok(result)
+    }
+}
+
+impl<'a, S, T> borrowed::DecodeScalar<'a, S> for Option<T>
+where
+    S: ErrorSpan,
+    T: borrowed::DecodeScalar<'a, S>,
+{
+    fn type_check(type_name: &Option<Spanned<TypeName, S>>, ctx: &mut Context<S>) {
+        T::type_check(type_name, ctx)
+    }
+    fn raw_decode(
+        value: &Spanned<borrowed::Literal<'a>, S>,
+        ctx: &mut Context<S>,
+    ) -> Result<Self, DecodeError<S>> {
+        match &**value {
+            borrowed::Literal::Null => Ok(None),
+            _ => borrowed::DecodeScalar::raw_decode(value, ctx).map(Some),
+        }
+    }
+}
+
+impl<'a, T, S, Q> borrowed::DecodeScalar<'a, S> for Spanned<T, Q>
+where
+    T: borrowed::DecodeScalar<'a, S>,
+    S: Span,
+    Q: DecodeSpan<S>,
+{
+    fn type_check(type_name: &Option<Spanned<TypeName, S>>, ctx: &mut Context<S>) {
+        T::type_check(type_name, ctx)
+    }
+    fn raw_decode(
+        value: &Spanned<borrowed::Literal<'a>, S>,
+        ctx: &mut Context<S>,
+    ) -> Result<Self, DecodeError<S>> {
+        let decoded = T::raw_decode(value, ctx)?;
+        Ok(Spanned {
+            span: DecodeSpan::decode_span(&value.span, ctx),
+            value: decoded,
+        })
+    }
+}
+
+macro_rules! impl_encode_pointer {
+    ($ptr: ident) => {
+        impl<T: Encode + ?Sized> Encode for $ptr<T> {
+            fn encode_node<S>(
+                &self,
+                node: &mut Node<S>,
+                ctx: &mut encode::Context,
+            ) -> Result<(), EncodeError>
+            where
+                S: ErrorSpan + Default,
+            {
+                (**self).encode_node(node, ctx)
+            }
+        }
+
+        impl<T: EncodeChildren + ?Sized> EncodeChildren for $ptr<T> {
+            fn encode_children<S>(
+                &self,
+                nodes: &mut Vec<SpannedNode<S>>,
+                ctx: &mut encode::Context,
+            ) -> Result<(), EncodeError>
+            where
+                S: ErrorSpan + Default,
+            {
+                (**self).encode_children(nodes, ctx)
+            }
+        }
+
+        impl<T: EncodeScalar + ?Sized> EncodeScalar for $ptr<T> {
+            fn type_name(&self) -> Option<TypeName> {
+                (**self).type_name()
+            }
+            fn raw_encode(&self, ctx: &mut encode::Context) -> Result<Literal, EncodeError> {
+                (**self).raw_encode(ctx)
+            }
+        }
+    };
+}
+
+impl_encode_pointer!(Box);
+impl_encode_pointer!(Arc);
+impl_encode_pointer!(Rc);
+
+impl<T: Encode> EncodeChildren for [T] {
+    fn encode_children<S>(
+        &self,
+        nodes: &mut Vec<SpannedNode<S>>,
+        ctx: &mut encode::Context,
+    ) -> Result<(), EncodeError>
+    where
+        S: ErrorSpan + Default,
+    {
+        nodes.reserve(self.len());
+        for item in self {
+            let mut node = Node::new("-");
+            item.encode_node(&mut node, ctx)?;
+            nodes.push(Spanned::new(node, S::default()));
+        }
+        Ok(())
+    }
+}
+
+impl<T: Encode> EncodeChildren for Vec<T> {
+    fn encode_children<S>(
+        &self,
+        nodes: &mut Vec<SpannedNode<S>>,
+        ctx: &mut encode::Context,
+    ) -> Result<(), EncodeError>
+    where
+        S: ErrorSpan + Default,
+    {
+        self[..].encode_children(nodes, ctx)
+    }
+}
+
+impl<T: EncodeScalar> EncodeScalar for Option<T> {
+    fn type_name(&self) -> Option<TypeName> {
+        self.as_ref().and_then(|v| v.type_name())
+    }
+    fn raw_encode(&self, ctx: &mut encode::Context) -> Result<Literal, EncodeError> {
+        match self {
+            Some(v) => v.raw_encode(ctx),
+            None => Ok(Literal::Null),
+        }
+    }
+}
+
+impl<T: EncodeScalar, Q> EncodeScalar for Spanned<T, Q> {
+    fn type_name(&self) -> Option<TypeName> {
+        self.value.type_name()
+    }
+    fn raw_encode(&self, ctx: &mut encode::Context) -> Result<Literal, EncodeError> {
+        self.value.raw_encode(ctx)
+    }
 }
@@ -1,16 +1,19 @@
+use std::borrow::Cow;
 use std::default::Default;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
 
 use crate::ast::{BuiltinType, Decimal, Integer, Literal, Radix, TypeName};
+use crate::borrowed;
 use crate::decode::{Context, Kind};
-use crate::errors::{DecodeError, ExpectedType};
+use crate::encode;
+use crate::errors::{DecodeError, EncodeError, ExpectedType};
 use crate::span::Spanned;
-use crate::traits::{DecodeScalar, ErrorSpan};
+use crate::traits::{DecodeScalar, EncodeScalar, ErrorSpan};
 
-macro_rules! impl_integer {
-    ($typ: ident, $marker: ident) => {
+macro_rules! impl_try_from_integer {
+    ($typ: ident) => {
         impl TryFrom<&Integer> for $typ {
             type Error = <$typ as FromStr>::Err;
             fn try_from(val: &Integer) -> Result<$typ, <$typ as FromStr>::Err> {
@@ -22,6 +25,48 @@ macro_rules! impl_integer {
                 }
             }
         }
+    };
+}
+
+macro_rules! impl_integer {
+    ($typ: ident, $marker: ident) => {
+        impl_try_from_integer!($typ);
+
+        impl TryFrom<&borrowed::Integer<'_>> for $typ {
+            type Error = <$typ as FromStr>::Err;
+            fn try_from(val: &borrowed::Integer) -> Result<$typ, <$typ as FromStr>::Err> {
+                match val.0 {
+                    Radix::Bin => <$typ>::from_str_radix(&val.1, 2),
+                    Radix::Oct => <$typ>::from_str_radix(&val.1, 8),
+                    Radix::Dec => <$typ>::from_str(&val.1),
+                    Radix::Hex => <$typ>::from_str_radix(&val.1, 16),
+                }
+            }
+        }
+
+        impl<'a, S: ErrorSpan> borrowed::DecodeScalar<'a, S> for $typ {
+            fn raw_decode(
+                val: &Spanned<borrowed::Literal<'a>, S>,
+                ctx: &mut Context<S>,
+            ) -> Result<$typ, DecodeError<S>> {
+                match &**val {
+                    borrowed::Literal::Int(ref value) => match value.try_into() {
+                        Ok(val) => Ok(val),
+                        Err(e) => {
+                            ctx.emit_error(DecodeError::conversion(val, e));
+                            Ok(0)
+                        }
+                    },
+                    _ => {
+                        ctx.emit_error(DecodeError::scalar_kind(Kind::String, val));
+                        Ok(0)
+                    }
+                }
+            }
+            fn type_check(type_name: &Option<Spanned<TypeName, S>>, ctx: &mut Context<S>) {
+                <$typ as DecodeScalar<S>>::type_check(type_name, ctx)
+            }
+        }
 
         impl<S: ErrorSpan> DecodeScalar<S> for $typ {
             fn raw_decode(
@@ -55,6 +100,12 @@ macro_rules! impl_integer {
                 }
             }
         }
+
+        impl EncodeScalar for $typ {
+            fn raw_encode(&self, _ctx: &mut encode::Context) -> Result<Literal, EncodeError> {
+                Ok(Literal::Int(Integer(Radix::Dec, self.to_string().into())))
+            }
+        }
     };
 }
 
@@ -68,6 +119,8 @@ impl_integer!(i64, I64);
 impl_integer!(u64, U64);
 impl_integer!(isize, Isize);
 impl_integer!(usize, Usize);
+impl_try_from_integer!(i128);
+impl_try_from_integer!(u128);
 
 macro_rules! impl_float {
     ($typ: ident, $marker: ident) => {
@@ -78,6 +131,37 @@ macro_rules! impl_float {
             }
         }
 
+        impl TryFrom<&borrowed::Decimal<'_>> for $typ {
+            type Error = <$typ as FromStr>::Err;
+            fn try_from(val: &borrowed::Decimal) -> Result<$typ, <$typ as FromStr>::Err> {
+                <$typ>::from_str(&val.0)
+            }
+        }
+
+        impl<'a, S: ErrorSpan> borrowed::DecodeScalar<'a, S> for $typ {
+            fn raw_decode(
+                val: &Spanned<borrowed::Literal<'a>, S>,
+                ctx: &mut Context<S>,
+            ) -> Result<$typ, DecodeError<S>> {
+                match &**val {
+                    borrowed::Literal::Decimal(ref value) => match value.try_into() {
+                        Ok(val) => Ok(val),
+                        Err(e) => {
+                            ctx.emit_error(DecodeError::conversion(val, e));
+                            Ok(0.0)
+                        }
+                    },
+                    _ => {
+                        ctx.emit_error(DecodeError::scalar_kind(Kind::String, val));
+                        Ok(0.0)
+                    }
+                }
+            }
+            fn type_check(type_name: &Option<Spanned<TypeName, S>>, ctx: &mut Context<S>) {
+                <$typ as DecodeScalar<S>>::type_check(type_name, ctx)
+            }
+        }
+
         impl<S: ErrorSpan> DecodeScalar<S> for $typ {
             fn raw_decode(
                 val: &Spanned<Literal, S>,
@@ -110,6 +194,21 @@ macro_rules! impl_float {
                 }
             }
         }
+
+        impl EncodeScalar for $typ {
+            fn raw_encode(&self, _ctx: &mut encode::Context) -> Result<Literal, EncodeError> {
+                if self.is_finite() {
+                    // debug representation always has either decimal point
+                    // or an exponent, so it's not parsed back as an integer
+                    Ok(Literal::Decimal(Decimal(format!("{:?}", self).into())))
+                } else {
+                    Err(EncodeError::unsupported(format!(
+                        "{} cannot be represented in KDL",
+                        self
+                    )))
+                }
+            }
+        }
     };
 }
 
@@ -237,3 +336,158 @@ impl<S: ErrorSpan> DecodeScalar<S> for bool {
         }
     }
 }
+
+macro_rules! impl_borrowed_string {
+    ($typ: ty) => {
+        impl<'a, S: ErrorSpan> borrowed::DecodeScalar<'a, S> for $typ {
+            fn raw_decode(
+                val: &Spanned<borrowed::Literal<'a>, S>,
+                ctx: &mut Context<S>,
+            ) -> Result<$typ, DecodeError<S>> {
+                match &**val {
+                    borrowed::Literal::String(ref s) => Ok((&s[..]).into()),
+                    _ => {
+                        ctx.emit_error(DecodeError::scalar_kind(Kind::String, val));
+                        Ok(<&str>::default().into())
+                    }
+                }
+            }
+            fn type_check(type_name: &Option<Spanned<TypeName, S>>, ctx: &mut Context<S>) {
+                <$typ as DecodeScalar<S>>::type_check(type_name, ctx)
+            }
+        }
+    };
+}
+
+impl_borrowed_string!(String);
+impl_borrowed_string!(PathBuf);
+impl_borrowed_string!(Arc<str>);
+
+impl<'a, S: ErrorSpan> borrowed::DecodeScalar<'a, S> for Arc<Path> {
+    fn raw_decode(
+        val: &Spanned<borrowed::Literal<'a>, S>,
+        ctx: &mut Context<S>,
+    ) -> Result<Arc<Path>, DecodeError<S>> {
+        match &**val {
+            borrowed::Literal::String(ref s) => Ok(Path::new(&s[..]).into()),
+            _ => {
+                ctx.emit_error(DecodeError::scalar_kind(Kind::String, val));
+                Ok(PathBuf::default().into())
+            }
+        }
+    }
+    fn type_check(type_name: &Option<Spanned<TypeName, S>>, ctx: &mut Context<S>) {
+        <Arc<Path> as DecodeScalar<S>>::type_check(type_name, ctx)
+    }
+}
+
+impl<'a, S: ErrorSpan> borrowed::DecodeScalar<'a, S> for bool {
+    fn raw_decode(
+        val: &Spanned<borrowed::Literal<'a>, S>,
+        ctx: &mut Context<S>,
+    ) -> Result<bool, DecodeError<S>> {
+        match &**val {
+            borrowed::Literal::Bool(value) => Ok(*value),
+            _ => {
+                ctx.emit_error(DecodeError::scalar_kind(Kind::Bool, val));
+                Ok(Default::default())
+            }
+        }
+    }
+    fn type_check(type_name: &Option<Spanned<TypeName, S>>, ctx: &mut Context<S>) {
+        <bool as DecodeScalar<S>>::type_check(type_name, ctx)
+    }
+}
+
+impl<'a: 'b, 'b, S: ErrorSpan> borrowed::DecodeScalar<'a, S> for Cow<'b, str> {
+    fn raw_decode(
+        val: &Spanned<borrowed::Literal<'a>, S>,
+        ctx: &mut Context<S>,
+    ) -> Result<Cow<'b, str>, DecodeError<S>> {
+        match &**val {
+            borrowed::Literal::String(ref s) => Ok(s.clone()),
+            _ => {
+                ctx.emit_error(DecodeError::scalar_kind(Kind::String, val));
+                Ok(Cow::Borrowed(""))
+            }
+        }
+    }
+    fn type_check(type_name: &Option<Spanned<TypeName, S>>, ctx: &mut Context<S>) {
+        if let Some(typ) = type_name {
+            ctx.emit_error(DecodeError::TypeName {
+                span: typ.span().clone(),
+                found: Some(typ.value.clone()),
+                expected: ExpectedType::no_type(),
+                rust_type: "Cow<str>",
+            });
+        }
+    }
+}
+
+impl<'a: 'b, 'b, S: ErrorSpan> borrowed::DecodeScalar<'a, S> for &'b str {
+    fn raw_decode(
+        val: &Spanned<borrowed::Literal<'a>, S>,
+        ctx: &mut Context<S>,
+    ) -> Result<&'b str, DecodeError<S>> {
+        match &**val {
+            borrowed::Literal::String(Cow::Borrowed(s)) => Ok(s),
+            borrowed::Literal::String(Cow::Owned(_)) => {
+                ctx.emit_error(DecodeError::unsupported(
+                    val,
+                    "string with escapes can't be borrowed, use `Cow<str>` or `String`",
+                ));
+                Ok("")
+            }
+            _ => {
+                ctx.emit_error(DecodeError::scalar_kind(Kind::String, val));
+                Ok("")
+            }
+        }
+    }
+    fn type_check(type_name: &Option<Spanned<TypeName, S>>, ctx: &mut Context<S>) {
+        if let Some(typ) = type_name {
+            ctx.emit_error(DecodeError::TypeName {
+                span: typ.span().clone(),
+                found: Some(typ.value.clone()),
+                expected: ExpectedType::no_type(),
+                rust_type: "&str",
+            });
+        }
+    }
+}
+
+impl EncodeScalar for String {
+    fn raw_encode(&self, _ctx: &mut encode::Context) -> Result<Literal, EncodeError> {
+        Ok(Literal::String(self.as_str().into()))
+    }
+}
+
+impl EncodeScalar for str {
+    fn raw_encode(&self, _ctx: &mut encode::Context) -> Result<Literal, EncodeError> {
+        Ok(Literal::String(self.into()))
+    }
+}
+
+impl EncodeScalar for Path {
+    fn raw_encode(&self, _ctx: &mut encode::Context) -> Result<Literal, EncodeError> {
+        match self.to_str() {
+            Some(s) => Ok(Literal::String(s.into())),
+            None => Err(EncodeError::conversion(format!(
+                "path {:?} is not valid unicode",
+                self
+            ))),
+        }
+    }
+}
+
+impl EncodeScalar for PathBuf {
+    fn raw_encode(&self, ctx: &mut encode::Context) -> Result<Literal, EncodeError> {
+        self.as_path().raw_encode(ctx)
+    }
+}
+
+impl EncodeScalar for bool {
+    fn raw_encode(&self, _ctx: &mut encode::Context) -> Result<Literal, EncodeError> {
+        Ok(Literal::Bool(*self))
+    }
+}
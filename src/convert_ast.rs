@@ -1,8 +1,11 @@
 use crate::ast::{Literal, Node, SpannedNode, TypeName, Value};
+use crate::borrowed;
 use crate::decode::Context;
-use crate::errors::DecodeError;
+use crate::encode;
+use crate::errors::{DecodeError, EncodeError};
 use crate::span::Spanned;
 use crate::traits::{Decode, DecodeScalar, DecodeSpan, Span};
+use crate::traits::{Encode, EncodeScalar, ErrorSpan};
 
 impl<S, T> Decode<S> for Node<T>
 where
@@ -90,3 +93,158 @@ where
         Ok((**value).clone())
     }
 }
+
+impl<'a, S, T> borrowed::Decode<'a, S> for borrowed::Node<'a, T>
+where
+    S: Span,
+    T: DecodeSpan<S>,
+{
+    fn decode_node(
+        node: &borrowed::SpannedNode<'a, S>,
+        ctx: &mut Context<S>,
+    ) -> Result<Self, DecodeError<S>> {
+        Ok(borrowed::Node {
+            type_name: node.type_name.as_ref().map(|n| n.clone_as(ctx)),
+            node_name: node.node_name.clone_as(ctx),
+            arguments: node
+                .arguments
+                .iter()
+                .map(|v| borrowed::DecodeScalar::decode(v, ctx))
+                .collect::<Result<_, _>>()?,
+            properties: node
+                .properties
+                .iter()
+                .map(|(k, v)| Ok((k.clone_as(ctx), borrowed::DecodeScalar::decode(v, ctx)?)))
+                .collect::<Result<_, _>>()?,
+            children: node
+                .children
+                .as_ref()
+                .map(|sc| {
+                    Ok(Spanned {
+                        span: DecodeSpan::decode_span(&sc.span, ctx),
+                        value: sc
+                            .iter()
+                            .map(|node| borrowed::Decode::decode_node(node, ctx))
+                            .collect::<Result<_, _>>()?,
+                    })
+                })
+                .transpose()?,
+        })
+    }
+}
+
+impl<'a, S, T> borrowed::Decode<'a, S> for borrowed::SpannedNode<'a, T>
+where
+    S: Span,
+    T: DecodeSpan<S>,
+{
+    fn decode_node(
+        node: &borrowed::SpannedNode<'a, S>,
+        ctx: &mut Context<S>,
+    ) -> Result<Self, DecodeError<S>> {
+        Ok(Spanned {
+            span: DecodeSpan::decode_span(&node.span, ctx),
+            value: borrowed::Decode::decode_node(node, ctx)?,
+        })
+    }
+}
+
+impl<'a, S, T> borrowed::DecodeScalar<'a, S> for borrowed::Value<'a, T>
+where
+    S: Span,
+    T: DecodeSpan<S>,
+{
+    fn type_check(_type_name: &Option<Spanned<TypeName, S>>, _ctx: &mut Context<S>) {}
+    fn raw_decode(
+        _value: &Spanned<borrowed::Literal<'a>, S>,
+        _ctx: &mut Context<S>,
+    ) -> Result<Self, DecodeError<S>> {
+        panic!("called `raw_decode` directly on the `Value`");
+    }
+    fn decode(
+        value: &borrowed::Value<'a, S>,
+        ctx: &mut Context<S>,
+    ) -> Result<Self, DecodeError<S>> {
+        Ok(borrowed::Value {
+            type_name: value.type_name.as_ref().map(|n| n.clone_as(ctx)),
+            literal: value.literal.clone_as(ctx),
+        })
+    }
+}
+
+impl<'a, S> borrowed::DecodeScalar<'a, S> for borrowed::Literal<'a>
+where
+    S: Span,
+{
+    fn type_check(_type_name: &Option<Spanned<TypeName, S>>, _ctx: &mut Context<S>) {}
+    fn raw_decode(
+        value: &Spanned<borrowed::Literal<'a>, S>,
+        _ctx: &mut Context<S>,
+    ) -> Result<Self, DecodeError<S>> {
+        Ok((**value).clone())
+    }
+}
+
+impl<T> Encode for Node<T> {
+    fn encode_node<S>(
+        &self,
+        node: &mut Node<S>,
+        ctx: &mut encode::Context,
+    ) -> Result<(), EncodeError>
+    where
+        S: ErrorSpan + Default,
+    {
+        node.type_name = self
+            .type_name
+            .as_ref()
+            .map(|t| Spanned::new((**t).clone(), S::default()));
+        node.node_name = Spanned::new(self.node_name.value.clone(), S::default());
+        for arg in &self.arguments {
+            node.arguments.push(arg.encode(ctx)?);
+        }
+        for (name, value) in &self.properties {
+            node.properties.insert(
+                Spanned::new(name.value.clone(), S::default()),
+                value.encode(ctx)?,
+            );
+        }
+        if let Some(children) = &self.children {
+            let mut nodes = Vec::with_capacity(children.len());
+            for child in children.iter() {
+                let mut new_child = Node::new("");
+                child.encode_node(&mut new_child, ctx)?;
+                nodes.push(Spanned::new(new_child, S::default()));
+            }
+            node.children = Some(Spanned::new(nodes, S::default()));
+        }
+        Ok(())
+    }
+}
+
+impl<T> Encode for SpannedNode<T> {
+    fn encode_node<S>(
+        &self,
+        node: &mut Node<S>,
+        ctx: &mut encode::Context,
+    ) -> Result<(), EncodeError>
+    where
+        S: ErrorSpan + Default,
+    {
+        self.value.encode_node(node, ctx)
+    }
+}
+
+impl<T> EncodeScalar for Value<T> {
+    fn type_name(&self) -> Option<TypeName> {
+        self.type_name.as_ref().map(|t| t.value.clone())
+    }
+    fn raw_encode(&self, _ctx: &mut encode::Context) -> Result<Literal, EncodeError> {
+        Ok(self.literal.value.clone())
+    }
+}
+
+impl EncodeScalar for Literal {
+    fn raw_encode(&self, _ctx: &mut encode::Context) -> Result<Literal, EncodeError> {
+        Ok(self.clone())
+    }
+}
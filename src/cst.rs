@@ -0,0 +1,1430 @@
+//! Lossless concrete syntax tree of the KDL document
+//!
+//! Unlike [`ast`], the tree keeps every byte of the source text:
+//! whitespace, comments, line continuations and entities commented out with
+//! `/-`. Printing the tree with [`Display`](fmt::Display) gives exactly the
+//! text it was parsed from, so it's suitable for editors and codemods that
+//! change a part of a hand-written file and leave the rest intact:
+//!
+//! ```rust
+//! use knus::ast::{Integer, Literal, Radix};
+//!
+//! let mut doc = knus::cst::parse("config.kdl", r#"
+//!     // server settings
+//!     server "localhost" port=8080 /* default */
+//! "#)?;
+//! let server = &mut doc.nodes[0];
+//! server.property_mut("port").unwrap().set_literal(&Literal::Int(Integer(Radix::Dec, "8081".into())));
+//! assert_eq!(doc.to_string(), r#"
+//!     // server settings
+//!     server "localhost" port=8081 /* default */
+//! "#);
+//! # Ok::<(), knus::Error>(())
+//! ```
+//!
+//! Use [`Document::to_ast`] to get the [`ast::Document`] that is accepted by
+//! the decoders, and [`Document::format`] to pretty-print the document
+//! keeping the comments.
+//!
+//! Both versions of the syntax are supported. Tokens keep the version they
+//! were parsed with, so edited values are written in the syntax of the
+//! document. Nodes, type names and values created from scratch take the
+//! version as an argument, pass [`Document::version`] to insert them into an
+//! existing document.
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+use std::mem;
+use std::ops::Range;
+
+use crate::ast::{self, Literal, SpannedNode};
+use crate::borrowed;
+use crate::errors::{Error, ParseErrorKind};
+use crate::format::Formatter;
+use crate::grammar::{Builder, Parsed, ParsedEntry, ParsedHead, ParsedType, ParsedValue};
+use crate::grammar::{Parser, Scalar, Scanner, Syntax};
+use crate::grammar_v2;
+use crate::print;
+use crate::span::{Span, Spanned};
+use crate::traits;
+use crate::traits::sealed::SpanTracker;
+use crate::wrappers::{KdlVersion, ParseOptions};
+
+/// Kind of the token in the concrete syntax tree
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+#[non_exhaustive]
+pub enum TokenKind {
+    /// Run of non-newline whitespace characters
+    Whitespace,
+    /// Single newline (`\r\n` is a single newline)
+    Newline,
+    /// Comment starting with `//`, not including the newline
+    LineComment,
+    /// Comment enclosed in `/*` and `*/`, possibly nested
+    BlockComment,
+    /// Backslash starting line continuation
+    Backslash,
+    /// The `/-` marker that comments out the next entity
+    SlashDash,
+    /// Bare identifier
+    Ident,
+    /// Quoted string with escapes
+    String,
+    /// Raw string `r#"..."#`
+    RawString,
+    /// Integer in any radix
+    Integer,
+    /// Decimal floating point number
+    Decimal,
+    /// One of `true`, `false`, `null`
+    Keyword,
+    /// Opening parenthesis of type name
+    OpenParen,
+    /// Closing parenthesis of type name
+    CloseParen,
+    /// Opening curly brace of children block
+    OpenBrace,
+    /// Closing curly brace of children block
+    CloseBrace,
+    /// Equals sign between property name and value
+    Equals,
+    /// Semicolon terminating the node
+    Semicolon,
+}
+
+/// A single token of the source text
+///
+/// Tokens can only be created by the parser or via the methods that keep the
+/// text valid, such as [`Value::set_literal`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Token {
+    kind: TokenKind,
+    text: Box<str>,
+    syntax: Syntax,
+}
+
+/// Whole KDL document
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Document {
+    /// Top-level nodes including the ones commented out using `/-`
+    pub nodes: Vec<Node>,
+    /// Whitespace, newlines and comments after the last node
+    pub trailing: Vec<Token>,
+}
+
+/// Node with the trivia around it
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Node {
+    /// Whitespace, newlines and comments before the node
+    pub leading: Vec<Token>,
+    /// The `/-` marker if the node is commented out
+    pub slashdash: Option<SlashDash>,
+    /// Type name in parenthesis before the node name
+    pub type_name: Option<TypeAnnotation>,
+    /// Name of the node: identifier or string
+    pub name: Token,
+    /// Arguments and properties including the commented out ones
+    pub entries: Vec<Entry>,
+    /// Children blocks
+    ///
+    /// There is at most one block in KDL 1.0. KDL 2.0 allows several blocks,
+    /// but all of them except one must be commented out using `/-`.
+    pub children: Vec<Children>,
+    /// Whitespace and comments before the terminator
+    pub trailing: Vec<Token>,
+    /// Newline, semicolon or single-line comment ending the node
+    ///
+    /// Empty if the node is terminated by the end of the input.
+    pub terminator: Vec<Token>,
+}
+
+/// The `/-` marker with whitespace after it
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SlashDash {
+    /// The `/-` token itself
+    pub token: Token,
+    /// Whitespace and comments between the marker and the entity
+    pub trailing: Vec<Token>,
+}
+
+/// Type name in parenthesis like `(u8)`
+///
+/// Whitespace around the type name is only allowed in KDL 2.0, so the
+/// trivia is always empty in KDL 1.0 documents.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TypeAnnotation {
+    /// Opening parenthesis
+    pub open: Token,
+    /// Whitespace and comments after the opening parenthesis
+    pub leading: Vec<Token>,
+    /// Type name: identifier or string
+    pub name: Token,
+    /// Whitespace and comments before the closing parenthesis
+    pub trailing: Vec<Token>,
+    /// Closing parenthesis
+    pub close: Token,
+    /// Whitespace and comments between the annotation and the node name or
+    /// the value
+    pub after: Vec<Token>,
+}
+
+/// Argument or property of the node
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Entry {
+    /// Whitespace, comments and line continuations before the entry
+    pub leading: Vec<Token>,
+    /// The `/-` marker if the entry is commented out
+    pub slashdash: Option<SlashDash>,
+    /// Name of the property, `None` for arguments
+    pub name: Option<PropertyName>,
+    /// Value of the property or argument
+    pub value: Value,
+}
+
+/// Name of the property and the equals sign
+///
+/// Whitespace around the equals sign is only allowed in KDL 2.0.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PropertyName {
+    /// Name of the property: identifier or string
+    pub name: Token,
+    /// Whitespace and comments between the name and the equals sign
+    pub before_equals: Vec<Token>,
+    /// The `=` token
+    pub equals: Token,
+    /// Whitespace and comments between the equals sign and the value
+    pub after_equals: Vec<Token>,
+}
+
+/// Possibly typed value
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Value {
+    /// Type name in parenthesis before the value
+    pub type_name: Option<TypeAnnotation>,
+    /// String, number or keyword
+    pub literal: Token,
+}
+
+/// Children block in curly braces
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Children {
+    /// Whitespace and comments between node entries and the block
+    pub leading: Vec<Token>,
+    /// The `/-` marker if the whole block is commented out
+    pub slashdash: Option<SlashDash>,
+    /// Opening curly brace
+    pub open: Token,
+    /// Child nodes including the commented out ones
+    pub nodes: Vec<Node>,
+    /// Whitespace, newlines and comments after the last child
+    pub trailing: Vec<Token>,
+    /// Closing curly brace
+    pub close: Token,
+}
+
+/// Parse KDL text into lossless concrete syntax tree
+///
+/// The version of the syntax is detected from the document, see
+/// [`ParseOptions::parse_cst`](crate::ParseOptions::parse_cst) for choosing
+/// it explicitly.
+pub fn parse(file_name: impl AsRef<str>, text: &str) -> Result<Document, Error> {
+    ParseOptions::new().parse_cst(file_name, text)
+}
+
+/// Parses the document keeping every byte of the text
+pub(crate) fn document(text: &str, syntax: Syntax) -> Result<Document, Vec<ParseErrorKind<Span>>> {
+    let mut parser = Parser::<Span, _>::new(text, syntax, Lossless::new(text, syntax));
+    let nodes = parser.document()?;
+    Ok(Document {
+        nodes,
+        trailing: parser.builder.trivia(text.len()),
+    })
+}
+
+/// Syntax of the nodes created from scratch, `Auto` means KDL 1.0
+fn syntax_of(version: KdlVersion) -> Syntax {
+    match version {
+        KdlVersion::V2 => Syntax::V2,
+        _ => Syntax::V1,
+    }
+}
+
+impl Token {
+    fn new(kind: TokenKind, text: impl Into<Box<str>>, syntax: Syntax) -> Token {
+        Token {
+            kind,
+            text: text.into(),
+            syntax,
+        }
+    }
+    fn space() -> Token {
+        Token::new(TokenKind::Whitespace, " ", Syntax::V1)
+    }
+    fn ident(name: &str, syntax: Syntax) -> Token {
+        let bare = match syntax {
+            Syntax::V1 => print::is_bare_ident(name),
+            Syntax::V2 => grammar_v2::is_bare_ident(name),
+        };
+        if bare {
+            Token::new(TokenKind::Ident, name, syntax)
+        } else {
+            let mut text = String::new();
+            print::write_string(&mut text, name).expect("can write to string");
+            Token::new(TokenKind::String, text, syntax)
+        }
+    }
+    /// Kind of the token
+    pub fn kind(&self) -> TokenKind {
+        self.kind
+    }
+    /// Source text of the token
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+    /// Scans the text of the value or name token again
+    fn scalar(&self) -> Scalar<'_> {
+        let mut scan = Scanner::<Span>::new(&self.text, self.syntax);
+        match scan.scalar() {
+            Ok(Some(scalar)) => scalar,
+            _ => unreachable!("token {:?} is not a value", self.text),
+        }
+    }
+    /// Value of the string or identifier token
+    pub(crate) fn string_value(&self) -> Box<str> {
+        match self.scalar() {
+            Scalar::Ident(s) | Scalar::String(s) => s.into(),
+            Scalar::Number(_) | Scalar::Keyword(..) => self.text.clone(),
+        }
+    }
+    fn literal(&self) -> Literal {
+        match self.scalar() {
+            Scalar::Ident(s) | Scalar::String(s) => Literal::String(s.into()),
+            Scalar::Number(lit) | Scalar::Keyword(_, lit) => lit.into_owned(),
+        }
+    }
+}
+
+impl TypeAnnotation {
+    /// Returns the type name
+    pub fn name(&self) -> ast::TypeName {
+        ast::TypeName::from_string(self.name.string_value())
+    }
+    /// Create a type annotation with the specified name
+    ///
+    /// The name is written in the specified version of the syntax.
+    pub fn new(name: &str, version: KdlVersion) -> TypeAnnotation {
+        let syntax = syntax_of(version);
+        TypeAnnotation {
+            open: Token::new(TokenKind::OpenParen, "(", syntax),
+            leading: Vec::new(),
+            name: Token::ident(name, syntax),
+            trailing: Vec::new(),
+            close: Token::new(TokenKind::CloseParen, ")", syntax),
+            after: Vec::new(),
+        }
+    }
+}
+
+impl Value {
+    /// Create an untyped value
+    ///
+    /// The literal is written in the specified version of the syntax, e.g.
+    /// `true` is `#true` in KDL 2.0.
+    pub fn new(literal: &Literal, version: KdlVersion) -> Value {
+        let mut value = Value {
+            type_name: None,
+            literal: Token::new(TokenKind::Keyword, "null", syntax_of(version)),
+        };
+        value.set_literal(literal);
+        value
+    }
+    /// Returns the value of the literal
+    pub fn literal(&self) -> Literal {
+        self.literal.literal()
+    }
+    /// Replace the literal keeping the type name
+    ///
+    /// The literal is written in the version of the syntax of the replaced
+    /// one. Raw strings are kept raw if the new value can be written as such.
+    pub fn set_literal(&mut self, literal: &Literal) {
+        let syntax = self.literal.syntax;
+        let mut text = String::new();
+        let kind = match literal {
+            Literal::String(s) => {
+                // single-line raw strings of KDL 2.0 can't have newlines
+                let raw = self.literal.kind == TokenKind::RawString
+                    && !(syntax == Syntax::V2 && s.contains('\n'))
+                    && print::write_raw_string(&mut text, s).expect("can write to string");
+                if raw {
+                    if syntax == Syntax::V2 {
+                        // `r#"..."#` becomes `#"..."#`, `r"..."` becomes `#"..."#`
+                        text.remove(0);
+                        if text.starts_with('"') {
+                            text = format!("#{}#", text);
+                        }
+                    }
+                    TokenKind::RawString
+                } else {
+                    text.clear();
+                    print::write_string(&mut text, s).expect("can write to string");
+                    TokenKind::String
+                }
+            }
+            Literal::Decimal(d) if syntax == Syntax::V2 && d.0.ends_with(char::is_alphabetic) => {
+                // `inf`, `-inf` and `nan` are keywords in KDL 2.0
+                TokenKind::Keyword
+            }
+            Literal::Int(_) => TokenKind::Integer,
+            Literal::Decimal(_) => TokenKind::Decimal,
+            Literal::Bool(_) | Literal::Null => TokenKind::Keyword,
+        };
+        // `Display` of infinity and NaN already has the `#` of KDL 2.0
+        if kind == TokenKind::Keyword
+            && syntax == Syntax::V2
+            && !matches!(literal, Literal::Decimal(_))
+        {
+            text.push('#');
+        }
+        if kind != TokenKind::String && kind != TokenKind::RawString {
+            write!(&mut text, "{}", literal).expect("can write to string");
+        }
+        self.literal = Token::new(kind, text, syntax);
+    }
+}
+
+impl Entry {
+    /// Create a new argument preceded by a space
+    pub fn argument(value: Value) -> Entry {
+        Entry {
+            leading: vec![Token::space()],
+            slashdash: None,
+            name: None,
+            value,
+        }
+    }
+    /// Create a new property preceded by a space
+    ///
+    /// The name is written in the version of the syntax of the value.
+    pub fn property(name: &str, value: Value) -> Entry {
+        let syntax = value.literal.syntax;
+        Entry {
+            leading: vec![Token::space()],
+            slashdash: None,
+            name: Some(PropertyName {
+                name: Token::ident(name, syntax),
+                before_equals: Vec::new(),
+                equals: Token::new(TokenKind::Equals, "=", syntax),
+                after_equals: Vec::new(),
+            }),
+            value,
+        }
+    }
+    /// Returns `true` if entry is commented out using `/-`
+    pub fn is_commented(&self) -> bool {
+        self.slashdash.is_some()
+    }
+    /// Returns name of the property or `None` if this is an argument
+    pub fn property_name(&self) -> Option<Box<str>> {
+        self.name.as_ref().map(|n| n.name.string_value())
+    }
+}
+
+impl Node {
+    /// Create a new node with the specified name
+    ///
+    /// The name is written in the specified version of the syntax. The node
+    /// is terminated by a newline.
+    pub fn new(name: &str, version: KdlVersion) -> Node {
+        let syntax = syntax_of(version);
+        Node {
+            leading: Vec::new(),
+            slashdash: None,
+            type_name: None,
+            name: Token::ident(name, syntax),
+            entries: Vec::new(),
+            children: Vec::new(),
+            trailing: Vec::new(),
+            terminator: vec![Token::new(TokenKind::Newline, "\n", syntax)],
+        }
+    }
+    /// Returns `true` if node is commented out using `/-`
+    pub fn is_commented(&self) -> bool {
+        self.slashdash.is_some()
+    }
+    /// Returns the name of the node
+    pub fn name(&self) -> Box<str> {
+        self.name.string_value()
+    }
+    /// Rename the node
+    ///
+    /// The name is written in the version of the syntax of the old one.
+    pub fn set_name(&mut self, name: &str) {
+        self.name = Token::ident(name, self.name.syntax);
+    }
+    /// Arguments of the node, excluding commented out ones
+    pub fn arguments(&self) -> impl Iterator<Item = &Value> {
+        self.entries
+            .iter()
+            .filter(|e| !e.is_commented() && e.name.is_none())
+            .map(|e| &e.value)
+    }
+    /// Mutable arguments of the node, excluding commented out ones
+    pub fn arguments_mut(&mut self) -> impl Iterator<Item = &mut Value> {
+        self.entries
+            .iter_mut()
+            .filter(|e| !e.is_commented() && e.name.is_none())
+            .map(|e| &mut e.value)
+    }
+    /// Returns value of the property
+    ///
+    /// If property is specified multiple times the last one is returned, as
+    /// it's the one that is used by the decoder.
+    pub fn property(&self, name: &str) -> Option<&Value> {
+        self.entries
+            .iter()
+            .rev()
+            .filter(|e| !e.is_commented())
+            .find(|e| e.property_name().is_some_and(|n| &*n == name))
+            .map(|e| &e.value)
+    }
+    /// Returns mutable value of the property
+    pub fn property_mut(&mut self, name: &str) -> Option<&mut Value> {
+        self.entries
+            .iter_mut()
+            .rev()
+            .filter(|e| !e.is_commented())
+            .find(|e| e.property_name().is_some_and(|n| &*n == name))
+            .map(|e| &mut e.value)
+    }
+    /// Child nodes unless there are none or they are commented out
+    pub fn children(&self) -> Option<&[Node]> {
+        self.children
+            .iter()
+            .find(|c| c.slashdash.is_none())
+            .map(|c| &c.nodes[..])
+    }
+
+    fn write_tokens(&self, f: &mut dyn FnMut(&Token)) {
+        write_nodes(std::slice::from_ref(self), f);
+    }
+
+    /// Writes the tokens up to the first children block
+    fn write_head(&self, f: &mut dyn FnMut(&Token)) {
+        self.leading.iter().for_each(&mut *f);
+        if let Some(slashdash) = &self.slashdash {
+            slashdash.write_tokens(f);
+        }
+        if let Some(type_name) = &self.type_name {
+            type_name.write_tokens(f);
+        }
+        f(&self.name);
+        for entry in &self.entries {
+            entry.write_tokens(f);
+        }
+    }
+
+    /// Writes the tokens after the last children block
+    fn write_tail(&self, f: &mut dyn FnMut(&Token)) {
+        self.trailing.iter().for_each(&mut *f);
+        self.terminator.iter().for_each(&mut *f);
+    }
+}
+
+/// Calls `f` for every token of the nodes in the order of the source text
+///
+/// Uses an explicit stack, so deeply nested nodes don't overflow the stack.
+fn write_nodes(nodes: &[Node], f: &mut dyn FnMut(&Token)) {
+    enum Visit<'a> {
+        Node(&'a Node),
+        Open(&'a Children),
+        Close(&'a Children),
+        Tail(&'a Node),
+    }
+    let mut stack = nodes.iter().rev().map(Visit::Node).collect::<Vec<_>>();
+    while let Some(visit) = stack.pop() {
+        match visit {
+            Visit::Node(node) => {
+                node.write_head(f);
+                stack.push(Visit::Tail(node));
+                for children in node.children.iter().rev() {
+                    stack.push(Visit::Close(children));
+                    stack.extend(children.nodes.iter().rev().map(Visit::Node));
+                    stack.push(Visit::Open(children));
+                }
+            }
+            Visit::Open(children) => children.write_open(f),
+            Visit::Close(children) => children.write_close(f),
+            Visit::Tail(node) => node.write_tail(f),
+        }
+    }
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        // children are flattened into a single list, so deeply nested nodes
+        // are dropped without recursion
+        let mut stack = Vec::new();
+        for children in self.children.drain(..) {
+            stack.extend(children.nodes);
+        }
+        while let Some(mut node) = stack.pop() {
+            for children in node.children.drain(..) {
+                stack.extend(children.nodes);
+            }
+        }
+    }
+}
+
+impl Entry {
+    fn write_tokens(&self, f: &mut dyn FnMut(&Token)) {
+        self.leading.iter().for_each(&mut *f);
+        if let Some(slashdash) = &self.slashdash {
+            slashdash.write_tokens(f);
+        }
+        if let Some(name) = &self.name {
+            f(&name.name);
+            name.before_equals.iter().for_each(&mut *f);
+            f(&name.equals);
+            name.after_equals.iter().for_each(&mut *f);
+        }
+        self.value.write_tokens(f);
+    }
+}
+
+impl Children {
+    /// Writes the tokens up to and including the opening brace
+    fn write_open(&self, f: &mut dyn FnMut(&Token)) {
+        self.leading.iter().for_each(&mut *f);
+        if let Some(slashdash) = &self.slashdash {
+            slashdash.write_tokens(f);
+        }
+        f(&self.open);
+    }
+
+    /// Writes the tokens after the last child node
+    fn write_close(&self, f: &mut dyn FnMut(&Token)) {
+        self.trailing.iter().for_each(&mut *f);
+        f(&self.close);
+    }
+
+    fn write_tokens(&self, f: &mut dyn FnMut(&Token)) {
+        self.write_open(f);
+        write_nodes(&self.nodes, f);
+        self.write_close(f);
+    }
+}
+
+impl SlashDash {
+    fn write_tokens(&self, f: &mut dyn FnMut(&Token)) {
+        f(&self.token);
+        self.trailing.iter().for_each(f);
+    }
+}
+
+impl TypeAnnotation {
+    fn write_tokens(&self, f: &mut dyn FnMut(&Token)) {
+        f(&self.open);
+        self.leading.iter().for_each(&mut *f);
+        f(&self.name);
+        self.trailing.iter().for_each(&mut *f);
+        f(&self.close);
+        self.after.iter().for_each(f);
+    }
+}
+
+impl Value {
+    fn write_tokens(&self, f: &mut dyn FnMut(&Token)) {
+        if let Some(type_name) = &self.type_name {
+            type_name.write_tokens(f);
+        }
+        f(&self.literal);
+    }
+}
+
+impl Document {
+    /// Calls `f` for every token in the order of the source text
+    pub(crate) fn write_tokens(&self, f: &mut dyn FnMut(&Token)) {
+        write_nodes(&self.nodes, f);
+        self.trailing.iter().for_each(f);
+    }
+    /// Returns the version of the syntax the document is written in
+    ///
+    /// Documents without any tokens are reported as KDL 1.0.
+    pub fn version(&self) -> KdlVersion {
+        let token = match self.nodes.first() {
+            Some(node) => Some(&node.name),
+            None => self.trailing.first(),
+        };
+        match token.map(|t| t.syntax) {
+            Some(Syntax::V2) => KdlVersion::V2,
+            _ => KdlVersion::V1,
+        }
+    }
+    /// Returns `true` if the document has any comments
+    ///
+    /// This includes entities commented out with `/-`, that is everything
+    /// that is lost when converting the document into the AST.
+    pub fn has_comments(&self) -> bool {
+        let mut found = false;
+        self.write_tokens(&mut |t| {
+            found |= matches!(
+                t.kind,
+                TokenKind::LineComment | TokenKind::BlockComment | TokenKind::SlashDash
+            );
+        });
+        found
+    }
+    /// Pretty-prints the document keeping the comments
+    ///
+    /// Every node is written on its own line indented by `indent` spaces
+    /// per level, entries are separated by single spaces. Only whitespace,
+    /// newlines and semicolons are changed: names, values and comments
+    /// (including the entities commented out using `/-`) are written as
+    /// they are in the source, in the same version of the syntax.
+    ///
+    /// ```rust
+    /// let doc = knus::cst::parse("<test>", "a   1 {b; /* note */ c;}")?;
+    /// assert_eq!(doc.format(2), "a 1 {\n  b\n  /* note */ c\n}\n");
+    /// # Ok::<(), knus::Error>(())
+    /// ```
+    pub fn format(&self, indent: usize) -> String {
+        Formatter::new(indent).document(self)
+    }
+    /// Convert into the abstract syntax tree
+    ///
+    /// Spans of the tree point into the text that the document is printed
+    /// to. So if the document was not modified, this returns the same tree as
+    /// [`parse_ast`](crate::parse_ast) on the original text.
+    pub fn to_ast<S: traits::Span>(&self) -> ast::Document<S> {
+        let text = self.to_string();
+        let nodes = Lower::<S>::new(&text).nodes(&self.nodes);
+        ast::Document { nodes }
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut result = Ok(());
+        self.write_tokens(&mut |t| {
+            if result.is_ok() {
+                result = f.write_str(&t.text);
+            }
+        });
+        result
+    }
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut result = Ok(());
+        self.write_tokens(&mut |t| {
+            if result.is_ok() {
+                result = f.write_str(&t.text);
+            }
+        });
+        result
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+struct Lower<'a, S: traits::Span> {
+    text: &'a str,
+    tracker: S::Tracker,
+}
+
+/// Node that is lowered after its children
+struct LowerFrame<'n, S: traits::Span> {
+    node: &'n Node,
+    start: S::Offset,
+    value: ast::Node<S>,
+    /// Index of the children block that is not commented out
+    block: usize,
+    block_start: S::Offset,
+    /// Already lowered siblings of the node
+    nodes: Vec<SpannedNode<S>>,
+    /// Siblings of the node that are not lowered yet
+    rest: std::slice::Iter<'n, Node>,
+}
+
+impl<'a, S: traits::Span> Lower<'a, S> {
+    fn new(text: &'a str) -> Self {
+        Lower {
+            text,
+            tracker: S::tracker(),
+        }
+    }
+
+    fn offset(&mut self, byte: usize) -> S::Offset {
+        self.tracker.offset(self.text, byte)
+    }
+
+    fn span(&mut self, range: Range<usize>) -> S {
+        let start = self.offset(range.start);
+        S::new(start, self.offset(range.end))
+    }
+
+    /// Lowers the nodes using an explicit stack instead of recursion
+    fn nodes(&mut self, nodes: &[Node]) -> Vec<SpannedNode<S>> {
+        let mut pos = 0;
+        let mut stack = Vec::<LowerFrame<S>>::new();
+        let mut result = Vec::with_capacity(nodes.len());
+        let mut rest = nodes.iter();
+        loop {
+            if let Some(node) = rest.next() {
+                if node.is_commented() {
+                    pos += token_len(|f| node.write_tokens(f));
+                    continue;
+                }
+                let (start, value, block) = self.head(node, &mut pos);
+                match block {
+                    Some(block) => {
+                        let block_start = self.offset(pos);
+                        let children = &node.children[block];
+                        pos += children.open.text.len();
+                        stack.push(LowerFrame {
+                            node,
+                            start,
+                            value,
+                            block,
+                            block_start,
+                            nodes: mem::take(&mut result),
+                            rest: mem::replace(&mut rest, children.nodes.iter()),
+                        });
+                    }
+                    None => {
+                        let node = self.tail(node, node.children.len(), start, value, &mut pos);
+                        result.push(node);
+                    }
+                }
+            } else if let Some(frame) = stack.pop() {
+                let children = &frame.node.children[frame.block];
+                pos += tokens_len(&children.trailing);
+                pos += children.close.text.len();
+                let children = mem::replace(&mut result, frame.nodes);
+                rest = frame.rest;
+                let mut value = frame.value;
+                value.children = Some(Spanned {
+                    span: S::new(frame.block_start, self.offset(pos)),
+                    value: children,
+                });
+                let node = self.tail(frame.node, frame.block + 1, frame.start, value, &mut pos);
+                result.push(node);
+            } else {
+                return result;
+            }
+        }
+    }
+
+    /// Lowers the node up to the opening brace of the children block
+    ///
+    /// Returns the index of the block that is not commented out, the ones
+    /// before it are skipped.
+    fn head(&mut self, node: &Node, pos: &mut usize) -> (S::Offset, ast::Node<S>, Option<usize>) {
+        *pos += tokens_len(&node.leading);
+        let start = self.offset(*pos);
+        let type_name = node.type_name.as_ref().map(|t| self.type_name(t, pos));
+        let node_name = self.token(&node.name, pos, Token::string_value);
+        let mut arguments = Vec::new();
+        let mut properties = BTreeMap::new();
+        for entry in &node.entries {
+            if entry.is_commented() {
+                *pos += token_len(|f| entry.write_tokens(f));
+                continue;
+            }
+            *pos += tokens_len(&entry.leading);
+            if let Some(name) = &entry.name {
+                let name_value = self.token(&name.name, pos, Token::string_value);
+                *pos += tokens_len(&name.before_equals);
+                *pos += name.equals.text.len();
+                *pos += tokens_len(&name.after_equals);
+                let value = self.value(&entry.value, pos);
+                properties.insert(name_value, value);
+            } else {
+                arguments.push(self.value(&entry.value, pos));
+            }
+        }
+        let mut block = None;
+        for (index, children) in node.children.iter().enumerate() {
+            if children.slashdash.is_none() {
+                *pos += tokens_len(&children.leading);
+                block = Some(index);
+                break;
+            }
+            *pos += token_len(|f| children.write_tokens(f));
+        }
+        let value = ast::Node {
+            type_name,
+            node_name,
+            arguments,
+            properties,
+            children: None,
+        };
+        (start, value, block)
+    }
+
+    /// Lowers the rest of the node starting from the children block with
+    /// the index, remaining blocks are commented out
+    fn tail(
+        &mut self,
+        node: &Node,
+        block: usize,
+        start: S::Offset,
+        value: ast::Node<S>,
+        pos: &mut usize,
+    ) -> SpannedNode<S> {
+        for children in &node.children[block..] {
+            *pos += token_len(|f| children.write_tokens(f));
+        }
+        *pos += tokens_len(&node.trailing);
+        *pos += tokens_len(&node.terminator);
+        Spanned {
+            span: S::new(start, self.offset(*pos)),
+            value,
+        }
+    }
+
+    fn type_name(&mut self, t: &TypeAnnotation, pos: &mut usize) -> Spanned<ast::TypeName, S> {
+        let len = token_len(|f| t.write_tokens(f)) - tokens_len(&t.after);
+        let span = self.span(*pos..*pos + len);
+        *pos += len + tokens_len(&t.after);
+        Spanned {
+            span,
+            value: t.name(),
+        }
+    }
+
+    fn value(&mut self, value: &Value, pos: &mut usize) -> ast::Value<S> {
+        let type_name = value.type_name.as_ref().map(|t| self.type_name(t, pos));
+        let literal = self.token(&value.literal, pos, Token::literal);
+        ast::Value { type_name, literal }
+    }
+
+    fn token<T>(
+        &mut self,
+        token: &Token,
+        pos: &mut usize,
+        f: impl FnOnce(&Token) -> T,
+    ) -> Spanned<T, S> {
+        let span = self.span(*pos..*pos + token.text.len());
+        *pos += token.text.len();
+        Spanned {
+            span,
+            value: f(token),
+        }
+    }
+}
+
+fn tokens_len(tokens: &[Token]) -> usize {
+    tokens.iter().map(|t| t.text.len()).sum()
+}
+
+fn token_len(visit: impl FnOnce(&mut dyn FnMut(&Token))) -> usize {
+    let mut len = 0;
+    visit(&mut |t| len += t.text.len());
+    len
+}
+
+/// Builds the concrete syntax tree from the parts found by the parser
+///
+/// The text between the parts is split into whitespace and comment tokens,
+/// so every byte of the source ends up in the tree.
+struct Lossless<'a> {
+    text: &'a str,
+    syntax: Syntax,
+    /// End of the last token added to the tree
+    last: usize,
+}
+
+/// Node of the [`Lossless`] builder that is being parsed
+pub(crate) struct PendingNode {
+    node: Node,
+    /// Start of the children block that is being parsed
+    block: Option<(Vec<Token>, Option<SlashDash>, Token)>,
+}
+
+/// Kind of the string or identifier token
+fn string_kind(text: &str) -> TokenKind {
+    if text.starts_with('"') {
+        TokenKind::String
+    } else if text.starts_with('#')
+        || (text.starts_with('r') && text[1..].trim_start_matches('#').starts_with('"'))
+    {
+        TokenKind::RawString
+    } else {
+        TokenKind::Ident
+    }
+}
+
+impl<'a> Lossless<'a> {
+    fn new(text: &'a str, syntax: Syntax) -> Self {
+        Lossless {
+            text,
+            syntax,
+            last: 0,
+        }
+    }
+
+    /// Splits the text up to the byte offset into whitespace and comments
+    fn trivia(&mut self, end: usize) -> Vec<Token> {
+        let mut scan = Scanner::<Span>::new(self.text, self.syntax);
+        scan.pos = self.last;
+        let mut tokens = Vec::new();
+        while scan.pos < end {
+            let start = scan.pos;
+            match scan.trivia() {
+                Ok(Some(kind)) => tokens.push(self.new_token(kind, start..scan.pos)),
+                // the text is checked by the parser already
+                _ => unreachable!("unexpected text at byte {}", start),
+            }
+        }
+        self.last = end;
+        tokens
+    }
+
+    fn new_token(&self, kind: TokenKind, range: Range<usize>) -> Token {
+        Token::new(kind, &self.text[range], self.syntax)
+    }
+
+    fn token(&mut self, kind: TokenKind, range: Range<usize>) -> Token {
+        self.last = range.end;
+        self.new_token(kind, range)
+    }
+
+    /// Adds the `/-` token with whitespace up to the byte offset
+    fn slashdash(&mut self, slashdash: Option<usize>, next: usize) -> Option<SlashDash> {
+        slashdash.map(|start| SlashDash {
+            token: self.token(TokenKind::SlashDash, start..start + 2),
+            trailing: self.trivia(next),
+        })
+    }
+
+    fn name(&mut self, name: Parsed<Cow<'a, str>>) -> Token {
+        let kind = string_kind(&self.text[name.range.clone()]);
+        self.token(kind, name.range)
+    }
+
+    /// Adds the type name with trivia up to the byte offset of the
+    /// annotated name or value
+    fn type_name(&mut self, type_name: ParsedType<'a>, next: usize) -> TypeAnnotation {
+        let open = self.token(TokenKind::OpenParen, type_name.open..type_name.open + 1);
+        let leading = self.trivia(type_name.name.range.start);
+        let name = self.name(type_name.name);
+        let trailing = self.trivia(type_name.close);
+        let close = self.token(TokenKind::CloseParen, type_name.close..type_name.close + 1);
+        TypeAnnotation {
+            open,
+            leading,
+            name,
+            trailing,
+            close,
+            after: self.trivia(next),
+        }
+    }
+
+    fn value(&mut self, value: ParsedValue<'a>) -> Value {
+        let start = value.literal.range.start;
+        let type_name = value.type_name.map(|t| self.type_name(t, start));
+        let text = &self.text[value.literal.range.clone()];
+        let kind = match value.literal.value {
+            borrowed::Literal::String(_) => string_kind(text),
+            borrowed::Literal::Int(_) => TokenKind::Integer,
+            borrowed::Literal::Decimal(_) if !text.starts_with('#') => TokenKind::Decimal,
+            _ => TokenKind::Keyword,
+        };
+        Value {
+            type_name,
+            literal: self.token(kind, value.literal.range),
+        }
+    }
+}
+
+/// Byte offset of the value including its type name
+fn value_start(value: &ParsedValue) -> usize {
+    value
+        .type_name
+        .as_ref()
+        .map_or(value.literal.range.start, |t| t.open)
+}
+
+impl<'a> Builder<'a> for Lossless<'a> {
+    type Node = PendingNode;
+    type Output = Node;
+
+    fn node(&mut self, head: ParsedHead<'a>) -> PendingNode {
+        let start = head
+            .type_name
+            .as_ref()
+            .map_or(head.name.range.start, |t| t.open);
+        let leading = self.trivia(head.slashdash.unwrap_or(start));
+        let slashdash = self.slashdash(head.slashdash, start);
+        let name_start = head.name.range.start;
+        let type_name = head.type_name.map(|t| self.type_name(t, name_start));
+        let name = self.name(head.name);
+        PendingNode {
+            node: Node {
+                leading,
+                slashdash,
+                type_name,
+                name,
+                entries: Vec::new(),
+                children: Vec::new(),
+                trailing: Vec::new(),
+                terminator: Vec::new(),
+            },
+            block: None,
+        }
+    }
+
+    fn entry(&mut self, node: &mut PendingNode, entry: ParsedEntry<'a>) {
+        let start = match &entry.name {
+            Some((name, _)) => name.range.start,
+            None => value_start(&entry.value),
+        };
+        let leading = self.trivia(entry.slashdash.unwrap_or(start));
+        let slashdash = self.slashdash(entry.slashdash, start);
+        let value = &entry.value;
+        let name = entry.name.map(|(name, equals)| PropertyName {
+            name: self.name(name),
+            before_equals: self.trivia(equals),
+            equals: self.token(TokenKind::Equals, equals..equals + 1),
+            after_equals: self.trivia(value_start(value)),
+        });
+        let value = self.value(entry.value);
+        node.node.entries.push(Entry {
+            leading,
+            slashdash,
+            name,
+            value,
+        });
+    }
+
+    fn open(&mut self, node: &mut PendingNode, slashdash: Option<usize>, open: usize) {
+        let leading = self.trivia(slashdash.unwrap_or(open));
+        let slashdash = self.slashdash(slashdash, open);
+        let open = self.token(TokenKind::OpenBrace, open..open + 1);
+        node.block = Some((leading, slashdash, open));
+    }
+
+    fn close(&mut self, node: &mut PendingNode, children: Vec<Node>, close: usize) {
+        let trailing = self.trivia(close);
+        let close = self.token(TokenKind::CloseBrace, close..close + 1);
+        if let Some((leading, slashdash, open)) = node.block.take() {
+            node.node.children.push(Children {
+                leading,
+                slashdash,
+                open,
+                nodes: children,
+                trailing,
+                close,
+            });
+        }
+    }
+
+    fn finish(&mut self, mut node: PendingNode, terminator: Range<usize>) -> Option<Node> {
+        node.node.trailing = self.trivia(terminator.start);
+        node.node.terminator = self.trivia(terminator.end);
+        Some(node.node)
+    }
+
+    fn discard(&mut self, nodes: Vec<Node>) {
+        // nodes of the concrete syntax tree are dropped without recursion
+        drop(nodes);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse, TokenKind};
+    use crate::ast::{Decimal, Document, Integer, Literal, Radix};
+    use crate::grammar::Syntax;
+    use crate::parse_ast;
+    use crate::span::{LineSpan, Span};
+    use crate::KdlVersion;
+
+    const SAMPLE: &str = r##"// leading comment
+/* block /* nested */ */ (t)node 1 "two" key=(u8)0x1f /-skipped=1 \
+    r#"raw "string""# /- "x" {
+    child; /- commented { a; }
+    - 1.5e10 null true false
+    "quoted name" prop="\u{1F600}\n" // trailing
+}
+/- gone {
+    really
+}
+last /- {
+    not-a-child
+} ;
+   "##;
+
+    fn cst(text: &str) -> super::Document {
+        parse("<test>", text).unwrap()
+    }
+
+    fn cst_err(text: &str) -> String {
+        let err = parse("<test>", text).unwrap_err();
+        err.errors
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn lossless() {
+        assert_eq!(cst(SAMPLE).to_string(), SAMPLE);
+        for text in [
+            "",
+            "\n\n",
+            "a",
+            "a;b;c",
+            "a\r\nb\u{2028}c\u{FEFF}",
+            "a \\ // comment\n  1",
+            "a\t/* x */\t1 ;// done",
+            "a{b;}",
+        ] {
+            assert_eq!(cst(text).to_string(), text);
+        }
+    }
+
+    #[test]
+    fn lower() {
+        let ast: Document<Span> = parse_ast("<test>", SAMPLE).unwrap();
+        let lowered = cst(SAMPLE).to_ast::<Span>();
+        assert_eq!(format!("{:?}", lowered), format!("{:?}", ast));
+        let ast: Document<LineSpan> = parse_ast("<test>", SAMPLE).unwrap();
+        let lowered = cst(SAMPLE).to_ast::<LineSpan>();
+        assert_eq!(format!("{:?}", lowered), format!("{:?}", ast));
+    }
+
+    #[test]
+    fn deep_nesting() {
+        let depth = 100_000;
+        let text = "a {\n".repeat(depth) + "/- b { c; }\n" + &"}\n".repeat(depth);
+        let doc = cst(&text);
+        assert_eq!(doc.to_string(), text);
+        assert!(doc.has_comments());
+        let ast = doc.to_ast::<Span>();
+        let mut node = &ast.nodes[0];
+        for _ in 1..depth {
+            assert_eq!(node.children().len(), 1);
+            node = node.children().next().unwrap();
+        }
+        assert_eq!(node.children().len(), 0);
+        crate::ast::drop_nodes(ast.nodes);
+        drop(doc);
+    }
+
+    #[test]
+    fn v2() {
+        let text = "/- kdl-version 2\n( t )a ( u8 ) 1 k = #true /-{x} {\n  b #null\n}\n";
+        let mut doc = cst(text);
+        assert_eq!(doc.to_string(), text);
+        let ast: Document<Span> = parse_ast("<test>", text).unwrap();
+        assert_eq!(format!("{:?}", doc.to_ast::<Span>()), format!("{:?}", ast));
+        let node = &mut doc.nodes[1];
+        assert_eq!(node.children().unwrap().len(), 1);
+        assert_eq!(node.property("k").unwrap().literal(), Literal::Bool(true));
+        node.property_mut("k").unwrap().set_literal(&Literal::Null);
+        node.arguments_mut()
+            .next()
+            .unwrap()
+            .set_literal(&Literal::String("true".into()));
+        node.set_name("#a");
+        assert_eq!(
+            doc.to_string(),
+            text.replace("( t )a", "( t )\"#a\"")
+                .replace("1 k = #true", "\"true\" k = #null")
+        );
+    }
+
+    #[test]
+    fn edit_v2() {
+        let text = "/- kdl-version 2\na 1\n";
+        let mut doc = cst(text);
+        assert_eq!(doc.version(), KdlVersion::V2);
+        let version = doc.version();
+        let node = &mut doc.nodes[1];
+        for literal in [
+            Literal::Bool(false),
+            Literal::Null,
+            Literal::Decimal(Decimal("inf".into())),
+        ] {
+            node.entries
+                .push(super::Entry::argument(super::Value::new(&literal, version)));
+        }
+        let mut value = super::Value::new(&Literal::String("x".into()), version);
+        value.type_name = Some(super::TypeAnnotation::new("null", version));
+        node.entries.push(super::Entry::property("true", value));
+        let mut child = super::Node::new("false", version);
+        child.leading.push(super::Token::space());
+        node.children.push(super::Children {
+            leading: vec![super::Token::space()],
+            slashdash: None,
+            open: super::Token::new(TokenKind::OpenBrace, "{", Syntax::V2),
+            nodes: vec![child],
+            trailing: Vec::new(),
+            close: super::Token::new(TokenKind::CloseBrace, "}", Syntax::V2),
+        });
+        let expected = concat!(
+            "/- kdl-version 2\n",
+            r#"a 1 #false #null #inf "true"=("null")"x" { "false""#,
+            "\n}\n",
+        );
+        assert_eq!(doc.to_string(), expected);
+        let ast: Document<Span> = parse_ast("<test>", expected).unwrap();
+        assert_eq!(doc.to_ast::<Span>(), ast);
+        assert_eq!(cst("a 1").version(), KdlVersion::V1);
+        assert_eq!(super::Document::default().version(), KdlVersion::V1);
+    }
+
+    #[test]
+    fn access() {
+        let doc = cst(SAMPLE);
+        assert_eq!(doc.nodes.len(), 3);
+        let node = &doc.nodes[0];
+        assert_eq!(&*node.name(), "node");
+        assert_eq!(
+            node.arguments().map(|v| v.literal()).collect::<Vec<_>>(),
+            vec![
+                Literal::Int(Integer(Radix::Dec, "1".into())),
+                Literal::String("two".into()),
+                Literal::String("raw \"string\"".into()),
+            ]
+        );
+        assert_eq!(node.property("skipped"), None);
+        assert_eq!(node.children().unwrap().len(), 4);
+        assert!(doc.nodes[1].is_commented());
+        assert_eq!(doc.nodes[2].children(), None);
+    }
+
+    #[test]
+    fn edit() {
+        let mut doc = cst(SAMPLE);
+        let version = doc.version();
+        let node = &mut doc.nodes[0];
+        node.property_mut("key")
+            .unwrap()
+            .set_literal(&Literal::Int(Integer(Radix::Dec, "42".into())));
+        node.arguments_mut()
+            .nth(2)
+            .unwrap()
+            .set_literal(&Literal::String("new \"raw\"".into()));
+        node.set_name("renamed node");
+        node.entries.push(super::Entry::property(
+            "added",
+            super::Value::new(&Literal::Bool(true), version),
+        ));
+        let expected = SAMPLE
+            .replace("(t)node", "(t)\"renamed node\"")
+            .replace("key=(u8)0x1f", "key=(u8)42")
+            .replace(r##"r#"raw "string""#"##, r##"r#"new "raw""#"##)
+            .replace(r#"/- "x" {"#, r#"/- "x" added=true {"#);
+        assert_eq!(doc.to_string(), expected);
+        let ast: Document<Span> = parse_ast("<test>", &expected).unwrap();
+        assert_eq!(doc.to_ast::<Span>(), ast);
+    }
+
+    fn format(text: &str) -> String {
+        let formatted = cst(text).format(4);
+        // formatting keeps the meaning and is stable
+        let ast: Document<Span> = parse_ast("<test>", text).unwrap();
+        let formatted_ast: Document<Span> = parse_ast("<formatted>", &formatted).unwrap();
+        assert_eq!(formatted_ast, ast, "{}", formatted);
+        assert_eq!(cst(&formatted).format(4), formatted);
+        formatted
+    }
+
+    #[test]
+    fn format_layout() {
+        assert_eq!(format(""), "");
+        assert_eq!(format("\n\n"), "");
+        assert_eq!(format("a   1    k=2"), "a 1 k=2\n");
+        assert_eq!(format("a;b;c"), "a\nb\nc\n");
+        assert_eq!(
+            format("a{b;c{d;};}"),
+            "a {\n    b\n    c {\n        d\n    }\n}\n"
+        );
+        assert_eq!(format("a {  }"), "a {}\n");
+        assert_eq!(format("\n\na\n\n\n\nb\n\n"), "a\n\nb\n");
+        assert_eq!(format("a {\n\n  b\n\n}"), "a {\n    b\n}\n");
+        assert_eq!(
+            format("a 1 \\\n2 \\ // x\n  3"),
+            "a 1 \\\n    2 \\ // x\n    3\n"
+        );
+        assert_eq!(format("(t)a (u8)1 k=(t)\"v\""), "(t)a (u8)1 k=(t)\"v\"\n");
+        assert_eq!(format("\u{FEFF}a"), "\u{FEFF}a\n");
+    }
+
+    #[test]
+    fn format_comments() {
+        assert_eq!(format("// c\na // d\n"), "// c\na // d\n");
+        assert_eq!(format("a; // d\nb"), "a // d\nb\n");
+        assert_eq!(format("a { // d\nb; }"), "a { // d\n    b\n}\n");
+        assert_eq!(format("a /* x */ 1 /*y*/;"), "a /* x */ 1 /*y*/\n");
+        assert_eq!(format("/* x */ a"), "/* x */ a\n");
+        assert_eq!(format("a\n\n// c\n\nb"), "a\n\n// c\n\nb\n");
+        assert_eq!(
+            format("a {\n  b\n  // end\n}"),
+            "a {\n    b\n    // end\n}\n"
+        );
+        assert_eq!(format("a {\n// only\n}"), "a {\n    // only\n}\n");
+        assert_eq!(
+            format("/-  a 1\nb /-  2 /- {c;}"),
+            "/- a 1\nb /- 2 /- {\n    c\n}\n"
+        );
+        assert_eq!(format("a /-{\n}"), "a /-{}\n");
+    }
+
+    #[test]
+    fn format_v2() {
+        let text = "/- kdl-version 2\n( t )a   ( u8 )1 k = #true {b; c}\n";
+        assert_eq!(
+            format(text),
+            "/- kdl-version 2\n(t)a (u8)1 k=#true {\n    b\n    c\n}\n"
+        );
+        let text = "/- kdl-version 2\na /-{x} {\n\"\"\"\n  multi\n  \"\"\"\n} /-{y}";
+        assert_eq!(
+            format(text),
+            "/- kdl-version 2\na /-{\n    x\n} {\n    \"\"\"\n  multi\n  \"\"\"\n} /-{\n    y\n}\n"
+        );
+    }
+
+    #[test]
+    fn format_deep() {
+        let depth = 100_000;
+        let text = "a {\n".repeat(depth) + "b\n" + &"}\n".repeat(depth);
+        assert!(cst(&text).format(0) == text);
+    }
+
+    #[test]
+    fn comments() {
+        assert!(cst(SAMPLE).has_comments());
+        assert!(!cst("a 1 \\\n  2 {\n b;\n}\n").has_comments());
+        assert!(cst("a // done").has_comments());
+        assert!(cst("a { b /* x */; }").has_comments());
+        assert!(cst("a /-1").has_comments());
+        assert!(cst("a /-{ b; }").has_comments());
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(cst_err("a x"), "identifiers cannot be used as arguments");
+        assert_eq!(cst_err("a 1=2"), "numbers cannot be used as property names");
+        assert_eq!(cst_err("a { b"), "unclosed curly braces `{`");
+        assert_eq!(cst_err("a \"b"), "unclosed string `\"`");
+        assert_eq!(
+            cst_err("a { b }"),
+            "found `}`, expected `;`, `{`, newline, value or end of input"
+        );
+        assert_eq!(
+            cst_err("a 1\"x\""),
+            "found `\"`, expected `;`, `{`, newline, whitespace or end of input"
+        );
+        assert_eq!(cst_err("true"), "found `true`, expected identifier");
+    }
+}
@@ -0,0 +1,1483 @@
+//! Serde deserializer for the KDL documents
+//!
+//! This module is only available with the `serde` feature. It allows decoding
+//! types that implement [`serde::Deserialize`], including the ones from
+//! third-party crates that can't implement [`Decode`](crate::Decode):
+//!
+//! ```rust
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize, Debug, PartialEq)]
+//! struct Config {
+//!     name: String,
+//!     tags: Vec<String>,
+//!     server: Server,
+//! }
+//!
+//! #[derive(Deserialize, Debug, PartialEq)]
+//! struct Server {
+//!     host: String,
+//!     port: u16,
+//! }
+//!
+//! let config: Config = knus::de::from_str("config.kdl", r#"
+//!     name "app"
+//!     tags "web" "api"
+//!     server host="localhost" {
+//!         port 8080
+//!     }
+//! "#)?;
+//! assert_eq!(config.server.port, 8080);
+//! # Ok::<(), knus::Error>(())
+//! ```
+//!
+//! # Mapping
+//!
+//! The document (and the children of a node) is a map of node names to the
+//! nodes, so it's deserialized as a struct or a map. When a sequence is
+//! requested instead, each node is an element of the sequence.
+//!
+//! A node is deserialized according to the requested type:
+//!
+//! * Scalars (strings, numbers, booleans) are read from the single argument
+//!   of the node, `null` argument is `None` for options.
+//! * Sequences and tuples are read from the arguments, or from the children
+//!   if there are no arguments.
+//! * Structs and maps are read from the properties and the children. The
+//!   arguments are available as a sequence under the special `$args` key.
+//! * Unit is a node without arguments, properties and children.
+//!
+//! Multiple nodes with the same name form a sequence, so they must be
+//! deserialized into a `Vec` or similar. A single node of sequence type that
+//! has no properties is deserialized from its arguments (or children).
+//!
+//! When enum is an element of the sequence of nodes, the node name is the
+//! name of the variant. Otherwise (i.e. for a struct field) the first argument
+//! is the name of the variant and the rest of the node is its contents. The
+//! values of unit variants can also be used as enum values.
+//!
+//! Values with the `(base64)` type name are deserialized as bytes or as
+//! sequences of `u8` (i.e. `Vec<u8>`).
+use std::collections::btree_map;
+use std::fmt;
+
+use miette::NamedSource;
+use serde::de::value::{BorrowedStrDeserializer, SeqDeserializer};
+use serde::de::{self, DeserializeOwned, DeserializeSeed, Deserializer, Visitor};
+use serde::forward_to_deserialize_any;
+
+use crate::ast::{self, BuiltinType, Document, Literal, SpannedName, SpannedNode, Value};
+use crate::decode::{self, Context, Kind};
+use crate::errors::{DecodeError, Error};
+use crate::span::Span;
+use crate::traits::ErrorSpan;
+
+/// Name of the key that contains arguments of the node in structs and maps
+pub const ARGUMENTS: &str = "$args";
+
+/// Deserializer of the document or the list of nodes
+#[derive(Debug, Clone, Copy)]
+pub struct DocumentDeserializer<'de, S> {
+    nodes: &'de [SpannedNode<S>],
+}
+
+/// Deserializer of a single node
+#[derive(Debug, Clone, Copy)]
+pub struct NodeDeserializer<'de, S> {
+    node: &'de SpannedNode<S>,
+    args: &'de [Value<S>],
+    /// Node name is the name of the enum variant
+    tag: bool,
+}
+
+/// Deserializer of the nodes with the same name
+struct NodesDeserializer<'de, S> {
+    nodes: Vec<&'de SpannedNode<S>>,
+}
+
+/// Deserializer of the arguments put under the `$args` key
+struct ArgumentsDeserializer<'de, S> {
+    node: &'de SpannedNode<S>,
+    args: &'de [Value<S>],
+}
+
+/// Deserializer of a single argument or property value
+struct ValueDeserializer<'de, S> {
+    value: &'de Value<S>,
+}
+
+struct Arguments<'de, S> {
+    iter: std::slice::Iter<'de, Value<S>>,
+}
+
+struct Nodes<I> {
+    iter: I,
+    tag: bool,
+}
+
+struct Entries<'de, S> {
+    args: Option<(&'de SpannedNode<S>, &'de [Value<S>])>,
+    properties: Option<btree_map::Iter<'de, SpannedName<S>, Value<S>>>,
+    groups: std::vec::IntoIter<Vec<&'de SpannedNode<S>>>,
+    value: Option<Pending<'de, S>>,
+}
+
+enum Pending<'de, S> {
+    Arguments(&'de SpannedNode<S>, &'de [Value<S>]),
+    Value(&'de Value<S>),
+    Nodes(Vec<&'de SpannedNode<S>>),
+}
+
+struct Variant<'de, S> {
+    name: &'de str,
+    span: &'de S,
+    contents: NodeDeserializer<'de, S>,
+}
+
+impl<S: ErrorSpan> de::Error for DecodeError<S> {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        DecodeError::Custom(msg.to_string().into())
+    }
+    fn missing_field(field: &'static str) -> Self {
+        DecodeError::MissingNode {
+            message: format!("field `{}` is required", field),
+        }
+    }
+}
+
+/// Attaches the span to the errors returned by serde visitors
+fn at<S: ErrorSpan>(err: DecodeError<S>, span: &S) -> DecodeError<S> {
+    match err {
+        DecodeError::Custom(source) => DecodeError::Conversion {
+            span: span.clone(),
+            source,
+        },
+        DecodeError::MissingNode { message } => DecodeError::Missing {
+            span: span.clone(),
+            message,
+        },
+        err => err,
+    }
+}
+
+/// Groups nodes by name keeping the order of the first occurrence
+fn group<S>(nodes: &[SpannedNode<S>]) -> Vec<Vec<&SpannedNode<S>>> {
+    let mut groups: Vec<Vec<&SpannedNode<S>>> = Vec::new();
+    for node in nodes {
+        match groups
+            .iter_mut()
+            .find(|group| *group[0].node_name == *node.node_name)
+        {
+            Some(group) => group.push(node),
+            None => groups.push(vec![node]),
+        }
+    }
+    groups
+}
+
+fn key<'de, K, S>(seed: K, name: &'de str, span: &S) -> Result<K::Value, DecodeError<S>>
+where
+    K: DeserializeSeed<'de>,
+    S: ErrorSpan,
+{
+    seed.deserialize(BorrowedStrDeserializer::new(name))
+        .map_err(|e| at(e, span))
+}
+
+/// Deserialize an instance of type `T` from the document
+pub fn from_document<'de, T, S>(document: &'de Document<S>) -> Result<T, DecodeError<S>>
+where
+    T: de::Deserialize<'de>,
+    S: ErrorSpan,
+{
+    T::deserialize(DocumentDeserializer::new(document))
+}
+
+/// Deserialize an instance of type `T` from the single node
+pub fn from_node<'de, T, S>(node: &'de SpannedNode<S>) -> Result<T, DecodeError<S>>
+where
+    T: de::Deserialize<'de>,
+    S: ErrorSpan,
+{
+    T::deserialize(NodeDeserializer::new(node))
+}
+
+/// Parse KDL text and deserialize an instance of type `T`
+///
+/// Both parsing and deserialization errors are returned as [`Error`], so
+/// they are rendered with the source code snippets.
+pub fn from_str<T>(file_name: impl AsRef<str>, text: &str) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+{
+    let document = crate::parse_ast::<Span>(file_name.as_ref(), text)?;
+    let result = from_document(&document);
+    ast::drop_nodes(document.nodes);
+    result.map_err(|e| Error {
+        source_code: NamedSource::new(file_name, text.to_string()),
+        errors: vec![e.into()],
+    })
+}
+
+impl<'de, S: ErrorSpan> DocumentDeserializer<'de, S> {
+    /// Create a deserializer of the document
+    pub fn new(document: &'de Document<S>) -> Self {
+        DocumentDeserializer::from_nodes(&document.nodes)
+    }
+    /// Create a deserializer of the list of nodes (i.e. children of a node)
+    pub fn from_nodes(nodes: &'de [SpannedNode<S>]) -> Self {
+        DocumentDeserializer { nodes }
+    }
+    /// Attaches the span of the first node to the errors of serde visitors
+    ///
+    /// Missing fields are kept as [`DecodeError::MissingNode`] as they are
+    /// not related to any existing node.
+    fn at(&self, err: DecodeError<S>) -> DecodeError<S> {
+        match (self.nodes.first(), err) {
+            (Some(node), DecodeError::Custom(source)) => DecodeError::Conversion {
+                span: node.node_name.span().clone(),
+                source,
+            },
+            (None, DecodeError::Custom(source)) => DecodeError::MissingNode {
+                message: source.to_string(),
+            },
+            (_, err) => err,
+        }
+    }
+}
+
+impl<'de, S: ErrorSpan> Deserializer<'de> for DocumentDeserializer<'de, S> {
+    type Error = DecodeError<S>;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor
+            .visit_map(Entries {
+                args: None,
+                properties: None,
+                groups: group(self.nodes).into_iter(),
+                value: None,
+            })
+            .map_err(|e| self.at(e))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor
+            .visit_seq(Nodes {
+                iter: self.nodes.iter(),
+                tag: true,
+            })
+            .map_err(|e| self.at(e))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.nodes {
+            [node] => NodeDeserializer::new(node).deserialize_enum(name, variants, visitor),
+            [] => Err(DecodeError::MissingNode {
+                message: "single node expected".into(),
+            }),
+            [_, node, ..] => Err(DecodeError::unexpected(
+                &node.node_name,
+                "node",
+                "single node expected",
+            )),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if self.nodes.is_empty() {
+            visitor.visit_none().map_err(|e| self.at(e))
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.nodes.first() {
+            Some(node) => Err(DecodeError::unexpected(
+                &node.node_name,
+                "node",
+                format!("unexpected node `{}`", node.node_name.escape_default()),
+            )),
+            None => visitor.visit_unit().map_err(|e| self.at(e)),
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf identifier ignored_any
+    }
+}
+
+macro_rules! forward_to_value {
+    ($($method: ident)*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+            self.value()?.$method(visitor)
+        }
+    )*};
+}
+
+impl<'de, S: ErrorSpan> NodeDeserializer<'de, S> {
+    /// Create a deserializer of the node
+    ///
+    /// The node name is used as the name of the variant when deserializing
+    /// enums.
+    pub fn new(node: &'de SpannedNode<S>) -> Self {
+        NodeDeserializer {
+            node,
+            args: &node.arguments,
+            tag: true,
+        }
+    }
+    fn contents(node: &'de SpannedNode<S>) -> Self {
+        NodeDeserializer {
+            node,
+            args: &node.arguments,
+            tag: false,
+        }
+    }
+    fn at(&self, err: DecodeError<S>) -> DecodeError<S> {
+        at(err, self.node.node_name.span())
+    }
+    fn children(&self) -> &'de [SpannedNode<S>] {
+        self.node.children.as_ref().map_or(&[], |c| &c[..])
+    }
+    fn no_arguments(&self) -> Result<(), DecodeError<S>> {
+        match self.args.first() {
+            Some(arg) => Err(DecodeError::unexpected(
+                &arg.literal,
+                "argument",
+                "unexpected argument",
+            )),
+            None => Ok(()),
+        }
+    }
+    fn no_properties(&self) -> Result<(), DecodeError<S>> {
+        match self.node.properties.keys().next() {
+            Some(name) => Err(DecodeError::unexpected(
+                name,
+                "property",
+                format!("unexpected property `{}`", name.escape_default()),
+            )),
+            None => Ok(()),
+        }
+    }
+    fn no_children(&self) -> Result<(), DecodeError<S>> {
+        match self.children().first() {
+            Some(child) => Err(DecodeError::unexpected(
+                &child.node_name,
+                "node",
+                format!("unexpected node `{}`", child.node_name.escape_default()),
+            )),
+            None => Ok(()),
+        }
+    }
+    fn value(&self) -> Result<ValueDeserializer<'de, S>, DecodeError<S>> {
+        self.no_properties()?;
+        self.no_children()?;
+        match self.args {
+            [value] => Ok(ValueDeserializer { value }),
+            [] => Err(DecodeError::missing(self.node, "argument is required")),
+            [_, arg, ..] => Err(DecodeError::unexpected(
+                &arg.literal,
+                "argument",
+                "unexpected argument",
+            )),
+        }
+    }
+    fn entries(&self, args: bool) -> Entries<'de, S> {
+        Entries {
+            args: if args {
+                Some((self.node, self.args))
+            } else {
+                None
+            },
+            properties: Some(self.node.properties.iter()),
+            groups: group(self.children()).into_iter(),
+            value: None,
+        }
+    }
+}
+
+impl<'de, S: ErrorSpan> Deserializer<'de> for NodeDeserializer<'de, S> {
+    type Error = DecodeError<S>;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if !self.node.properties.is_empty() || !self.children().is_empty() {
+            return self.deserialize_map(visitor);
+        }
+        match self.args {
+            [] => visitor.visit_unit().map_err(|e| self.at(e)),
+            [value] => ValueDeserializer { value }.deserialize_any(visitor),
+            _ => self.deserialize_seq(visitor),
+        }
+    }
+
+    forward_to_value! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32
+        deserialize_i64 deserialize_i128 deserialize_u8 deserialize_u16
+        deserialize_u32 deserialize_u64 deserialize_u128 deserialize_f32
+        deserialize_f64 deserialize_char
+        deserialize_str deserialize_string deserialize_bytes
+        deserialize_byte_buf deserialize_identifier
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.args {
+            [value]
+                if *value.literal == Literal::Null
+                    && self.node.properties.is_empty()
+                    && self.children().is_empty() =>
+            {
+                visitor.visit_none().map_err(|e| self.at(e))
+            }
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.no_arguments()?;
+        self.no_properties()?;
+        self.no_children()?;
+        visitor.visit_unit().map_err(|e| self.at(e))
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.no_properties()?;
+        if let [value] = self.args {
+            let value = ValueDeserializer { value };
+            if value.is_bytes() {
+                self.no_children()?;
+                return value.deserialize_seq(visitor);
+            }
+        }
+        if self.args.is_empty() {
+            visitor.visit_seq(Nodes {
+                iter: self.children().iter(),
+                tag: true,
+            })
+        } else {
+            self.no_children()?;
+            visitor.visit_seq(Arguments {
+                iter: self.args.iter(),
+            })
+        }
+        .map_err(|e| self.at(e))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor
+            .visit_map(self.entries(!self.args.is_empty()))
+            .map_err(|e| self.at(e))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let args = fields.contains(&ARGUMENTS);
+        if !args {
+            self.no_arguments()?;
+        }
+        visitor
+            .visit_map(self.entries(args))
+            .map_err(|e| self.at(e))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let variant = if self.tag {
+            Variant {
+                name: &self.node.node_name,
+                span: self.node.node_name.span(),
+                contents: NodeDeserializer::contents(self.node),
+            }
+        } else {
+            match self.args.split_first() {
+                Some((value, args)) => match &*value.literal {
+                    Literal::String(name) => Variant {
+                        name,
+                        span: value.literal.span(),
+                        contents: NodeDeserializer {
+                            node: self.node,
+                            args,
+                            tag: false,
+                        },
+                    },
+                    _ => return Err(DecodeError::scalar_kind(Kind::String, &value.literal)),
+                },
+                None => {
+                    return Err(DecodeError::missing(
+                        self.node,
+                        "argument with the variant name is required",
+                    ))
+                }
+            }
+        };
+        visitor.visit_enum(variant).map_err(|e| self.at(e))
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+}
+
+impl<'de, S: ErrorSpan> de::EnumAccess<'de> for Variant<'de, S> {
+    type Error = DecodeError<S>;
+    type Variant = NodeDeserializer<'de, S>;
+
+    fn variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<(T::Value, Self::Variant), Self::Error> {
+        let value = key(seed, self.name, self.span)?;
+        Ok((value, self.contents))
+    }
+}
+
+impl<'de, S: ErrorSpan> de::VariantAccess<'de> for NodeDeserializer<'de, S> {
+    type Error = DecodeError<S>;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        self.no_arguments()?;
+        self.no_properties()?;
+        self.no_children()
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, Self::Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_struct("", fields, visitor)
+    }
+}
+
+macro_rules! forward_to_single {
+    ($($method: ident)*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+            self.single()?.$method(visitor)
+        }
+    )*};
+}
+
+impl<'de, S: ErrorSpan> NodesDeserializer<'de, S> {
+    fn single(&self) -> Result<NodeDeserializer<'de, S>, DecodeError<S>> {
+        match self.nodes[..] {
+            [node] => Ok(NodeDeserializer::contents(node)),
+            _ => {
+                let name = &self.nodes[1].node_name;
+                Err(DecodeError::unexpected(
+                    name,
+                    "node",
+                    format!(
+                        "duplicate node `{}`, single node expected",
+                        name.escape_default()
+                    ),
+                ))
+            }
+        }
+    }
+}
+
+impl<'de, S: ErrorSpan> Deserializer<'de> for NodesDeserializer<'de, S> {
+    type Error = DecodeError<S>;
+
+    forward_to_single! {
+        deserialize_any deserialize_bool deserialize_i8 deserialize_i16
+        deserialize_i32 deserialize_i64 deserialize_i128 deserialize_u8
+        deserialize_u16 deserialize_u32 deserialize_u64 deserialize_u128
+        deserialize_f32 deserialize_f64 deserialize_char deserialize_str
+        deserialize_string deserialize_bytes deserialize_byte_buf
+        deserialize_option deserialize_unit deserialize_map
+        deserialize_identifier deserialize_ignored_any
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.single()?.deserialize_unit_struct(name, visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.nodes[..] {
+            [node] if node.properties.is_empty() => {
+                NodeDeserializer::contents(node).deserialize_seq(visitor)
+            }
+            _ => {
+                let span = self.nodes[0].node_name.span();
+                visitor
+                    .visit_seq(Nodes {
+                        iter: self.nodes.iter().copied(),
+                        tag: false,
+                    })
+                    .map_err(|e| at(e, span))
+            }
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.single()?.deserialize_struct(name, fields, visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.single()?.deserialize_enum(name, variants, visitor)
+    }
+}
+
+impl<'de, S: ErrorSpan> Deserializer<'de> for ArgumentsDeserializer<'de, S> {
+    type Error = DecodeError<S>;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor
+            .visit_seq(Arguments {
+                iter: self.args.iter(),
+            })
+            .map_err(|e| at(e, self.node.node_name.span()))
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct enum
+        identifier ignored_any
+    }
+}
+
+macro_rules! deserialize_integer {
+    ($($method: ident => $visit: ident($typ: ident))*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+            match &*self.value.literal {
+                Literal::Int(value) => match $typ::try_from(value) {
+                    Ok(value) => visitor.$visit(value).map_err(|e| self.at(e)),
+                    Err(e) => Err(DecodeError::conversion(&self.value.literal, e)),
+                },
+                _ => Err(DecodeError::scalar_kind(Kind::Int, &self.value.literal)),
+            }
+        }
+    )*};
+}
+
+macro_rules! deserialize_float {
+    ($($method: ident => $visit: ident($typ: ident))*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+            let value = match &*self.value.literal {
+                Literal::Decimal(value) => $typ::try_from(value)
+                    .map_err(|e| DecodeError::conversion(&self.value.literal, e))?,
+                Literal::Int(value) => i64::try_from(value)
+                    .map_err(|e| DecodeError::conversion(&self.value.literal, e))?
+                    as $typ,
+                _ => return Err(DecodeError::scalar_kind(Kind::Decimal, &self.value.literal)),
+            };
+            visitor.$visit(value).map_err(|e| self.at(e))
+        }
+    )*};
+}
+
+impl<'de, S: ErrorSpan> ValueDeserializer<'de, S> {
+    fn at(&self, err: DecodeError<S>) -> DecodeError<S> {
+        at(err, self.value.literal.span())
+    }
+    fn is_bytes(&self) -> bool {
+        self.value
+            .type_name
+            .as_ref()
+            .is_some_and(|typ| typ.as_builtin() == Some(&BuiltinType::Base64))
+    }
+    fn bytes(&self) -> Result<Vec<u8>, DecodeError<S>> {
+        let mut ctx = Context::new();
+        let bytes = decode::bytes(self.value, &mut ctx);
+        match ctx.into_errors().into_iter().next() {
+            Some(err) => Err(err),
+            None => Ok(bytes),
+        }
+    }
+}
+
+impl<'de, S: ErrorSpan> Deserializer<'de> for ValueDeserializer<'de, S> {
+    type Error = DecodeError<S>;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if self.is_bytes() {
+            return self.deserialize_byte_buf(visitor);
+        }
+        match &*self.value.literal {
+            Literal::Null => visitor.visit_unit(),
+            Literal::Bool(value) => visitor.visit_bool(*value),
+            Literal::String(value) => visitor.visit_borrowed_str(value),
+            Literal::Int(value) => match i64::try_from(value) {
+                Ok(value) => visitor.visit_i64(value),
+                Err(_) => match u64::try_from(value) {
+                    Ok(value) => visitor.visit_u64(value),
+                    Err(_) => match i128::try_from(value) {
+                        Ok(value) => visitor.visit_i128(value),
+                        Err(_) => match u128::try_from(value) {
+                            Ok(value) => visitor.visit_u128(value),
+                            Err(e) => return Err(DecodeError::conversion(&self.value.literal, e)),
+                        },
+                    },
+                },
+            },
+            Literal::Decimal(value) => match f64::try_from(value) {
+                Ok(value) => visitor.visit_f64(value),
+                Err(e) => return Err(DecodeError::conversion(&self.value.literal, e)),
+            },
+        }
+        .map_err(|e| self.at(e))
+    }
+
+    deserialize_integer! {
+        deserialize_i8 => visit_i8(i8)
+        deserialize_i16 => visit_i16(i16)
+        deserialize_i32 => visit_i32(i32)
+        deserialize_i64 => visit_i64(i64)
+        deserialize_u8 => visit_u8(u8)
+        deserialize_u16 => visit_u16(u16)
+        deserialize_u32 => visit_u32(u32)
+        deserialize_u64 => visit_u64(u64)
+        deserialize_i128 => visit_i128(i128)
+        deserialize_u128 => visit_u128(u128)
+    }
+
+    deserialize_float! {
+        deserialize_f32 => visit_f32(f32)
+        deserialize_f64 => visit_f64(f64)
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match &*self.value.literal {
+            Literal::Bool(value) => visitor.visit_bool(*value).map_err(|e| self.at(e)),
+            _ => Err(DecodeError::scalar_kind(Kind::Bool, &self.value.literal)),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match &*self.value.literal {
+            Literal::String(value) => visitor.visit_borrowed_str(value).map_err(|e| self.at(e)),
+            _ => Err(DecodeError::scalar_kind(Kind::String, &self.value.literal)),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let bytes = self.bytes()?;
+        visitor.visit_byte_buf(bytes).map_err(|e| self.at(e))
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if self.is_bytes() {
+            // makes `Vec<u8>` work, which is deserialized as a sequence
+            let bytes = self.bytes()?;
+            SeqDeserializer::new(bytes.into_iter())
+                .deserialize_any(visitor)
+                .map_err(|e| self.at(e))
+        } else {
+            self.deserialize_any(visitor)
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match &*self.value.literal {
+            Literal::Null => visitor.visit_none().map_err(|e| self.at(e)),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match &*self.value.literal {
+            Literal::String(_) => {
+                let span = self.value.literal.span();
+                visitor.visit_enum(self).map_err(|e| at(e, span))
+            }
+            _ => Err(DecodeError::scalar_kind(Kind::String, &self.value.literal)),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        unit unit_struct tuple tuple_struct map struct
+    }
+}
+
+impl<'de, S: ErrorSpan> de::EnumAccess<'de> for ValueDeserializer<'de, S> {
+    type Error = DecodeError<S>;
+    type Variant = Self;
+
+    fn variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<(T::Value, Self::Variant), Self::Error> {
+        let name = match &*self.value.literal {
+            Literal::String(name) => name,
+            _ => return Err(DecodeError::scalar_kind(Kind::String, &self.value.literal)),
+        };
+        let value = key(seed, name, self.value.literal.span())?;
+        Ok((value, self))
+    }
+}
+
+impl<'de, S: ErrorSpan> de::VariantAccess<'de> for ValueDeserializer<'de, S> {
+    type Error = DecodeError<S>;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        _seed: T,
+    ) -> Result<T::Value, Self::Error> {
+        Err(self.unit_only())
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        Err(self.unit_only())
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        Err(self.unit_only())
+    }
+}
+
+impl<'de, S: ErrorSpan> ValueDeserializer<'de, S> {
+    fn unit_only(&self) -> DecodeError<S> {
+        DecodeError::unsupported(
+            &self.value.literal,
+            "only unit variants can be used as a value",
+        )
+    }
+}
+
+impl<'de, S: ErrorSpan> de::SeqAccess<'de> for Arguments<'de, S> {
+    type Error = DecodeError<S>;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        self.iter
+            .next()
+            .map(|value| seed.deserialize(ValueDeserializer { value }))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+impl<'de, S, I> de::SeqAccess<'de> for Nodes<I>
+where
+    S: ErrorSpan,
+    I: ExactSizeIterator<Item = &'de SpannedNode<S>>,
+{
+    type Error = DecodeError<S>;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        let tag = self.tag;
+        self.iter
+            .next()
+            .map(|node| {
+                seed.deserialize(NodeDeserializer {
+                    node,
+                    args: &node.arguments,
+                    tag,
+                })
+            })
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+impl<'de, S: ErrorSpan> de::MapAccess<'de> for Entries<'de, S> {
+    type Error = DecodeError<S>;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        if let Some((node, args)) = self.args.take() {
+            self.value = Some(Pending::Arguments(node, args));
+            return key(seed, ARGUMENTS, node.node_name.span()).map(Some);
+        }
+        if let Some((name, value)) = self.properties.as_mut().and_then(|p| p.next()) {
+            self.value = Some(Pending::Value(value));
+            return key(seed, name, name.span()).map(Some);
+        }
+        if let Some(nodes) = self.groups.next() {
+            let name = &nodes[0].node_name;
+            self.value = Some(Pending::Nodes(nodes));
+            return key(seed, name, name.span()).map(Some);
+        }
+        Ok(None)
+    }
+
+    fn next_value_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<T::Value, Self::Error> {
+        match self.value.take() {
+            Some(Pending::Arguments(node, args)) => {
+                seed.deserialize(ArgumentsDeserializer { node, args })
+            }
+            Some(Pending::Value(value)) => seed.deserialize(ValueDeserializer { value }),
+            Some(Pending::Nodes(nodes)) => seed.deserialize(NodesDeserializer { nodes }),
+            None => Err(de::Error::custom("value is missing")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use miette::Diagnostic;
+    use serde::Deserialize;
+
+    use super::{from_document, from_str};
+    use crate::ast::Document;
+    use crate::errors::DecodeError;
+    use crate::span::Span;
+
+    fn parse<'de, T: Deserialize<'de>>(
+        doc: &'de mut Option<Document<Span>>,
+        text: &str,
+    ) -> Result<T, DecodeError<Span>> {
+        let doc = doc.insert(crate::parse_ast("<test>", text).unwrap());
+        from_document(doc)
+    }
+
+    fn error<T: serde::de::DeserializeOwned>(text: &str) -> String {
+        let err = from_str::<T>("<test>", text).map(|_| ()).unwrap_err();
+        err.related()
+            .unwrap()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Config {
+        name: String,
+        #[serde(default)]
+        tags: Vec<String>,
+        port: Option<u16>,
+        server: Option<Server>,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Server {
+        #[serde(rename = "$args")]
+        hosts: Vec<String>,
+        timeout: f64,
+        #[serde(default)]
+        verbose: bool,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "kebab-case")]
+    enum Plugin {
+        Logger,
+        Http(String),
+        Proxy(String, u16),
+        Cache { size: u32 },
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Mode {
+        Fast,
+        Slow,
+    }
+
+    #[test]
+    fn structs() {
+        let config: Config = from_str(
+            "<test>",
+            r#"
+            name "app"
+            tags "a" "b"
+            port null
+            server "a.example" "b.example" timeout=1.5 {
+                verbose true
+            }
+        "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            Config {
+                name: "app".into(),
+                tags: vec!["a".into(), "b".into()],
+                port: None,
+                server: Some(Server {
+                    hosts: vec!["a.example".into(), "b.example".into()],
+                    timeout: 1.5,
+                    verbose: true,
+                }),
+            }
+        );
+        let config: Config = from_str("<test>", "name \"x\"; tags \"a\"; port 80").unwrap();
+        assert_eq!(config.tags, vec!["a"]);
+        assert_eq!(config.port, Some(80));
+        assert_eq!(config.server, None);
+    }
+
+    #[test]
+    fn repeated_nodes() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Item {
+            name: String,
+        }
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Items {
+            item: Vec<Item>,
+            value: Vec<u32>,
+        }
+        let items: Items = from_str(
+            "<test>",
+            r#"
+            item name="a"
+            value 1
+            item name="b"
+            value 2
+        "#,
+        )
+        .unwrap();
+        assert_eq!(
+            items,
+            Items {
+                item: vec![Item { name: "a".into() }, Item { name: "b".into() }],
+                value: vec![1, 2],
+            }
+        );
+        let items: Items = from_str("<test>", "item name=\"a\"; value 1 2 3").unwrap();
+        assert_eq!(items.item, vec![Item { name: "a".into() }]);
+        assert_eq!(items.value, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn enums() {
+        let plugins: Vec<Plugin> = from_str(
+            "<test>",
+            r#"
+            logger
+            http "/api"
+            proxy "localhost" 8080
+            cache size=100
+        "#,
+        )
+        .unwrap();
+        assert_eq!(
+            plugins,
+            vec![
+                Plugin::Logger,
+                Plugin::Http("/api".into()),
+                Plugin::Proxy("localhost".into(), 8080),
+                Plugin::Cache { size: 100 },
+            ]
+        );
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Fields {
+            mode: Mode,
+            plugin: Plugin,
+            modes: Vec<Mode>,
+        }
+        let fields: Fields = from_str(
+            "<test>",
+            r#"mode "slow"; plugin "proxy" "x" 1; modes "fast" "slow""#,
+        )
+        .unwrap();
+        assert_eq!(
+            fields,
+            Fields {
+                mode: Mode::Slow,
+                plugin: Plugin::Proxy("x".into(), 1),
+                modes: vec![Mode::Fast, Mode::Slow],
+            }
+        );
+    }
+
+    #[test]
+    fn children_sequence() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Doc {
+            plugins: Vec<Plugin>,
+        }
+        let doc: Doc = from_str("<test>", "plugins { logger; http \"/\"; }").unwrap();
+        assert_eq!(doc.plugins, vec![Plugin::Logger, Plugin::Http("/".into())]);
+    }
+
+    #[test]
+    fn maps() {
+        let mut doc = None;
+        let map: BTreeMap<&str, BTreeMap<&str, i64>> =
+            parse(&mut doc, "a x=1 y=2 { z 3; }\nb").unwrap_or_else(|_| panic!());
+        assert_eq!(
+            map["a"],
+            [("x", 1), ("y", 2), ("z", 3)].into_iter().collect()
+        );
+        assert!(map["b"].is_empty());
+
+        let value: serde_json::Value =
+            from_str("<test>", "a 1 2 key=\"v\"; b 1.5; c; d true { e null; }").unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "a": {"$args": [1, 2], "key": "v"},
+                "b": 1.5,
+                "c": null,
+                "d": {"$args": [true], "e": null},
+            })
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "base64")]
+    fn bytes() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Data {
+            #[serde(with = "serde_bytes_buf")]
+            data: Vec<u8>,
+            plain: Vec<u8>,
+        }
+        mod serde_bytes_buf {
+            pub fn deserialize<'de, D: serde::Deserializer<'de>>(
+                d: D,
+            ) -> Result<Vec<u8>, D::Error> {
+                struct Bytes;
+                impl<'de> serde::de::Visitor<'de> for Bytes {
+                    type Value = Vec<u8>;
+                    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                        f.write_str("bytes")
+                    }
+                    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Vec<u8>, E> {
+                        Ok(v)
+                    }
+                }
+                d.deserialize_byte_buf(Bytes)
+            }
+        }
+        let data: Data = from_str("<test>", "data (base64)\"aGVsbG8=\"; plain 1 2").unwrap();
+        assert_eq!(
+            data,
+            Data {
+                data: b"hello".to_vec(),
+                plain: vec![1, 2],
+            }
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let mut doc = None;
+        let err = parse::<Config>(&mut doc, "name \"x\"\nport \"80\"").unwrap_err();
+        assert!(matches!(
+            err,
+            DecodeError::ScalarKind {
+                span: Span(14, 18),
+                ..
+            }
+        ));
+        let err = parse::<Config>(&mut doc, "name \"x\"\nport 100000").unwrap_err();
+        assert!(matches!(
+            err,
+            DecodeError::Conversion {
+                span: Span(14, 20),
+                ..
+            }
+        ));
+        let err = parse::<Config>(&mut doc, "port 1").unwrap_err();
+        assert!(matches!(err, DecodeError::MissingNode { .. }));
+        let err = parse::<Config>(&mut doc, "name \"x\"; server \"a\"").unwrap_err();
+        assert!(matches!(
+            err,
+            DecodeError::Missing {
+                span: Span(10, 16),
+                ..
+            }
+        ));
+        let err = parse::<Config>(&mut doc, "name \"x\"; name \"y\"").unwrap_err();
+        assert!(matches!(
+            err,
+            DecodeError::Unexpected {
+                span: Span(10, 14),
+                ..
+            }
+        ));
+        let err = parse::<Vec<Plugin>>(&mut doc, "http \"x\"; other").unwrap_err();
+        assert!(matches!(
+            err,
+            DecodeError::Conversion {
+                span: Span(10, 15),
+                ..
+            }
+        ));
+
+        assert_eq!(error::<Config>("port 1"), "field `name` is required");
+        assert_eq!(error::<Config>("name \"x\" y=1"), "unexpected property `y`");
+        assert_eq!(
+            error::<Vec<Plugin>>("unknown"),
+            "unknown variant `unknown`, expected one of \
+             `logger`, `http`, `proxy`, `cache`"
+        );
+        assert_eq!(
+            error::<Vec<Mode>>("mode"),
+            "unknown variant `mode`, expected `fast` or `slow`"
+        );
+    }
+
+    #[test]
+    fn error_spans() {
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        enum Action {
+            Run { mode: Mode },
+        }
+
+        let mut doc = None;
+        let err = parse::<Vec<Action>>(&mut doc, "Run").unwrap_err();
+        assert!(matches!(
+            err,
+            DecodeError::Missing {
+                span: Span(0, 3),
+                ..
+            }
+        ));
+        assert_eq!(err.to_string(), "field `mode` is required");
+        let err = parse::<(u8, u8)>(&mut doc, "a 1").unwrap_err();
+        assert!(matches!(
+            err,
+            DecodeError::Conversion {
+                span: Span(0, 1),
+                ..
+            }
+        ));
+        let err = parse::<(u8, u8)>(&mut doc, "").unwrap_err();
+        assert!(matches!(err, DecodeError::MissingNode { .. }));
+        assert_eq!(
+            err.to_string(),
+            "invalid length 0, expected a tuple of size 2"
+        );
+    }
+
+    #[test]
+    fn big_integers() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Big {
+            signed: i128,
+            unsigned: u128,
+        }
+
+        let big: Big = from_str(
+            "<test>",
+            "signed -170141183460469231731687303715884105728
+             unsigned 0xffffffffffffffffffffffffffffffff",
+        )
+        .unwrap();
+        assert_eq!(big.signed, i128::MIN);
+        assert_eq!(big.unsigned, u128::MAX);
+        assert_eq!(
+            error::<Big>("signed 170141183460469231731687303715884105728"),
+            "number too large to fit in target type"
+        );
+    }
+}
@@ -6,17 +6,26 @@ use std::collections::HashMap;
 use std::default::Default;
 use std::fmt;
 
-use crate::ast::{BuiltinType, Literal, SpannedNode, Value};
-use crate::errors::{DecodeError, ExpectedType};
+use miette::Severity;
+
+use crate::ast::{BuiltinType, Literal, SpannedNode, TypeName, Value};
+use crate::errors::{DecodeError, ExpectedType, Warning};
+use crate::span::Spanned;
 use crate::traits::{Decode, ErrorSpan};
 
 /// Context is passed through all the decode operations and can be used for:
 ///
 /// 1. To emit error and proceed (so multiple errors presented to user)
 /// 2. To store and retrieve data in decoders of nodes, scalars and spans
+/// 3. To allow unknown arguments, properties and nodes and to list the ones
+///    that were skipped
+/// 4. To emit warnings that don't fail decoding
 #[derive(Debug, Default)]
 pub struct Context<S: ErrorSpan> {
     errors: Vec<DecodeError<S>>,
+    warnings: Vec<Warning<S>>,
+    ignored: Vec<DecodeError<S>>,
+    allow_unknown: bool,
     extensions: HashMap<TypeId, Box<dyn Any>>,
 }
 
@@ -47,30 +56,50 @@ pub enum Kind {
 /// Used internally by `#[knus(..., bytes)]` attribute. But can be used
 /// manually for implementing [`DecodeScalar`](crate::traits::DecodeScalar).
 pub fn bytes<S: ErrorSpan>(value: &Value<S>, ctx: &mut Context<S>) -> Vec<u8> {
-    if let Some(typ) = &value.type_name {
+    let string = match &*value.literal {
+        Literal::String(s) => Some(&s[..]),
+        _ => None,
+    };
+    decode_bytes(&value.type_name, &value.literal, string, ctx)
+}
+
+/// Decodes bytes from the value of either owned or borrowed tree
+///
+/// The `string` is the value of the `literal` if it's a string.
+pub(crate) fn decode_bytes<S: ErrorSpan, L>(
+    type_name: &Option<Spanned<TypeName, S>>,
+    literal: &Spanned<L, S>,
+    string: Option<&str>,
+    ctx: &mut Context<S>,
+) -> Vec<u8>
+where
+    for<'x> &'x L: Into<Kind>,
+{
+    if let Some(typ) = type_name {
         match typ.as_builtin() {
             Some(&BuiltinType::Base64) => {
                 #[cfg(feature = "base64")]
                 {
                     use base64::{engine::general_purpose::STANDARD, Engine};
-                    match &*value.literal {
-                        Literal::String(s) => match STANDARD.decode(s.as_bytes()) {
+                    match string {
+                        Some(s) => match STANDARD.decode(s.as_bytes()) {
                             Ok(vec) => vec,
                             Err(e) => {
-                                ctx.emit_error(DecodeError::conversion(&value.literal, e));
+                                ctx.emit_error(DecodeError::conversion(literal, e));
                                 Default::default()
                             }
                         },
-                        _ => {
-                            ctx.emit_error(DecodeError::scalar_kind(Kind::String, &value.literal));
+                        None => {
+                            ctx.emit_error(DecodeError::scalar_kind(Kind::String, literal));
                             Default::default()
                         }
                     }
                 }
                 #[cfg(not(feature = "base64"))]
                 {
+                    let _ = string;
                     ctx.emit_error(DecodeError::unsupported(
-                        &value.literal,
+                        literal,
                         "base64 support is not compiled in",
                     ));
                     Default::default()
@@ -87,10 +116,10 @@ pub fn bytes<S: ErrorSpan>(value: &Value<S>, ctx: &mut Context<S>) -> Vec<u8> {
             }
         }
     } else {
-        match &*value.literal {
-            Literal::String(s) => s.as_bytes().to_vec(),
-            _ => {
-                ctx.emit_error(DecodeError::scalar_kind(Kind::String, &value.literal));
+        match string {
+            Some(s) => s.as_bytes().to_vec(),
+            None => {
+                ctx.emit_error(DecodeError::scalar_kind(Kind::String, literal));
                 Default::default()
             }
         }
@@ -129,6 +158,79 @@ pub fn check_flag_node<S: ErrorSpan>(node: &SpannedNode<S>, ctx: &mut Context<S>
     }
 }
 
+/// Builds a help message for a name that matched none of `expected`
+///
+/// Lists all the valid names and, if one of them is close enough by edit
+/// distance, suggests it first:
+/// ``"did you mean `port`? expected one of: `host`, `port`"``.
+/// Returns `None` if there are no valid names at all.
+///
+/// Used by the derive for unknown nodes, properties and enum variants. Use
+/// with [`DecodeError::with_help`] in manual implementations.
+pub fn names_help(name: &str, expected: &[&str]) -> Option<String> {
+    if expected.is_empty() {
+        return None;
+    }
+    let list = expected
+        .iter()
+        .map(|n| format!("`{}`", n.escape_default()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    Some(match similar_name(name, expected) {
+        Some(similar) => format!(
+            "did you mean `{}`? expected one of: {}",
+            similar.escape_default(),
+            list
+        ),
+        None => format!("expected one of: {}", list),
+    })
+}
+
+/// Returns the name from `expected` that is close enough to `name` by edit
+/// distance to be suggested instead of it
+///
+/// Used by the derive instead of [`names_help`] when the error message
+/// already lists all the valid names.
+pub fn similar_name<'a>(name: &str, expected: &[&'a str]) -> Option<&'a str> {
+    let mut best = None;
+    for candidate in expected {
+        let distance = edit_distance(name, candidate);
+        let limit = std::cmp::max(1, candidate.chars().count() / 3);
+        if distance <= limit && best.map(|(_, d)| distance < d).unwrap_or(true) {
+            best = Some((*candidate, distance));
+        }
+    }
+    best.map(|(similar, _)| similar)
+}
+
+/// Optimal string alignment distance in characters
+///
+/// Same as Levenshtein distance, but swapping two adjacent characters is
+/// a single edit, as it's a common typo.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    let mut before = vec![0; b.len() + 1];
+    let mut prev = vec![0; b.len() + 1];
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for i in 0..a.len() {
+        std::mem::swap(&mut before, &mut prev);
+        std::mem::swap(&mut prev, &mut row);
+        row[0] = i + 1;
+        for j in 0..b.len() {
+            row[j + 1] = if a[i] == b[j] {
+                prev[j]
+            } else {
+                1 + prev[j].min(prev[j + 1]).min(row[j])
+            };
+            if i > 0 && j > 0 && a[i] == b[j - 1] && a[i - 1] == b[j] {
+                row[j + 1] = row[j + 1].min(before[j - 1] + 1);
+            }
+        }
+    }
+    row[b.len()]
+}
+
 /// Parse single KDL node from AST
 pub fn node<T, S>(ast: &SpannedNode<S>) -> Result<T, Vec<DecodeError<S>>>
 where
@@ -150,6 +252,9 @@ impl<S: ErrorSpan> Context<S> {
     pub(crate) fn new() -> Context<S> {
         Context {
             errors: Vec::new(),
+            warnings: Vec::new(),
+            ignored: Vec::new(),
+            allow_unknown: false,
             extensions: HashMap::new(),
         }
     }
@@ -168,6 +273,63 @@ impl<S: ErrorSpan> Context<S> {
     pub(crate) fn into_errors(self) -> Vec<DecodeError<S>> {
         self.errors
     }
+    /// Add warning
+    ///
+    /// Unlike [`emit_error`](Self::emit_error) this doesn't fail decoding.
+    /// Use it for deprecated names, suspicious values and similar problems.
+    /// Warnings are returned by
+    /// [`parse_with_context`](crate::parse_with_context) along with the
+    /// value and are rendered with [`Severity::Warning`].
+    pub fn emit_warning(&mut self, err: impl Into<DecodeError<S>>) {
+        self.warnings.push(Warning::new(Severity::Warning, err));
+    }
+    /// Add advice
+    ///
+    /// Same as [`emit_warning`](Self::emit_warning) but rendered with
+    /// [`Severity::Advice`].
+    pub fn emit_advice(&mut self, err: impl Into<DecodeError<S>>) {
+        self.warnings.push(Warning::new(Severity::Advice, err));
+    }
+    /// Returns the warnings and advices emitted so far
+    pub fn warnings(&self) -> &[Warning<S>] {
+        &self.warnings
+    }
+    /// Returns errors and warnings, including the ignored entities as
+    /// warnings
+    pub(crate) fn into_diagnostics(self) -> (Vec<DecodeError<S>>, Vec<Warning<S>>) {
+        let mut warnings = self.warnings;
+        warnings.extend(
+            self.ignored
+                .into_iter()
+                .map(|e| Warning::new(Severity::Warning, e)),
+        );
+        (self.errors, warnings)
+    }
+    /// Allow unknown arguments, properties and child nodes everywhere
+    ///
+    /// Derived decoders skip them instead of failing, as if all the types
+    /// had the `#[knus(allow_unknown)]` attribute. Skipped entities are
+    /// listed by [`ignored`](Self::ignored).
+    pub fn set_allow_unknown(&mut self, value: bool) {
+        self.allow_unknown = value;
+    }
+    /// Returns `true` if unknown entities are allowed everywhere
+    pub fn allow_unknown(&self) -> bool {
+        self.allow_unknown
+    }
+    /// Record the entity that was skipped by the decoder
+    ///
+    /// Unlike [`emit_error`](Self::emit_error) this doesn't fail decoding.
+    /// The error describes the entity, usually it's
+    /// [`DecodeError::Unexpected`]. The entities are returned as warnings by
+    /// [`parse_with_context`](crate::parse_with_context).
+    pub fn emit_ignored(&mut self, err: impl Into<DecodeError<S>>) {
+        self.ignored.push(err.into());
+    }
+    /// Returns the entities skipped by the decoders so far
+    pub fn ignored(&self) -> &[DecodeError<S>] {
+        &self.ignored
+    }
     /// Set context value
     ///
     /// These values aren't used by the knus itself. But can be used by
@@ -227,3 +389,43 @@ impl Kind {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{edit_distance, names_help, similar_name};
+
+    #[test]
+    fn distance() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("port", "port"), 0);
+        assert_eq!(edit_distance("prot", "port"), 1);
+        assert_eq!(edit_distance("hots", "host"), 1);
+        assert_eq!(edit_distance("ab", "ba"), 1);
+        assert_eq!(edit_distance("ca", "abc"), 3);
+        assert_eq!(edit_distance("lisen", "listen"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("ä", "a"), 1);
+    }
+
+    #[test]
+    fn help() {
+        assert_eq!(names_help("x", &[]), None);
+        assert_eq!(
+            names_help("lisen", &["host", "listen"]).unwrap(),
+            "did you mean `listen`? expected one of: `host`, `listen`"
+        );
+        assert_eq!(
+            names_help("hots", &["hot", "host"]).unwrap(),
+            "did you mean `hot`? expected one of: `hot`, `host`"
+        );
+        assert_eq!(
+            names_help("something", &["host", "port"]).unwrap(),
+            "expected one of: `host`, `port`"
+        );
+        assert_eq!(similar_name("lisen", &["host", "listen"]), Some("listen"));
+        assert_eq!(similar_name("hots", &["port", "host"]), Some("host"));
+        assert_eq!(similar_name("lsiten", &["host", "listen"]), Some("listen"));
+        assert_eq!(similar_name("something", &["host", "port"]), None);
+    }
+}
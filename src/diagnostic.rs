@@ -0,0 +1,490 @@
+//! Machine-readable diagnostics
+//!
+//! [`Error`] is normally rendered by one of the [miette] report handlers.
+//! For CI and editor integrations it can be turned into a list of
+//! [`Diagnostic`] records instead, each having a file name, byte range,
+//! line and column, severity, code, message, labels and help:
+//!
+//! ```rust
+//! let err = knus::parse_ast::<knus::span::Span>("config.kdl", "node {").unwrap_err();
+//! let diagnostics = err.diagnostics();
+//! assert_eq!(diagnostics[0].file.as_deref(), Some("config.kdl"));
+//! assert_eq!(diagnostics[0].start.line, 0);
+//! ```
+//!
+//! Positions are zero-based and follow the Language Server Protocol: only
+//! `\n`, `\r\n` and `\r` start a new line, and [`Position::character`] is
+//! counted in UTF-16 code units. [`Position::column`] is the width of the
+//! line up to the position, counted the same way as in
+//! [`LineSpan`](crate::span::LineSpan). Use [`LineIndex`] to compute
+//! positions for other offsets of the same text.
+//!
+//! With the `serde` feature records are serializable, and the output is a
+//! valid LSP [`Diagnostic`][lsp] object with a few extra fields: `file`,
+//! `byteRange`, `labels` and `help`.
+//!
+//! [lsp]: https://microsoft.github.io/language-server-protocol/specifications/lsp/3.17/specification/#diagnostic
+use std::borrow::Cow;
+use std::ops::Range;
+
+use miette::{NamedSource, Severity, SourceCode};
+
+use crate::errors::{Error, Warnings};
+use crate::span::char_width;
+
+/// Diagnostic record that can be serialized or sent to an editor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Name of the source file, if source code is known
+    pub file: Option<String>,
+    /// Byte range of the primary label
+    ///
+    /// Empty range at the start of the file if there are no labels.
+    pub range: Range<usize>,
+    /// Start of the primary label
+    pub start: Position,
+    /// End of the primary label
+    pub end: Position,
+    /// Severity, [`Severity::Error`] unless diagnostic specifies otherwise
+    pub severity: Severity,
+    /// Diagnostic code
+    pub code: Option<String>,
+    /// Error message
+    pub message: String,
+    /// All labels including the primary one
+    pub labels: Vec<Label>,
+    /// Help text
+    pub help: Option<String>,
+}
+
+/// Labeled source code span of the [`Diagnostic`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    /// Byte range
+    pub range: Range<usize>,
+    /// Start of the label
+    pub start: Position,
+    /// End of the label
+    pub end: Position,
+    /// Text of the label
+    pub message: Option<String>,
+    /// Whether it's the primary label of the diagnostic
+    pub primary: bool,
+}
+
+/// Zero-based position in the source code
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    /// Line number, only `\n`, `\r\n` and `\r` are line breaks
+    pub line: usize,
+    /// Width of the line up to the position
+    ///
+    /// Tabs are 8 columns, otherwise it's counted the same way as in
+    /// [`LineSpan`](crate::span::LineSpan).
+    pub column: usize,
+    /// Column in UTF-16 code units, as LSP `Position::character`
+    pub character: usize,
+}
+
+/// Offsets of the line starts for converting byte offsets into [`Position`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineIndex {
+    starts: Vec<usize>,
+}
+
+/// Source file read once and shared by all the diagnostics pointing into it
+struct Source<'a> {
+    file: Option<String>,
+    text: Cow<'a, str>,
+    lines: LineIndex,
+}
+
+impl Error {
+    /// Returns machine-readable records for all the errors
+    ///
+    /// See [`diagnostic`](crate::diagnostic) module for details.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        diagnostics(&self.errors, &self.source_code)
+    }
+}
+
+impl Warnings {
+    /// Returns machine-readable records for all the warnings
+    ///
+    /// See [`diagnostic`](crate::diagnostic) module for details.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        diagnostics(&self.warnings, &self.source_code)
+    }
+}
+
+fn diagnostics(reports: &[miette::Report], source_code: &NamedSource<String>) -> Vec<Diagnostic> {
+    let text = source_code.inner();
+    let mut sources = vec![Source {
+        file: Some(source_code.name().to_string()),
+        text: Cow::Borrowed(text),
+        lines: LineIndex::new(text),
+    }];
+    reports
+        .iter()
+        .map(|report| {
+            // errors in included files carry their own source code
+            let idx = match report.source_code() {
+                Some(code) => {
+                    let file = source_name(code);
+                    match sources.iter().position(|s| s.file == file) {
+                        Some(idx) => idx,
+                        None => {
+                            sources.push(Source::read(code));
+                            sources.len() - 1
+                        }
+                    }
+                }
+                None => 0,
+            };
+            let source = &sources[idx];
+            Diagnostic::with_line_index(&**report, source.file.clone(), &source.text, &source.lines)
+        })
+        .collect()
+}
+
+fn source_name(code: &dyn SourceCode) -> Option<String> {
+    code.read_span(&(0, 0).into(), 0, 0)
+        .ok()
+        .and_then(|c| c.name().map(|name| name.to_string()))
+}
+
+impl<'a> Source<'a> {
+    fn read(code: &'a dyn SourceCode) -> Source<'a> {
+        let contents = code.read_span(&(0, 0).into(), 0, usize::MAX).ok();
+        let file = contents
+            .as_ref()
+            .and_then(|c| c.name())
+            .map(|name| name.to_string());
+        let text = match contents {
+            Some(contents) => String::from_utf8_lossy(contents.data()),
+            None => Cow::Borrowed(""),
+        };
+        let lines = LineIndex::new(&text);
+        Source { file, text, lines }
+    }
+}
+
+impl LineIndex {
+    /// Indexes the line starts of the text
+    pub fn new(text: &str) -> LineIndex {
+        let mut starts = vec![0];
+        let bytes = text.as_bytes();
+        for (idx, &b) in bytes.iter().enumerate() {
+            match b {
+                b'\n' => starts.push(idx + 1),
+                b'\r' if bytes.get(idx + 1) != Some(&b'\n') => starts.push(idx + 1),
+                _ => {}
+            }
+        }
+        LineIndex { starts }
+    }
+
+    /// Position of the byte offset in the `text` the index was built for
+    ///
+    /// Offsets past the end of the text point to the end of the text.
+    pub fn position(&self, text: &str, offset: usize) -> Position {
+        // spans can point past the end, e.g. for unexpected end of file
+        let mut offset = offset.min(text.len());
+        while !text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let line = self.starts.partition_point(|&start| start <= offset) - 1;
+        let prefix = text.get(self.starts[line]..offset).unwrap_or("");
+        Position {
+            line,
+            column: prefix.chars().map(char_width).sum(),
+            character: prefix.encode_utf16().count(),
+        }
+    }
+}
+
+impl Diagnostic {
+    /// Convert any miette diagnostic into a record
+    ///
+    /// Source code attached to the diagnostic itself is preferred over the
+    /// `source_code` passed. Without source code line and column numbers are
+    /// zero.
+    ///
+    /// The source code is read and indexed for each call, use
+    /// [`Diagnostic::with_line_index`] to convert many diagnostics pointing
+    /// into the same text.
+    pub fn new(
+        diagnostic: &dyn miette::Diagnostic,
+        source_code: Option<&dyn SourceCode>,
+    ) -> Diagnostic {
+        let source = match diagnostic.source_code().or(source_code) {
+            Some(code) => Source::read(code),
+            None => Source {
+                file: None,
+                text: Cow::Borrowed(""),
+                lines: LineIndex::new(""),
+            },
+        };
+        Diagnostic::with_line_index(diagnostic, source.file, &source.text, &source.lines)
+    }
+    /// Convert a diagnostic pointing into the already indexed `text`
+    ///
+    /// Source code attached to the diagnostic itself is ignored.
+    pub fn with_line_index(
+        diagnostic: &dyn miette::Diagnostic,
+        file: Option<String>,
+        text: &str,
+        lines: &LineIndex,
+    ) -> Diagnostic {
+        let labels = diagnostic
+            .labels()
+            .into_iter()
+            .flatten()
+            .map(|label| {
+                let range = label.offset()..label.offset() + label.len();
+                let start = lines.position(text, range.start);
+                let end = lines.position(text, range.end);
+                Label {
+                    range,
+                    start,
+                    end,
+                    message: label.label().map(|x| x.to_string()),
+                    primary: label.primary(),
+                }
+            })
+            .collect::<Vec<_>>();
+        let primary = labels
+            .iter()
+            .find(|label| label.primary)
+            .or_else(|| labels.first());
+        let (range, start, end) = match primary {
+            Some(label) => (label.range.clone(), label.start, label.end),
+            None => (0..0, Position::default(), Position::default()),
+        };
+        Diagnostic {
+            file,
+            range,
+            start,
+            end,
+            severity: diagnostic.severity().unwrap_or(Severity::Error),
+            code: diagnostic.code().map(|x| x.to_string()),
+            message: diagnostic.to_string(),
+            labels,
+            help: diagnostic.help().map(|x| x.to_string()),
+        }
+    }
+    /// Severity as a number used in LSP `DiagnosticSeverity`
+    pub fn lsp_severity(&self) -> u8 {
+        match self.severity {
+            Severity::Error => 1,
+            Severity::Warning => 2,
+            Severity::Advice => 3,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serialize {
+    use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+    use super::{Diagnostic, Label, Position};
+
+    struct Range<'a>(&'a Position, &'a Position);
+    struct ByteRange<'a>(&'a std::ops::Range<usize>);
+
+    impl Serialize for Diagnostic {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut s = serializer.serialize_struct("Diagnostic", 9)?;
+            s.serialize_field("range", &Range(&self.start, &self.end))?;
+            s.serialize_field("severity", &self.lsp_severity())?;
+            if let Some(code) = &self.code {
+                s.serialize_field("code", code)?;
+            } else {
+                s.skip_field("code")?;
+            }
+            s.serialize_field("source", "knus")?;
+            s.serialize_field("message", &self.message)?;
+            s.serialize_field("file", &self.file)?;
+            s.serialize_field("byteRange", &ByteRange(&self.range))?;
+            s.serialize_field("labels", &self.labels)?;
+            s.serialize_field("help", &self.help)?;
+            s.end()
+        }
+    }
+
+    impl Serialize for Label {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut s = serializer.serialize_struct("Label", 4)?;
+            s.serialize_field("range", &Range(&self.start, &self.end))?;
+            s.serialize_field("byteRange", &ByteRange(&self.range))?;
+            s.serialize_field("message", &self.message)?;
+            s.serialize_field("primary", &self.primary)?;
+            s.end()
+        }
+    }
+
+    impl Serialize for Position {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut s = serializer.serialize_struct("Position", 3)?;
+            s.serialize_field("line", &self.line)?;
+            s.serialize_field("character", &self.character)?;
+            s.serialize_field("column", &self.column)?;
+            s.end()
+        }
+    }
+
+    impl Serialize for Range<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut s = serializer.serialize_struct("Range", 2)?;
+            s.serialize_field("start", self.0)?;
+            s.serialize_field("end", self.1)?;
+            s.end()
+        }
+    }
+
+    impl Serialize for ByteRange<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut s = serializer.serialize_struct("ByteRange", 2)?;
+            s.serialize_field("start", &self.0.start)?;
+            s.serialize_field("end", &self.0.end)?;
+            s.end()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Diagnostic, Label, LineIndex, Position};
+    use crate::span::Span;
+    use miette::Severity;
+
+    fn pos(line: usize, column: usize, character: usize) -> Position {
+        Position {
+            line,
+            column,
+            character,
+        }
+    }
+
+    #[test]
+    fn parse_error() {
+        let err = crate::parse_ast::<Span>("a.kdl", "node 1\r\nnode \"ж\" )").unwrap_err();
+        let diagnostics = err.diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        let diag = &diagnostics[0];
+        assert_eq!(diag.file.as_deref(), Some("a.kdl"));
+        assert_eq!(diag.range, 18..19);
+        assert_eq!(diag.start, pos(1, 9, 9));
+        assert_eq!(diag.end, pos(1, 10, 10));
+        assert_eq!(diag.severity, Severity::Error);
+        assert_eq!(diag.lsp_severity(), 1);
+        assert_eq!(diag.labels.len(), 1);
+    }
+
+    #[test]
+    fn unicode_newlines() {
+        let err = crate::parse_ast::<Span>("a.kdl", "a\u{2028}b =").unwrap_err();
+        let diag = &err.diagnostics()[0];
+        assert_eq!(diag.range, 6..7);
+        assert_eq!((diag.start.line, diag.start.character), (0, 4));
+    }
+
+    #[test]
+    fn line_index() {
+        let text = "a\r\nb\rc\n\t😀x";
+        let lines = LineIndex::new(text);
+        assert_eq!(lines.position(text, 0), pos(0, 0, 0));
+        assert_eq!(lines.position(text, 3), pos(1, 0, 0));
+        assert_eq!(lines.position(text, 5), pos(2, 0, 0));
+        assert_eq!(lines.position(text, 7), pos(3, 0, 0));
+        assert_eq!(lines.position(text, 8).character, 1);
+        assert_eq!(lines.position(text, 12).character, 3);
+        assert_eq!(lines.position(text, 13), lines.position(text, 100));
+    }
+
+    #[test]
+    fn decode_error() {
+        #[derive(Debug)]
+        struct Port;
+        impl crate::Decode<Span> for Port {
+            fn decode_node(
+                node: &crate::ast::SpannedNode<Span>,
+                _: &mut crate::decode::Context<Span>,
+            ) -> Result<Self, crate::errors::DecodeError<Span>> {
+                Err(crate::errors::DecodeError::unexpected(
+                    &node.node_name,
+                    "node",
+                    "unexpected node `x`",
+                )
+                .with_help("expected `port`"))
+            }
+        }
+        let err = crate::parse::<Vec<Port>>("b.kdl", "\n\n  x").unwrap_err();
+        assert_eq!(
+            err.diagnostics(),
+            [Diagnostic {
+                file: Some("b.kdl".into()),
+                range: 4..5,
+                start: pos(2, 2, 2),
+                end: pos(2, 3, 3),
+                severity: Severity::Error,
+                code: None,
+                message: "unexpected node `x`".into(),
+                labels: vec![Label {
+                    range: 4..5,
+                    start: pos(2, 2, 2),
+                    end: pos(2, 3, 3),
+                    message: Some("unexpected node".into()),
+                    primary: false,
+                }],
+                help: Some("expected `port`".into()),
+            }]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "line-numbers")]
+    fn line_span() {
+        use crate::span::LineSpan;
+
+        let text = "a\r\n\tb \"ü😀\" 1";
+        let doc = crate::parse_ast::<LineSpan>("c.kdl", text).unwrap();
+        let value = &doc.nodes[1].arguments[1].literal;
+        let err = crate::errors::DecodeError::conversion(value, "invalid");
+        let source = miette::NamedSource::new("c.kdl", text.to_string());
+        let diag = Diagnostic::new(&err, Some(&source));
+        let span = value.span();
+        assert_eq!((diag.start.line, diag.start.column), (1, 16));
+        assert_eq!(
+            (diag.start.line, diag.start.column),
+            (span.0.line, span.0.column)
+        );
+        assert_eq!(
+            (diag.end.line, diag.end.column),
+            (span.1.line, span.1.column)
+        );
+        assert_eq!(diag.start.character, 9);
+        assert_eq!(diag.message, "invalid");
+    }
+
+    #[test]
+    #[cfg(all(feature = "serde", feature = "serde_json"))]
+    fn json() {
+        let err = crate::parse_ast::<Span>("a.kdl", "node )").unwrap_err();
+        let json = serde_json::to_value(err.diagnostics()).unwrap();
+        let range = serde_json::json!({
+            "start": {"line": 0, "character": 5, "column": 5},
+            "end": {"line": 0, "character": 6, "column": 6},
+        });
+        assert_eq!(json[0]["range"], range);
+        assert_eq!(json[0]["severity"], 1);
+        assert_eq!(json[0]["source"], "knus");
+        assert_eq!(json[0]["file"], "a.kdl");
+        assert_eq!(
+            json[0]["byteRange"],
+            serde_json::json!({"start": 5, "end": 6})
+        );
+        assert_eq!(json[0]["labels"][0]["range"], range);
+        assert!(json[0].get("code").is_none());
+    }
+}
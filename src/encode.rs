@@ -0,0 +1,81 @@
+//! Encode support stuff
+//!
+//! Mostly useful for manual implementation of various `Encode*` traits.
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+use crate::ast::{Literal, TypeName, Value};
+use crate::errors::EncodeError;
+use crate::span::Spanned;
+use crate::traits::ErrorSpan;
+
+/// Context is passed through all the encode operations
+///
+/// It can be used to store and retrieve data in encoders of nodes and
+/// scalars, similarly to [`decode::Context`](crate::decode::Context).
+#[derive(Debug, Default)]
+pub struct Context {
+    extensions: HashMap<TypeId, Box<dyn Any>>,
+}
+
+/// Encodes bytes as KDL value
+///
+/// Used internally by `#[knus(..., bytes)]` attribute. Bytes are written as
+/// `(base64)` string if `base64` feature is enabled, otherwise only bytes
+/// that are valid UTF-8 can be encoded.
+pub fn bytes<S>(value: &[u8], _ctx: &mut Context) -> Result<Value<S>, EncodeError>
+where
+    S: ErrorSpan + Default,
+{
+    #[cfg(feature = "base64")]
+    {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+        Ok(Value {
+            type_name: Some(Spanned::new(
+                crate::ast::BuiltinType::Base64.into(),
+                S::default(),
+            )),
+            literal: Spanned::new(Literal::String(STANDARD.encode(value).into()), S::default()),
+        })
+    }
+    #[cfg(not(feature = "base64"))]
+    {
+        match std::str::from_utf8(value) {
+            Ok(s) => Ok(Value::new(Literal::String(s.into()))),
+            Err(_) => Err(EncodeError::unsupported(
+                "base64 support is not compiled in",
+            )),
+        }
+    }
+}
+
+/// Makes type name from a string
+///
+/// Used internally by `#[knus(type_name)]` attribute.
+pub fn type_name<S: Default>(name: &str) -> Spanned<TypeName, S> {
+    Spanned::new(TypeName::from_string(name.into()), S::default())
+}
+
+impl Context {
+    pub(crate) fn new() -> Context {
+        Context {
+            extensions: HashMap::new(),
+        }
+    }
+    /// Set context value
+    ///
+    /// These values aren't used by the knus itself. But can be used by
+    /// user-defined encoders to get some value. Each type can have a single but
+    /// separate value set.
+    pub fn set<T: 'static>(&mut self, value: T) {
+        self.extensions.insert(TypeId::of::<T>(), Box::new(value));
+    }
+    /// Get context value
+    ///
+    /// Returns a value previously set in context
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.extensions
+            .get(&TypeId::of::<T>())
+            .and_then(|b| b.downcast_ref())
+    }
+}
@@ -6,13 +6,13 @@ use std::borrow::Cow;
 use std::collections::BTreeSet;
 use std::fmt::{self, Write};
 
-use miette::{Diagnostic, NamedSource};
+use miette::{Diagnostic, NamedSource, Severity};
 use thiserror::Error;
 
-use crate::ast::{Literal, SpannedNode, TypeName};
+use crate::ast::{SpannedNode, TypeName};
 use crate::decode::Kind;
-use crate::span::Spanned;
-use crate::traits::{ErrorSpan, Span};
+use crate::span::{Span, Spanned};
+use crate::traits::ErrorSpan;
 
 /// Main error that is returned from KDL parsers
 ///
@@ -30,6 +30,22 @@ pub struct Error {
     pub(crate) errors: Vec<miette::Report>,
 }
 
+/// Warnings that didn't prevent decoding the document
+///
+/// Returned along with the value by
+/// [`parse_with_context`](crate::parse_with_context). Renders as
+/// [`miette::Diagnostic`] with [`Severity::Warning`], each warning is
+/// rendered with its own severity.
+#[derive(Debug, Diagnostic, Error)]
+#[error("warnings decoding KDL")]
+#[diagnostic(severity(Warning))]
+pub struct Warnings {
+    #[source_code]
+    pub(crate) source_code: NamedSource<String>,
+    #[related]
+    pub(crate) warnings: Vec<miette::Report>,
+}
+
 /// An error type that is returned by decoder traits and emitted to the context
 ///
 /// These are elements of the
@@ -145,6 +161,66 @@ pub enum DecodeError<S: ErrorSpan> {
     /// source code span to the error.
     #[error(transparent)]
     Custom(Box<dyn std::error::Error + Send + Sync + 'static>),
+    /// Error with a help message attached
+    ///
+    /// Created by [`DecodeError::with_help`], e.g. to list the valid names
+    /// and the closest match for a misspelled one. Everything except the
+    /// help is taken from the original error.
+    #[error("{}", error)]
+    #[diagnostic(forward(error))]
+    WithHelp {
+        /// The original error
+        error: Box<DecodeError<S>>,
+        /// Help message
+        #[help]
+        help: String,
+    },
+}
+
+/// A non-fatal problem emitted to the context
+///
+/// See [`Context::emit_warning`](crate::decode::Context::emit_warning) and
+/// [`Context::emit_advice`](crate::decode::Context::emit_advice). The problem
+/// is described by the [`DecodeError`], which is reported with
+/// [`Severity::Warning`] or [`Severity::Advice`].
+#[derive(Debug)]
+pub struct Warning<S: ErrorSpan> {
+    severity: Severity,
+    error: DecodeError<S>,
+}
+
+/// An error type that is returned by encoder traits
+///
+/// Unlike [`DecodeError`] it has no spans attached, as there is no source
+/// code to point into when the document is being built from Rust values.
+#[derive(Debug, Diagnostic, Error)]
+#[non_exhaustive]
+pub enum EncodeError {
+    /// Value cannot be represented in KDL
+    ///
+    /// This is emitted for floating point infinities and NaN, and for
+    /// `(base64)` values when `base64` feature is not enabled.
+    #[error("{}", message)]
+    #[diagnostic()]
+    Unsupported {
+        /// Description of why the value is not supported
+        message: Cow<'static, str>,
+    },
+    /// Bad value conversion
+    ///
+    /// This is emitted when Rust value cannot be converted to a KDL scalar,
+    /// for example when a path is not valid unicode.
+    #[error("{}", source)]
+    #[diagnostic()]
+    Conversion {
+        /// Original error
+        source: Box<dyn std::error::Error + Send + Sync + 'static>,
+    },
+    /// Custom error that can be returned during encoding
+    ///
+    /// This is not used by the knus itself.
+    #[error(transparent)]
+    Custom(Box<dyn std::error::Error + Send + Sync + 'static>),
 }
 
 #[derive(Clone, Debug, PartialOrd, Ord, PartialEq, Eq)]
@@ -154,13 +230,78 @@ pub(crate) enum TokenFormat {
     Kind(&'static str),
     OpenRaw(usize),
     CloseRaw(usize),
+    OpenHashRaw(usize),
     Eoi,
 }
 
 struct FormatUnexpected<'x>(&'x TokenFormat, &'x BTreeSet<TokenFormat>);
 
+/// Syntax error of the KDL document
+///
+/// These are the errors listed in the [`Error`] returned by
+/// [`parse_ast`](crate::parse_ast) and the errors returned by
+/// [`parse_ast_recovery`](crate::parse_ast_recovery).
+#[derive(Debug, Diagnostic, Error)]
+#[error(transparent)]
+#[diagnostic(transparent)]
+pub struct ParseError<S: ErrorSpan>(pub(crate) ParseErrorKind<S>);
+
+/// Syntax error of the [query](crate::query::Query)
+///
+/// Contains the text of the query, so can be shown with the snippet as is.
 #[derive(Debug, Diagnostic, Error)]
-pub(crate) enum ParseError<S: ErrorSpan> {
+#[error("{}", message)]
+pub struct QueryError {
+    #[source_code]
+    query: String,
+    #[label("{}", label)]
+    span: Span,
+    label: &'static str,
+    message: String,
+}
+
+/// Error converting XML events into [XML-in-KDL](crate::xik) document
+#[derive(Debug, Diagnostic, Error, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum XikError {
+    /// The end tag doesn't match the currently open element
+    #[error(
+        "closing tag `</{}>` doesn't match opening tag `<{}>`",
+        found,
+        expected
+    )]
+    MismatchedEnd {
+        /// Name of the open element
+        expected: String,
+        /// Name in the end tag
+        found: String,
+    },
+    /// The end tag when there is no open element
+    #[error("closing tag `</{}>` has no opening tag", _0)]
+    UnexpectedEnd(String),
+    /// The element isn't closed at the end of the events
+    #[error("element `<{}>` is not closed", _0)]
+    Unclosed(String),
+}
+
+/// Error replacing variables in the
+/// [`Interpolated`](crate::interpolate::Interpolated) string
+#[derive(Debug, Diagnostic, Error, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InterpolationError {
+    /// The variable is neither in the context nor in the environment
+    #[error("variable `{}` is not defined", _0)]
+    Undefined(String),
+    /// The `${` placeholder has no closing brace
+    #[error("unclosed placeholder, expected `}}`")]
+    Unclosed,
+    /// The name of the variable is empty or has invalid characters
+    #[error("invalid variable name `{}`", _0)]
+    InvalidName(String),
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub(crate) enum ParseErrorKind<S: ErrorSpan> {
     #[error("{}", FormatUnexpected(found, expected))]
     #[diagnostic()]
     Unexpected {
@@ -245,6 +386,13 @@ impl fmt::Display for TokenFormat {
                 }
                 f.write_str("\"`")
             }
+            OpenHashRaw(n) => {
+                f.write_char('`')?;
+                for _ in 0..*n {
+                    f.write_char('#')?;
+                }
+                f.write_str("\"`")
+            }
             CloseRaw(0) => f.write_str("`\"`"),
             CloseRaw(n) => {
                 f.write_str("`\"")?;
@@ -276,42 +424,46 @@ impl fmt::Display for FormatUnexpected<'_> {
 }
 
 impl<S: ErrorSpan> ParseError<S> {
-    pub(crate) fn with_expected_token(mut self, token: &'static str) -> Self {
-        use ParseError::*;
-        if let Unexpected {
-            ref mut expected, ..
-        } = &mut self
-        {
-            *expected = [TokenFormat::Token(token)].into_iter().collect();
+    /// Position of the error in the source
+    pub fn span(&self) -> &S {
+        use ParseErrorKind::*;
+        match &self.0 {
+            Unexpected { span, .. } | Message { span, .. } | MessageWithHelp { span, .. } => span,
+            Unclosed { expected_at, .. } => expected_at,
         }
-        self
-    }
-    pub(crate) fn with_expected_kind(mut self, token: &'static str) -> Self {
-        use ParseError::*;
-        if let Unexpected {
-            ref mut expected, ..
-        } = &mut self
-        {
-            *expected = [TokenFormat::Kind(token)].into_iter().collect();
-        }
-        self
-    }
-    pub(crate) fn with_no_expected(mut self) -> Self {
-        use ParseError::*;
-        if let Unexpected {
-            ref mut expected, ..
-        } = &mut self
-        {
-            *expected = BTreeSet::new();
+    }
+}
+
+impl QueryError {
+    pub(crate) fn new(query: &str, err: ParseErrorKind<Span>) -> QueryError {
+        use ParseErrorKind::*;
+        let message = err.to_string();
+        let (span, label) = match err {
+            Unexpected { span, label, .. }
+            | Message { span, label, .. }
+            | MessageWithHelp { span, label, .. } => (span, label.unwrap_or("unexpected token")),
+            Unclosed { expected_at, .. } => (expected_at, "unclosed"),
+        };
+        QueryError {
+            query: query.into(),
+            span,
+            label,
+            message,
         }
-        self
     }
+    /// Position of the error in the query
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+}
+
+impl<S: ErrorSpan> ParseErrorKind<S> {
     #[allow(dead_code)]
-    pub(crate) fn map_span<T>(self, f: impl Fn(S) -> T) -> ParseError<T>
+    pub(crate) fn map_span<T>(self, f: impl Fn(S) -> T) -> ParseErrorKind<T>
     where
         T: ErrorSpan,
     {
-        use ParseError::*;
+        use ParseErrorKind::*;
         match self {
             Unexpected {
                 label,
@@ -363,66 +515,6 @@ impl<S: ErrorSpan> ParseError<S> {
     }
 }
 
-impl<S: Span> chumsky::Error<char> for ParseError<S> {
-    type Span = S;
-    type Label = &'static str;
-    fn expected_input_found<Iter>(span: Self::Span, expected: Iter, found: Option<char>) -> Self
-    where
-        Iter: IntoIterator<Item = Option<char>>,
-    {
-        ParseError::Unexpected {
-            label: None,
-            span,
-            found: found.into(),
-            expected: expected.into_iter().map(Into::into).collect(),
-        }
-    }
-    fn with_label(mut self, new_label: Self::Label) -> Self {
-        use ParseError::*;
-        match self {
-            Unexpected { ref mut label, .. } => *label = Some(new_label),
-            Unclosed { ref mut label, .. } => *label = new_label,
-            Message { ref mut label, .. } => *label = Some(new_label),
-            MessageWithHelp { ref mut label, .. } => *label = Some(new_label),
-        }
-        self
-    }
-    fn merge(mut self, other: Self) -> Self {
-        use ParseError::*;
-        match (&mut self, other) {
-            (Unclosed { .. }, _) => self,
-            (_, other @ Unclosed { .. }) => other,
-            (
-                Unexpected {
-                    expected: ref mut dest,
-                    ..
-                },
-                Unexpected { expected, .. },
-            ) => {
-                dest.extend(expected);
-                self
-            }
-            (_, other) => todo!("{} -> {}", self, other),
-        }
-    }
-    fn unclosed_delimiter(
-        unclosed_span: Self::Span,
-        unclosed: char,
-        span: Self::Span,
-        expected: char,
-        found: Option<char>,
-    ) -> Self {
-        ParseError::Unclosed {
-            label: "delimited",
-            opened_at: unclosed_span,
-            opened: unclosed.into(),
-            expected_at: span,
-            expected: expected.into(),
-            found: found.into(),
-        }
-    }
-}
-
 impl<S: ErrorSpan> DecodeError<S> {
     /// Construct [`DecodeError::Conversion`] error
     pub fn conversion<T, E>(span: &Spanned<T, S>, err: E) -> Self
@@ -435,7 +527,13 @@ impl<S: ErrorSpan> DecodeError<S> {
         }
     }
     /// Construct [`DecodeError::ScalarKind`] error
-    pub fn scalar_kind(expected: Kind, found: &Spanned<Literal, S>) -> Self {
+    ///
+    /// Accepts literals of both [`ast`](crate::ast) and
+    /// [`borrowed`](crate::borrowed) trees.
+    pub fn scalar_kind<L>(expected: Kind, found: &Spanned<L, S>) -> Self
+    where
+        for<'x> &'x L: Into<Kind>,
+    {
         DecodeError::ScalarKind {
             span: found.span().clone(),
             expected: expected.into(),
@@ -461,6 +559,22 @@ impl<S: ErrorSpan> DecodeError<S> {
             message: message.into(),
         }
     }
+    /// Attach a help message to the error
+    ///
+    /// Wraps the error into [`DecodeError::WithHelp`], replacing the help
+    /// if there is one already. See
+    /// [`decode::names_help`](crate::decode::names_help) for building the
+    /// message for a misspelled name.
+    pub fn with_help(self, text: impl Into<String>) -> Self {
+        let error = match self {
+            DecodeError::WithHelp { error, .. } => error,
+            error => Box::new(error),
+        };
+        DecodeError::WithHelp {
+            error,
+            help: text.into(),
+        }
+    }
     /// Construct [`DecodeError::Unsupported`] error
     pub fn unsupported<T, M>(span: &Spanned<T, S>, message: M) -> Self
     where
@@ -521,10 +635,112 @@ impl<S: ErrorSpan> DecodeError<S> {
                 message,
             },
             Custom(e) => Custom(e),
+            WithHelp { error, help } => WithHelp {
+                // `dyn` keeps the recursion from instantiating new closure types
+                error: Box::new(error.map_span(&mut f as &mut dyn FnMut(S) -> T)),
+                help,
+            },
+        }
+    }
+}
+
+impl<S: ErrorSpan> Warning<S> {
+    /// Construct a warning reporting the error with the given severity
+    pub fn new(severity: Severity, error: impl Into<DecodeError<S>>) -> Self {
+        Warning {
+            severity,
+            error: error.into(),
+        }
+    }
+    /// Severity of the warning
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+    /// The error describing the problem
+    pub fn error(&self) -> &DecodeError<S> {
+        &self.error
+    }
+    #[allow(dead_code)]
+    pub(crate) fn map_span<T>(self, f: impl FnMut(S) -> T) -> Warning<T>
+    where
+        T: ErrorSpan,
+    {
+        Warning {
+            severity: self.severity,
+            error: self.error.map_span(f),
         }
     }
 }
 
+impl<S: ErrorSpan> fmt::Display for Warning<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl<S: ErrorSpan> std::error::Error for Warning<S> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+impl<S: ErrorSpan> Diagnostic for Warning<S> {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.error.code()
+    }
+    fn severity(&self) -> Option<Severity> {
+        Some(self.severity)
+    }
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.error.help()
+    }
+    fn url<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.error.url()
+    }
+    fn source_code(&self) -> Option<&dyn miette::SourceCode> {
+        self.error.source_code()
+    }
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = miette::LabeledSpan> + '_>> {
+        self.error.labels()
+    }
+    fn related<'a>(&'a self) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
+        self.error.related()
+    }
+    fn diagnostic_source(&self) -> Option<&dyn Diagnostic> {
+        self.error.diagnostic_source()
+    }
+}
+
+impl Warnings {
+    /// Returns `true` if there are no warnings
+    pub fn is_empty(&self) -> bool {
+        self.warnings.is_empty()
+    }
+    /// Number of warnings
+    pub fn len(&self) -> usize {
+        self.warnings.len()
+    }
+}
+
+impl EncodeError {
+    /// Construct [`EncodeError::Unsupported`] error
+    pub fn unsupported<M>(message: M) -> Self
+    where
+        M: Into<Cow<'static, str>>,
+    {
+        EncodeError::Unsupported {
+            message: message.into(),
+        }
+    }
+    /// Construct [`EncodeError::Conversion`] error
+    pub fn conversion<E>(err: E) -> Self
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync + 'static>>,
+    {
+        EncodeError::Conversion { source: err.into() }
+    }
+}
+
 /// Wrapper around expected type that is used in [`DecodeError::TypeName`].
 // MISSING: The `Default` implementation is missing, since there is no clear
 // default here — really, this should be an enum, since `types` only makes sense
@@ -0,0 +1,303 @@
+//! Formatting of the [concrete syntax tree](crate::cst)
+//!
+//! Only the whitespace between the tokens is changed: every node is put on
+//! its own line with the indentation of its level and entries are
+//! separated by single spaces. Names and values are written exactly as in
+//! the source, so the formatter works the same for both versions of the
+//! syntax. Comments and entities commented out using `/-` are kept.
+use crate::cst::{Children, Document, Node, SlashDash, Token, TokenKind, TypeAnnotation};
+
+/// Writes the formatted document
+pub(crate) struct Formatter {
+    out: String,
+    indent: usize,
+    /// Nesting level of the nodes being written
+    level: usize,
+    /// Current line is continued using `\`, so it's indented one level more
+    continued: bool,
+    /// Nothing is written on the current line yet
+    line_start: bool,
+    /// A space is written before the next token unless it starts a line
+    space: bool,
+    /// No nodes or comments are written in the current block yet, blank
+    /// lines are not kept there
+    fresh: bool,
+    /// The line of the source text is not ended after the last node or
+    /// brace, so the comment that follows belongs to the same line
+    same_line: bool,
+}
+
+enum Visit<'a> {
+    Node(&'a Node),
+    Open(&'a Children),
+    Close(&'a Children),
+    Tail(&'a Node),
+}
+
+impl Formatter {
+    pub(crate) fn new(indent: usize) -> Formatter {
+        Formatter {
+            out: String::new(),
+            indent,
+            level: 0,
+            continued: false,
+            line_start: true,
+            space: false,
+            fresh: true,
+            same_line: false,
+        }
+    }
+
+    /// Formats the document using an explicit stack instead of recursion
+    pub(crate) fn document(mut self, doc: &Document) -> String {
+        let first = doc.nodes.first().map_or(&doc.trailing, |n| &n.leading);
+        if first
+            .first()
+            .is_some_and(|t| t.text().starts_with('\u{FEFF}'))
+        {
+            self.out.push('\u{FEFF}');
+        }
+        let mut stack = doc.nodes.iter().rev().map(Visit::Node).collect::<Vec<_>>();
+        while let Some(visit) = stack.pop() {
+            match visit {
+                Visit::Node(node) => {
+                    self.head(node);
+                    stack.push(Visit::Tail(node));
+                    for children in node.children.iter().rev() {
+                        stack.push(Visit::Close(children));
+                        stack.extend(children.nodes.iter().rev().map(Visit::Node));
+                        stack.push(Visit::Open(children));
+                    }
+                }
+                Visit::Open(children) => self.open(children),
+                Visit::Close(children) => self.close(children),
+                Visit::Tail(node) => self.tail(node),
+            }
+        }
+        self.lines(&doc.trailing, false);
+        if !self.line_start {
+            self.newline();
+        }
+        self.out
+    }
+
+    fn write(&mut self, text: &str) {
+        if self.line_start {
+            let width = self.indent * (self.level + usize::from(self.continued));
+            self.out.extend(std::iter::repeat(' ').take(width));
+            self.line_start = false;
+        } else if self.space {
+            self.out.push(' ');
+        }
+        self.space = false;
+        self.fresh = false;
+        self.out.push_str(text);
+    }
+
+    /// Writes the token without a space before it
+    fn write_tight(&mut self, text: &str) {
+        self.space = false;
+        self.write(text);
+    }
+
+    fn newline(&mut self) {
+        let len = self.out.trim_end_matches(' ').len();
+        self.out.truncate(len);
+        self.out.push('\n');
+        self.line_start = true;
+        self.space = false;
+    }
+
+    /// Writes comments between the nodes, each on its own line
+    ///
+    /// A single blank line is kept where there were any.
+    fn lines(&mut self, tokens: &[Token], before_node: bool) {
+        let same_line = std::mem::replace(&mut self.same_line, false);
+        // the line of the previous node counts as ended
+        let mut newlines = usize::from(!same_line);
+        let mut first = true;
+        for token in tokens {
+            match token.kind() {
+                TokenKind::Newline => newlines += 1,
+                TokenKind::LineComment | TokenKind::BlockComment => {
+                    let kind = token.kind();
+                    if first && same_line && newlines == 0 && kind == TokenKind::LineComment {
+                        // comment after the `;` or `{` stays on that line
+                        if self.out.ends_with('\n') {
+                            self.out.pop();
+                            self.line_start = false;
+                        }
+                        self.space = true;
+                    } else {
+                        self.break_lines(newlines);
+                    }
+                    self.write(token.text());
+                    if kind == TokenKind::LineComment {
+                        // the newline ending the comment is counted next
+                        self.newline();
+                    }
+                    newlines = 0;
+                    first = false;
+                }
+                _ => {}
+            }
+        }
+        if before_node {
+            self.break_lines(newlines);
+            self.space = !self.line_start;
+        } else if !self.line_start {
+            self.newline();
+        }
+    }
+
+    /// Starts a new line if there were `newlines` in the source, keeping a
+    /// blank line if there were any
+    fn break_lines(&mut self, newlines: usize) {
+        if newlines == 0 {
+            self.space = true;
+            return;
+        }
+        if !self.line_start {
+            self.newline();
+        }
+        if newlines > 1 && !self.fresh {
+            self.out.push('\n');
+        }
+    }
+
+    /// Writes comments and line continuations within the node
+    ///
+    /// `continuation` is unset at the end of the node, where `\` would join
+    /// the next node, so it is dropped.
+    fn inline(&mut self, tokens: &[Token], continuation: bool) {
+        for (i, token) in tokens.iter().enumerate() {
+            match token.kind() {
+                TokenKind::BlockComment => {
+                    self.space = true;
+                    self.write(token.text());
+                    self.space = true;
+                }
+                TokenKind::LineComment => {
+                    self.space = true;
+                    self.write(token.text());
+                    self.newline();
+                    self.continued = continuation;
+                }
+                TokenKind::Backslash if continuation => {
+                    self.space = true;
+                    self.write(token.text());
+                    let comment = tokens[i + 1..]
+                        .iter()
+                        .find(|t| t.kind() != TokenKind::Whitespace)
+                        .is_some_and(|t| t.kind() == TokenKind::LineComment);
+                    if !comment {
+                        self.newline();
+                        self.continued = true;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Writes the `/-`, separated from the entity by a space if it was in
+    /// the source
+    fn slashdash(&mut self, slashdash: &SlashDash) {
+        self.write(slashdash.token.text());
+        self.space = !slashdash.trailing.is_empty();
+        self.inline(&slashdash.trailing, true);
+    }
+
+    fn type_name(&mut self, type_name: &TypeAnnotation) {
+        self.write(type_name.open.text());
+        self.inline(&type_name.leading, true);
+        self.write(type_name.name.text());
+        self.inline(&type_name.trailing, true);
+        self.write_tight(type_name.close.text());
+        self.inline(&type_name.after, true);
+    }
+
+    /// Writes the node up to the first children block
+    fn head(&mut self, node: &Node) {
+        self.lines(&node.leading, true);
+        if let Some(slashdash) = &node.slashdash {
+            self.slashdash(slashdash);
+        }
+        if let Some(type_name) = &node.type_name {
+            self.type_name(type_name);
+        }
+        self.write(node.name.text());
+        for entry in &node.entries {
+            self.space = true;
+            self.inline(&entry.leading, true);
+            if let Some(slashdash) = &entry.slashdash {
+                self.slashdash(slashdash);
+            }
+            if let Some(name) = &entry.name {
+                self.write(name.name.text());
+                self.inline(&name.before_equals, true);
+                self.write_tight(name.equals.text());
+                self.inline(&name.after_equals, true);
+            }
+            if let Some(type_name) = &entry.value.type_name {
+                self.type_name(type_name);
+            }
+            self.write(entry.value.literal.text());
+        }
+    }
+
+    fn open(&mut self, children: &Children) {
+        self.space = true;
+        self.inline(&children.leading, true);
+        if let Some(slashdash) = &children.slashdash {
+            self.slashdash(slashdash);
+        }
+        self.write(children.open.text());
+        if is_empty(children) {
+            self.write_tight(children.close.text());
+            return;
+        }
+        self.newline();
+        self.level += 1;
+        self.continued = false;
+        self.fresh = true;
+        self.same_line = true;
+    }
+
+    fn close(&mut self, children: &Children) {
+        if is_empty(children) {
+            return;
+        }
+        self.lines(&children.trailing, false);
+        self.level -= 1;
+        self.write(children.close.text());
+    }
+
+    /// Writes the rest of the node after the children blocks
+    fn tail(&mut self, node: &Node) {
+        self.inline(&node.trailing, false);
+        for token in &node.terminator {
+            if token.kind() == TokenKind::LineComment {
+                self.space = true;
+                self.write(token.text());
+            }
+        }
+        if !self.line_start {
+            self.newline();
+        }
+        self.continued = false;
+        self.same_line = !node
+            .terminator
+            .iter()
+            .any(|t| matches!(t.kind(), TokenKind::Newline | TokenKind::LineComment));
+    }
+}
+
+/// Block without child nodes and comments is written as `{}`
+fn is_empty(children: &Children) -> bool {
+    children.nodes.is_empty()
+        && !children
+            .trailing
+            .iter()
+            .any(|t| matches!(t.kind(), TokenKind::LineComment | TokenKind::BlockComment))
+}
@@ -1,660 +1,1513 @@
+//! Parser of the KDL documents
+//!
+//! The text is split into tokens by a byte-level [`Scanner`] only when the
+//! [`Parser`] asks for the next one, so no intermediate token list is built.
+//! Nodes whose children are being parsed are kept on an explicit stack, so
+//! deeply nested documents can't overflow the call stack.
+//!
+//! This module contains the rules shared by both versions of the syntax and
+//! the KDL 1.0 specifics, KDL 2.0 rules live in
+//! [`grammar_v2`](crate::grammar_v2).
+use std::borrow::Cow;
 use std::collections::{BTreeMap, BTreeSet};
-
-use chumsky::prelude::*;
-
-use crate::ast::{Decimal, Integer, Literal, Node, Radix, TypeName, Value};
-use crate::ast::{Document, SpannedName, SpannedNode};
-use crate::errors::{ParseError as Error, TokenFormat};
+use std::mem;
+use std::ops::Range;
+
+use crate::ast::{self, Radix};
+use crate::ast::{Document, Node, SpannedName, SpannedNode, TypeName, Value};
+use crate::borrowed::{Decimal, Integer, Literal};
+use crate::cst::TokenKind;
+use crate::errors::{ParseErrorKind as Error, TokenFormat};
+use crate::grammar_v2;
 use crate::span::Spanned;
+use crate::traits::sealed::SpanTracker;
 use crate::traits::Span;
 
-use chumsky::chain::Chain;
-use chumsky::combinator::{Map, Then};
-
-type MapChar<O, U> = fn(_: (O, U)) -> Vec<char>;
-
-trait ChainChar<I: Clone, O> {
-    type Error;
-    fn chain_c<U, P>(self, other: P) -> Map<Then<Self, P>, MapChar<O, U>, (O, U)>
-    where
-        Self: Sized,
-        U: Chain<char>,
-        O: Chain<char>,
-        P: Parser<I, U, Error = Self::Error>;
+/// Version of the KDL syntax
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Syntax {
+    V1,
+    V2,
 }
 
-impl<I: Clone, O, R: Parser<I, O>> ChainChar<I, O> for R {
-    type Error = <R as Parser<I, O>>::Error;
-    fn chain_c<U, P>(self, other: P) -> Map<Then<Self, P>, MapChar<O, U>, (O, U)>
-    where
-        Self: Sized,
-        U: Chain<char>,
-        O: Chain<char>,
-        P: Parser<I, U, Error = Self::Error>,
-    {
-        Parser::chain(self, other)
-    }
+fn is_ws_char(c: char) -> bool {
+    matches!(
+        c,
+        '\t' | ' ' | '\u{00a0}' | '\u{1680}' | '\u{2000}'
+            ..='\u{200A}' | '\u{202F}' | '\u{205F}' | '\u{3000}' | '\u{FEFF}'
+    )
 }
 
-fn begin_comment<S: Span>(which: char) -> impl Parser<char, (), Error = Error<S>> + Clone {
-    just('/')
-        .map_err(|e: Error<S>| e.with_no_expected())
-        .ignore_then(just(which).ignored())
+fn is_id_char(c: char) -> bool {
+    !matches!(c,
+        '\u{0000}'..='\u{0021}' |
+        '\\'|'/'|'('|')'|'{'|'}'|'<'|'>'|';'|'['|']'|'='|','|'"' |
+        // whitespace, excluding 0x20
+        '\u{00a0}' | '\u{1680}' |
+        '\u{2000}'..='\u{200A}' |
+        '\u{202F}' | '\u{205F}' | '\u{3000}' |
+        // newline (excluding <= 0x20)
+        '\u{0085}' | '\u{2028}' | '\u{2029}'
+    )
 }
 
-fn newline<S: Span>() -> impl Parser<char, (), Error = Error<S>> {
-    just('\r')
-        .or_not()
-        .ignore_then(just('\n'))
-        .or(just('\r')) // Carriage return
-        .or(just('\x0C')) // Form feed
-        .or(just('\u{0085}')) // Next line
-        .or(just('\u{2028}')) // Line separator
-        .or(just('\u{2029}')) // Paragraph separator
-        .ignored()
-        .map_err(|e: Error<S>| e.with_expected_kind("newline"))
+fn is_id_sans_dig(c: char) -> bool {
+    !matches!(c,
+        '0'..='9' |
+        '\u{0000}'..='\u{0020}' |
+        '\\'|'/'|'('|')'|'{'|'}'|'<'|'>'|';'|'['|']'|'='|','|'"' |
+        // whitespace, excluding 0x20
+        '\u{00a0}' | '\u{1680}' |
+        '\u{2000}'..='\u{200A}' |
+        '\u{202F}' | '\u{205F}' | '\u{3000}' |
+        // newline (excluding <= 0x20)
+        '\u{0085}' | '\u{2028}' | '\u{2029}'
+    )
 }
 
-fn ws_char<S: Span>() -> impl Parser<char, (), Error = Error<S>> {
-    filter(|c| {
-        matches!(
-            c,
-            '\t' | ' ' | '\u{00a0}' | '\u{1680}' | '\u{2000}'
-                ..='\u{200A}' | '\u{202F}' | '\u{205F}' | '\u{3000}' | '\u{FEFF}'
-        )
-    })
-    .ignored()
+fn is_id_sans_sign_dig(c: char) -> bool {
+    c != '-' && c != '+' && is_id_sans_dig(c)
 }
 
-fn id_char<S: Span>() -> impl Parser<char, char, Error = Error<S>> {
-    filter(|c| {
-        !matches!(c,
-            '\u{0000}'..='\u{0021}' |
-            '\\'|'/'|'('|')'|'{'|'}'|'<'|'>'|';'|'['|']'|'='|','|'"' |
-            // whitespace, excluding 0x20
-            '\u{00a0}' | '\u{1680}' |
-            '\u{2000}'..='\u{200A}' |
-            '\u{202F}' | '\u{205F}' | '\u{3000}' |
-            // newline (excluding <= 0x20)
-            '\u{0085}' | '\u{2028}' | '\u{2029}'
-        )
-    })
-    .map_err(|e: Error<S>| e.with_expected_kind("letter"))
+pub(crate) fn expected_kind(s: &'static str) -> BTreeSet<TokenFormat> {
+    [TokenFormat::Kind(s)].into_iter().collect()
 }
 
-fn id_sans_dig<S: Span>() -> impl Parser<char, char, Error = Error<S>> {
-    filter(|c| {
-        !matches!(c,
-            '0'..='9' |
-            '\u{0000}'..='\u{0020}' |
-            '\\'|'/'|'('|')'|'{'|'}'|'<'|'>'|';'|'['|']'|'='|','|'"' |
-            // whitespace, excluding 0x20
-            '\u{00a0}' | '\u{1680}' |
-            '\u{2000}'..='\u{200A}' |
-            '\u{202F}' | '\u{205F}' | '\u{3000}' |
-            // newline (excluding <= 0x20)
-            '\u{0085}' | '\u{2028}' | '\u{2029}'
-        )
-    })
-    .map_err(|e: Error<S>| e.with_expected_kind("letter"))
+/// Single value token: a string, a number, a keyword or a bare identifier
+///
+/// Values borrow from the source text unless a string has escapes or a
+/// number has underscores.
+pub(crate) enum Scalar<'a> {
+    /// Bare identifier of KDL 1.0, in KDL 2.0 these are strings
+    Ident(Cow<'a, str>),
+    String(Cow<'a, str>),
+    Number(Literal<'a>),
+    /// Keyword as written in the source and its value
+    Keyword(&'static str, Literal<'a>),
 }
 
-fn id_sans_sign_dig<S: Span>() -> impl Parser<char, char, Error = Error<S>> {
-    filter(|c| {
-        !matches!(c,
-            '-'| '+' | '0'..='9' |
-            '\u{0000}'..='\u{0020}' |
-            '\\'|'/'|'('|')'|'{'|'}'|'<'|'>'|';'|'['|']'|'='|','|'"' |
-            // whitespace, excluding 0x20
-            '\u{00a0}' | '\u{1680}' |
-            '\u{2000}'..='\u{200A}' |
-            '\u{202F}' | '\u{205F}' | '\u{3000}' |
-            // newline (excluding <= 0x20)
-            '\u{0085}' | '\u{2028}' | '\u{2029}'
-        )
-    })
-    .map_err(|e: Error<S>| e.with_expected_kind("letter"))
+/// Byte-level lexer
+///
+/// Scans a single token at the current position on request. Characters are
+/// decoded only where non-ASCII ones are significant.
+pub(crate) struct Scanner<'a, S: Span> {
+    pub(crate) text: &'a str,
+    pub(crate) pos: usize,
+    pub(crate) syntax: Syntax,
+    tracker: S::Tracker,
+    /// Errors that scanner and parser have recovered from
+    pub(crate) errors: Vec<Error<S>>,
 }
 
-fn ws<S: Span>() -> impl Parser<char, (), Error = Error<S>> {
-    ws_char()
-        .repeated()
-        .at_least(1)
-        .ignored()
-        .or(ml_comment())
-        .map_err(|e| e.with_expected_kind("whitespace"))
+/// Value of the token and its byte range in the source text
+pub(crate) struct Parsed<T> {
+    pub(crate) range: Range<usize>,
+    pub(crate) value: T,
 }
 
-fn comment<S: Span>() -> impl Parser<char, (), Error = Error<S>> {
-    begin_comment('/')
-        .then(take_until(newline().or(end())))
-        .ignored()
+/// Type name in parenthesis
+pub(crate) struct ParsedType<'a> {
+    /// Byte offset of the opening parenthesis
+    pub(crate) open: usize,
+    pub(crate) name: Parsed<Cow<'a, str>>,
+    /// Byte offset of the closing parenthesis
+    pub(crate) close: usize,
 }
 
-fn ml_comment<S: Span>() -> impl Parser<char, (), Error = Error<S>> {
-    recursive::<_, _, _, _, Error<S>>(|comment| {
-        choice((
-            comment,
-            none_of('*').ignored(),
-            just('*').then_ignore(none_of('/').rewind()).ignored(),
-        ))
-        .repeated()
-        .ignored()
-        .delimited_by(begin_comment('*'), just("*/"))
-    })
-    .map_err_with_span(|e, span| {
-        if matches!(
-            &e,
-            Error::Unexpected {
-                found: TokenFormat::Eoi,
-                ..
-            }
-        ) && span.length() > 2
-        {
-            e.merge(Error::Unclosed {
-                label: "comment",
-                opened_at: span.at_start(2),
-                opened: "/*".into(),
-                expected_at: span.at_end(),
-                expected: "*/".into(),
-                found: None.into(),
-            })
-        } else {
-            // otherwise opening /* is not matched
-            e
-        }
-    })
+/// Possibly typed value
+pub(crate) struct ParsedValue<'a> {
+    pub(crate) type_name: Option<ParsedType<'a>>,
+    pub(crate) literal: Parsed<Literal<'a>>,
 }
 
-fn raw_string<S: Span>() -> impl Parser<char, Box<str>, Error = Error<S>> {
-    just('r')
-        .ignore_then(just('#').repeated().map(|v| v.len()))
-        .then_ignore(just('"'))
-        .then_with(|sharp_num| {
-            take_until(just('"').ignore_then(just('#').repeated().exactly(sharp_num).ignored()))
-                .map_err_with_span(move |e: Error<S>, span| {
-                    if matches!(
-                        &e,
-                        Error::Unexpected {
-                            found: TokenFormat::Eoi,
-                            ..
-                        }
-                    ) {
-                        e.merge(Error::Unclosed {
-                            label: "raw string",
-                            opened_at: span.before_start(sharp_num + 2),
-                            opened: TokenFormat::OpenRaw(sharp_num),
-                            expected_at: span.at_end(),
-                            expected: TokenFormat::CloseRaw(sharp_num),
-                            found: None.into(),
-                        })
-                    } else {
-                        e
-                    }
-                })
-        })
-        .map(|(text, ())| text.into_iter().collect::<String>().into())
+/// Argument or property of the node
+pub(crate) struct ParsedEntry<'a> {
+    /// Byte offset of the `/-` if the entry is commented out
+    pub(crate) slashdash: Option<usize>,
+    /// Name of the property and byte offset of the equals sign, `None` for
+    /// arguments
+    pub(crate) name: Option<(Parsed<Cow<'a, str>>, usize)>,
+    pub(crate) value: ParsedValue<'a>,
 }
 
-fn string<S: Span>() -> impl Parser<char, Box<str>, Error = Error<S>> {
-    raw_string().or(escaped_string())
+/// Start of the node up to its name
+pub(crate) struct ParsedHead<'a> {
+    /// Byte offset of the `/-` if the node is commented out
+    pub(crate) slashdash: Option<usize>,
+    pub(crate) type_name: Option<ParsedType<'a>>,
+    pub(crate) name: Parsed<Cow<'a, str>>,
 }
 
-fn expected_kind(s: &'static str) -> BTreeSet<TokenFormat> {
-    [TokenFormat::Kind(s)].into_iter().collect()
+/// Builds a tree out of the parts of the document found by the [`Parser`]
+///
+/// Parts are passed in the order of the source text, including the ones
+/// commented out with `/-`, it's up to the builder to skip them.
+pub(crate) trait Builder<'a> {
+    /// Node whose entries or children are being parsed
+    type Node;
+    /// Completely parsed node
+    type Output;
+    /// Starts the node
+    fn node(&mut self, head: ParsedHead<'a>) -> Self::Node;
+    /// Adds an argument or a property to the node
+    fn entry(&mut self, node: &mut Self::Node, entry: ParsedEntry<'a>);
+    /// Children block is opened by the brace at the byte offset
+    fn open(&mut self, node: &mut Self::Node, slashdash: Option<usize>, open: usize);
+    /// Children block is closed by the brace at the byte offset
+    fn close(&mut self, node: &mut Self::Node, children: Vec<Self::Output>, close: usize);
+    /// Finishes the node terminated by the text in the range
+    ///
+    /// The range is empty at the end of input and, in KDL 2.0, before the
+    /// closing brace. Returns `None` if the node is skipped.
+    fn finish(&mut self, node: Self::Node, terminator: Range<usize>) -> Option<Self::Output>;
+    /// Drops the nodes parsed before the error
+    ///
+    /// Nodes are dropped without recursion, so that deeply nested documents
+    /// don't overflow the stack.
+    fn discard(&mut self, nodes: Vec<Self::Output>);
 }
 
-fn esc_char<S: Span>() -> impl Parser<char, char, Error = Error<S>> {
-    filter_map(|span, c| match c {
-        '"' | '\\' | '/' => Ok(c),
-        'b' => Ok('\u{0008}'),
-        'f' => Ok('\u{000C}'),
-        'n' => Ok('\n'),
-        'r' => Ok('\r'),
-        't' => Ok('\t'),
-        c => Err(Error::Unexpected {
-            label: Some("invalid escape char"),
-            span,
-            found: c.into(),
-            expected: "\"\\/bfnrt".chars().map(|c| c.into()).collect(),
-        }),
-    })
-    .or(just('u').ignore_then(
-        filter_map(|span, c: char| {
-            c.is_ascii_hexdigit()
-                .then_some(c)
-                .ok_or_else(|| Error::Unexpected {
-                    label: Some("unexpected character"),
-                    span,
-                    found: c.into(),
-                    expected: expected_kind("hexadecimal digit"),
-                })
-        })
-        .repeated()
-        .at_least(1)
-        .at_most(6)
-        .delimited_by(just('{'), just('}'))
-        .try_map(|hex_chars, span| {
-            let s = hex_chars.into_iter().collect::<String>();
-            let c = u32::from_str_radix(&s, 16)
-                .map_err(|e| e.to_string())
-                .and_then(|n| char::try_from(n).map_err(|e| e.to_string()))
-                .map_err(|e| Error::Message {
-                    label: Some("invalid character code"),
-                    span,
-                    message: e.to_string(),
-                })?;
-            Ok(c)
-        })
-        .recover_with(skip_until(['}', '"', '\\'], |_| '\0')),
-    ))
+/// Node that is being parsed
+struct Pending<N> {
+    /// Byte offset of the node start including `/-`, parsing is restarted
+    /// from here on recovery
+    start: usize,
+    /// Byte offset of the type name or the node name
+    head: usize,
+    /// Number of children blocks that are not commented out
+    blocks: usize,
+    node: N,
 }
 
-fn escaped_string<S: Span>() -> impl Parser<char, Box<str>, Error = Error<S>> {
-    just('"').ignore_then(
-        filter(|&c| c != '"' && c != '\\')
-            .or(just('\\').ignore_then(esc_char()))
-            .repeated()
-            .then_ignore(just('"'))
-            .map(|val| val.into_iter().collect::<String>().into())
-            .map_err_with_span(|e: Error<S>, span| {
-                if matches!(
-                    &e,
-                    Error::Unexpected {
-                        found: TokenFormat::Eoi,
-                        ..
-                    }
-                ) {
-                    e.merge(Error::Unclosed {
-                        label: "string",
-                        opened_at: span.before_start(1),
-                        opened: '"'.into(),
-                        expected_at: span.at_end(),
-                        expected: '"'.into(),
-                        found: None.into(),
-                    })
-                } else {
-                    e
-                }
-            }),
-    )
+/// Node whose children block is being parsed
+struct Frame<N, O> {
+    node: Pending<N>,
+    /// Byte offset of the opening brace
+    open: usize,
+    commented: bool,
+    /// Nodes preceding `node` at its own level
+    siblings: Vec<O>,
 }
 
-fn bare_ident<S: Span>() -> impl Parser<char, Box<str>, Error = Error<S>> {
-    let sign = just('+').or(just('-'));
-    choice((
-        sign.chain(id_sans_dig().chain(id_char().repeated())),
-        sign.repeated().exactly(1),
-        id_sans_sign_dig().chain(id_char().repeated()),
-    ))
-    .map(|v| v.into_iter().collect())
-    .try_map(|s: String, span| match &s[..] {
-        "true" => Err(Error::Unexpected {
-            label: Some("keyword"),
-            span,
-            found: TokenFormat::Token("true"),
-            expected: expected_kind("identifier"),
-        }),
-        "false" => Err(Error::Unexpected {
-            label: Some("keyword"),
-            span,
-            found: TokenFormat::Token("false"),
-            expected: expected_kind("identifier"),
-        }),
-        "null" => Err(Error::Unexpected {
-            label: Some("keyword"),
-            span,
-            found: TokenFormat::Token("null"),
-            expected: expected_kind("identifier"),
-        }),
-        _ => Ok(s.into()),
-    })
+enum Step<N, O> {
+    /// Node is parsed, `None` if it's skipped by the builder
+    Done(Option<O>),
+    /// Children block of the node is opened
+    Children {
+        node: Pending<N>,
+        open: usize,
+        commented: bool,
+    },
 }
 
-fn ident<S: Span>() -> impl Parser<char, Box<str>, Error = Error<S>> {
-    choice((
-        // match -123 so `-` will not be treated as an ident by backtracking
-        number().map(Err),
-        bare_ident().map(Ok),
-        string().map(Ok),
-    ))
-    // when backtracking is not already possible,
-    // throw error for numbers (mapped to `Result::Err`)
-    .try_map(|res, span| {
-        res.map_err(|_| Error::Unexpected {
-            label: Some("unexpected number"),
-            span,
-            found: TokenFormat::Kind("number"),
-            expected: expected_kind("identifier"),
-        })
-    })
+/// Parser of the nodes
+///
+/// The same parser is used for every kind of the tree, see [`Builder`].
+pub(crate) struct Parser<'a, S: Span, B> {
+    pub(crate) scan: Scanner<'a, S>,
+    pub(crate) builder: B,
 }
 
-fn keyword<S: Span>() -> impl Parser<char, Literal, Error = Error<S>> {
-    choice((
-        just("null")
-            .map_err(|e: Error<S>| e.with_expected_token("null"))
-            .to(Literal::Null),
-        just("true")
-            .map_err(|e: Error<S>| e.with_expected_token("true"))
-            .to(Literal::Bool(true)),
-        just("false")
-            .map_err(|e: Error<S>| e.with_expected_token("false"))
-            .to(Literal::Bool(false)),
-    ))
+/// Builds the [`ast`](crate::ast) of the document
+pub(crate) struct Tree<'a, S: Span> {
+    text: &'a str,
+    tracker: S::Tracker,
 }
 
-fn digit<S: Span>(radix: u32) -> impl Parser<char, char, Error = Error<S>> {
-    filter(move |c: &char| c.is_digit(radix))
+/// Node of the [`Tree`] that is being parsed
+pub(crate) struct TreeNode<S: Span> {
+    span_start: S::Offset,
+    commented: bool,
+    /// Start of the children block that is being parsed
+    open: Option<S::Offset>,
+    node: Node<S>,
 }
 
-fn digits<S: Span>(radix: u32) -> impl Parser<char, Vec<char>, Error = Error<S>> {
-    filter(move |c: &char| c == &'_' || c.is_digit(radix)).repeated()
-}
+impl<'a, S: Span> Scanner<'a, S> {
+    pub(crate) fn new(text: &'a str, syntax: Syntax) -> Self {
+        Scanner {
+            text,
+            pos: 0,
+            syntax,
+            tracker: S::tracker(),
+            errors: Vec::new(),
+        }
+    }
 
-fn decimal_number<S: Span>() -> impl Parser<char, Literal, Error = Error<S>> {
-    just('-')
-        .or(just('+'))
-        .or_not()
-        .chain_c(digit(10))
-        .chain_c(digits(10))
-        .chain_c(
-            just('.')
-                .chain_c(digit(10))
-                .chain_c(digits(10))
-                .or_not()
-                .flatten(),
-        )
-        .chain_c(
-            just('e')
-                .or(just('E'))
-                .chain_c(just('-').or(just('+')).or_not())
-                .chain_c(digits(10))
-                .or_not()
-                .flatten(),
-        )
-        .map(|v| {
-            let is_decimal = v.iter().any(|c| matches!(c, '.' | 'e' | 'E'));
-            let s: String = v.into_iter().filter(|c| c != &'_').collect();
-            if is_decimal {
-                Literal::Decimal(Decimal(s.into()))
-            } else {
-                Literal::Int(Integer(Radix::Dec, s.into()))
+    pub(crate) fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.pos).copied()
+    }
+
+    pub(crate) fn peek_at(&self, n: usize) -> Option<u8> {
+        self.text.as_bytes().get(self.pos + n).copied()
+    }
+
+    pub(crate) fn peek_char(&self) -> Option<char> {
+        match self.peek() {
+            Some(b) if b < 0x80 => Some(b as char),
+            Some(_) => self.text[self.pos..].chars().next(),
+            None => None,
+        }
+    }
+
+    pub(crate) fn starts_with(&self, s: &str) -> bool {
+        self.text.as_bytes()[self.pos..].starts_with(s.as_bytes())
+    }
+
+    /// Advances past the characters matching the predicate
+    pub(crate) fn skip_while(&mut self, f: impl Fn(char) -> bool) {
+        while let Some(c) = self.peek_char() {
+            if !f(c) {
+                break;
             }
-        })
-}
+            self.pos += c.len_utf8();
+        }
+    }
+
+    /// Converts byte offset to the span offset
+    ///
+    /// Offsets should be requested in increasing order, otherwise tracking
+    /// line numbers restarts from the beginning of the text.
+    pub(crate) fn offset(&mut self, byte: usize) -> S::Offset {
+        self.tracker.offset(self.text, byte)
+    }
+
+    pub(crate) fn span(&mut self, start: usize, end: usize) -> S {
+        let start = self.offset(start);
+        S::new(start, self.offset(end))
+    }
+
+    /// Span of the character at the byte offset, empty at the end of input
+    fn char_span(&mut self, pos: usize) -> S {
+        let len = self.text[pos..].chars().next().map_or(0, char::len_utf8);
+        self.span(pos, pos + len)
+    }
 
-fn radix_number<S: Span>() -> impl Parser<char, Literal, Error = Error<S>> {
-    just('-')
-        .or(just('+'))
-        .or_not()
-        .then_ignore(just('0'))
-        .then(choice((
-            just('b').ignore_then(digit(2).chain(digits(2)).map(|s| (Radix::Bin, s))),
-            just('o').ignore_then(digit(8).chain(digits(8)).map(|s| (Radix::Oct, s))),
-            just('x').ignore_then(digit(16).chain(digits(16)).map(|s| (Radix::Hex, s))),
-        )))
-        .map(|(sign, (radix, value))| {
-            let mut s = String::with_capacity(value.len() + sign.map_or(0, |_| 1));
-            if let Some(c) = sign {
-                s.push(c);
+    /// Error for the unexpected character at the current position
+    pub(crate) fn unexpected(
+        &mut self,
+        label: Option<&'static str>,
+        expected: &[TokenFormat],
+    ) -> Error<S> {
+        Error::Unexpected {
+            label,
+            span: self.char_span(self.pos),
+            found: self.peek_char().into(),
+            expected: expected.iter().cloned().collect(),
+        }
+    }
+
+    /// Error for the token that is not closed until the end of input
+    pub(crate) fn unclosed(
+        &mut self,
+        label: &'static str,
+        open: Range<usize>,
+        opened: TokenFormat,
+        expected: TokenFormat,
+    ) -> Error<S> {
+        let end = self.text.len();
+        Error::Unclosed {
+            label,
+            opened_at: self.span(open.start, open.end),
+            opened,
+            expected_at: self.span(end, end),
+            expected,
+            found: TokenFormat::Eoi,
+        }
+    }
+
+    /// Returns length of the newline at the byte offset, zero if there is
+    /// no newline
+    pub(crate) fn newline_at(&self, pos: usize) -> usize {
+        let bytes = self.text.as_bytes();
+        match bytes.get(pos) {
+            Some(b'\r') if bytes.get(pos + 1) == Some(&b'\n') => 2,
+            Some(b'\r' | b'\n' | b'\x0C') => 1,
+            Some(b'\x0B') if self.syntax == Syntax::V2 => 1,
+            // next line
+            Some(0xC2) if bytes.get(pos + 1) == Some(&0x85) => 2,
+            // line and paragraph separators
+            Some(0xE2)
+                if bytes.get(pos + 1) == Some(&0x80)
+                    && matches!(bytes.get(pos + 2), Some(0xA8 | 0xA9)) =>
+            {
+                3
             }
-            s.extend(value.into_iter().filter(|&c| c != '_'));
-            Literal::Int(Integer(radix, s.into()))
-        })
-}
+            _ => 0,
+        }
+    }
 
-fn number<S: Span>() -> impl Parser<char, Literal, Error = Error<S>> {
-    radix_number().or(decimal_number())
-}
+    pub(crate) fn newline(&mut self) -> bool {
+        let len = self.newline_at(self.pos);
+        self.pos += len;
+        len > 0
+    }
 
-fn literal<S: Span>() -> impl Parser<char, Literal, Error = Error<S>> {
-    choice((string().map(Literal::String), keyword(), number()))
-}
+    pub(crate) fn is_ws_char(&self, c: char) -> bool {
+        match self.syntax {
+            Syntax::V1 => is_ws_char(c),
+            Syntax::V2 => grammar_v2::is_ws_char(c),
+        }
+    }
 
-fn type_name<S: Span>() -> impl Parser<char, TypeName, Error = Error<S>> {
-    ident()
-        .delimited_by(just('('), just(')'))
-        .map(TypeName::from_string)
-}
+    /// Skips a run of whitespace characters or a multi-line comment
+    pub(crate) fn ws(&mut self) -> Result<bool, Error<S>> {
+        let start = self.pos;
+        while let Some(c) = self.peek_char() {
+            if !self.is_ws_char(c) {
+                break;
+            }
+            self.pos += c.len_utf8();
+        }
+        if self.pos > start {
+            return Ok(true);
+        }
+        self.ml_comment()
+    }
 
-fn spanned<T, S, P>(p: P) -> impl Parser<char, Spanned<T, S>, Error = Error<S>>
-where
-    P: Parser<char, T, Error = Error<S>>,
-    S: Span,
-{
-    p.map_with_span(|value, span| Spanned { span, value })
-}
+    /// Skips a possibly nested `/* .. */` comment
+    pub(crate) fn ml_comment(&mut self) -> Result<bool, Error<S>> {
+        if !self.starts_with("/*") {
+            return Ok(false);
+        }
+        let bytes = self.text.as_bytes();
+        let start = self.pos;
+        // openings of the comments that are not closed yet
+        let mut open = vec![start];
+        let mut pos = start + 2;
+        loop {
+            match (bytes.get(pos), bytes.get(pos + 1)) {
+                (Some(b'/'), Some(b'*')) => {
+                    open.push(pos);
+                    pos += 2;
+                    continue;
+                }
+                (Some(b'*'), Some(b'/')) => {
+                    open.pop();
+                    pos += 2;
+                }
+                (Some(_), _) => {
+                    pos += 1;
+                    continue;
+                }
+                (None, _) => {
+                    // Nested comment that is never closed is not a comment.
+                    // Its opening is rescanned as a part of the parent,
+                    // which is only closed if `/*` is followed by `/`,
+                    // otherwise the parent can't be closed either.
+                    loop {
+                        let nested = open.pop().expect("comment is open");
+                        if open.is_empty() {
+                            self.pos = pos;
+                            return Err(self.unclosed(
+                                "comment",
+                                start..start + 2,
+                                "/*".into(),
+                                "*/".into(),
+                            ));
+                        }
+                        if bytes.get(nested + 2) == Some(&b'/') {
+                            open.pop();
+                            pos = nested + 3;
+                            break;
+                        }
+                    }
+                }
+            }
+            if open.is_empty() {
+                self.pos = pos;
+                return Ok(true);
+            }
+        }
+    }
 
-fn esc_line<S: Span>() -> impl Parser<char, (), Error = Error<S>> {
-    just('\\')
-        .ignore_then(ws().repeated())
-        .ignore_then(comment().or(newline()))
-}
+    /// Skips a `//` comment along with the newline
+    pub(crate) fn comment(&mut self) -> bool {
+        if !self.starts_with("//") {
+            return false;
+        }
+        self.pos += 2;
+        while self.pos < self.text.len() {
+            if self.newline() {
+                break;
+            }
+            self.pos += 1;
+        }
+        true
+    }
 
-fn node_space<S: Span>() -> impl Parser<char, (), Error = Error<S>> {
-    ws().or(esc_line())
-}
+    /// Skips newlines, whitespace and comments between the nodes
+    pub(crate) fn line_space(&mut self) -> Result<(), Error<S>> {
+        while self.newline() || self.ws()? || self.comment() {}
+        Ok(())
+    }
 
-fn node_terminator<S: Span>() -> impl Parser<char, (), Error = Error<S>> {
-    choice((newline(), comment(), just(';').ignored(), end()))
-}
+    /// Skips whitespace and escaped newlines within the node
+    ///
+    /// Returns whether anything was skipped.
+    pub(crate) fn node_space(&mut self) -> Result<bool, Error<S>> {
+        let start = self.pos;
+        loop {
+            if self.ws()? {
+                continue;
+            }
+            if self.peek() != Some(b'\\') {
+                break;
+            }
+            self.pos += 1;
+            while self.ws()? {}
+            let eoi = self.syntax == Syntax::V2 && self.pos == self.text.len();
+            if !(self.comment() || self.newline() || eoi) {
+                return Err(self.unexpected(
+                    None,
+                    &[TokenFormat::Token("//"), TokenFormat::Kind("newline")],
+                ));
+            }
+        }
+        Ok(self.pos > start)
+    }
 
-enum PropOrArg<S> {
-    Prop(SpannedName<S>, Value<S>),
-    Arg(Value<S>),
-    Ignore,
-}
+    /// Scans a single token of the text that the parser skips between the
+    /// values: whitespace, a newline, a comment, a line continuation or a
+    /// semicolon
+    ///
+    /// Unlike [`comment`](Self::comment) newline after the `//` comment is a
+    /// separate token. Returns `None` if there is no such token.
+    pub(crate) fn trivia(&mut self) -> Result<Option<TokenKind>, Error<S>> {
+        let kind = match self.peek() {
+            None => return Ok(None),
+            Some(b'\\') => {
+                self.pos += 1;
+                TokenKind::Backslash
+            }
+            Some(b';') => {
+                self.pos += 1;
+                TokenKind::Semicolon
+            }
+            Some(b'/') if self.starts_with("//") => {
+                while self.pos < self.text.len() && self.newline_at(self.pos) == 0 {
+                    self.pos += 1;
+                }
+                TokenKind::LineComment
+            }
+            Some(b'/') if self.ml_comment()? => TokenKind::BlockComment,
+            _ if self.newline() => TokenKind::Newline,
+            _ => {
+                let start = self.pos;
+                // byte order mark is kept as whitespace in both versions
+                while let Some(c) = self.peek_char() {
+                    if !(self.is_ws_char(c) || c == '\u{FEFF}') {
+                        break;
+                    }
+                    self.pos += c.len_utf8();
+                }
+                if self.pos == start {
+                    return Ok(None);
+                }
+                TokenKind::Whitespace
+            }
+        };
+        Ok(Some(kind))
+    }
 
-fn type_name_value<S: Span>() -> impl Parser<char, Value<S>, Error = Error<S>> {
-    spanned(type_name())
-        .then(spanned(literal()))
-        .map(|(type_name, literal)| Value {
-            type_name: Some(type_name),
-            literal,
-        })
-}
+    /// Scans a string, a number, a keyword or a bare identifier
+    ///
+    /// Returns `None` if no such token starts at the current position.
+    pub(crate) fn scalar(&mut self) -> Result<Option<Scalar<'a>>, Error<S>> {
+        match self.syntax {
+            Syntax::V1 => self.scalar_v1(),
+            Syntax::V2 => self.scalar_v2(),
+        }
+    }
 
-fn value<S: Span>() -> impl Parser<char, Value<S>, Error = Error<S>> {
-    type_name_value().or(spanned(literal()).map(|literal| Value {
-        type_name: None,
-        literal,
-    }))
+    fn scalar_v1(&mut self) -> Result<Option<Scalar<'a>>, Error<S>> {
+        let c = match self.peek_char() {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+        let digit_next = self.peek_at(1).is_some_and(|b| b.is_ascii_digit());
+        match c {
+            '"' => self.escaped_string().map(|s| Some(Scalar::String(s))),
+            'r' if self.raw_string_starts() => self.raw_string().map(|s| Some(Scalar::String(s))),
+            '0'..='9' => Ok(self.number().map(Scalar::Number)),
+            '-' | '+' if digit_next => Ok(self.number().map(Scalar::Number)),
+            _ => Ok(self.bare_ident().map(|s| match s {
+                "true" => Scalar::Keyword("true", Literal::Bool(true)),
+                "false" => Scalar::Keyword("false", Literal::Bool(false)),
+                "null" => Scalar::Keyword("null", Literal::Null),
+                _ => Scalar::Ident(Cow::Borrowed(s)),
+            })),
+        }
+    }
+
+    fn bare_ident(&mut self) -> Option<&'a str> {
+        let start = self.pos;
+        let c = self.peek_char()?;
+        if c == '-' || c == '+' {
+            self.pos += 1;
+            match self.peek_char() {
+                Some(c) if is_id_sans_dig(c) => {
+                    self.pos += c.len_utf8();
+                    self.skip_while(is_id_char);
+                }
+                _ => {}
+            }
+        } else if is_id_sans_sign_dig(c) {
+            self.pos += c.len_utf8();
+            self.skip_while(is_id_char);
+        } else {
+            return None;
+        }
+        Some(&self.text[start..self.pos])
+    }
+
+    fn raw_string_starts(&self) -> bool {
+        let bytes = &self.text.as_bytes()[self.pos + 1..];
+        let hashes = bytes.iter().take_while(|&&b| b == b'#').count();
+        bytes.get(hashes) == Some(&b'"')
+    }
+
+    fn raw_string(&mut self) -> Result<Cow<'a, str>, Error<S>> {
+        let start = self.pos;
+        let hashes = self.text.as_bytes()[start + 1..]
+            .iter()
+            .take_while(|&&b| b == b'#')
+            .count();
+        let content = start + hashes + 2;
+        self.pos = content;
+        while let Some(quote) = self.text[self.pos..].find('"') {
+            let end = self.pos + quote;
+            self.pos = end + 1;
+            let closing = self.text.as_bytes()[self.pos..]
+                .iter()
+                .take(hashes)
+                .take_while(|&&b| b == b'#')
+                .count();
+            if closing == hashes {
+                self.pos += hashes;
+                return Ok(Cow::Borrowed(&self.text[content..end]));
+            }
+        }
+        self.pos = self.text.len();
+        Err(self.unclosed(
+            "raw string",
+            start..content,
+            TokenFormat::OpenRaw(hashes),
+            TokenFormat::CloseRaw(hashes),
+        ))
+    }
+
+    /// Scans a string in double quotes with escapes
+    ///
+    /// The value is only copied if there are escapes in the string.
+    pub(crate) fn escaped_string(&mut self) -> Result<Cow<'a, str>, Error<S>> {
+        let start = self.pos;
+        let v2 = self.syntax == Syntax::V2;
+        self.pos += 1;
+        let content = self.pos;
+        let mut value: Option<String> = None;
+        loop {
+            let rest = &self.text.as_bytes()[self.pos..];
+            // KDL 2.0 disallows some control and non-ASCII chars in strings
+            let plain = rest
+                .iter()
+                .position(|&b| {
+                    b == b'"' || b == b'\\' || (v2 && (b < 0x20 || b == 0x7F || b >= 0x80))
+                })
+                .unwrap_or(rest.len());
+            if let Some(value) = &mut value {
+                value.push_str(&self.text[self.pos..self.pos + plain]);
+            }
+            self.pos += plain;
+            match self.peek_char() {
+                None => {
+                    return Err(self.unclosed("string", start..start + 1, '"'.into(), '"'.into()))
+                }
+                Some('"') => {
+                    let end = self.pos;
+                    self.pos += 1;
+                    return Ok(match value {
+                        Some(value) => Cow::Owned(value),
+                        None => Cow::Borrowed(&self.text[content..end]),
+                    });
+                }
+                Some('\\') => {
+                    let text = self.text;
+                    let pos = self.pos;
+                    let value = value.get_or_insert_with(|| text[content..pos].to_string());
+                    self.pos += 1;
+                    self.escape(value, start)?;
+                }
+                Some(c)
+                    if v2
+                        && c != '\t'
+                        && (grammar_v2::is_newline_char(c) || grammar_v2::is_disallowed(c)) =>
+                {
+                    return Err(self.unexpected(None, &['"'.into()]));
+                }
+                Some(c) => {
+                    if let Some(value) = &mut value {
+                        value.push(c);
+                    }
+                    self.pos += c.len_utf8();
+                }
+            }
+        }
+    }
+
+    /// Unescapes a character after the backslash in the string
+    ///
+    /// Errors in the unicode escapes are recovered from.
+    fn escape(&mut self, value: &mut String, string_start: usize) -> Result<(), Error<S>> {
+        let v2 = self.syntax == Syntax::V2;
+        let c = match self.peek_char() {
+            Some(c) => c,
+            None => {
+                return Err(self.unclosed(
+                    "string",
+                    string_start..string_start + 1,
+                    '"'.into(),
+                    '"'.into(),
+                ))
+            }
+        };
+        let unescaped = match c {
+            '"' | '\\' => c,
+            '/' if !v2 => c,
+            'b' => '\u{0008}',
+            'f' => '\u{000C}',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            's' if v2 => ' ',
+            'u' => {
+                self.pos += 1;
+                return self.unicode_escape(value, string_start);
+            }
+            c if v2 && (grammar_v2::is_ws_char(c) || grammar_v2::is_newline_char(c)) => {
+                self.skip_while(|c| grammar_v2::is_ws_char(c) || grammar_v2::is_newline_char(c));
+                return Ok(());
+            }
+            c => {
+                let escapes = if v2 { "\"\\bfnrstu" } else { "\"\\/bfnrtu" };
+                return Err(Error::Unexpected {
+                    label: Some("invalid escape char"),
+                    span: self.char_span(self.pos),
+                    found: c.into(),
+                    expected: escapes.chars().map(|c| c.into()).collect(),
+                });
+            }
+        };
+        self.pos += 1;
+        value.push(unescaped);
+        Ok(())
+    }
+
+    fn unicode_escape(&mut self, value: &mut String, string_start: usize) -> Result<(), Error<S>> {
+        let open = self.pos;
+        if self.peek() == Some(b'{') {
+            self.pos += 1;
+        } else {
+            let err = self.unexpected(None, &['{'.into()]);
+            return self.skip_invalid_escape(err, string_start);
+        }
+        let digits = self.pos;
+        while self.pos - digits < 6 && self.peek().is_some_and(|b| b.is_ascii_hexdigit()) {
+            self.pos += 1;
+        }
+        let count = self.pos - digits;
+        if count == 0 || self.peek() != Some(b'}') {
+            let err = match count {
+                0 => self.unexpected(
+                    Some("unexpected character"),
+                    &[TokenFormat::Kind("hexadecimal digit")],
+                ),
+                1..=5 => {
+                    self.unexpected(None, &['}'.into(), TokenFormat::Kind("hexadecimal digit")])
+                }
+                _ => self.unexpected(None, &['}'.into()]),
+            };
+            return self.skip_invalid_escape(err, string_start);
+        }
+        let hex = &self.text[digits..self.pos];
+        self.pos += 1;
+        let c = u32::from_str_radix(hex, 16)
+            .map_err(|e| e.to_string())
+            .and_then(|n| char::try_from(n).map_err(|e| e.to_string()));
+        match c {
+            Ok(c) => value.push(c),
+            Err(message) => {
+                let span = self.span(open, self.pos);
+                self.errors.push(Error::Message {
+                    label: Some("invalid character code"),
+                    span,
+                    message,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Records the error in the unicode escape and skips the rest of it
+    fn skip_invalid_escape(&mut self, err: Error<S>, string_start: usize) -> Result<(), Error<S>> {
+        if self.peek().is_none() {
+            return Err(self.unclosed(
+                "string",
+                string_start..string_start + 1,
+                '"'.into(),
+                '"'.into(),
+            ));
+        }
+        self.errors.push(err);
+        while let Some(b) = self.peek() {
+            if matches!(b, b'}' | b'"' | b'\\') {
+                break;
+            }
+            self.pos += 1;
+        }
+        Ok(())
+    }
+
+    /// Scans a decimal number or a number with a radix prefix
+    ///
+    /// Returns `None` if there is no number at the current position. Digits
+    /// are borrowed if the number has no underscores and no sign before the
+    /// radix prefix.
+    pub(crate) fn number(&mut self) -> Option<Literal<'a>> {
+        fn skip_digits(bytes: &[u8], mut pos: usize, radix: u32) -> usize {
+            while bytes
+                .get(pos)
+                .is_some_and(|&b| b == b'_' || (b as char).is_digit(radix))
+            {
+                pos += 1;
+            }
+            pos
+        }
+        fn is_digit(b: Option<&u8>, radix: u32) -> bool {
+            b.is_some_and(|&b| (b as char).is_digit(radix))
+        }
+
+        let bytes = self.text.as_bytes();
+        let start = self.pos;
+        let sign = matches!(bytes.get(start), Some(b'-' | b'+'));
+        let mut pos = if sign { start + 1 } else { start };
+        if bytes.get(pos) == Some(&b'0') {
+            let radix = match bytes.get(pos + 1) {
+                Some(b'b') => Some((Radix::Bin, 2)),
+                Some(b'o') => Some((Radix::Oct, 8)),
+                Some(b'x') => Some((Radix::Hex, 16)),
+                _ => None,
+            };
+            if let Some((radix, base)) = radix {
+                if is_digit(bytes.get(pos + 2), base) {
+                    let digits = pos + 2;
+                    pos = skip_digits(bytes, digits, base);
+                    let text = &self.text[digits..pos];
+                    let value = if sign || text.contains('_') {
+                        let mut value = String::with_capacity(pos - digits + 1);
+                        if sign {
+                            value.push(bytes[start] as char);
+                        }
+                        value.extend(text.chars().filter(|&c| c != '_'));
+                        Cow::Owned(value)
+                    } else {
+                        Cow::Borrowed(text)
+                    };
+                    self.pos = pos;
+                    return Some(Literal::Int(Integer(radix, value)));
+                }
+            }
+        }
+        if !is_digit(bytes.get(pos), 10) {
+            return None;
+        }
+        pos = skip_digits(bytes, pos, 10);
+        let mut is_decimal = false;
+        if bytes.get(pos) == Some(&b'.') && is_digit(bytes.get(pos + 1), 10) {
+            is_decimal = true;
+            pos = skip_digits(bytes, pos + 1, 10);
+        }
+        if matches!(bytes.get(pos), Some(b'e' | b'E')) {
+            let mut exp = pos + 1;
+            if matches!(bytes.get(exp), Some(b'-' | b'+')) {
+                exp += 1;
+            }
+            // KDL 1.0 allows exponent without digits
+            if self.syntax == Syntax::V1 || is_digit(bytes.get(exp), 10) {
+                is_decimal = true;
+                pos = skip_digits(bytes, exp, 10);
+            }
+        }
+        let text = &self.text[start..pos];
+        let value = if text.contains('_') {
+            Cow::Owned(text.chars().filter(|&c| c != '_').collect())
+        } else {
+            Cow::Borrowed(text)
+        };
+        self.pos = pos;
+        if is_decimal {
+            Some(Literal::Decimal(Decimal(value)))
+        } else {
+            Some(Literal::Int(Integer(Radix::Dec, value)))
+        }
+    }
 }
 
-fn prop_or_arg_inner<S: Span>() -> impl Parser<char, PropOrArg<S>, Error = Error<S>> {
-    use PropOrArg::*;
-    choice((
-        spanned(literal())
-            .then(just('=').ignore_then(value()).or_not())
-            .try_map(|(name, value), _| {
-                let name_span = name.span;
-                match (name.value, value) {
-                    (Literal::String(s), Some(value)) => {
-                        let name = Spanned {
-                            span: name_span,
-                            value: s,
-                        };
-                        Ok(Prop(name, value))
+impl<'a, S: Span, B: Builder<'a>> Parser<'a, S, B> {
+    pub(crate) fn new(text: &'a str, syntax: Syntax, builder: B) -> Self {
+        let mut scan = Scanner::new(text, syntax);
+        // byte order mark is allowed at the start of KDL 2.0 document
+        if syntax == Syntax::V2 && text.starts_with('\u{FEFF}') {
+            scan.pos = '\u{FEFF}'.len_utf8();
+        }
+        Parser { scan, builder }
+    }
+
+    fn v2(&self) -> bool {
+        self.scan.syntax == Syntax::V2
+    }
+
+    pub(crate) fn document(&mut self) -> Result<Vec<B::Output>, Vec<Error<S>>> {
+        let result = match self.nodes(false) {
+            Ok(nodes) if self.scan.peek().is_some() => {
+                self.builder.discard(nodes);
+                Err(self.unmatched_brace())
+            }
+            result => result,
+        };
+        let mut errors = mem::take(&mut self.scan.errors);
+        match result {
+            Ok(nodes) if errors.is_empty() => Ok(nodes),
+            Ok(nodes) => {
+                self.builder.discard(nodes);
+                Err(errors)
+            }
+            Err(e) => {
+                errors.push(e);
+                Err(errors)
+            }
+        }
+    }
+
+    /// Parses the document recovering from errors in the nodes
+    pub(crate) fn document_recovery(&mut self) -> (Option<Vec<B::Output>>, Vec<Error<S>>) {
+        let mut nodes = Vec::new();
+        loop {
+            match self.nodes(true) {
+                Ok(level) => nodes.extend(level),
+                Err(e) => {
+                    self.builder.discard(nodes);
+                    self.scan.errors.push(e);
+                    return (None, mem::take(&mut self.scan.errors));
+                }
+            }
+            if self.scan.peek().is_none() {
+                break;
+            }
+            let err = self.unmatched_brace();
+            self.scan.errors.push(err);
+            self.scan.pos += 1;
+        }
+        (Some(nodes), mem::take(&mut self.scan.errors))
+    }
+
+    fn unmatched_brace(&mut self) -> Error<S> {
+        Error::Message {
+            label: Some("unmatched brace"),
+            span: self.scan.char_span(self.scan.pos),
+            message: "found `}` without matching `{`".into(),
+        }
+    }
+
+    /// Parses nodes up to the end of input or the unmatched closing brace
+    ///
+    /// In recovery mode nodes that fail to parse are skipped and errors are
+    /// recorded instead of being returned.
+    pub(crate) fn nodes(&mut self, recover: bool) -> Result<Vec<B::Output>, Error<S>> {
+        let mut stack = Vec::new();
+        let mut nodes = Vec::new();
+        match self.parse_nodes(recover, &mut stack, &mut nodes) {
+            Ok(()) => Ok(nodes),
+            Err(e) => {
+                self.builder.discard(nodes);
+                for frame in stack {
+                    self.builder.discard(frame.siblings);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    fn parse_nodes(
+        &mut self,
+        recover: bool,
+        stack: &mut Vec<Frame<B::Node, B::Output>>,
+        nodes: &mut Vec<B::Output>,
+    ) -> Result<(), Error<S>> {
+        loop {
+            self.scan.line_space()?;
+            let start;
+            let step = match self.scan.peek() {
+                None | Some(b'}') if stack.is_empty() => return Ok(()),
+                None if recover => {
+                    // the block is closed at the end of input, so the nodes
+                    // parsed so far are kept
+                    let frame = stack.pop().expect("stack is not empty");
+                    let err = self.scan.unclosed(
+                        "curly braces",
+                        frame.open..frame.open + 1,
+                        '{'.into(),
+                        '}'.into(),
+                    );
+                    self.scan.errors.push(err);
+                    let children = mem::replace(nodes, frame.siblings);
+                    start = frame.node.start;
+                    let mut node = frame.node;
+                    if !frame.commented {
+                        node.blocks += 1;
+                    }
+                    // the span of the block ends at the end of input
+                    self.builder
+                        .close(&mut node.node, children, self.scan.pos - 1);
+                    self.node_rest(node, true)
+                }
+                None => {
+                    let frame = stack.last().expect("stack is not empty");
+                    return Err(self.scan.unclosed(
+                        "curly braces",
+                        frame.open..frame.open + 1,
+                        '{'.into(),
+                        '}'.into(),
+                    ));
+                }
+                Some(b'}') => {
+                    let frame = stack.pop().expect("stack is not empty");
+                    let children = mem::replace(nodes, frame.siblings);
+                    start = frame.node.start;
+                    let mut node = frame.node;
+                    if !frame.commented {
+                        node.blocks += 1;
                     }
-                    (Literal::Bool(_) | Literal::Null, Some(_)) => Err(Error::Unexpected {
+                    self.builder.close(&mut node.node, children, self.scan.pos);
+                    self.scan.pos += 1;
+                    self.node_rest(node, true)
+                }
+                Some(_) => {
+                    start = self.scan.pos;
+                    self.node()
+                }
+            };
+            match step {
+                Ok(Step::Done(Some(node))) => nodes.push(node),
+                Ok(Step::Done(None)) => {}
+                Ok(Step::Children {
+                    node,
+                    open,
+                    commented,
+                }) => stack.push(Frame {
+                    node,
+                    open,
+                    commented,
+                    siblings: mem::take(nodes),
+                }),
+                Err(e) if recover => {
+                    self.scan.errors.push(e);
+                    self.scan.pos = start;
+                    self.skip_node();
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Skips the rest of the node that failed to parse
+    ///
+    /// This is the recovery strategy for the nodes: the node is skipped up to
+    /// the node terminator, along with the children in braces `{..}`. An
+    /// unclosed brace skips the rest of the input.
+    fn skip_node(&mut self) {
+        let bytes = self.scan.text.as_bytes();
+        let mut depth = 0;
+        while let Some(&b) = bytes.get(self.scan.pos) {
+            match b {
+                b'{' => depth += 1,
+                b'}' if depth == 0 => return,
+                b'}' => depth -= 1,
+                b'\n' | b';' if depth == 0 => {
+                    self.scan.pos += 1;
+                    return;
+                }
+                _ => {}
+            }
+            self.scan.pos += 1;
+        }
+    }
+
+    /// Skips `/-` and the following whitespace
+    ///
+    /// Returns the byte offset of the `/-` if there is one.
+    fn slashdash(&mut self) -> Result<Option<usize>, Error<S>> {
+        if !self.scan.starts_with("/-") {
+            return Ok(None);
+        }
+        let start = self.scan.pos;
+        self.scan.pos += 2;
+        if self.v2() {
+            self.scan.line_space()?;
+        } else {
+            self.scan.node_space()?;
+        }
+        Ok(Some(start))
+    }
+
+    fn node(&mut self) -> Result<Step<B::Node, B::Output>, Error<S>> {
+        let start = self.scan.pos;
+        let slashdash = self.slashdash()?;
+        let head = self.scan.pos;
+        let type_name = if self.scan.peek() == Some(b'(') {
+            let type_name = self.type_name()?;
+            if self.v2() {
+                self.scan.node_space()?;
+            }
+            Some(type_name)
+        } else {
+            None
+        };
+        let name = self.ident()?;
+        let node = self.builder.node(ParsedHead {
+            slashdash,
+            type_name,
+            name,
+        });
+        self.node_rest(
+            Pending {
+                start,
+                head,
+                blocks: 0,
+                node,
+            },
+            false,
+        )
+    }
+
+    /// Parses entries and children of the node up to the terminator
+    ///
+    /// `after_block` is set when continuing after the children block.
+    fn node_rest(
+        &mut self,
+        mut node: Pending<B::Node>,
+        after_block: bool,
+    ) -> Result<Step<B::Node, B::Output>, Error<S>> {
+        // only KDL 2.0 allows multiple (commented out) children blocks
+        let block_allowed = self.v2() || !after_block;
+        loop {
+            let space = self.scan.node_space()?;
+            let slashdash = self.slashdash()?;
+            if self.scan.peek() == Some(b'{') && block_allowed {
+                let open = self.scan.pos;
+                self.builder.open(&mut node.node, slashdash, open);
+                self.scan.pos += 1;
+                return Ok(Step::Children {
+                    node,
+                    open,
+                    commented: slashdash.is_some(),
+                });
+            }
+            if slashdash.is_some() {
+                if !space || after_block {
+                    return Err(self.scan.unexpected(None, &['{'.into()]));
+                }
+                let entry = self.entry(slashdash)?;
+                self.builder.entry(&mut node.node, entry);
+                continue;
+            }
+            let terminator = self.scan.pos;
+            if self.terminator() {
+                return self.finish(node, terminator);
+            }
+            if !space || after_block {
+                let mut expected = vec![';'.into(), TokenFormat::Kind("newline"), TokenFormat::Eoi];
+                if !space {
+                    expected.push(TokenFormat::Kind("whitespace"));
+                }
+                if block_allowed {
+                    expected.push('{'.into());
+                }
+                return Err(self.scan.unexpected(None, &expected));
+            }
+            let entry = self.entry(None)?;
+            self.builder.entry(&mut node.node, entry);
+        }
+    }
+
+    /// Skips the node terminator, returns `false` if there is none
+    fn terminator(&mut self) -> bool {
+        match self.scan.peek() {
+            None => true,
+            Some(b';') => {
+                self.scan.pos += 1;
+                true
+            }
+            // the last node in the block doesn't need a terminator
+            Some(b'}') => self.v2(),
+            _ => self.scan.comment() || self.scan.newline(),
+        }
+    }
+
+    fn finish(
+        &mut self,
+        node: Pending<B::Node>,
+        terminator: usize,
+    ) -> Result<Step<B::Node, B::Output>, Error<S>> {
+        if node.blocks > 1 {
+            return Err(Error::Message {
+                label: Some("second children block"),
+                span: self.scan.span(node.head, self.scan.pos),
+                message: "node can only have one children block".into(),
+            });
+        }
+        let node = self.builder.finish(node.node, terminator..self.scan.pos);
+        Ok(Step::Done(node))
+    }
+
+    pub(crate) fn type_name(&mut self) -> Result<ParsedType<'a>, Error<S>> {
+        let open = self.scan.pos;
+        if self.scan.peek() != Some(b'(') {
+            return Err(self.scan.unexpected(None, &['('.into()]));
+        }
+        self.scan.pos += 1;
+        if self.v2() {
+            self.scan.node_space()?;
+        }
+        let name = self.ident()?;
+        if self.v2() {
+            self.scan.node_space()?;
+        }
+        if self.scan.peek() != Some(b')') {
+            return Err(self.scan.unexpected(None, &[')'.into()]));
+        }
+        let close = self.scan.pos;
+        self.scan.pos += 1;
+        Ok(ParsedType { open, name, close })
+    }
+
+    pub(crate) fn ident(&mut self) -> Result<Parsed<Cow<'a, str>>, Error<S>> {
+        let start = self.scan.pos;
+        let scalar = self.scan.scalar()?;
+        let range = start..self.scan.pos;
+        match scalar {
+            Some(Scalar::Ident(value) | Scalar::String(value)) => Ok(Parsed { range, value }),
+            Some(Scalar::Number(_)) => Err(Error::Unexpected {
+                label: Some("unexpected number"),
+                span: self.scan.span(range.start, range.end),
+                found: TokenFormat::Kind("number"),
+                expected: expected_kind("identifier"),
+            }),
+            Some(Scalar::Keyword(name, _)) => Err(Error::Unexpected {
+                label: Some("keyword"),
+                span: self.scan.span(range.start, range.end),
+                found: TokenFormat::Token(name),
+                expected: expected_kind("identifier"),
+            }),
+            None => {
+                self.scan.pos = start;
+                Err(self.scan.unexpected(
+                    None,
+                    &[TokenFormat::Kind("identifier"), TokenFormat::Kind("string")],
+                ))
+            }
+        }
+    }
+
+    pub(crate) fn literal(&mut self) -> Result<Parsed<Literal<'a>>, Error<S>> {
+        let start = self.scan.pos;
+        let literal = match self.scan.scalar()? {
+            Some(Scalar::String(s)) => Literal::String(s),
+            Some(Scalar::Number(lit) | Scalar::Keyword(_, lit)) => lit,
+            Some(Scalar::Ident(_)) | None => {
+                self.scan.pos = start;
+                let expected: &[TokenFormat] = if self.v2() {
+                    &[
+                        TokenFormat::Kind("string"),
+                        TokenFormat::Kind("number"),
+                        TokenFormat::Kind("keyword"),
+                    ]
+                } else {
+                    &[
+                        TokenFormat::Kind("string"),
+                        TokenFormat::Kind("number"),
+                        TokenFormat::Token("true"),
+                        TokenFormat::Token("false"),
+                        TokenFormat::Token("null"),
+                    ]
+                };
+                return Err(self.scan.unexpected(None, expected));
+            }
+        };
+        Ok(Parsed {
+            range: start..self.scan.pos,
+            value: literal,
+        })
+    }
+
+    fn value(&mut self) -> Result<ParsedValue<'a>, Error<S>> {
+        let type_name = if self.scan.peek() == Some(b'(') {
+            let type_name = self.type_name()?;
+            if self.v2() {
+                self.scan.node_space()?;
+            }
+            Some(type_name)
+        } else {
+            None
+        };
+        Ok(ParsedValue {
+            type_name,
+            literal: self.literal()?,
+        })
+    }
+
+    fn entry(&mut self, slashdash: Option<usize>) -> Result<ParsedEntry<'a>, Error<S>> {
+        if self.scan.peek() == Some(b'(') {
+            return Ok(ParsedEntry {
+                slashdash,
+                name: None,
+                value: self.value()?,
+            });
+        }
+        let start = self.scan.pos;
+        let scalar = match self.scan.scalar()? {
+            Some(scalar) => scalar,
+            None => {
+                return Err(self.scan.unexpected(
+                    None,
+                    &[
+                        ';'.into(),
+                        '{'.into(),
+                        TokenFormat::Kind("newline"),
+                        TokenFormat::Kind("value"),
+                        TokenFormat::Eoi,
+                    ],
+                ))
+            }
+        };
+        let range = start..self.scan.pos;
+        let is_prop = if self.v2() {
+            let value_start = self.scan.pos;
+            self.scan.node_space()?;
+            let is_prop = self.scan.peek() == Some(b'=');
+            if !is_prop {
+                self.scan.pos = value_start;
+            }
+            is_prop
+        } else {
+            self.scan.peek() == Some(b'=')
+        };
+        if is_prop {
+            let name = match scalar {
+                Scalar::Ident(value) | Scalar::String(value) => Parsed { range, value },
+                Scalar::Keyword(..) => {
+                    return Err(Error::Unexpected {
                         label: Some("unexpected keyword"),
-                        span: name_span,
+                        span: self.scan.span(range.start, range.end),
                         found: TokenFormat::Kind("keyword"),
                         expected: [TokenFormat::Kind("identifier"), TokenFormat::Kind("string")]
                             .into_iter()
                             .collect(),
-                    }),
-                    (Literal::Int(_) | Literal::Decimal(_), Some(_)) => {
-                        Err(Error::MessageWithHelp {
-                            label: Some("unexpected number"),
-                            span: name_span,
-                            message: "numbers cannot be used as property names".into(),
-                            help: "consider enclosing in double quotes \"..\"",
-                        })
-                    }
-                    (value, None) => Ok(Arg(Value {
-                        type_name: None,
-                        literal: Spanned {
-                            span: name_span,
-                            value,
-                        },
-                    })),
+                    })
                 }
-            }),
-        spanned(bare_ident())
-            .then(just('=').ignore_then(value()).or_not())
-            .validate(|(name, value), span, emit| {
-                if value.is_none() {
-                    emit(Error::MessageWithHelp {
-                        label: Some("unexpected identifier"),
-                        span,
-                        message: "identifiers cannot be used as arguments".into(),
+                Scalar::Number(_) => {
+                    return Err(Error::MessageWithHelp {
+                        label: Some("unexpected number"),
+                        span: self.scan.span(range.start, range.end),
+                        message: "numbers cannot be used as property names".into(),
                         help: "consider enclosing in double quotes \"..\"",
-                    });
-                }
-                (name, value)
-            })
-            .map(|(name, value)| {
-                if let Some(value) = value {
-                    Prop(name, value)
-                } else {
-                    // this is invalid, but we already emitted error
-                    // in validate() above, so doing a sane fallback
-                    Arg(Value {
-                        type_name: None,
-                        literal: name.map(Literal::String),
                     })
                 }
-            }),
-        type_name_value().map(Arg),
-    ))
+            };
+            let equals = self.scan.pos;
+            self.scan.pos += 1;
+            if self.v2() {
+                self.scan.node_space()?;
+            }
+            return Ok(ParsedEntry {
+                slashdash,
+                name: Some((name, equals)),
+                value: self.value()?,
+            });
+        }
+        let literal = match scalar {
+            Scalar::Ident(s) => {
+                // this is invalid, but recording the error is enough to
+                // do a sane fallback
+                let span = self.scan.span(range.start, range.end);
+                self.scan.errors.push(Error::MessageWithHelp {
+                    label: Some("unexpected identifier"),
+                    span,
+                    message: "identifiers cannot be used as arguments".into(),
+                    help: "consider enclosing in double quotes \"..\"",
+                });
+                Literal::String(s)
+            }
+            Scalar::String(s) => Literal::String(s),
+            Scalar::Number(lit) | Scalar::Keyword(_, lit) => lit,
+        };
+        Ok(ParsedEntry {
+            slashdash,
+            name: None,
+            value: ParsedValue {
+                type_name: None,
+                literal: Parsed {
+                    range,
+                    value: literal,
+                },
+            },
+        })
+    }
 }
 
-fn prop_or_arg<S: Span>() -> impl Parser<char, PropOrArg<S>, Error = Error<S>> {
-    begin_comment('-')
-        .ignore_then(node_space().repeated())
-        .ignore_then(prop_or_arg_inner())
-        .map(|_| PropOrArg::Ignore)
-        .or(prop_or_arg_inner())
-}
+impl<'a, S: Span> Tree<'a, S> {
+    pub(crate) fn new(text: &'a str) -> Self {
+        Tree {
+            text,
+            tracker: S::tracker(),
+        }
+    }
 
-fn line_space<S: Span>() -> impl Parser<char, (), Error = Error<S>> {
-    newline().or(ws()).or(comment())
+    fn offset(&mut self, byte: usize) -> S::Offset {
+        self.tracker.offset(self.text, byte)
+    }
+
+    fn span(&mut self, range: Range<usize>) -> S {
+        let start = self.offset(range.start);
+        S::new(start, self.offset(range.end))
+    }
+
+    fn name(&mut self, name: Parsed<Cow<'a, str>>) -> SpannedName<S> {
+        Spanned {
+            span: self.span(name.range),
+            value: name.value.into(),
+        }
+    }
+
+    fn type_name(&mut self, type_name: ParsedType<'a>) -> Spanned<TypeName, S> {
+        Spanned {
+            span: self.span(type_name.open..type_name.close + 1),
+            value: TypeName::from_string(type_name.name.value.into()),
+        }
+    }
+
+    fn value(&mut self, value: ParsedValue<'a>) -> Value<S> {
+        Value {
+            type_name: value.type_name.map(|t| self.type_name(t)),
+            literal: Spanned {
+                span: self.span(value.literal.range),
+                value: value.literal.value.into_owned(),
+            },
+        }
+    }
 }
 
-fn nodes<S: Span>() -> impl Parser<char, Vec<SpannedNode<S>>, Error = Error<S>> {
-    use PropOrArg::*;
-    recursive(|nodes: chumsky::recursive::Recursive<char, _, Error<S>>| {
-        let braced_nodes =
-            just('{').ignore_then(nodes.then_ignore(just('}')).map_err_with_span(|e, span| {
-                if matches!(
-                    &e,
-                    Error::Unexpected {
-                        found: TokenFormat::Eoi,
-                        ..
-                    }
-                ) {
-                    e.merge(Error::Unclosed {
-                        label: "curly braces",
-                        // we know it's `{` at the start of the span
-                        opened_at: span.before_start(1),
-                        opened: '{'.into(),
-                        expected_at: span.at_end(),
-                        expected: '}'.into(),
-                        found: None.into(),
-                    })
-                } else {
-                    e
-                }
-            }));
-
-        let node = spanned(type_name())
-            .or_not()
-            .then(spanned(ident()))
-            .then(
-                node_space()
-                    .repeated()
-                    .at_least(1)
-                    .ignore_then(prop_or_arg())
-                    .repeated(),
-            )
-            .then(
-                node_space()
-                    .repeated()
-                    .ignore_then(
-                        begin_comment('-')
-                            .then_ignore(node_space().repeated())
-                            .or_not(),
-                    )
-                    .then(spanned(braced_nodes))
-                    .or_not(),
-            )
-            .then_ignore(node_space().repeated().then(node_terminator()))
-            .map(|(((type_name, node_name), line_items), opt_children)| {
-                let mut node = Node {
-                    type_name,
-                    node_name,
-                    properties: BTreeMap::new(),
-                    arguments: Vec::new(),
-                    children: match opt_children {
-                        Some((Some(_comment), _)) => None,
-                        Some((None, children)) => Some(children),
-                        None => None,
-                    },
-                };
-                for item in line_items {
-                    match item {
-                        Prop(name, value) => {
-                            node.properties.insert(name, value);
-                        }
-                        Arg(value) => {
-                            node.arguments.push(value);
-                        }
-                        Ignore => {}
-                    }
-                }
-                node
+impl<'a, S: Span> Builder<'a> for Tree<'a, S> {
+    type Node = TreeNode<S>;
+    type Output = SpannedNode<S>;
+
+    fn node(&mut self, head: ParsedHead<'a>) -> TreeNode<S> {
+        let start = head
+            .type_name
+            .as_ref()
+            .map_or(head.name.range.start, |t| t.open);
+        let span_start = self.offset(start);
+        let type_name = head.type_name.map(|t| self.type_name(t));
+        let node_name = self.name(head.name);
+        TreeNode {
+            span_start,
+            commented: head.slashdash.is_some(),
+            open: None,
+            node: Node {
+                type_name,
+                node_name,
+                arguments: Vec::new(),
+                properties: BTreeMap::new(),
+                children: None,
+            },
+        }
+    }
+
+    fn entry(&mut self, node: &mut TreeNode<S>, entry: ParsedEntry<'a>) {
+        if entry.slashdash.is_some() {
+            return;
+        }
+        match entry.name {
+            Some((name, _)) => {
+                let name = self.name(name);
+                let value = self.value(entry.value);
+                node.node.properties.insert(name, value);
+            }
+            None => {
+                let value = self.value(entry.value);
+                node.node.arguments.push(value);
+            }
+        }
+    }
+
+    fn open(&mut self, node: &mut TreeNode<S>, slashdash: Option<usize>, open: usize) {
+        if slashdash.is_none() && node.node.children.is_none() {
+            node.open = Some(self.offset(open));
+        }
+    }
+
+    fn close(&mut self, node: &mut TreeNode<S>, children: Vec<SpannedNode<S>>, close: usize) {
+        if let Some(open) = node.open.take() {
+            node.node.children = Some(Spanned {
+                span: S::new(open, self.offset(close + 1)),
+                value: children,
             });
+        }
+    }
+
+    fn finish(&mut self, node: TreeNode<S>, terminator: Range<usize>) -> Option<SpannedNode<S>> {
+        if node.commented {
+            return None;
+        }
+        Some(Spanned {
+            span: S::new(node.span_start, self.offset(terminator.end)),
+            value: node.node,
+        })
+    }
+
+    fn discard(&mut self, nodes: Vec<SpannedNode<S>>) {
+        ast::drop_nodes(nodes);
+    }
+}
 
-        begin_comment('-')
-            .then_ignore(node_space().repeated())
-            .or_not()
-            .then(spanned(node))
-            .separated_by(line_space().repeated())
-            .allow_leading()
-            .allow_trailing()
-            .map(|vec| {
-                vec.into_iter()
-                    .filter_map(
-                        |(comment, node)| {
-                            if comment.is_none() {
-                                Some(node)
-                            } else {
-                                None
-                            }
-                        },
-                    )
-                    .collect()
-            })
-    })
+/// Parses the document into the abstract syntax tree
+pub(crate) fn parse<S: Span>(text: &str, syntax: Syntax) -> Result<Document<S>, Vec<Error<S>>> {
+    Parser::new(text, syntax, Tree::new(text))
+        .document()
+        .map(|nodes| Document { nodes })
 }
 
-pub(crate) fn document<S: Span>() -> impl Parser<char, Document<S>, Error = Error<S>> {
-    nodes().then_ignore(end()).map(|nodes| Document { nodes })
+/// Parses the document recovering from errors in the nodes
+pub(crate) fn parse_recovery<S: Span>(
+    text: &str,
+    syntax: Syntax,
+) -> (Option<Document<S>>, Vec<Error<S>>) {
+    let (nodes, errors) = Parser::new(text, syntax, Tree::new(text)).document_recovery();
+    (nodes.map(|nodes| Document { nodes }), errors)
 }
 
 #[cfg(test)]
 mod test {
-    use super::{comment, ident, literal, ml_comment, string, type_name, ws};
-    use super::{nodes, number};
+    use super::{Scalar, Syntax, Tree};
+    use crate::ast::SpannedNode;
     use crate::ast::{Decimal, Integer, Literal, Radix, TypeName};
-    use crate::errors::{Error, ParseError};
+    use crate::errors::{Error, ParseErrorKind, TokenFormat};
     use crate::span::Span;
-    use crate::traits::sealed::Sealed;
-    use chumsky::prelude::*;
     use miette::NamedSource;
 
+    type Result<T> = std::result::Result<T, ParseErrorKind<Span>>;
+    type Parser<'a> = super::Parser<'a, Span, Tree<'a, Span>>;
+
     macro_rules! err_eq {
         ($left: expr, $right: expr) => {
             let left = $left.unwrap_err();
@@ -667,49 +1520,126 @@ mod test {
         }
     }
 
-    fn parse<P, T>(p: P, text: &str) -> Result<T, String>
-    where
-        P: Parser<char, T, Error = ParseError<Span>>,
-    {
-        p.then_ignore(end())
-            .parse(Span::stream(text))
-            .map_err(|errors| {
-                let source = text.to_string() + " ";
-                let e = Error {
-                    source_code: NamedSource::new("<test>", source),
-                    errors: errors.into_iter().map(Into::into).collect(),
-                };
-                let mut buf = String::with_capacity(512);
-                miette::GraphicalReportHandler::new()
-                    .render_report(&mut buf, &e)
-                    .unwrap();
-                println!("{}", buf);
-                buf.truncate(0);
-                miette::JSONReportHandler::new()
-                    .render_report(&mut buf, &e)
-                    .unwrap();
-                buf
-            })
+    fn parse<T>(
+        f: impl FnOnce(&mut Parser) -> Result<T>,
+        text: &str,
+    ) -> std::result::Result<T, String> {
+        let mut p = Parser::new(text, Syntax::V1, Tree::new(text));
+        let mut errors = Vec::new();
+        match f(&mut p) {
+            Ok(_) if p.scan.peek().is_some() => {
+                errors.append(&mut p.scan.errors);
+                errors.push(p.scan.unexpected(None, &[TokenFormat::Eoi]));
+            }
+            Ok(value) if p.scan.errors.is_empty() => return Ok(value),
+            Ok(_) => errors.append(&mut p.scan.errors),
+            Err(e) => {
+                errors.append(&mut p.scan.errors);
+                errors.push(e);
+            }
+        }
+        let source = text.to_string() + " ";
+        let e = Error {
+            source_code: NamedSource::new("<test>", source),
+            errors: errors.into_iter().map(Into::into).collect(),
+        };
+        let mut buf = String::with_capacity(512);
+        miette::GraphicalReportHandler::new()
+            .render_report(&mut buf, &e)
+            .unwrap();
+        println!("{}", buf);
+        buf.truncate(0);
+        miette::JSONReportHandler::new()
+            .render_report(&mut buf, &e)
+            .unwrap();
+        Err(buf)
+    }
+
+    fn ws(p: &mut Parser) -> Result<()> {
+        if p.scan.ws()? {
+            Ok(())
+        } else {
+            Err(p.scan.unexpected(None, &[TokenFormat::Kind("whitespace")]))
+        }
+    }
+
+    fn comment(p: &mut Parser) -> Result<()> {
+        if p.scan.comment() {
+            Ok(())
+        } else {
+            Err(p.scan.unexpected(None, &[TokenFormat::Token("//")]))
+        }
+    }
+
+    fn ml_comment(p: &mut Parser) -> Result<()> {
+        if p.scan.ml_comment()? {
+            Ok(())
+        } else {
+            Err(p.scan.unexpected(None, &[TokenFormat::Token("/*")]))
+        }
+    }
+
+    fn string(p: &mut Parser) -> Result<Box<str>> {
+        match p.scan.scalar()? {
+            Some(Scalar::String(s)) => Ok(s.into()),
+            _ => Err(p.scan.unexpected(None, &[TokenFormat::Kind("string")])),
+        }
+    }
+
+    fn ident(p: &mut Parser) -> Result<Box<str>> {
+        p.ident().map(|name| name.value.into())
+    }
+
+    fn literal(p: &mut Parser) -> Result<Literal> {
+        p.literal().map(|literal| literal.value.into_owned())
+    }
+
+    fn type_name(p: &mut Parser) -> Result<TypeName> {
+        p.type_name()
+            .map(|name| TypeName::from_string(name.name.value.into()))
+    }
+
+    fn number(p: &mut Parser) -> Result<Literal> {
+        match p.scan.number() {
+            Some(number) => Ok(number.into_owned()),
+            None => Err(p.scan.unexpected(None, &[TokenFormat::Kind("number")])),
+        }
+    }
+
+    fn nodes(p: &mut Parser) -> Result<Vec<SpannedNode<Span>>> {
+        p.nodes(false)
     }
 
     #[test]
     fn parse_ws() {
-        parse(ws(), "   ").unwrap();
-        parse(ws(), "text").unwrap_err();
+        parse(ws, "   ").unwrap();
+        parse(ws, "text").unwrap_err();
     }
 
     #[test]
     fn parse_comments() {
-        parse(comment(), "//hello").unwrap();
-        parse(comment(), "//hello\n").unwrap();
-        parse(ml_comment(), "/*nothing*/").unwrap();
-        parse(ml_comment(), "/*nothing**/").unwrap();
-        parse(ml_comment(), "/*no*thing*/").unwrap();
-        parse(ml_comment(), "/*no/**/thing*/").unwrap();
-        parse(ml_comment(), "/*no/*/**/*/thing*/").unwrap();
-        parse(ws().then(comment()), "   // hello").unwrap();
+        parse(comment, "//hello").unwrap();
+        parse(comment, "//hello\n").unwrap();
+        parse(ml_comment, "/*nothing*/").unwrap();
+        parse(ml_comment, "/*nothing**/").unwrap();
+        parse(ml_comment, "/*no*thing*/").unwrap();
+        parse(ml_comment, "/*no/**/thing*/").unwrap();
+        parse(ml_comment, "/*no/*/**/*/thing*/").unwrap();
+        parse(
+            |p| {
+                ws(p)?;
+                comment(p)
+            },
+            "   // hello",
+        )
+        .unwrap();
         parse(
-            ws().then(comment()).then(ws()).then(comment()),
+            |p| {
+                ws(p)?;
+                comment(p)?;
+                ws(p)?;
+                comment(p)
+            },
             "   // hello\n   //world",
         )
         .unwrap();
@@ -718,7 +1648,7 @@ mod test {
     #[test]
     fn parse_comment_err() {
         err_eq!(
-            parse(ws(), r#"/* comment"#),
+            parse(ws, r#"/* comment"#),
             r#"{
             "message": "error parsing KDL",
             "severity": "error",
@@ -738,7 +1668,7 @@ mod test {
         }"#
         );
         err_eq!(
-            parse(ws(), r#"/* com/*ment *"#),
+            parse(ws, r#"/* com/*ment *"#),
             r#"{
             "message": "error parsing KDL",
             "severity": "error",
@@ -758,7 +1688,7 @@ mod test {
         }"#
         );
         err_eq!(
-            parse(ws(), r#"/* com/*me*/nt *"#),
+            parse(ws, r#"/* com/*me*/nt *"#),
             r#"{
             "message": "error parsing KDL",
             "severity": "error",
@@ -778,7 +1708,7 @@ mod test {
         }"#
         );
         err_eq!(
-            parse(ws(), r#"/* comment *"#),
+            parse(ws, r#"/* comment *"#),
             r#"{
             "message": "error parsing KDL",
             "severity": "error",
@@ -798,7 +1728,7 @@ mod test {
         }"#
         );
         err_eq!(
-            parse(ws(), r#"/*/"#),
+            parse(ws, r#"/*/"#),
             r#"{
             "message": "error parsing KDL",
             "severity": "error",
@@ -819,7 +1749,7 @@ mod test {
         );
         // nothing is expected for comment or whitespace
         err_eq!(
-            parse(ws(), r#"xxx"#),
+            parse(ws, r#"xxx"#),
             r#"{
             "message": "error parsing KDL",
             "severity": "error",
@@ -840,23 +1770,23 @@ mod test {
 
     #[test]
     fn parse_str() {
-        assert_eq!(&*parse(string(), r#""hello""#).unwrap(), "hello");
-        assert_eq!(&*parse(string(), r#""""#).unwrap(), "");
-        assert_eq!(&*parse(string(), r#""hel\"lo""#).unwrap(), "hel\"lo");
+        assert_eq!(&*parse(string, r#""hello""#).unwrap(), "hello");
+        assert_eq!(&*parse(string, r#""""#).unwrap(), "");
+        assert_eq!(&*parse(string, r#""hel\"lo""#).unwrap(), "hel\"lo");
         assert_eq!(
-            &*parse(string(), r#""hello\nworld!""#).unwrap(),
+            &*parse(string, r#""hello\nworld!""#).unwrap(),
             "hello\nworld!"
         );
-        assert_eq!(&*parse(string(), r#""\u{1F680}""#).unwrap(), "🚀");
+        assert_eq!(&*parse(string, r#""\u{1F680}""#).unwrap(), "🚀");
     }
 
     #[test]
     fn parse_raw_str() {
-        assert_eq!(&*parse(string(), r#"r"hello""#).unwrap(), "hello");
-        assert_eq!(&*parse(string(), r##"r#"world"#"##).unwrap(), "world");
-        assert_eq!(&*parse(string(), r##"r#"world"#"##).unwrap(), "world");
+        assert_eq!(&*parse(string, r#"r"hello""#).unwrap(), "hello");
+        assert_eq!(&*parse(string, r##"r#"world"#"##).unwrap(), "world");
+        assert_eq!(&*parse(string, r##"r#"world"#"##).unwrap(), "world");
         assert_eq!(
-            &*parse(string(), r####"r###"a\n"##b"###"####).unwrap(),
+            &*parse(string, r####"r###"a\n"##b"###"####).unwrap(),
             "a\\n\"##b"
         );
     }
@@ -864,7 +1794,7 @@ mod test {
     #[test]
     fn parse_str_err() {
         err_eq!(
-            parse(string(), r#""hello"#),
+            parse(string, r#""hello"#),
             r#"{
             "message": "error parsing KDL",
             "severity": "error",
@@ -884,7 +1814,7 @@ mod test {
         }"#
         );
         err_eq!(
-            parse(string(), r#""he\u{FFFFFF}llo""#),
+            parse(string, r#""he\u{FFFFFF}llo""#),
             r#"{
             "message": "error parsing KDL",
             "severity": "error",
@@ -902,7 +1832,7 @@ mod test {
         }"#
         );
         err_eq!(
-            parse(string(), r#""he\u{1234567}llo""#),
+            parse(string, r#""he\u{1234567}llo""#),
             r#"{
             "message": "error parsing KDL",
             "severity": "error",
@@ -920,7 +1850,7 @@ mod test {
         }"#
         );
         err_eq!(
-            parse(string(), r#""he\u{1gh}llo""#),
+            parse(string, r#""he\u{1gh}llo""#),
             r#"{
             "message": "error parsing KDL",
             "severity": "error",
@@ -938,7 +1868,7 @@ mod test {
         }"#
         );
         err_eq!(
-            parse(string(), r#""he\x01llo""#),
+            parse(string, r#""he\x01llo""#),
             r#"{
             "message": "error parsing KDL",
             "severity": "error",
@@ -958,7 +1888,7 @@ mod test {
         );
         // Tests error recovery
         err_eq!(
-            parse(string(), r#""he\u{FFFFFF}l\!lo""#),
+            parse(string, r#""he\u{FFFFFF}l\!lo""#),
             r#"{
             "message": "error parsing KDL",
             "severity": "error",
@@ -989,7 +1919,7 @@ mod test {
     #[test]
     fn parse_raw_str_err() {
         err_eq!(
-            parse(string(), r#"r"hello"#),
+            parse(string, r#"r"hello"#),
             r#"{
             "message": "error parsing KDL",
             "severity": "error",
@@ -1009,7 +1939,7 @@ mod test {
         }"#
         );
         err_eq!(
-            parse(string(), r###"r#"hello""###),
+            parse(string, r###"r#"hello""###),
             r###"{
             "message": "error parsing KDL",
             "severity": "error",
@@ -1029,7 +1959,7 @@ mod test {
         }"###
         );
         err_eq!(
-            parse(string(), r####"r###"hello"####),
+            parse(string, r####"r###"hello"####),
             r####"{
             "message": "error parsing KDL",
             "severity": "error",
@@ -1049,7 +1979,7 @@ mod test {
         }"####
         );
         err_eq!(
-            parse(string(), r####"r###"hello"#world"####),
+            parse(string, r####"r###"hello"#world"####),
             r####"{
             "message": "error parsing KDL",
             "severity": "error",
@@ -1072,44 +2002,60 @@ mod test {
 
     #[test]
     fn parse_ident() {
-        assert_eq!(&*parse(ident(), "abcdef").unwrap(), "abcdef");
-        assert_eq!(&*parse(ident(), "xx_cd$yy").unwrap(), "xx_cd$yy");
-        assert_eq!(&*parse(ident(), "-").unwrap(), "-");
-        assert_eq!(&*parse(ident(), "--hello").unwrap(), "--hello");
-        assert_eq!(&*parse(ident(), "--hello1234").unwrap(), "--hello1234");
-        assert_eq!(&*parse(ident(), "--1").unwrap(), "--1");
-        assert_eq!(&*parse(ident(), "++1").unwrap(), "++1");
-        assert_eq!(&*parse(ident(), "-hello").unwrap(), "-hello");
-        assert_eq!(&*parse(ident(), "+hello").unwrap(), "+hello");
-        assert_eq!(&*parse(ident(), "-A").unwrap(), "-A");
-        assert_eq!(&*parse(ident(), "+b").unwrap(), "+b");
+        assert_eq!(&*parse(ident, "abcdef").unwrap(), "abcdef");
+        assert_eq!(&*parse(ident, "xx_cd$yy").unwrap(), "xx_cd$yy");
+        assert_eq!(&*parse(ident, "-").unwrap(), "-");
+        assert_eq!(&*parse(ident, "--hello").unwrap(), "--hello");
+        assert_eq!(&*parse(ident, "--hello1234").unwrap(), "--hello1234");
+        assert_eq!(&*parse(ident, "--1").unwrap(), "--1");
+        assert_eq!(&*parse(ident, "++1").unwrap(), "++1");
+        assert_eq!(&*parse(ident, "-hello").unwrap(), "-hello");
+        assert_eq!(&*parse(ident, "+hello").unwrap(), "+hello");
+        assert_eq!(&*parse(ident, "-A").unwrap(), "-A");
+        assert_eq!(&*parse(ident, "+b").unwrap(), "+b");
         assert_eq!(
-            &*parse(ident().then_ignore(ws()), "adef   ").unwrap(),
+            &*parse(
+                |p| {
+                    let ident = ident(p)?;
+                    ws(p)?;
+                    Ok(ident)
+                },
+                "adef   "
+            )
+            .unwrap(),
             "adef"
         );
         assert_eq!(
-            &*parse(ident().then_ignore(ws()), "a123@   ").unwrap(),
+            &*parse(
+                |p| {
+                    let ident = ident(p)?;
+                    ws(p)?;
+                    Ok(ident)
+                },
+                "a123@   "
+            )
+            .unwrap(),
             "a123@"
         );
-        parse(ident(), "1abc").unwrap_err();
-        parse(ident(), "-1").unwrap_err();
-        parse(ident(), "-1test").unwrap_err();
-        parse(ident(), "+1").unwrap_err();
+        parse(ident, "1abc").unwrap_err();
+        parse(ident, "-1").unwrap_err();
+        parse(ident, "-1test").unwrap_err();
+        parse(ident, "+1").unwrap_err();
     }
 
     #[test]
     fn parse_literal() {
-        assert_eq!(parse(literal(), "true").unwrap(), Literal::Bool(true));
-        assert_eq!(parse(literal(), "false").unwrap(), Literal::Bool(false));
-        assert_eq!(parse(literal(), "null").unwrap(), Literal::Null);
+        assert_eq!(parse(literal, "true").unwrap(), Literal::Bool(true));
+        assert_eq!(parse(literal, "false").unwrap(), Literal::Bool(false));
+        assert_eq!(parse(literal, "null").unwrap(), Literal::Null);
     }
 
     #[test]
     fn exclude_keywords() {
-        parse(nodes(), "item true").unwrap();
+        parse(nodes, "item true").unwrap();
 
         err_eq!(
-            parse(nodes(), "true \"item\""),
+            parse(nodes, "true \"item\""),
             r#"{
             "message": "error parsing KDL",
             "severity": "error",
@@ -1129,7 +2075,7 @@ mod test {
         );
 
         err_eq!(
-            parse(nodes(), "item false=true"),
+            parse(nodes, "item false=true"),
             r#"{
             "message": "error parsing KDL",
             "severity": "error",
@@ -1149,7 +2095,7 @@ mod test {
         );
 
         err_eq!(
-            parse(nodes(), "item 2=2"),
+            parse(nodes, "item 2=2"),
             r#"{
             "message": "error parsing KDL",
             "severity": "error",
@@ -1172,22 +2118,22 @@ mod test {
     #[test]
     fn parse_type() {
         assert_eq!(
-            parse(type_name(), "(abcdef)").unwrap(),
+            parse(type_name, "(abcdef)").unwrap(),
             TypeName::from_string("abcdef".into())
         );
         assert_eq!(
-            parse(type_name(), "(xx_cd$yy)").unwrap(),
+            parse(type_name, "(xx_cd$yy)").unwrap(),
             TypeName::from_string("xx_cd$yy".into())
         );
-        parse(type_name(), "(1abc)").unwrap_err();
-        parse(type_name(), "( abc)").unwrap_err();
-        parse(type_name(), "(abc )").unwrap_err();
+        parse(type_name, "(1abc)").unwrap_err();
+        parse(type_name, "( abc)").unwrap_err();
+        parse(type_name, "(abc )").unwrap_err();
     }
 
     #[test]
     fn parse_type_err() {
         err_eq!(
-            parse(type_name(), "(123)"),
+            parse(type_name, "(123)"),
             r#"{
             "message": "error parsing KDL",
             "severity": "error",
@@ -1206,7 +2152,7 @@ mod test {
         );
 
         err_eq!(
-            parse(type_name(), "(-1)"),
+            parse(type_name, "(-1)"),
             r#"{
             "message": "error parsing KDL",
             "severity": "error",
@@ -1225,7 +2171,7 @@ mod test {
         );
     }
 
-    fn single<T, E: std::fmt::Debug>(r: Result<Vec<T>, E>) -> T {
+    fn single<T, E: std::fmt::Debug>(r: std::result::Result<Vec<T>, E>) -> T {
         let mut v = r.unwrap();
         assert_eq!(v.len(), 1);
         v.remove(0)
@@ -1233,40 +2179,40 @@ mod test {
 
     #[test]
     fn parse_node() {
-        let nval = single(parse(nodes(), "hello"));
+        let nval = single(parse(nodes, "hello"));
         assert_eq!(nval.node_name.as_ref(), "hello");
         assert_eq!(nval.type_name.as_ref(), None);
 
-        let nval = single(parse(nodes(), "\"123\""));
+        let nval = single(parse(nodes, "\"123\""));
         assert_eq!(nval.node_name.as_ref(), "123");
         assert_eq!(nval.type_name.as_ref(), None);
 
-        let nval = single(parse(nodes(), "(typ)other"));
+        let nval = single(parse(nodes, "(typ)other"));
         assert_eq!(nval.node_name.as_ref(), "other");
         assert_eq!(nval.type_name.as_ref().map(|x| &***x), Some("typ"));
 
-        let nval = single(parse(nodes(), "(\"std::duration\")\"timeout\""));
+        let nval = single(parse(nodes, "(\"std::duration\")\"timeout\""));
         assert_eq!(nval.node_name.as_ref(), "timeout");
         assert_eq!(
             nval.type_name.as_ref().map(|x| &***x),
             Some("std::duration")
         );
 
-        let nval = single(parse(nodes(), "hello \"arg1\""));
+        let nval = single(parse(nodes, "hello \"arg1\""));
         assert_eq!(nval.node_name.as_ref(), "hello");
         assert_eq!(nval.type_name.as_ref(), None);
         assert_eq!(nval.arguments.len(), 1);
         assert_eq!(nval.properties.len(), 0);
         assert_eq!(&*nval.arguments[0].literal, &Literal::String("arg1".into()));
 
-        let nval = single(parse(nodes(), "node \"true\""));
+        let nval = single(parse(nodes, "node \"true\""));
         assert_eq!(nval.node_name.as_ref(), "node");
         assert_eq!(nval.type_name.as_ref(), None);
         assert_eq!(nval.arguments.len(), 1);
         assert_eq!(nval.properties.len(), 0);
         assert_eq!(&*nval.arguments[0].literal, &Literal::String("true".into()));
 
-        let nval = single(parse(nodes(), "hello (string)\"arg1\""));
+        let nval = single(parse(nodes, "hello (string)\"arg1\""));
         assert_eq!(nval.node_name.as_ref(), "hello");
         assert_eq!(nval.type_name.as_ref(), None);
         assert_eq!(nval.arguments.len(), 1);
@@ -1274,7 +2220,7 @@ mod test {
         assert_eq!(&***nval.arguments[0].type_name.as_ref().unwrap(), "string");
         assert_eq!(&*nval.arguments[0].literal, &Literal::String("arg1".into()));
 
-        let nval = single(parse(nodes(), "hello key=(string)\"arg1\""));
+        let nval = single(parse(nodes, "hello key=(string)\"arg1\""));
         assert_eq!(nval.node_name.as_ref(), "hello");
         assert_eq!(nval.type_name.as_ref(), None);
         assert_eq!(nval.arguments.len(), 0);
@@ -1294,7 +2240,7 @@ mod test {
             &Literal::String("arg1".into())
         );
 
-        let nval = single(parse(nodes(), "hello key=\"arg1\""));
+        let nval = single(parse(nodes, "hello key=\"arg1\""));
         assert_eq!(nval.node_name.as_ref(), "hello");
         assert_eq!(nval.type_name.as_ref(), None);
         assert_eq!(nval.arguments.len(), 0);
@@ -1304,7 +2250,7 @@ mod test {
             &Literal::String("arg1".into())
         );
 
-        let nval = single(parse(nodes(), "parent {\nchild\n}"));
+        let nval = single(parse(nodes, "parent {\nchild\n}"));
         assert_eq!(nval.node_name.as_ref(), "parent");
         assert_eq!(nval.children().len(), 1);
         assert_eq!(
@@ -1312,7 +2258,7 @@ mod test {
             "child"
         );
 
-        let nval = single(parse(nodes(), "parent {\nchild1\nchild2\n}"));
+        let nval = single(parse(nodes, "parent {\nchild1\nchild2\n}"));
         assert_eq!(nval.node_name.as_ref(), "parent");
         assert_eq!(nval.children().len(), 2);
         assert_eq!(
@@ -1324,7 +2270,7 @@ mod test {
             "child2"
         );
 
-        let nval = single(parse(nodes(), "parent{\nchild3\n}"));
+        let nval = single(parse(nodes, "parent{\nchild3\n}"));
         assert_eq!(nval.node_name.as_ref(), "parent");
         assert_eq!(nval.children().len(), 1);
         assert_eq!(
@@ -1332,7 +2278,7 @@ mod test {
             "child3"
         );
 
-        let nval = single(parse(nodes(), "parent \"x\"=1 {\nchild4\n}"));
+        let nval = single(parse(nodes, "parent \"x\"=1 {\nchild4\n}"));
         assert_eq!(nval.node_name.as_ref(), "parent");
         assert_eq!(nval.properties.len(), 1);
         assert_eq!(nval.children().len(), 1);
@@ -1341,7 +2287,7 @@ mod test {
             "child4"
         );
 
-        let nval = single(parse(nodes(), "parent \"x\" {\nchild4\n}"));
+        let nval = single(parse(nodes, "parent \"x\" {\nchild4\n}"));
         assert_eq!(nval.node_name.as_ref(), "parent");
         assert_eq!(nval.arguments.len(), 1);
         assert_eq!(nval.children().len(), 1);
@@ -1350,7 +2296,7 @@ mod test {
             "child4"
         );
 
-        let nval = single(parse(nodes(), "parent \"x\"{\nchild5\n}"));
+        let nval = single(parse(nodes, "parent \"x\"{\nchild5\n}"));
         assert_eq!(nval.node_name.as_ref(), "parent");
         assert_eq!(nval.arguments.len(), 1);
         assert_eq!(nval.children().len(), 1);
@@ -1359,21 +2305,21 @@ mod test {
             "child5"
         );
 
-        let nval = single(parse(nodes(), "hello /-\"skip_arg\" \"arg2\""));
+        let nval = single(parse(nodes, "hello /-\"skip_arg\" \"arg2\""));
         assert_eq!(nval.node_name.as_ref(), "hello");
         assert_eq!(nval.type_name.as_ref(), None);
         assert_eq!(nval.arguments.len(), 1);
         assert_eq!(nval.properties.len(), 0);
         assert_eq!(&*nval.arguments[0].literal, &Literal::String("arg2".into()));
 
-        let nval = single(parse(nodes(), "hello /- \"skip_arg\" \"arg2\""));
+        let nval = single(parse(nodes, "hello /- \"skip_arg\" \"arg2\""));
         assert_eq!(nval.node_name.as_ref(), "hello");
         assert_eq!(nval.type_name.as_ref(), None);
         assert_eq!(nval.arguments.len(), 1);
         assert_eq!(nval.properties.len(), 0);
         assert_eq!(&*nval.arguments[0].literal, &Literal::String("arg2".into()));
 
-        let nval = single(parse(nodes(), "hello prop1=\"1\" /-prop1=\"2\""));
+        let nval = single(parse(nodes, "hello prop1=\"1\" /-prop1=\"2\""));
         assert_eq!(nval.node_name.as_ref(), "hello");
         assert_eq!(nval.type_name.as_ref(), None);
         assert_eq!(nval.arguments.len(), 0);
@@ -1383,26 +2329,26 @@ mod test {
             &Literal::String("1".into())
         );
 
-        let nval = single(parse(nodes(), "parent /-{\nchild\n}"));
+        let nval = single(parse(nodes, "parent /-{\nchild\n}"));
         assert_eq!(nval.node_name.as_ref(), "parent");
         assert_eq!(nval.children().len(), 0);
     }
 
     #[test]
     fn parse_node_whitespace() {
-        let nval = single(parse(nodes(), "hello  {   }"));
+        let nval = single(parse(nodes, "hello  {   }"));
         assert_eq!(nval.node_name.as_ref(), "hello");
         assert_eq!(nval.type_name.as_ref(), None);
 
-        let nval = single(parse(nodes(), "hello  {   }  "));
+        let nval = single(parse(nodes, "hello  {   }  "));
         assert_eq!(nval.node_name.as_ref(), "hello");
         assert_eq!(nval.type_name.as_ref(), None);
 
-        let nval = single(parse(nodes(), "hello "));
+        let nval = single(parse(nodes, "hello "));
         assert_eq!(nval.node_name.as_ref(), "hello");
         assert_eq!(nval.type_name.as_ref(), None);
 
-        let nval = single(parse(nodes(), "hello   "));
+        let nval = single(parse(nodes, "hello   "));
         assert_eq!(nval.node_name.as_ref(), "hello");
         assert_eq!(nval.type_name.as_ref(), None);
     }
@@ -1410,7 +2356,7 @@ mod test {
     #[test]
     fn parse_node_err() {
         err_eq!(
-            parse(nodes(), "hello{"),
+            parse(nodes, "hello{"),
             r#"{
             "message": "error parsing KDL",
             "severity": "error",
@@ -1430,7 +2376,7 @@ mod test {
         }"#
         );
         err_eq!(
-            parse(nodes(), "hello world"),
+            parse(nodes, "hello world"),
             r#"{
             "message": "error parsing KDL",
             "severity": "error",
@@ -1450,7 +2396,7 @@ mod test {
         );
 
         err_eq!(
-            parse(nodes(), "hello world {"),
+            parse(nodes, "hello world {"),
             r#"{
             "message": "error parsing KDL",
             "severity": "error",
@@ -1481,7 +2427,7 @@ mod test {
         );
 
         err_eq!(
-            parse(nodes(), "1 + 2"),
+            parse(nodes, "1 + 2"),
             r#"{
             "message": "error parsing KDL",
             "severity": "error",
@@ -1500,7 +2446,7 @@ mod test {
         );
 
         err_eq!(
-            parse(nodes(), "-1 +2"),
+            parse(nodes, "-1 +2"),
             r#"{
             "message": "error parsing KDL",
             "severity": "error",
@@ -1521,53 +2467,72 @@ mod test {
 
     #[test]
     fn parse_nodes() {
-        let nval = parse(nodes(), "parent {\n/-  child\n}").unwrap();
+        let nval = parse(nodes, "parent {\n/-  child\n}").unwrap();
         assert_eq!(nval.len(), 1);
         assert_eq!(nval[0].node_name.as_ref(), "parent");
         assert_eq!(nval[0].children().len(), 0);
 
-        let nval = parse(nodes(), "/-parent {\n  child\n}\nsecond").unwrap();
+        let nval = parse(nodes, "/-parent {\n  child\n}\nsecond").unwrap();
         assert_eq!(nval.len(), 1);
         assert_eq!(nval[0].node_name.as_ref(), "second");
         assert_eq!(nval[0].children().len(), 0);
     }
 
+    #[test]
+    fn parse_deep_nesting() {
+        let depth = 200_000;
+        let text = "a {\n".repeat(depth) + &"}\n".repeat(depth);
+        let doc = super::parse::<Span>(&text, Syntax::V1).unwrap();
+        let mut node = &doc.nodes[0];
+        for _ in 1..depth {
+            assert_eq!(node.children().len(), 1);
+            node = node.children().next().unwrap();
+        }
+        assert_eq!(node.children().len(), 0);
+        crate::ast::drop_nodes(doc.nodes);
+        // nodes parsed before the error are dropped without recursion too
+        let invalid = text.clone() + "b =\n";
+        assert!(super::parse::<Span>(&invalid, Syntax::V1).is_err());
+        let unclosed = "a {\n".repeat(depth) + "b {\n" + &text;
+        assert!(super::parse::<Span>(&unclosed, Syntax::V1).is_err());
+    }
+
     #[test]
     fn parse_number() {
         assert_eq!(
-            parse(number(), "12").unwrap(),
+            parse(number, "12").unwrap(),
             Literal::Int(Integer(Radix::Dec, "12".into()))
         );
         assert_eq!(
-            parse(number(), "012").unwrap(),
+            parse(number, "012").unwrap(),
             Literal::Int(Integer(Radix::Dec, "012".into()))
         );
         assert_eq!(
-            parse(number(), "0").unwrap(),
+            parse(number, "0").unwrap(),
             Literal::Int(Integer(Radix::Dec, "0".into()))
         );
         assert_eq!(
-            parse(number(), "-012").unwrap(),
+            parse(number, "-012").unwrap(),
             Literal::Int(Integer(Radix::Dec, "-012".into()))
         );
         assert_eq!(
-            parse(number(), "+0").unwrap(),
+            parse(number, "+0").unwrap(),
             Literal::Int(Integer(Radix::Dec, "+0".into()))
         );
         assert_eq!(
-            parse(number(), "123_555").unwrap(),
+            parse(number, "123_555").unwrap(),
             Literal::Int(Integer(Radix::Dec, "123555".into()))
         );
         assert_eq!(
-            parse(number(), "123.555").unwrap(),
+            parse(number, "123.555").unwrap(),
             Literal::Decimal(Decimal("123.555".into()))
         );
         assert_eq!(
-            parse(number(), "+1_23.5_55E-17").unwrap(),
+            parse(number, "+1_23.5_55E-17").unwrap(),
             Literal::Decimal(Decimal("+123.555E-17".into()))
         );
         assert_eq!(
-            parse(number(), "123e+555").unwrap(),
+            parse(number, "123e+555").unwrap(),
             Literal::Decimal(Decimal("123e+555".into()))
         );
     }
@@ -1575,56 +2540,56 @@ mod test {
     #[test]
     fn parse_radix_number() {
         assert_eq!(
-            parse(number(), "0x12").unwrap(),
+            parse(number, "0x12").unwrap(),
             Literal::Int(Integer(Radix::Hex, "12".into()))
         );
         assert_eq!(
-            parse(number(), "0xab_12").unwrap(),
+            parse(number, "0xab_12").unwrap(),
             Literal::Int(Integer(Radix::Hex, "ab12".into()))
         );
         assert_eq!(
-            parse(number(), "-0xab_12").unwrap(),
+            parse(number, "-0xab_12").unwrap(),
             Literal::Int(Integer(Radix::Hex, "-ab12".into()))
         );
         assert_eq!(
-            parse(number(), "0o17").unwrap(),
+            parse(number, "0o17").unwrap(),
             Literal::Int(Integer(Radix::Oct, "17".into()))
         );
         assert_eq!(
-            parse(number(), "+0o17").unwrap(),
+            parse(number, "+0o17").unwrap(),
             Literal::Int(Integer(Radix::Oct, "+17".into()))
         );
         assert_eq!(
-            parse(number(), "0b1010_101").unwrap(),
+            parse(number, "0b1010_101").unwrap(),
             Literal::Int(Integer(Radix::Bin, "1010101".into()))
         );
     }
 
     #[test]
     fn parse_dashes() {
-        let nval = parse(nodes(), "-").unwrap();
+        let nval = parse(nodes, "-").unwrap();
         assert_eq!(nval.len(), 1);
         assert_eq!(nval[0].node_name.as_ref(), "-");
         assert_eq!(nval[0].children().len(), 0);
 
-        let nval = parse(nodes(), "--").unwrap();
+        let nval = parse(nodes, "--").unwrap();
         assert_eq!(nval.len(), 1);
         assert_eq!(nval[0].node_name.as_ref(), "--");
         assert_eq!(nval[0].children().len(), 0);
 
-        let nval = parse(nodes(), "--1").unwrap();
+        let nval = parse(nodes, "--1").unwrap();
         assert_eq!(nval.len(), 1);
         assert_eq!(nval[0].node_name.as_ref(), "--1");
         assert_eq!(nval[0].children().len(), 0);
 
-        let nval = parse(nodes(), "-\n-").unwrap();
+        let nval = parse(nodes, "-\n-").unwrap();
         assert_eq!(nval.len(), 2);
         assert_eq!(nval[0].node_name.as_ref(), "-");
         assert_eq!(nval[0].children().len(), 0);
         assert_eq!(nval[1].node_name.as_ref(), "-");
         assert_eq!(nval[1].children().len(), 0);
 
-        let nval = parse(nodes(), "node -1 --x=2").unwrap();
+        let nval = parse(nodes, "node -1 --x=2").unwrap();
         assert_eq!(nval.len(), 1);
         assert_eq!(nval[0].arguments.len(), 1);
         assert_eq!(nval[0].properties.len(), 1);
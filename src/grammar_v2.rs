@@ -0,0 +1,517 @@
+//! Rules of the KDL 2.0 syntax
+//!
+//! Documents of both versions are parsed into the same trees, so decoders
+//! don't need to know which version of the document was parsed. Only the
+//! scalars differ enough to need their own scanning rules, the node
+//! structure is parsed by the shared [`Parser`](crate::grammar::Parser).
+use std::borrow::Cow;
+
+use crate::borrowed::{Decimal, Literal};
+use crate::errors::{ParseErrorKind as Error, TokenFormat};
+use crate::grammar::{expected_kind, Scalar, Scanner};
+use crate::traits::Span;
+
+pub(crate) fn is_ws_char(c: char) -> bool {
+    matches!(
+        c,
+        '\t' | ' ' | '\u{00a0}' | '\u{1680}' | '\u{2000}'
+            ..='\u{200A}' | '\u{202F}' | '\u{205F}' | '\u{3000}'
+    )
+}
+
+pub(crate) fn is_newline_char(c: char) -> bool {
+    matches!(
+        c,
+        '\r' | '\n' | '\x0B' | '\x0C' | '\u{0085}' | '\u{2028}' | '\u{2029}'
+    )
+}
+
+pub(crate) fn is_disallowed(c: char) -> bool {
+    matches!(c,
+        '\u{0000}'..='\u{0008}' | '\u{000E}'..='\u{001F}' | '\u{007F}' |
+        // direction control characters
+        '\u{200E}'..='\u{200F}' | '\u{202A}'..='\u{202E}' |
+        '\u{2066}'..='\u{2069}' |
+        // byte order mark is only allowed at the start of the document
+        '\u{FEFF}'
+    )
+}
+
+pub(crate) fn is_id_char(c: char) -> bool {
+    !is_ws_char(c)
+        && !is_newline_char(c)
+        && !is_disallowed(c)
+        && !matches!(
+            c,
+            '\\' | '/' | '(' | ')' | '{' | '}' | ';' | '[' | ']' | '"' | '#' | '='
+        )
+}
+
+/// Splits text into lines, treating `\r\n` as a single newline
+fn lines(text: &str) -> Vec<&str> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    let mut iter = text.char_indices().peekable();
+    while let Some((idx, c)) = iter.next() {
+        if is_newline_char(c) {
+            lines.push(&text[start..idx]);
+            start = idx + c.len_utf8();
+            if c == '\r' && matches!(iter.peek(), Some((_, '\n'))) {
+                iter.next();
+                start += 1;
+            }
+        }
+    }
+    lines.push(&text[start..]);
+    lines
+}
+
+/// Removes indentation of the closing line from every line of the
+/// multi-line string
+fn dedent(text: &str) -> Result<String, String> {
+    let mut lines = lines(text);
+    let prefix = lines.pop().expect("at least one line");
+    if !prefix.chars().all(is_ws_char) {
+        return Err("closing quotes of multi-line string must be \
+                    on their own line"
+            .into());
+    }
+    let mut result = String::with_capacity(text.len());
+    for (idx, line) in lines.iter().enumerate() {
+        if idx > 0 {
+            result.push('\n');
+        }
+        if line.chars().all(is_ws_char) {
+            continue;
+        }
+        match line.strip_prefix(prefix) {
+            Some(line) => result.push_str(line),
+            None => {
+                return Err(format!(
+                    "line {} of multi-line string is not indented \
+                     by the same whitespace as the closing quotes",
+                    idx + 1
+                ))
+            }
+        }
+    }
+    Ok(result)
+}
+
+fn unescape(text: &str) -> Result<String, String> {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('"') => result.push('"'),
+            Some('\\') => result.push('\\'),
+            Some('b') => result.push('\u{0008}'),
+            Some('f') => result.push('\u{000C}'),
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some('t') => result.push('\t'),
+            Some('s') => result.push(' '),
+            Some('u') => {
+                if chars.next() != Some('{') {
+                    return Err("expected `{` after `\\u`".into());
+                }
+                let mut hex = String::new();
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        break;
+                    }
+                    hex.push(c);
+                }
+                if hex.is_empty() || hex.len() > 6 {
+                    return Err(format!("invalid unicode escape `\\u{{{}}}`", hex));
+                }
+                let c = u32::from_str_radix(&hex, 16)
+                    .map_err(|e| e.to_string())
+                    .and_then(|n| char::try_from(n).map_err(|e| e.to_string()))?;
+                result.push(c);
+            }
+            Some(c) if is_ws_char(c) || is_newline_char(c) => {
+                while chars
+                    .peek()
+                    .is_some_and(|&c| is_ws_char(c) || is_newline_char(c))
+                {
+                    chars.next();
+                }
+            }
+            Some(c) => return Err(format!("invalid escape char `{}`", c.escape_default())),
+            None => return Err("unexpected end of string after `\\`".into()),
+        }
+    }
+    Ok(result)
+}
+
+/// Returns help for the keywords that can't be used as bare identifiers
+fn keyword_help(s: &str) -> Option<&'static str> {
+    match s {
+        "true" => Some("use `#true` for the boolean value"),
+        "false" => Some("use `#false` for the boolean value"),
+        "null" => Some("use `#null` for the null value"),
+        "inf" => Some("use `#inf` for the infinity"),
+        "-inf" => Some("use `#-inf` for the negative infinity"),
+        "nan" => Some("use `#nan` for the not-a-number value"),
+        _ => None,
+    }
+}
+
+fn looks_like_number(s: &str) -> bool {
+    let unsigned = s.strip_prefix(|c| c == '-' || c == '+').unwrap_or(s);
+    let undotted = unsigned.strip_prefix('.').unwrap_or(unsigned);
+    undotted.starts_with(|c: char| c.is_ascii_digit())
+}
+
+/// Checks whether the string can be written as a bare identifier
+pub(crate) fn is_bare_ident(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(is_id_char) && keyword_help(s).is_none() && !looks_like_number(s)
+}
+
+impl<'a, S: Span> Scanner<'a, S> {
+    pub(crate) fn scalar_v2(&mut self) -> Result<Option<Scalar<'a>>, Error<S>> {
+        match self.peek_char() {
+            Some('#') => self.hash_scalar().map(Some),
+            Some('"') if self.starts_with("\"\"\"") => {
+                self.multi_line_string().map(|s| Some(Scalar::String(s)))
+            }
+            Some('"') => self.escaped_string().map(|s| Some(Scalar::String(s))),
+            Some(c) if is_id_char(c) => self.bare_scalar().map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Scans a keyword or a raw string, both start with `#`
+    fn hash_scalar(&mut self) -> Result<Scalar<'a>, Error<S>> {
+        let start = self.pos;
+        let hashes = self.text.as_bytes()[start..]
+            .iter()
+            .take_while(|&&b| b == b'#')
+            .count();
+        self.pos += hashes;
+        if self.peek() == Some(b'"') {
+            return self.hash_raw_string(start, hashes).map(Scalar::String);
+        }
+        if hashes > 1 {
+            return Err(self.unexpected(None, &['"'.into()]));
+        }
+        self.skip_while(is_id_char);
+        let keyword = match &self.text[start..self.pos] {
+            "#true" => Scalar::Keyword("#true", Literal::Bool(true)),
+            "#false" => Scalar::Keyword("#false", Literal::Bool(false)),
+            "#null" => Scalar::Keyword("#null", Literal::Null),
+            "#inf" => Scalar::Keyword("#inf", Literal::Decimal(Decimal("inf".into()))),
+            "#-inf" => Scalar::Keyword("#-inf", Literal::Decimal(Decimal("-inf".into()))),
+            "#nan" => Scalar::Keyword("#nan", Literal::Decimal(Decimal("nan".into()))),
+            "#" => {
+                self.pos = start + 1;
+                return Err(self.unexpected(None, &['"'.into(), TokenFormat::Kind("keyword")]));
+            }
+            keyword => {
+                let message = format!("unknown keyword `{}`", keyword);
+                return Err(Error::Message {
+                    label: Some("unknown keyword"),
+                    span: self.span(start, self.pos),
+                    message,
+                });
+            }
+        };
+        Ok(keyword)
+    }
+
+    /// Scans a raw string, the position is at the opening quote
+    fn hash_raw_string(&mut self, start: usize, hashes: usize) -> Result<Cow<'a, str>, Error<S>> {
+        let newline = self.newline_at(self.pos + 3);
+        let multi_line = self.starts_with("\"\"\"") && newline > 0;
+        let quotes = if multi_line { 3 } else { 1 };
+        let content = if multi_line {
+            self.pos + quotes + newline
+        } else {
+            self.pos + quotes
+        };
+        self.pos = content;
+        let end = loop {
+            let quote = match self.text[self.pos..].find('"') {
+                Some(quote) => self.pos + quote,
+                None => {
+                    self.pos = self.text.len();
+                    return Err(self.unclosed(
+                        "raw string",
+                        start..start + hashes + 1,
+                        TokenFormat::OpenHashRaw(hashes),
+                        TokenFormat::CloseRaw(hashes),
+                    ));
+                }
+            };
+            self.pos = quote + 1;
+            let closing = &self.text.as_bytes()[quote..];
+            let closed = closing.len() >= quotes + hashes
+                && closing[..quotes].iter().all(|&b| b == b'"')
+                && closing[quotes..quotes + hashes].iter().all(|&b| b == b'#');
+            if closed {
+                self.pos = quote + quotes + hashes;
+                break quote;
+            }
+        };
+        let text = &self.text[content..end];
+        if multi_line {
+            dedent(text).map(Cow::Owned)
+        } else if text.chars().any(is_newline_char) {
+            Err("raw strings cannot contain newlines, \
+                 use multi-line raw string instead"
+                .into())
+        } else {
+            Ok(Cow::Borrowed(text))
+        }
+        .map_err(|message| Error::Message {
+            label: Some("invalid raw string"),
+            span: self.span(content, self.pos),
+            message,
+        })
+    }
+
+    fn multi_line_string(&mut self) -> Result<Cow<'a, str>, Error<S>> {
+        let start = self.pos;
+        self.pos += 3;
+        if !self.newline() {
+            return Err(self.unexpected(None, &[TokenFormat::Kind("newline")]));
+        }
+        let content = self.pos;
+        let end = loop {
+            match self.peek_char() {
+                None => {
+                    return Err(self.unclosed(
+                        "multi-line string",
+                        start..start + 3,
+                        "\"\"\"".into(),
+                        "\"\"\"".into(),
+                    ))
+                }
+                Some('"') if self.starts_with("\"\"\"") => break self.pos,
+                // escapes are interpreted after dedenting
+                Some('\\') => {
+                    self.pos += 1;
+                    if let Some(c) = self.peek_char() {
+                        self.pos += c.len_utf8();
+                    }
+                }
+                Some(c) if is_disallowed(c) => {
+                    return Err(self.unexpected(None, &["\"\"\"".into()]));
+                }
+                Some(c) => self.pos += c.len_utf8(),
+            }
+        };
+        self.pos += 3;
+        dedent(&self.text[content..end])
+            .and_then(|text| unescape(&text))
+            .map(|text| text.into())
+            .map_err(|message| Error::Message {
+                label: Some("invalid multi-line string"),
+                span: self.span(content, self.pos),
+                message,
+            })
+    }
+
+    /// Scans a bare identifier or a number
+    fn bare_scalar(&mut self) -> Result<Scalar<'a>, Error<S>> {
+        let start = self.pos;
+        self.skip_while(is_id_char);
+        let end = self.pos;
+        let text = &self.text[start..end];
+        if looks_like_number(text) {
+            self.pos = start;
+            match self.number() {
+                Some(number) if self.pos == end => return Ok(Scalar::Number(number)),
+                _ => self.pos = end,
+            }
+            return Err(Error::Unexpected {
+                label: Some("unexpected number"),
+                span: self.span(start, end),
+                found: TokenFormat::Kind("number"),
+                expected: expected_kind("identifier"),
+            });
+        }
+        if let Some(help) = keyword_help(text) {
+            return Err(Error::MessageWithHelp {
+                label: Some("keyword"),
+                span: self.span(start, end),
+                message: format!("`{}` cannot be used as an identifier", text),
+                help,
+            });
+        }
+        Ok(Scalar::String(Cow::Borrowed(text)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ast::{Decimal, Document, Literal};
+    use crate::errors::Error;
+    use crate::grammar::{self, Syntax};
+    use crate::span::Span;
+    use miette::NamedSource;
+
+    fn parse(text: &str) -> Result<Document<Span>, String> {
+        grammar::parse(text, Syntax::V2).map_err(|errors| {
+            let e = Error {
+                source_code: NamedSource::new("<test>", text.to_string()),
+                errors: errors.into_iter().map(Into::into).collect(),
+            };
+            let mut buf = String::with_capacity(512);
+            miette::JSONReportHandler::new()
+                .render_report(&mut buf, &e)
+                .unwrap();
+            buf
+        })
+    }
+
+    fn parse_v1(text: &str) -> Document<Span> {
+        grammar::parse(text, Syntax::V1).unwrap()
+    }
+
+    fn args(text: &str) -> Vec<Literal> {
+        let doc = parse(text).unwrap();
+        doc.nodes[0]
+            .arguments
+            .iter()
+            .map(|a| (*a.literal).clone())
+            .collect()
+    }
+
+    fn string(text: &str) -> String {
+        match args(&format!("node {}", text)).remove(0) {
+            Literal::String(s) => s.into(),
+            other => panic!("expected string, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn same_as_v1() {
+        let v1 = r####"
+            // comment
+            (t)node "arg" r#"raw "str""# 0x1f 1.5 true null key="val" {
+                child; /- commented
+                "quoted name" -1 nested="\"q\""
+            }
+            /- skipped { a; }
+            last
+        "####;
+        let v2 = r####"
+            // comment
+            (t)node arg #"raw "str""# 0x1f 1.5 #true #null key=val {
+                child; /- commented
+                "quoted name" -1 nested="\"q\""
+            }
+            /- skipped { a; }
+            last
+        "####;
+        assert_eq!(parse(v2).unwrap(), parse_v1(v1));
+    }
+
+    #[test]
+    fn keywords() {
+        assert_eq!(
+            args("node #true #false #null #inf #-inf #nan"),
+            vec![
+                Literal::Bool(true),
+                Literal::Bool(false),
+                Literal::Null,
+                Literal::Decimal(Decimal("inf".into())),
+                Literal::Decimal(Decimal("-inf".into())),
+                Literal::Decimal(Decimal("nan".into())),
+            ]
+        );
+        let err = parse("node true").unwrap_err();
+        assert!(err.contains("`true` cannot be used as an identifier"));
+        assert!(err.contains("use `#true` for the boolean value"));
+        parse("node #truex").unwrap_err();
+        parse("node #maybe").unwrap_err();
+    }
+
+    #[test]
+    fn identifiers() {
+        assert_eq!(
+            args("node foo <tag> a,b - -a .a"),
+            ["foo", "<tag>", "a,b", "-", "-a", ".a"]
+                .iter()
+                .map(|s| Literal::String((*s).into()))
+                .collect::<Vec<_>>()
+        );
+        let doc = parse("node key=value").unwrap();
+        let (name, value) = doc.nodes[0].properties.iter().next().unwrap();
+        assert_eq!(&***name, "key");
+        assert_eq!(*value.literal, Literal::String("value".into()));
+        parse("node 1abc").unwrap_err();
+        parse("node .1a").unwrap_err();
+        parse("node a#b").unwrap_err();
+        parse("1 a").unwrap_err();
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(string(r#""a\sb\tc""#), "a b\tc");
+        assert_eq!(string("\"a\\   \n   b\""), "ab");
+        assert_eq!(string(r#""\u{1F680}""#), "🚀");
+        assert_eq!(string(r##"#"a\nb"#"##), "a\\nb");
+        assert_eq!(string(r###"##"a"#b"##"###), "a\"#b");
+        parse(r#"node "a\/b""#).unwrap_err();
+        parse("node \"a\nb\"").unwrap_err();
+        parse("node #\"a\nb\"#").unwrap_err();
+        let err = parse(r#"node "abc"#).unwrap_err();
+        assert!(err.contains("unclosed string"));
+        let err = parse(r##"node #"abc"##).unwrap_err();
+        assert!(err.contains(r#"unclosed raw string `#\"`"#));
+    }
+
+    #[test]
+    fn multi_line_strings() {
+        assert_eq!(
+            string("\"\"\"\n    hello\n\n      \"world\"\n    \"\"\""),
+            "hello\n\n  \"world\""
+        );
+        assert_eq!(string("\"\"\"\r\n  a\\tb\r\n  \"\"\""), "a\tb");
+        assert_eq!(string("\"\"\"\n\"\"\""), "");
+        assert_eq!(string("#\"\"\"\n  a\\n\"\"\n  \"\"\"#"), "a\\n\"\"");
+        let err = parse("node \"\"\"\n  a\n b\n  \"\"\"").unwrap_err();
+        assert!(err.contains("line 2 of multi-line string is not indented"));
+        let err = parse("node \"\"\"\n  a\n  b\"\"\"").unwrap_err();
+        assert!(err.contains("must be on their own line"));
+        let err = parse("node \"\"\"\n  a").unwrap_err();
+        assert!(err.contains("unclosed multi-line string"));
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(parse("node 1e10").unwrap(), parse_v1("node 1e10"));
+        assert_eq!(
+            parse("node 0b1_0 -0o7").unwrap(),
+            parse_v1("node 0b1_0 -0o7")
+        );
+        parse("node 1e").unwrap_err();
+        parse("node 1.").unwrap_err();
+        parse("node 0x1g").unwrap_err();
+    }
+
+    #[test]
+    fn whitespace() {
+        assert_eq!(parse("node (u8) 1").unwrap(), parse_v1("node (u8)1"));
+        assert_eq!(parse("( t ) node").unwrap(), parse_v1("(t)node"));
+        assert_eq!(parse("node key = 1").unwrap(), parse_v1("node key=1"));
+        assert_eq!(parse("a { b }").unwrap(), parse_v1("a { b\n}"));
+        assert_eq!(parse("a{b;c}").unwrap(), parse_v1("a{b;c;}"));
+        assert_eq!(parse("/-\n  a\nb").unwrap(), parse_v1("b"));
+        assert_eq!(parse("a 1 \\\n 2").unwrap(), parse_v1("a 1 2"));
+        assert_eq!(parse("a \\").unwrap(), parse_v1("a"));
+        assert_eq!(parse("\u{FEFF}a\x0Bb").unwrap(), parse_v1("a\nb"));
+        assert_eq!(parse("a /- { x } { y }").unwrap(), parse_v1("a { y; }"));
+        let err = parse("a { x } { y }").unwrap_err();
+        assert!(err.contains("node can only have one children block"));
+        parse("a\u{FEFF}b").unwrap_err();
+        parse("a }").unwrap_err();
+    }
+}
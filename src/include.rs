@@ -0,0 +1,632 @@
+//! Documents split into several files using `include` nodes
+//!
+//! The [`Resolver`] parses the file and replaces each `include "path.kdl"`
+//! node with the nodes of the included file, both at the top level and in the
+//! children blocks. Paths are relative to the including file and files are
+//! read through the [`Loader`] trait, so documents can come from the
+//! filesystem ([`FsLoader`]), memory ([`MemoryLoader`]) or anywhere else:
+//!
+//! ```rust
+//! use knus::include::{MemoryLoader, Resolver};
+//!
+//! #[derive(knus::Decode, Debug)]
+//! struct Config {
+//!     #[knus(children(name="plugin"), unwrap(argument))]
+//!     plugins: Vec<String>,
+//! }
+//!
+//! let mut loader = MemoryLoader::new();
+//! loader.insert("config.kdl", r#"
+//!     plugin "fmt"
+//!     include "plugins/extra.kdl"
+//! "#);
+//! loader.insert("plugins/extra.kdl", r#"
+//!     plugin "lint"
+//! "#);
+//! let config: Config = Resolver::new(loader).parse("config.kdl")?;
+//! assert_eq!(config.plugins, ["fmt", "lint"]);
+//! # Ok::<(), knus::Error>(())
+//! ```
+//!
+//! The resulting document has [`FileSpan`] spans that refer to the file the
+//! node comes from, so errors show the snippets of the right files. Files
+//! that include themselves, directly or indirectly, are reported as errors.
+use std::collections::HashMap;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use miette::NamedSource;
+
+use crate::ast::{self, Document, Literal, Node, SpannedNode, Value};
+use crate::decode::{Context, Kind};
+use crate::errors::{DecodeError, Error, Warnings};
+use crate::span::{FileSpan, SourceFile, Span, Spanned};
+use crate::traits::DecodeChildren;
+use crate::wrappers::{self, ParseOptions};
+
+/// Source of the included files
+pub trait Loader {
+    /// Returns the path of the file included from the `base` file
+    ///
+    /// By default, relative paths are joined to the directory of the `base`
+    /// file and `.` and `..` components are removed.
+    fn resolve(&self, base: &Path, path: &str) -> PathBuf {
+        let dir = base.parent().unwrap_or_else(|| Path::new(""));
+        normalize(&dir.join(path))
+    }
+    /// Reads the text of the file
+    fn load(&self, path: &Path) -> io::Result<String>;
+}
+
+/// Loader that reads files from the filesystem
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct FsLoader;
+
+/// Loader that keeps the files in memory
+///
+/// Useful for tests and for documents that are not stored in files.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryLoader {
+    files: HashMap<PathBuf, String>,
+}
+
+/// Parses the documents replacing include nodes with included files
+#[derive(Debug, Clone)]
+pub struct Resolver<L> {
+    loader: L,
+    options: ParseOptions,
+    node_name: String,
+}
+
+struct Expander<'a, L> {
+    resolver: &'a Resolver<L>,
+    /// The chain of files being included, for detecting cycles
+    stack: Vec<PathBuf>,
+    errors: Vec<miette::Report>,
+}
+
+impl Loader for FsLoader {
+    fn load(&self, path: &Path) -> io::Result<String> {
+        std::fs::read_to_string(path)
+    }
+}
+
+impl MemoryLoader {
+    /// Creates a loader without files
+    pub fn new() -> MemoryLoader {
+        MemoryLoader::default()
+    }
+    /// Adds a file replacing the one at the same path
+    pub fn insert(&mut self, path: impl AsRef<Path>, text: impl Into<String>) {
+        self.files.insert(normalize(path.as_ref()), text.into());
+    }
+}
+
+impl Loader for MemoryLoader {
+    fn load(&self, path: &Path) -> io::Result<String> {
+        self.files
+            .get(&normalize(path))
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "file not found"))
+    }
+}
+
+impl<L: Loader> Resolver<L> {
+    /// Creates a resolver that reads files using the loader
+    pub fn new(loader: L) -> Resolver<L> {
+        Resolver {
+            loader,
+            options: ParseOptions::new(),
+            node_name: "include".into(),
+        }
+    }
+    /// Options used for parsing each file
+    pub fn parse_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+    /// Name of the include node (default is `include`)
+    pub fn node_name(mut self, name: impl Into<String>) -> Self {
+        self.node_name = name.into();
+        self
+    }
+
+    /// Parses the file and all the included files, returns the combined AST
+    pub fn parse_ast(&self, path: impl AsRef<Path>) -> Result<Document<FileSpan>, Error> {
+        self.parse_ast_file(path.as_ref())
+            .map(|(document, _)| document)
+    }
+
+    /// Parses the file and all the included files, and decodes Rust object
+    pub fn parse<T>(&self, path: impl AsRef<Path>) -> Result<T, Error>
+    where
+        T: DecodeChildren<FileSpan>,
+    {
+        self.parse_with_context(path, |_| {})
+            .map(|(value, _)| value)
+    }
+
+    /// Parses the file and all the included files, and decodes Rust object
+    /// providing extra context for the decoder
+    ///
+    /// Returns the warnings emitted to the context along with the value.
+    pub fn parse_with_context<T, F>(
+        &self,
+        path: impl AsRef<Path>,
+        set_ctx: F,
+    ) -> Result<(T, Warnings), Error>
+    where
+        F: FnOnce(&mut Context<FileSpan>),
+        T: DecodeChildren<FileSpan>,
+    {
+        let (document, root) = self.parse_ast_file(path.as_ref())?;
+        let result = decode(&document, named_source(&root), set_ctx);
+        ast::drop_nodes(document.nodes);
+        result
+    }
+
+    fn parse_ast_file(&self, path: &Path) -> Result<(Document<FileSpan>, Arc<SourceFile>), Error> {
+        let name = path.display().to_string();
+        let text = self.loader.load(path).map_err(|e| Error {
+            source_code: NamedSource::new(&name, String::new()),
+            errors: vec![DecodeError::<Span>::Custom(Box::new(io::Error::new(
+                e.kind(),
+                format!("cannot read `{}`: {}", name, e),
+            )))
+            .into()],
+        })?;
+        let document = self.options.parse_ast::<Span>(&name, &text)?;
+        let root = Arc::new(SourceFile { name, text });
+        let mut expander = Expander {
+            resolver: self,
+            stack: vec![normalize(path)],
+            errors: Vec::new(),
+        };
+        let nodes = expander.nodes(with_file(document.nodes, &root));
+        if expander.errors.is_empty() {
+            Ok((Document { nodes }, root))
+        } else {
+            Err(Error {
+                source_code: named_source(&root),
+                errors: expander.errors,
+            })
+        }
+    }
+}
+
+impl<L: Loader> Expander<'_, L> {
+    fn nodes(&mut self, nodes: Vec<SpannedNode<FileSpan>>) -> Vec<SpannedNode<FileSpan>> {
+        let mut result = Vec::with_capacity(nodes.len());
+        for mut node in nodes {
+            if **node.node_name == *self.resolver.node_name {
+                if let Some((path, relative)) = self.include_path(&node) {
+                    result.extend(self.include(&node, path, relative));
+                }
+                continue;
+            }
+            if let Some(children) = node.children.take() {
+                node.children = Some(children.map(|nodes| self.nodes(nodes)));
+            }
+            result.push(node);
+        }
+        result
+    }
+
+    /// Returns the argument of the include node, emitting errors if the node
+    /// has anything else
+    fn include_path<'n>(
+        &mut self,
+        node: &'n SpannedNode<FileSpan>,
+    ) -> Option<(&'n Spanned<Literal, FileSpan>, &'n str)> {
+        let mut errors = Vec::new();
+        if let Some(type_name) = &node.type_name {
+            errors.push(DecodeError::unexpected(
+                type_name,
+                "type name",
+                "include node can't have a type name",
+            ));
+        }
+        for name in node.properties.keys() {
+            errors.push(DecodeError::unexpected(
+                name,
+                "property",
+                format!("unexpected property `{}`", name.escape_default()),
+            ));
+        }
+        for child in node.children() {
+            errors.push(DecodeError::unexpected(
+                child,
+                "node",
+                format!("unexpected node `{}`", child.node_name.escape_default()),
+            ));
+        }
+        let mut args = node.arguments.iter();
+        let path = match args.next() {
+            Some(value) => match &*value.literal {
+                Literal::String(path) => Some((&value.literal, &path[..])),
+                _ => {
+                    errors.push(DecodeError::scalar_kind(Kind::String, &value.literal));
+                    None
+                }
+            },
+            None => {
+                errors.push(DecodeError::missing(
+                    node,
+                    "path of the included file is required",
+                ));
+                None
+            }
+        };
+        for arg in args {
+            errors.push(DecodeError::unexpected(
+                &arg.literal,
+                "argument",
+                "unexpected argument",
+            ));
+        }
+        if errors.is_empty() {
+            path
+        } else {
+            self.errors.extend(errors.into_iter().map(report));
+            None
+        }
+    }
+
+    fn include(
+        &mut self,
+        node: &SpannedNode<FileSpan>,
+        path: &Spanned<Literal, FileSpan>,
+        relative: &str,
+    ) -> Vec<SpannedNode<FileSpan>> {
+        let base = self.stack.last().expect("root file is on the stack");
+        let resolved = self.resolver.loader.resolve(base, relative);
+        if let Some(pos) = self.stack.iter().position(|p| *p == resolved) {
+            let chain = self.stack[pos..]
+                .iter()
+                .chain(Some(&resolved))
+                .map(|p| format!("`{}`", p.display()))
+                .collect::<Vec<_>>();
+            self.errors.push(report(DecodeError::unexpected(
+                node,
+                "include",
+                format!("include cycle: {}", chain.join(" -> ")),
+            )));
+            return Vec::new();
+        }
+        let text = match self.resolver.loader.load(&resolved) {
+            Ok(text) => text,
+            Err(e) => {
+                self.errors.push(report(DecodeError::conversion(
+                    path,
+                    format!("cannot read `{}`: {}", resolved.display(), e),
+                )));
+                return Vec::new();
+            }
+        };
+        let name = resolved.display().to_string();
+        let document = match self.resolver.options.parse_ast::<Span>(&name, &text) {
+            Ok(document) => document,
+            Err(e) => {
+                let source = e.source_code;
+                self.errors.extend(e.errors.into_iter().map(|report| {
+                    report.with_source_code(NamedSource::new(source.name(), text.clone()))
+                }));
+                return Vec::new();
+            }
+        };
+        let file = Arc::new(SourceFile { name, text });
+        self.stack.push(resolved);
+        let nodes = self.nodes(with_file(document.nodes, &file));
+        self.stack.pop();
+        nodes
+    }
+}
+
+/// Decodes the document made of several files
+pub(crate) fn decode<T, F>(
+    document: &Document<FileSpan>,
+    source_code: NamedSource<String>,
+    set_ctx: F,
+) -> Result<(T, Warnings), Error>
+where
+    F: FnOnce(&mut Context<FileSpan>),
+    T: DecodeChildren<FileSpan>,
+{
+    let mut ctx = Context::new();
+    set_ctx(&mut ctx);
+    let result = DecodeChildren::decode_children(&document.nodes, &mut ctx);
+    wrappers::finish(result, ctx, source_code, report, |warning| {
+        let mut file = None;
+        let warning = warning.map_span(|span| {
+            file = Some(span.file);
+            span.span
+        });
+        with_source(warning, file)
+    })
+}
+
+pub(crate) fn named_source(file: &SourceFile) -> NamedSource<String> {
+    NamedSource::new(&file.name, file.text.clone())
+}
+
+/// Converts the error into report that shows the snippet of the file the
+/// error is in
+fn report(error: DecodeError<FileSpan>) -> miette::Report {
+    let mut file = None;
+    let error = error.map_span(|span| {
+        file = Some(span.file);
+        span.span
+    });
+    with_source(error, file)
+}
+
+fn with_source<E>(error: E, file: Option<Arc<SourceFile>>) -> miette::Report
+where
+    E: miette::Diagnostic + Send + Sync + 'static,
+{
+    match file {
+        Some(file) => miette::Report::new(error).with_source_code(named_source(&file)),
+        None => miette::Report::new(error),
+    }
+}
+
+pub(crate) fn with_file(
+    nodes: Vec<SpannedNode<Span>>,
+    file: &Arc<SourceFile>,
+) -> Vec<SpannedNode<FileSpan>> {
+    let span = |span: Span| FileSpan {
+        file: file.clone(),
+        span,
+    };
+    let value = |value: Value<Span>| Value {
+        type_name: value.type_name.map(|t| t.map_span(span)),
+        literal: value.literal.map_span(span),
+    };
+    nodes
+        .into_iter()
+        .map(|node| {
+            node.map_span(span).map(|node| Node {
+                type_name: node.type_name.map(|t| t.map_span(span)),
+                node_name: node.node_name.map_span(span),
+                arguments: node.arguments.into_iter().map(value).collect(),
+                properties: node
+                    .properties
+                    .into_iter()
+                    .map(|(name, v)| (name.map_span(span), value(v)))
+                    .collect(),
+                children: node
+                    .children
+                    .map(|c| c.map_span(span).map(|nodes| with_file(nodes, file))),
+            })
+        })
+        .collect()
+}
+
+/// Removes `.` and `..` components from the path, where possible
+fn normalize(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match result.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    result.pop();
+                }
+                // parent of the root is the root itself
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => result.push(component),
+            },
+            component => result.push(component),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+
+    use super::{normalize, MemoryLoader, Resolver};
+    use crate::errors::Error;
+    use crate::span::{FileSpan, Span};
+
+    fn loader(files: &[(&str, &str)]) -> Resolver<MemoryLoader> {
+        let mut loader = MemoryLoader::new();
+        for (path, text) in files {
+            loader.insert(path, *text);
+        }
+        Resolver::new(loader)
+    }
+
+    fn names(resolver: &Resolver<MemoryLoader>) -> Vec<String> {
+        let document = resolver.parse_ast("main.kdl").unwrap();
+        document
+            .nodes
+            .iter()
+            .map(|n| n.node_name.to_string())
+            .collect()
+    }
+
+    fn render(err: &Error) -> String {
+        let mut buf = String::new();
+        miette::NarratableReportHandler::new()
+            .render_report(&mut buf, err)
+            .unwrap();
+        buf
+    }
+
+    fn messages(err: &Error) -> Vec<String> {
+        miette::Diagnostic::related(err)
+            .unwrap()
+            .map(|e| e.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn splice() {
+        let resolver = loader(&[
+            (
+                "conf/main.kdl",
+                r#"
+                a
+                include "plugins.kdl"
+                server {
+                    include "../shared/./port.kdl"
+                }
+                d
+            "#,
+            ),
+            ("conf/plugins.kdl", r#"b; include "more/c.kdl""#),
+            ("conf/more/c.kdl", "c"),
+            ("shared/port.kdl", "port 8080"),
+        ]);
+        let document = resolver.parse_ast("conf/main.kdl").unwrap();
+        let nodes = document
+            .nodes
+            .iter()
+            .map(|n| (&n.node_name[..], n.span().file.name.clone()))
+            .collect::<Vec<_>>();
+        let main = "conf/main.kdl".to_string();
+        let plugins = Path::new("conf").join("plugins.kdl");
+        let c = Path::new("conf").join("more").join("c.kdl");
+        assert_eq!(
+            nodes,
+            [
+                ("a", main.clone()),
+                ("b", plugins.display().to_string()),
+                ("c", c.display().to_string()),
+                ("server", main.clone()),
+                ("d", main),
+            ]
+        );
+        let port = document.nodes[3].children().next().unwrap();
+        assert_eq!(&port.node_name[..], "port");
+        assert_eq!(
+            port.span().file.name,
+            Path::new("shared").join("port.kdl").display().to_string()
+        );
+        assert_eq!(port.node_name.span().span, Span(0, 4));
+    }
+
+    #[test]
+    fn repeated_include() {
+        let resolver = loader(&[
+            ("main.kdl", r#"include "a.kdl"; include "b.kdl""#),
+            ("a.kdl", r#"include "c.kdl""#),
+            ("b.kdl", r#"include "c.kdl""#),
+            ("c.kdl", "c"),
+        ]);
+        assert_eq!(names(&resolver), ["c", "c"]);
+    }
+
+    #[test]
+    fn custom_node_name() {
+        let resolver = loader(&[
+            ("main.kdl", r#"import "a.kdl"; include "b.kdl""#),
+            ("a.kdl", "a"),
+        ])
+        .node_name("import");
+        assert_eq!(names(&resolver), ["a", "include"]);
+    }
+
+    #[test]
+    fn cycle() {
+        let resolver = loader(&[
+            ("main.kdl", r#"include "a.kdl""#),
+            ("a.kdl", r#"include "sub/b.kdl""#),
+            ("sub/b.kdl", r#"include "../a.kdl""#),
+        ]);
+        let err = resolver.parse_ast("main.kdl").unwrap_err();
+        let b = Path::new("sub").join("b.kdl");
+        assert_eq!(
+            messages(&err),
+            [format!(
+                "include cycle: `a.kdl` -> `{}` -> `a.kdl`",
+                b.display()
+            )]
+        );
+        assert!(render(&err).contains(&format!("snippet for {}", b.display())));
+
+        let resolver = loader(&[("main.kdl", r#"include "main.kdl""#)]);
+        let err = resolver.parse_ast("main.kdl").unwrap_err();
+        assert_eq!(messages(&err), ["include cycle: `main.kdl` -> `main.kdl`"]);
+    }
+
+    #[test]
+    fn errors() {
+        let resolver = loader(&[(
+            "main.kdl",
+            r#"
+            include "missing.kdl"
+            include
+            include 1
+            include "a.kdl" "b.kdl" x=1 { y; }
+            "#,
+        )]);
+        let err = resolver.parse_ast("main.kdl").unwrap_err();
+        assert_eq!(
+            messages(&err),
+            [
+                "cannot read `missing.kdl`: file not found",
+                "path of the included file is required",
+                "expected string scalar, found integer",
+                "unexpected property `x`",
+                "unexpected node `y`",
+                "unexpected argument",
+            ]
+        );
+
+        let err = loader(&[]).parse_ast("main.kdl").unwrap_err();
+        assert_eq!(messages(&err), ["cannot read `main.kdl`: file not found"]);
+    }
+
+    #[test]
+    fn syntax_errors_in_included_files() {
+        let resolver = loader(&[
+            ("main.kdl", r#"a; include "bad.kdl"; include "b.kdl""#),
+            ("bad.kdl", "node {"),
+            ("b.kdl", "b"),
+        ]);
+        let err = resolver.parse_ast("main.kdl").unwrap_err();
+        let text = render(&err);
+        assert!(text.contains("snippet for bad.kdl"), "{}", text);
+        assert!(text.contains("snippet line 1: node {"), "{}", text);
+    }
+
+    #[test]
+    fn warnings_in_included_files() {
+        struct Deprecated;
+        impl crate::Decode<FileSpan> for Deprecated {
+            fn decode_node(
+                node: &crate::ast::SpannedNode<FileSpan>,
+                ctx: &mut crate::decode::Context<FileSpan>,
+            ) -> Result<Self, crate::errors::DecodeError<FileSpan>> {
+                ctx.emit_warning(crate::errors::DecodeError::unexpected(
+                    &node.node_name,
+                    "node",
+                    "deprecated node",
+                ));
+                Ok(Deprecated)
+            }
+        }
+        let resolver = loader(&[("main.kdl", r#"include "old.kdl""#), ("old.kdl", "old")]);
+        let (nodes, warnings) = resolver
+            .parse_with_context::<Vec<Deprecated>, _>("main.kdl", |_| {})
+            .unwrap();
+        assert_eq!(nodes.len(), 1);
+        let mut text = String::new();
+        miette::NarratableReportHandler::new()
+            .render_report(&mut text, &warnings)
+            .unwrap();
+        assert!(text.contains("snippet for old.kdl"), "{}", text);
+        assert!(text.contains("deprecated node"), "{}", text);
+    }
+
+    #[test]
+    fn normalize_paths() {
+        assert_eq!(normalize(Path::new("a/./b/../c")), PathBuf::from("a/c"));
+        assert_eq!(normalize(Path::new("../a/..")), PathBuf::from(".."));
+        assert_eq!(normalize(Path::new("/a/../../b")), PathBuf::from("/b"));
+    }
+}
@@ -0,0 +1,472 @@
+//! Variables in string values
+//!
+//! Values decoded as [`Interpolated`] have `${NAME}` placeholders replaced
+//! with the values of the variables, and values with `(env)` type name are
+//! replaced by the variable as a whole:
+//! ```kdl
+//! data-dir "${HOME}/data"
+//! database (env)"DATABASE_URL"
+//! ```
+//!
+//! Variables are looked up in the [`Variables`] placed into the context
+//! using [`parse_with_context`](crate::parse_with_context), or in the
+//! environment of the process if there are no variables in the context. `$$`
+//! is replaced by a single `$`, and `$` not followed by `{` is left as is.
+//!
+//! ```rust
+//! use std::path::PathBuf;
+//! use knus::interpolate::{Interpolated, Variables};
+//!
+//! #[derive(knus::Decode, Debug)]
+//! struct Config {
+//!     #[knus(child, unwrap(argument))]
+//!     data_dir: Interpolated<PathBuf>,
+//!     #[knus(child, unwrap(argument))]
+//!     database: Interpolated<String>,
+//! }
+//!
+//! let (config, _warnings) = knus::parse_with_context::<Config, knus::span::Span, _>(
+//!     "config.kdl",
+//!     r#"
+//!         data-dir "${HOME}/data"
+//!         database (env)"DATABASE_URL"
+//!     "#,
+//!     |ctx| {
+//!         ctx.set(Variables::from_iter([
+//!             ("HOME", "/home/user"),
+//!             ("DATABASE_URL", "postgres://localhost/app"),
+//!         ]))
+//!     },
+//! )?;
+//! assert_eq!(*config.data_dir, PathBuf::from("/home/user/data"));
+//! assert_eq!(*config.database, "postgres://localhost/app");
+//! # Ok::<(), knus::Error>(())
+//! ```
+//!
+//! Undefined variables and malformed placeholders are reported as
+//! [`DecodeError::Conversion`] pointing at the placeholder.
+use std::any::Any;
+use std::collections::HashMap;
+use std::error::Error;
+use std::iter::FromIterator;
+use std::ops::{Deref, DerefMut, Range};
+use std::str::FromStr;
+
+use crate::ast::{Literal, TypeName, Value};
+use crate::decode::{Context, Kind};
+use crate::errors::{DecodeError, ExpectedType, InterpolationError};
+use crate::schema::Validations;
+use crate::span::{FileSpan, LineSpan, Span, Spanned};
+use crate::traits::{DecodeScalar, ErrorSpan, ScalarSchema};
+
+/// Type name of the value that is the name of the variable
+pub const ENV: &str = "env";
+
+/// Value that has variables replaced before it's decoded
+///
+/// `T` is parsed using [`FromStr`] from the interpolated string. Values that
+/// aren't strings are decoded by the [`DecodeScalar`] of `T`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+pub struct Interpolated<T>(pub T);
+
+/// Values of the variables used in [`Interpolated`] values
+///
+/// Put into the context to override the environment variables.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Variables {
+    values: HashMap<String, String>,
+    env: bool,
+}
+
+impl<T> Interpolated<T> {
+    /// Returns the decoded value
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Interpolated<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Interpolated<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl Variables {
+    /// Creates an empty set of variables that falls back to the environment
+    pub fn new() -> Variables {
+        Variables {
+            values: HashMap::new(),
+            env: true,
+        }
+    }
+    /// Disables looking up the variables in the environment
+    pub fn without_env(mut self) -> Self {
+        self.env = false;
+        self
+    }
+    /// Sets the value of the variable
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.values.insert(name.into(), value.into());
+    }
+    /// Returns the value of the variable
+    ///
+    /// Environment variables that aren't valid unicode are not defined.
+    pub fn get(&self, name: &str) -> Option<String> {
+        match self.values.get(name) {
+            Some(value) => Some(value.clone()),
+            None if self.env => std::env::var(name).ok(),
+            None => None,
+        }
+    }
+}
+
+impl Default for Variables {
+    fn default() -> Variables {
+        Variables::new()
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for Variables {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Variables {
+        let mut variables = Variables::new();
+        for (name, value) in iter {
+            variables.insert(name, value);
+        }
+        variables
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> Extend<(K, V)> for Variables {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (name, value) in iter {
+            self.insert(name, value);
+        }
+    }
+}
+
+/// Replaces `${NAME}` placeholders in the string value
+///
+/// Useful for implementing [`DecodeScalar`] manually. Variables are taken
+/// from [`Variables`] in the context or from the environment.
+pub fn interpolate<S: ErrorSpan>(
+    value: &Spanned<Literal, S>,
+    ctx: &Context<S>,
+) -> Result<String, DecodeError<S>> {
+    let text = match &**value {
+        Literal::String(text) => text,
+        _ => return Err(DecodeError::scalar_kind(Kind::String, value)),
+    };
+    let mut result = String::with_capacity(text.len());
+    let mut pos = 0;
+    while let Some(idx) = text[pos..].find('$') {
+        let start = pos + idx;
+        result.push_str(&text[pos..start]);
+        let rest = &text[start + 1..];
+        if rest.starts_with('$') {
+            result.push('$');
+            pos = start + 2;
+        } else if rest.starts_with('{') {
+            let close = match rest.find('}') {
+                Some(close) => close,
+                None => {
+                    let err = InterpolationError::Unclosed;
+                    return Err(placeholder_error(value, start..text.len(), err));
+                }
+            };
+            let name = &rest[1..close];
+            let end = start + close + 2;
+            if !is_valid_name(name) {
+                let err = InterpolationError::InvalidName(name.into());
+                return Err(placeholder_error(value, start..end, err));
+            }
+            match lookup(name, ctx) {
+                Some(var) => result.push_str(&var),
+                None => {
+                    let err = InterpolationError::Undefined(name.into());
+                    return Err(placeholder_error(value, start..end, err));
+                }
+            }
+            pos = end;
+        } else {
+            result.push('$');
+            pos = start + 1;
+        }
+    }
+    result.push_str(&text[pos..]);
+    Ok(result)
+}
+
+/// Returns the value of the variable named by the `(env)` value
+fn env_value<S: ErrorSpan>(
+    value: &Spanned<Literal, S>,
+    ctx: &Context<S>,
+) -> Result<String, DecodeError<S>> {
+    let name = match &**value {
+        Literal::String(name) => name,
+        _ => return Err(DecodeError::scalar_kind(Kind::String, value)),
+    };
+    if !is_valid_name(name) {
+        let err = InterpolationError::InvalidName(name.to_string());
+        return Err(DecodeError::conversion(value, err));
+    }
+    lookup(name, ctx).ok_or_else(|| {
+        DecodeError::conversion(value, InterpolationError::Undefined(name.to_string()))
+    })
+}
+
+fn lookup<S: ErrorSpan>(name: &str, ctx: &Context<S>) -> Option<String> {
+    match ctx.get::<Variables>() {
+        Some(variables) => variables.get(name),
+        None => std::env::var(name).ok(),
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '.' | '-'))
+}
+
+/// Creates an error pointing at the placeholder inside of the string
+///
+/// Points at the whole string if the position of the placeholder in the
+/// source is unknown, i.e. the string has escapes or a custom span type.
+fn placeholder_error<S: ErrorSpan>(
+    value: &Spanned<Literal, S>,
+    range: Range<usize>,
+    err: InterpolationError,
+) -> DecodeError<S> {
+    let text = match &**value {
+        Literal::String(text) => text,
+        _ => "",
+    };
+    let span = value.span();
+    let source: miette::SourceSpan = span.clone().into();
+    let source_len = source.len();
+    // only plain `"strings"` and bare identifiers are mapped exactly
+    let quote = match source_len.checked_sub(text.len()) {
+        Some(2) => 1,
+        Some(0) => 0,
+        _ => return DecodeError::conversion(value, err),
+    };
+    let ascii = text[..range.end].is_ascii();
+    let range = range.start + quote..range.end + quote;
+    match sub_span(span, range, ascii) {
+        Some(span) => DecodeError::Conversion {
+            span,
+            source: Box::new(err),
+        },
+        None => DecodeError::conversion(value, err),
+    }
+}
+
+/// Returns the part of the known span types
+///
+/// Columns of [`LineSpan`] are only known if the text before the end of the
+/// range is `ascii`.
+fn sub_span<S: ErrorSpan>(span: &S, range: Range<usize>, ascii: bool) -> Option<S> {
+    let span: &dyn Any = span;
+    let result: Box<dyn Any> = if let Some(span) = span.downcast_ref::<Span>() {
+        Box::new(Span(span.0 + range.start, span.0 + range.end))
+    } else if let Some(span) = span.downcast_ref::<FileSpan>() {
+        Box::new(FileSpan {
+            file: span.file.clone(),
+            span: Span(span.span.0 + range.start, span.span.0 + range.end),
+        })
+    } else if let Some(span) = span.downcast_ref::<LineSpan>() {
+        if !ascii || span.0.line != span.1.line {
+            return None;
+        }
+        let mut start = span.0;
+        start.offset += range.start;
+        start.column += range.start;
+        let mut end = span.0;
+        end.offset += range.end;
+        end.column += range.end;
+        Box::new(LineSpan(start, end))
+    } else {
+        return None;
+    };
+    result.downcast().ok().map(|span| *span)
+}
+
+impl<S, T> DecodeScalar<S> for Interpolated<T>
+where
+    S: ErrorSpan,
+    T: DecodeScalar<S> + FromStr,
+    T::Err: Into<Box<dyn Error + Send + Sync + 'static>>,
+{
+    fn type_check(type_name: &Option<Spanned<TypeName, S>>, ctx: &mut Context<S>) {
+        if let Some(typ) = type_name {
+            if typ.as_str() != ENV {
+                ctx.emit_error(DecodeError::TypeName {
+                    span: typ.span().clone(),
+                    found: Some(typ.value.clone()),
+                    expected: ExpectedType::optional(TypeName::from_string(ENV.into())),
+                    rust_type: "Interpolated",
+                });
+            }
+        }
+    }
+    fn raw_decode(
+        value: &Spanned<Literal, S>,
+        ctx: &mut Context<S>,
+    ) -> Result<Self, DecodeError<S>> {
+        match &**value {
+            Literal::String(_) => {
+                let text = interpolate(value, ctx)?;
+                parse(value, &text)
+            }
+            _ => T::raw_decode(value, ctx).map(Interpolated),
+        }
+    }
+    fn decode(value: &Value<S>, ctx: &mut Context<S>) -> Result<Self, DecodeError<S>> {
+        Self::type_check(&value.type_name, ctx);
+        match &value.type_name {
+            Some(typ) if typ.as_str() == ENV => {
+                let text = env_value(&value.literal, ctx)?;
+                parse(&value.literal, &text)
+            }
+            _ => Self::raw_decode(&value.literal, ctx),
+        }
+    }
+}
+
+fn parse<S, T>(value: &Spanned<Literal, S>, text: &str) -> Result<Interpolated<T>, DecodeError<S>>
+where
+    S: ErrorSpan,
+    T: FromStr,
+    T::Err: Into<Box<dyn Error + Send + Sync + 'static>>,
+{
+    text.parse()
+        .map(Interpolated)
+        .map_err(|e| DecodeError::conversion(value, e))
+}
+
+impl<T> ScalarSchema for Interpolated<T> {
+    fn validations() -> Validations {
+        // any string can become a valid value after interpolation
+        Validations::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ast::Value;
+    use crate::decode::Context;
+    use crate::errors::DecodeError;
+    use crate::span::Span;
+    use crate::traits::DecodeScalar;
+
+    use super::{Interpolated, Variables};
+
+    fn value(text: &str) -> Value<Span> {
+        let document = crate::parse_ast::<Span>("<test>", &format!("- {}", text)).unwrap();
+        document.nodes[0].arguments[0].clone()
+    }
+
+    fn decode<T>(text: &str) -> Result<T, (String, Span)>
+    where
+        Interpolated<T>: DecodeScalar<Span>,
+    {
+        let mut ctx = Context::new();
+        ctx.set(Variables::from_iter([("HOME", "/home/user"), ("port", "8080")]).without_env());
+        let result = Interpolated::<T>::decode(&value(text), &mut ctx);
+        let mut errors = ctx.into_errors();
+        match result {
+            Ok(value) if errors.is_empty() => Ok(value.0),
+            Ok(_) => Err(error(errors.remove(0))),
+            Err(e) => Err(error(e)),
+        }
+    }
+
+    fn error(err: DecodeError<Span>) -> (String, Span) {
+        match err {
+            DecodeError::Conversion { span, source } => (source.to_string(), span),
+            DecodeError::TypeName { span, .. } | DecodeError::ScalarKind { span, .. } => {
+                (err.to_string(), span)
+            }
+            err => panic!("unexpected error {:?}", err),
+        }
+    }
+
+    #[test]
+    fn placeholders() {
+        assert_eq!(
+            decode::<String>(r#""${HOME}/data""#).unwrap(),
+            "/home/user/data"
+        );
+        assert_eq!(
+            decode::<String>(r#""${HOME}${HOME}""#).unwrap(),
+            "/home/user/home/user"
+        );
+        assert_eq!(decode::<String>(r#""$$HOME $x $""#).unwrap(), "$HOME $x $");
+        assert_eq!(decode::<String>(r#""plain""#).unwrap(), "plain");
+        assert_eq!(decode::<u16>(r#""${port}""#).unwrap(), 8080);
+        assert_eq!(decode::<u16>("1234").unwrap(), 1234);
+    }
+
+    #[test]
+    fn env() {
+        assert_eq!(decode::<String>(r#"(env)"HOME""#).unwrap(), "/home/user");
+        // the variable value isn't interpolated
+        assert_eq!(decode::<u16>(r#"(env)"port""#).unwrap(), 8080);
+        assert_eq!(
+            decode::<String>(r#"(env)"NOPE""#).unwrap_err(),
+            ("variable `NOPE` is not defined".into(), Span(7, 13))
+        );
+        assert_eq!(
+            decode::<String>(r#"(path)"HOME""#).unwrap_err().1,
+            Span(2, 8)
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            decode::<String>(r#""a/${NOPE}/b""#).unwrap_err(),
+            ("variable `NOPE` is not defined".into(), Span(5, 12))
+        );
+        assert_eq!(
+            decode::<String>(r#""a ${HOME""#).unwrap_err(),
+            ("unclosed placeholder, expected `}`".into(), Span(5, 11))
+        );
+        assert_eq!(
+            decode::<String>(r#""${}""#).unwrap_err(),
+            ("invalid variable name ``".into(), Span(3, 6))
+        );
+        assert_eq!(
+            decode::<u16>(r#""${HOME}""#).unwrap_err(),
+            ("invalid digit found in string".into(), Span(2, 11))
+        );
+        // position in the escaped string is unknown
+        assert_eq!(
+            decode::<String>(r#""\t${NOPE}""#).unwrap_err().1,
+            Span(2, 13)
+        );
+        assert_eq!(
+            decode::<String>(r#"r"${NOPE}""#).unwrap_err().1,
+            Span(2, 12)
+        );
+    }
+
+    #[test]
+    fn environment() {
+        let mut ctx = Context::<Span>::new();
+        let path = std::env::var("PATH").unwrap();
+        let result = Interpolated::<String>::decode(&value(r#""${PATH}""#), &mut ctx);
+        assert_eq!(result.unwrap().0, path);
+
+        ctx.set(Variables::new());
+        let result = Interpolated::<String>::decode(&value(r#"(env)"PATH""#), &mut ctx);
+        assert_eq!(result.unwrap().0, path);
+    }
+}
@@ -0,0 +1,618 @@
+//! JSON-in-KDL (JiK) conversion
+//!
+//! This module is only available with the `serde_json` feature. It converts
+//! between [`serde_json::Value`] and KDL documents as defined by the
+//! [JiK](https://github.com/kdl-org/kdl/blob/main/JSON-IN-KDL.md)
+//! specification:
+//!
+//! ```rust
+//! let value = knus::jik::parse("data.kdl", r#"
+//!     - name="knus" {
+//!         tags "kdl" "parser"
+//!         (array)versions 3
+//!         deps {
+//!             - name="miette"
+//!         }
+//!     }
+//! "#)?;
+//! assert_eq!(value, serde_json::json!({
+//!     "name": "knus",
+//!     "tags": ["kdl", "parser"],
+//!     "versions": [3],
+//!     "deps": [{"name": "miette"}],
+//! }));
+//!
+//! let document = knus::jik::from_json(&value);
+//! assert_eq!(knus::jik::to_json(&document).unwrap(), value);
+//! # Ok::<(), knus::Error>(())
+//! ```
+//!
+//! # Mapping
+//!
+//! The document consists of a single node, the name of which is ignored
+//! (`-` by convention). Each node is one of:
+//!
+//! * Literal: a node with a single argument, e.g. `- "text"` or `- null`.
+//! * Array: a node with arguments and children named `-`, that are
+//!   concatenated, e.g. `- 1 2 3` or `- { - 1; - a=1 }`.
+//! * Object: a node with properties and children, where the property and
+//!   the node names are keys, e.g. `- a=1 { b 2 }`.
+//!
+//! The `(array)` and `(object)` type names are needed for empty arrays and
+//! objects, for single-element arrays, and for objects having only children
+//! named `-`. Type names of the arguments and properties are ignored.
+//!
+//! # Numbers
+//!
+//! Integers of any radix become decimal JSON integers. By default numbers are
+//! stored the way `serde_json` stores them: integers that don't fit into
+//! `i64` or `u64` and all decimals become `f64` and may lose precision.
+//!
+//! Enable the `jik-arbitrary-precision` feature (it turns on the
+//! `arbitrary_precision` feature of `serde_json`) to convert numbers exactly:
+//! integers of any size are kept and decimals keep their digits. Note that
+//! this changes how `serde_json` works for the whole program. Infinity and NaN
+//! can't be represented in JSON, so they are errors.
+use std::str::FromStr;
+
+use miette::NamedSource;
+use serde_json::{Map, Number, Value as Json};
+
+use crate::ast::{self, Decimal, Document, Integer, Literal, Node, Radix};
+use crate::ast::{SpannedNode, TypeName, Value};
+use crate::decode::{self, Context};
+use crate::errors::{DecodeError, Error};
+use crate::span::{Span, Spanned};
+use crate::traits::{Decode, ErrorSpan};
+use crate::wrappers::ParseOptions;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Literal,
+    Array,
+    Object,
+}
+
+/// Converts the JiK document into JSON value
+///
+/// The document must have exactly one node.
+pub fn to_json<S: ErrorSpan>(document: &Document<S>) -> Result<Json, Vec<DecodeError<S>>> {
+    match &document.nodes[..] {
+        [] => Err(vec![DecodeError::MissingNode {
+            message: "JiK document must contain a node".into(),
+        }]),
+        [node] => decode::node(node),
+        [_, rest @ ..] => Err(rest
+            .iter()
+            .map(|node| {
+                DecodeError::unexpected(node, "node", "JiK document must contain a single node")
+            })
+            .collect()),
+    }
+}
+
+/// Parses KDL text and converts it into JSON value
+pub fn parse(file_name: impl AsRef<str>, text: &str) -> Result<Json, Error> {
+    let document = ParseOptions::new().parse_ast::<Span>(file_name.as_ref(), text)?;
+    let result = to_json(&document);
+    ast::drop_nodes(document.nodes);
+    result.map_err(|errors| Error {
+        source_code: NamedSource::new(file_name, text.to_string()),
+        errors: errors.into_iter().map(Into::into).collect(),
+    })
+}
+
+/// Converts JSON value into the JiK document
+///
+/// Arrays and objects that contain only literals are written as arguments
+/// and properties respectively, others are written as children.
+pub fn from_json(value: &Json) -> Document<Span> {
+    Document {
+        nodes: vec![Spanned::new(node("-", value), Span::default())],
+    }
+}
+
+impl<S: ErrorSpan> Decode<S> for Json {
+    fn decode_node(node: &SpannedNode<S>, ctx: &mut Context<S>) -> Result<Self, DecodeError<S>> {
+        Ok(node_value(node, ctx))
+    }
+}
+
+fn node_kind<S: ErrorSpan>(node: &SpannedNode<S>, ctx: &mut Context<S>) -> Option<Kind> {
+    if let Some(type_name) = &node.type_name {
+        match type_name.as_str() {
+            "array" => return Some(Kind::Array),
+            "object" => return Some(Kind::Object),
+            _ => {
+                ctx.emit_error(DecodeError::unexpected(
+                    type_name,
+                    "type name",
+                    "expected `array` or `object` type name",
+                ));
+                return None;
+            }
+        }
+    }
+    let has_children = node.children().len() > 0;
+    match (node.arguments.len(), node.properties.is_empty()) {
+        (0, true) if !has_children => {
+            ctx.emit_error(DecodeError::unexpected(
+                &node.node_name,
+                "node",
+                "empty node must have `(array)` or `(object)` type name",
+            ));
+            None
+        }
+        (0, true) => {
+            if node.children().all(|child| &**child.node_name == "-") {
+                Some(Kind::Array)
+            } else {
+                Some(Kind::Object)
+            }
+        }
+        (0, false) => Some(Kind::Object),
+        (1, true) if !has_children => Some(Kind::Literal),
+        (_, true) => Some(Kind::Array),
+        (_, false) => {
+            ctx.emit_error(DecodeError::unexpected(
+                &node.node_name,
+                "node",
+                "node can't have both arguments and properties, \
+                 it must be either an array or an object",
+            ));
+            None
+        }
+    }
+}
+
+/// Array or object, the elements of which are being converted
+enum Container {
+    Array(Vec<Json>),
+    Object(Map<String, Json>),
+}
+
+struct Frame<'a, S> {
+    container: Container,
+    children: std::slice::Iter<'a, SpannedNode<S>>,
+    /// Child node the value of which is being converted
+    child: Option<&'a SpannedNode<S>>,
+}
+
+fn node_value<S: ErrorSpan>(node: &SpannedNode<S>, ctx: &mut Context<S>) -> Json {
+    // Children are converted using an explicit stack, so that deeply nested
+    // documents don't overflow the stack
+    let mut stack = Vec::<Frame<S>>::new();
+    let mut next = Some(node);
+    let mut value = None;
+    loop {
+        if let Some(node) = next.take() {
+            match node_kind(node, ctx) {
+                None => value = Some(Json::Null),
+                Some(Kind::Literal) => value = Some(literal_value(&node.arguments[0], ctx)),
+                Some(kind) => {
+                    let container = match kind {
+                        Kind::Array => array(node, ctx),
+                        _ => object(node, ctx),
+                    };
+                    stack.push(Frame {
+                        container,
+                        children: children(node).iter(),
+                        child: None,
+                    });
+                }
+            }
+        }
+        let frame = match stack.last_mut() {
+            Some(frame) => frame,
+            None => return value.unwrap_or(Json::Null),
+        };
+        if let (Some(value), Some(child)) = (value.take(), frame.child.take()) {
+            match &mut frame.container {
+                Container::Array(array) => array.push(value),
+                Container::Object(object) => {
+                    if object.insert(child.node_name.to_string(), value).is_some() {
+                        ctx.emit_error(DecodeError::unexpected(
+                            &child.node_name,
+                            "node",
+                            format!("duplicate key `{}`", child.node_name.escape_default()),
+                        ));
+                    }
+                }
+            }
+        }
+        match frame.children.next() {
+            Some(child) => {
+                if matches!(frame.container, Container::Array(_)) && &**child.node_name != "-" {
+                    ctx.emit_error(DecodeError::unexpected(
+                        &child.node_name,
+                        "node",
+                        "array elements must be named `-`",
+                    ));
+                }
+                frame.child = Some(child);
+                next = Some(child);
+            }
+            None => {
+                let frame = stack.pop().expect("frame is on the stack");
+                value = Some(match frame.container {
+                    Container::Array(array) => Json::Array(array),
+                    Container::Object(object) => Json::Object(object),
+                });
+            }
+        }
+    }
+}
+
+fn children<S>(node: &Node<S>) -> &[SpannedNode<S>] {
+    node.children.as_ref().map_or(&[], |children| &children[..])
+}
+
+/// Starts an array with the arguments of the node
+fn array<S: ErrorSpan>(node: &SpannedNode<S>, ctx: &mut Context<S>) -> Container {
+    for name in node.properties.keys() {
+        ctx.emit_error(DecodeError::unexpected(
+            name,
+            "property",
+            "arrays can't have properties",
+        ));
+    }
+    let mut array = Vec::with_capacity(node.arguments.len() + node.children().len());
+    for arg in &node.arguments {
+        array.push(literal_value(arg, ctx));
+    }
+    Container::Array(array)
+}
+
+/// Starts an object with the properties of the node
+fn object<S: ErrorSpan>(node: &SpannedNode<S>, ctx: &mut Context<S>) -> Container {
+    for arg in &node.arguments {
+        ctx.emit_error(DecodeError::unexpected(
+            &arg.literal,
+            "argument",
+            "objects can't have arguments",
+        ));
+    }
+    let mut object = Map::new();
+    for (name, value) in &node.properties {
+        object.insert(name.to_string(), literal_value(value, ctx));
+    }
+    Container::Object(object)
+}
+
+fn literal_value<S: ErrorSpan>(value: &Value<S>, ctx: &mut Context<S>) -> Json {
+    let number = match &*value.literal {
+        Literal::Null => return Json::Null,
+        Literal::Bool(value) => return Json::Bool(*value),
+        Literal::String(value) => return Json::String(value.to_string()),
+        Literal::Int(value) => Some(integer_text(value)),
+        Literal::Decimal(value) => decimal_text(value),
+    };
+    match number.as_deref().map(Number::from_str) {
+        Some(Ok(number)) => Json::Number(number),
+        Some(Err(e)) => {
+            ctx.emit_error(DecodeError::conversion(&value.literal, e));
+            Json::Null
+        }
+        None => {
+            ctx.emit_error(DecodeError::conversion(
+                &value.literal,
+                "infinity and NaN can't be represented in JSON",
+            ));
+            Json::Null
+        }
+    }
+}
+
+/// Returns decimal digits of the integer with an optional minus sign
+fn integer_text(value: &Integer) -> String {
+    let Integer(radix, text) = value;
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let base = match radix {
+        Radix::Bin => 2,
+        Radix::Oct => 8,
+        Radix::Dec => 10,
+        Radix::Hex => 16,
+    };
+    // little-endian decimal digits
+    let mut decimal = vec![0u8];
+    for digit in digits.chars().filter_map(|c| c.to_digit(base)) {
+        let mut carry = digit;
+        for item in decimal.iter_mut() {
+            let value = u32::from(*item) * base + carry;
+            *item = (value % 10) as u8;
+            carry = value / 10;
+        }
+        while carry > 0 {
+            decimal.push((carry % 10) as u8);
+            carry /= 10;
+        }
+    }
+    while decimal.len() > 1 && decimal.last() == Some(&0) {
+        decimal.pop();
+    }
+    let mut result = String::with_capacity(decimal.len() + 1);
+    if negative && decimal != [0] {
+        result.push('-');
+    }
+    result.extend(decimal.iter().rev().map(|&d| char::from(b'0' + d)));
+    result
+}
+
+/// Returns the decimal in JSON syntax, or `None` for infinity and NaN
+fn decimal_text(value: &Decimal) -> Option<String> {
+    let text = value.0.strip_prefix('+').unwrap_or(&value.0);
+    let (sign, text) = match text.strip_prefix('-') {
+        Some(text) => ("-", text),
+        None => ("", text),
+    };
+    if !text.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    let (mantissa, exponent) = match text.find(['e', 'E']) {
+        Some(pos) => (&text[..pos], &text[pos + 1..]),
+        None => (text, ""),
+    };
+    let (int, fraction) = match mantissa.find('.') {
+        Some(pos) => (&mantissa[..pos], &mantissa[pos..]),
+        None => (mantissa, ""),
+    };
+    let int = int.trim_start_matches('0');
+    let int = if int.is_empty() { "0" } else { int };
+    let mut result = format!("{}{}{}", sign, int, fraction);
+    // KDL 1.0 allows exponent without digits
+    if exponent.trim_start_matches(['+', '-']).is_empty() {
+        if fraction.is_empty() {
+            result.push_str(".0");
+        }
+    } else {
+        result.push('e');
+        result.push_str(exponent);
+    }
+    Some(result)
+}
+
+fn literal(value: &Json) -> Option<Literal> {
+    match value {
+        Json::Null => Some(Literal::Null),
+        Json::Bool(value) => Some(Literal::Bool(*value)),
+        Json::Number(value) => {
+            let text = value.to_string();
+            if text.contains(['.', 'e', 'E']) {
+                Some(Literal::Decimal(Decimal(text.into())))
+            } else {
+                Some(Literal::Int(Integer(Radix::Dec, text.into())))
+            }
+        }
+        Json::String(value) => Some(Literal::String(value[..].into())),
+        Json::Array(_) | Json::Object(_) => None,
+    }
+}
+
+fn node(name: &str, value: &Json) -> Node<Span> {
+    let mut node = Node::new(name);
+    let annotate = match value {
+        Json::Array(items) => {
+            match items.iter().map(literal).collect::<Option<Vec<_>>>() {
+                Some(literals) => {
+                    node.arguments = literals.into_iter().map(Value::new).collect();
+                }
+                None => {
+                    for item in items {
+                        node.push_child(self::node("-", item));
+                    }
+                }
+            }
+            items.len() <= 1
+        }
+        Json::Object(object) => {
+            let literals = object
+                .iter()
+                .map(|(key, value)| literal(value).map(|value| (key, value)))
+                .collect::<Option<Vec<_>>>();
+            match literals {
+                Some(literals) => {
+                    node.properties = literals
+                        .into_iter()
+                        .map(|(key, value)| {
+                            (
+                                Spanned::new(key[..].into(), Span::default()),
+                                Value::new(value),
+                            )
+                        })
+                        .collect();
+                }
+                None => {
+                    for (key, value) in object {
+                        node.push_child(self::node(key, value));
+                    }
+                }
+            }
+            object.keys().all(|key| key == "-")
+        }
+        _ => {
+            node.arguments.extend(literal(value).map(Value::new));
+            false
+        }
+    };
+    if annotate {
+        let name = if value.is_array() { "array" } else { "object" };
+        node.type_name = Some(Spanned::new(
+            TypeName::from_string(name.into()),
+            Span::default(),
+        ));
+    }
+    node
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::{from_json, parse, to_json};
+    use crate::span::Span;
+
+    fn errors(text: &str) -> Vec<String> {
+        let document = crate::parse_ast::<Span>("<test>", text).unwrap();
+        to_json(&document)
+            .unwrap_err()
+            .iter()
+            .map(|e| e.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(parse("<test>", "- null").unwrap(), json!(null));
+        assert_eq!(parse("<test>", "- true").unwrap(), json!(true));
+        assert_eq!(parse("<test>", r#"- "x""#).unwrap(), json!("x"));
+        assert_eq!(parse("<test>", "- 12").unwrap(), json!(12));
+        assert_eq!(parse("<test>", "node (u8)12").unwrap(), json!(12));
+    }
+
+    #[test]
+    #[cfg(feature = "jik-arbitrary-precision")]
+    fn numbers() {
+        let value = parse(
+            "<test>",
+            "- 0x1f -0o17 0b101 +007 -0 \
+               123456789012345678901234567890 0xffffffffffffffffffff \
+               1.50 -0.5e-10 +00.1 1.0E+3",
+        )
+        .unwrap();
+        assert_eq!(
+            serde_json::to_string(&value).unwrap(),
+            "[31,-15,5,7,0,\
+              123456789012345678901234567890,1208925819614629174706175,\
+              1.50,-0.5e-10,0.1,1.0e+3]"
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "jik-arbitrary-precision"))]
+    fn numbers() {
+        let value = parse(
+            "<test>",
+            "- 0x1f -0o17 0b101 +007 -0 \
+               123456789012345678901234567890 1.50 -0.5e-10 +00.1 1.0E+3",
+        )
+        .unwrap();
+        assert_eq!(
+            serde_json::to_string(&value).unwrap(),
+            "[31,-15,5,7,0,1.2345678901234568e+29,1.5,-5e-11,0.1,1000.0]"
+        );
+    }
+
+    #[test]
+    fn arrays() {
+        assert_eq!(parse("<test>", "- 1 2").unwrap(), json!([1, 2]));
+        assert_eq!(parse("<test>", "(array)- 1").unwrap(), json!([1]));
+        assert_eq!(parse("<test>", "(array)-").unwrap(), json!([]));
+        assert_eq!(parse("<test>", "(array)- {}").unwrap(), json!([]));
+        assert_eq!(
+            parse("<test>", "- 1 { - 2; - 3 4; (array)-; }").unwrap(),
+            json!([1, 2, [3, 4], []])
+        );
+    }
+
+    #[test]
+    fn objects() {
+        assert_eq!(
+            parse("<test>", "- a=1 b=null").unwrap(),
+            json!({"a": 1, "b": null})
+        );
+        assert_eq!(parse("<test>", "(object)-").unwrap(), json!({}));
+        assert_eq!(
+            parse("<test>", "(object)- { - 1; }").unwrap(),
+            json!({"-": 1})
+        );
+        assert_eq!(
+            parse("<test>", "- a=1 { b { c 2; }; d 3 4; }").unwrap(),
+            json!({"a": 1, "b": {"c": 2}, "d": [3, 4]})
+        );
+    }
+
+    #[test]
+    fn roundtrip() {
+        let values = [
+            json!(null),
+            json!("text"),
+            json!([]),
+            json!([1]),
+            json!([1, "x", null]),
+            json!([[1], {}, {"a": [true]}]),
+            json!({}),
+            json!({"-": 1}),
+            json!({"-": [1, {"-": 2}]}),
+            json!({"a": 1, "b": 2.5, "c": {"d": []}, "e f": "g"}),
+        ];
+        for value in values {
+            let document = from_json(&value);
+            assert_eq!(to_json(&document).unwrap(), value);
+            let text = document.to_string();
+            assert_eq!(parse("<test>", &text).unwrap(), value, "{}", text);
+        }
+    }
+
+    #[test]
+    fn deep_nesting() {
+        let depth = 100_000;
+        let text = "- {\n".repeat(depth) + "- 1\n" + &"}\n".repeat(depth);
+        let mut value = parse("<test>", &text).unwrap();
+        for _ in 0..depth {
+            // take the element out, so the value isn't dropped recursively
+            value = match value {
+                serde_json::Value::Array(mut items) if items.len() == 1 => items.pop().unwrap(),
+                value => panic!("expected single-element array, got {:?}", value),
+            };
+        }
+        assert_eq!(value, json!(1));
+    }
+
+    #[test]
+    #[cfg(feature = "jik-arbitrary-precision")]
+    fn roundtrip_exact() {
+        let value: serde_json::Value =
+            serde_json::from_str("[123456789012345678901234567890, 1.000]").unwrap();
+        assert_eq!(
+            from_json(&value).to_string(),
+            "- 123456789012345678901234567890 1.000\n"
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            errors("-"),
+            ["empty node must have `(array)` or `(object)` type name"]
+        );
+        assert_eq!(
+            errors("- 1; - 2"),
+            ["JiK document must contain a single node"]
+        );
+        assert_eq!(
+            errors("- 1 a=2"),
+            ["node can't have both arguments and properties, \
+              it must be either an array or an object"]
+        );
+        assert_eq!(
+            errors("(map)- 1"),
+            ["expected `array` or `object` type name"]
+        );
+        assert_eq!(
+            errors("(array)- a=1 { x 2; }"),
+            [
+                "arrays can't have properties",
+                "array elements must be named `-`"
+            ]
+        );
+        assert_eq!(errors("(object)- 1"), ["objects can't have arguments"]);
+        assert_eq!(errors("- { a 1; a 2; }"), ["duplicate key `a`"]);
+        assert_eq!(
+            errors("/- kdl-version 2\n- 1 #inf"),
+            ["infinity and NaN can't be represented in JSON"]
+        );
+    }
+}
@@ -5,18 +5,42 @@
 mod containers;
 mod convert;
 mod convert_ast;
+mod format;
 mod grammar;
+mod grammar_v2;
 mod wrappers;
 
 pub mod ast;
+pub mod borrowed;
+pub mod cst;
+#[cfg(feature = "serde")]
+pub mod de;
 pub mod decode;
+pub mod diagnostic;
+pub mod encode;
 pub mod errors;
+pub mod include;
+pub mod interpolate;
+#[cfg(feature = "serde_json")]
+pub mod jik;
+pub mod merge;
+pub mod migrate;
+pub mod print;
+pub mod query;
+pub mod schema;
+#[cfg(feature = "serde")]
+pub mod ser;
 pub mod span;
 pub mod traits;
+pub mod xik;
 
 #[cfg(feature = "derive")]
-pub use knus_derive::{Decode, DecodeScalar};
+pub use knus_derive::{Decode, DecodeScalar, DecodeSchema, Encode, EncodeScalar, Merge};
 
-pub use errors::Error;
-pub use traits::{Decode, DecodeChildren, DecodeScalar};
-pub use wrappers::{parse, parse_ast, parse_with_context};
+pub use errors::{Error, Warnings};
+pub use traits::Merge;
+pub use traits::{Decode, DecodeChildren, DecodeScalar, DecodeSchema};
+pub use traits::{Encode, EncodeChildren, EncodeScalar};
+pub use wrappers::{parse, parse_ast, parse_ast_recovery, parse_with_context};
+pub use wrappers::{to_document, to_document_with_context, to_string};
+pub use wrappers::{KdlVersion, ParseOptions};
@@ -0,0 +1,451 @@
+//! Layered configuration made of several KDL documents
+//!
+//! Configuration is often read from several places: defaults, then system,
+//! user and project files, each one overriding the previous ones. The
+//! [`merge`] function combines such layers into a single document before it's
+//! decoded. The rules are defined by the [`Merge`] trait of the document type,
+//! which is usually implemented by the [`Merge`](derive@crate::Merge) derive:
+//!
+//! 1. Arguments of the node are replaced as a whole if the layer has any.
+//! 2. Type name and properties are replaced one by one, last layer wins.
+//! 3. A `child` is merged recursively using the same rules. Flags, `unwrap`
+//!    children and ones marked `merge = "replace"` are replaced instead.
+//! 4. Nodes of `children` are appended to the nodes of the previous layers.
+//!    If the field is marked `merge = "replace"`, nodes of the layer replace
+//!    all the previous ones.
+//! 5. A node with `(delete)` type name removes the previous nodes with the
+//!    same name whose arguments start with the arguments of the node, and a
+//!    property with `(delete)` value removes the property.
+//!
+//! ```rust
+//! #[derive(knus::Decode, knus::Merge, Debug)]
+//! struct Server {
+//!     #[knus(property)]
+//!     host: String,
+//!     #[knus(property)]
+//!     port: u16,
+//! }
+//!
+//! #[derive(knus::Decode, knus::Merge, Debug)]
+//! struct Config {
+//!     #[knus(child)]
+//!     server: Server,
+//!     #[knus(children(name="plugin"), unwrap(argument))]
+//!     plugins: Vec<String>,
+//!     #[knus(children(name="allow"), unwrap(argument), merge="replace")]
+//!     allow: Vec<String>,
+//! }
+//!
+//! let config: Config = knus::merge::parse([
+//!     ("defaults.kdl", r#"
+//!         server host="localhost" port=8080
+//!         plugin "fmt"
+//!         plugin "lint"
+//!         allow "127.0.0.1"
+//!     "#),
+//!     ("project.kdl", r#"
+//!         server port=9000
+//!         (delete)plugin "lint"
+//!         plugin "test"
+//!         allow "10.0.0.1"
+//!     "#),
+//! ])?;
+//! assert_eq!(config.server.host, "localhost");
+//! assert_eq!(config.server.port, 9000);
+//! assert_eq!(config.plugins, ["fmt", "test"]);
+//! assert_eq!(config.allow, ["10.0.0.1"]);
+//! # Ok::<(), knus::Error>(())
+//! ```
+//!
+//! The [`parse`] function keeps the file of every node in the [`FileSpan`],
+//! so decode errors point to the layer that supplied the value.
+use std::rc::Rc;
+use std::sync::Arc;
+
+use miette::NamedSource;
+
+use crate::ast::{self, Document, Node, SpannedNode, TypeName};
+use crate::decode::Context;
+use crate::errors::{Error, Warnings};
+use crate::include::{decode, named_source, with_file};
+use crate::span::{FileSpan, SourceFile, Span, Spanned};
+use crate::traits::{DecodeChildren, Merge};
+use crate::wrappers::ParseOptions;
+
+/// Type name that marks the nodes and properties to remove
+pub const DELETE: &str = "delete";
+
+/// Merges the documents, each one overriding the previous ones
+///
+/// `T` is the type the resulting document is decoded into.
+pub fn merge<T, S>(layers: impl IntoIterator<Item = Document<S>>) -> Document<S>
+where
+    T: Merge<S> + ?Sized,
+{
+    let mut nodes = Vec::new();
+    for layer in layers {
+        T::merge_children(&mut nodes, layer.nodes);
+    }
+    Document { nodes }
+}
+
+/// Parses `(file_name, text)` layers and merges them into a single document
+pub fn parse_ast<T, N, X>(
+    layers: impl IntoIterator<Item = (N, X)>,
+) -> Result<Document<FileSpan>, Error>
+where
+    T: Merge<FileSpan> + ?Sized,
+    N: AsRef<str>,
+    X: AsRef<str>,
+{
+    parse_layers::<T, N, X>(layers).map(|(document, _)| document)
+}
+
+/// Parses `(file_name, text)` layers, merges them, and decodes Rust object
+pub fn parse<T, N, X>(layers: impl IntoIterator<Item = (N, X)>) -> Result<T, Error>
+where
+    T: DecodeChildren<FileSpan> + Merge<FileSpan>,
+    N: AsRef<str>,
+    X: AsRef<str>,
+{
+    parse_with_context(layers, |_| {}).map(|(value, _)| value)
+}
+
+/// Parses `(file_name, text)` layers, merges them, and decodes Rust object
+/// providing extra context for the decoder
+///
+/// Returns the warnings emitted to the context along with the value.
+pub fn parse_with_context<T, N, X, F>(
+    layers: impl IntoIterator<Item = (N, X)>,
+    set_ctx: F,
+) -> Result<(T, Warnings), Error>
+where
+    F: FnOnce(&mut Context<FileSpan>),
+    T: DecodeChildren<FileSpan> + Merge<FileSpan>,
+    N: AsRef<str>,
+    X: AsRef<str>,
+{
+    let (document, last) = parse_layers::<T, N, X>(layers)?;
+    let source_code = match last {
+        Some(file) => named_source(&file),
+        None => NamedSource::new("", String::new()),
+    };
+    let result = decode(&document, source_code, set_ctx);
+    ast::drop_nodes(document.nodes);
+    result
+}
+
+fn parse_layers<T, N, X>(
+    layers: impl IntoIterator<Item = (N, X)>,
+) -> Result<(Document<FileSpan>, Option<Arc<SourceFile>>), Error>
+where
+    T: Merge<FileSpan> + ?Sized,
+    N: AsRef<str>,
+    X: AsRef<str>,
+{
+    let options = ParseOptions::new();
+    let mut nodes = Vec::new();
+    let mut last = None;
+    for (name, text) in layers {
+        let (name, text) = (name.as_ref(), text.as_ref());
+        let document = options.parse_ast::<Span>(name, text)?;
+        let file = Arc::new(SourceFile {
+            name: name.into(),
+            text: text.into(),
+        });
+        T::merge_children(&mut nodes, with_file(document.nodes, &file));
+        last = Some(file);
+    }
+    Ok((Document { nodes }, last))
+}
+
+/// Returns `true` if the type name is `(delete)`
+pub fn is_delete<S>(type_name: &Option<Spanned<TypeName, S>>) -> bool {
+    matches!(type_name, Some(t) if t.as_str() == DELETE)
+}
+
+/// Default implementation of [`Merge::merge_node`]
+///
+/// Replaces type name, arguments (if there are any in the layer) and each
+/// property, and merges children using [`Merge::merge_children`] of `T`.
+pub fn merge_node<T, S>(base: &mut SpannedNode<S>, layer: SpannedNode<S>)
+where
+    T: Merge<S> + ?Sized,
+{
+    let Spanned { span, value: layer } = layer;
+    base.span = span;
+    let base = &mut base.value;
+    base.node_name = layer.node_name;
+    if layer.type_name.is_some() {
+        base.type_name = layer.type_name;
+    }
+    if !layer.arguments.is_empty() {
+        base.arguments = layer.arguments;
+    }
+    for (name, value) in layer.properties {
+        // removed first, so the name has the span of the layer
+        base.properties.remove(&name);
+        if !is_delete(&value.type_name) {
+            base.properties.insert(name, value);
+        }
+    }
+    if let Some(Spanned { span, value: nodes }) = layer.children {
+        let mut children = base
+            .children
+            .take()
+            .map(Spanned::into_inner)
+            .unwrap_or_default();
+        T::merge_children(&mut children, nodes);
+        base.children = Some(Spanned::new(children, span));
+    }
+}
+
+/// Default implementation of [`Merge::merge_children`]
+///
+/// Calls [`Merge::remove_replaced`], then removes the nodes deleted by the
+/// `(delete)` nodes and merges other nodes using [`Merge::merge_child`].
+pub fn merge_children<T, S>(base: &mut Vec<SpannedNode<S>>, layer: Vec<SpannedNode<S>>)
+where
+    T: Merge<S> + ?Sized,
+{
+    T::remove_replaced(base, &layer);
+    for node in layer {
+        if is_delete(&node.type_name) {
+            base.retain(|old| !deletes(&node, old));
+        } else if let Some(mut node) = T::merge_child(base, node) {
+            strip_deleted(&mut node);
+            base.push(node);
+        }
+    }
+}
+
+/// Merges the child into the last node named one of `names` using
+/// [`Merge::merge_node`] of `T`
+///
+/// `names` are the name of the child and its aliases. Returns the child back
+/// if there is no such node.
+pub fn merge_named<T, S>(
+    base: &mut [SpannedNode<S>],
+    child: SpannedNode<S>,
+    names: &[&str],
+) -> Option<SpannedNode<S>>
+where
+    T: Merge<S> + ?Sized,
+{
+    match base
+        .iter_mut()
+        .rev()
+        .find(|old| names.contains(&&**old.node_name))
+    {
+        Some(old) => {
+            T::merge_node(old, child);
+            None
+        }
+        None => Some(child),
+    }
+}
+
+/// Replaces the last node named one of `names` with the child
+///
+/// `names` are the name of the child and its aliases. Returns the child back
+/// if there is no such node.
+pub fn replace_named<S>(
+    base: &mut [SpannedNode<S>],
+    child: SpannedNode<S>,
+    names: &[&str],
+) -> Option<SpannedNode<S>> {
+    match base
+        .iter_mut()
+        .rev()
+        .find(|old| names.contains(&&**old.node_name))
+    {
+        Some(old) => {
+            replace_node(old, child);
+            None
+        }
+        None => Some(child),
+    }
+}
+
+/// Replaces the node with the node of the layer
+///
+/// Used for the types that can't be merged, like enums.
+pub fn replace_node<S>(base: &mut SpannedNode<S>, mut layer: SpannedNode<S>) {
+    strip_deleted(&mut layer);
+    *base = layer;
+}
+
+/// Removes all the nodes of the collection if the layer has any of them
+///
+/// Node belongs to the collection if `contains` returns `true` for its name.
+/// Used for `children` with `merge = "replace"`.
+pub fn replace_collection<S>(
+    base: &mut Vec<SpannedNode<S>>,
+    layer: &[SpannedNode<S>],
+    contains: impl Fn(&str) -> bool,
+) {
+    let replaced = layer
+        .iter()
+        .any(|node| !is_delete(&node.type_name) && contains(&node.node_name));
+    if replaced {
+        base.retain(|node| !contains(&node.node_name));
+    }
+}
+
+/// Returns `true` if the `(delete)` node removes the old node
+fn deletes<S>(delete: &Node<S>, old: &Node<S>) -> bool {
+    delete.node_name == old.node_name
+        && delete.arguments.len() <= old.arguments.len()
+        && delete
+            .arguments
+            .iter()
+            .zip(&old.arguments)
+            .all(|(a, b)| a.literal == b.literal)
+}
+
+/// Removes `(delete)` properties and nodes that have nothing to delete
+fn strip_deleted<S>(node: &mut Node<S>) {
+    node.properties
+        .retain(|_, value| !is_delete(&value.type_name));
+    if let Some(children) = &mut node.children {
+        children.retain(|child| !is_delete(&child.type_name));
+        for child in children.iter_mut() {
+            strip_deleted(child);
+        }
+    }
+}
+
+impl<S> Merge<S> for Node<S> {}
+
+impl<S> Merge<S> for SpannedNode<S> {}
+
+macro_rules! impl_pointer_merge {
+    ($($ptr:ident)*) => {$(
+        impl<S, T: Merge<S> + ?Sized> Merge<S> for $ptr<T> {
+            fn merge_node(base: &mut SpannedNode<S>, layer: SpannedNode<S>) {
+                T::merge_node(base, layer)
+            }
+            fn merge_children(base: &mut Vec<SpannedNode<S>>, layer: Vec<SpannedNode<S>>) {
+                T::merge_children(base, layer)
+            }
+            fn merge_child(
+                base: &mut Vec<SpannedNode<S>>,
+                child: SpannedNode<S>,
+            ) -> Option<SpannedNode<S>> {
+                T::merge_child(base, child)
+            }
+            fn remove_replaced(base: &mut Vec<SpannedNode<S>>, layer: &[SpannedNode<S>]) {
+                T::remove_replaced(base, layer)
+            }
+        }
+    )*};
+}
+
+impl_pointer_merge!(Box Arc Rc);
+
+#[cfg(test)]
+mod test {
+    use crate::ast::{Document, SpannedNode};
+    use crate::parse_ast;
+    use crate::span::Span;
+    use crate::traits::Merge;
+
+    fn merge(layers: &[&str]) -> String {
+        let layers = layers
+            .iter()
+            .map(|text| parse_ast::<Span>("<test>", text).unwrap());
+        let document: Document<Span> = super::merge::<SpannedNode<Span>, _>(layers);
+        document.to_string()
+    }
+
+    /// Document that deep merges `server` nodes
+    struct Config;
+
+    impl<S> Merge<S> for Config {
+        fn merge_child(
+            base: &mut Vec<SpannedNode<S>>,
+            child: SpannedNode<S>,
+        ) -> Option<SpannedNode<S>> {
+            match &**child.node_name {
+                "server" | "srv" => {
+                    super::merge_named::<SpannedNode<S>, S>(base, child, &["server", "srv"])
+                }
+                "mode" => super::replace_named(base, child, &["mode"]),
+                _ => Some(child),
+            }
+        }
+        fn remove_replaced(base: &mut Vec<SpannedNode<S>>, layer: &[SpannedNode<S>]) {
+            super::replace_collection(base, layer, |name| name == "allow");
+        }
+    }
+
+    fn merge_config(layers: &[&str]) -> String {
+        let layers = layers
+            .iter()
+            .map(|text| parse_ast::<Span>("<test>", text).unwrap());
+        super::merge::<Config, _>(layers).to_string()
+    }
+
+    #[test]
+    fn append() {
+        assert_eq!(merge(&["a 1", "b 2", "a 3"]), "a 1\nb 2\na 3\n");
+        assert_eq!(merge(&[]), "");
+    }
+
+    #[test]
+    fn delete() {
+        assert_eq!(
+            merge(&["a 1\na 2 3\nb\n", "(delete)a 2\n(delete)c"]),
+            "a 1\nb\n"
+        );
+        assert_eq!(merge(&["a 1\na 2\n", "(delete)a"]), "");
+        // nothing to delete in the new nodes
+        assert_eq!(
+            merge(&["", "a x=(delete)null y=1 {\n (delete)b; c\n}"]),
+            "a y=1 {\n    c\n}\n"
+        );
+    }
+
+    #[test]
+    fn deep() {
+        assert_eq!(
+            merge_config(&[
+                "server \"a\" host=\"x\" port=1 {\n log; level 1\n}",
+                "server port=2 host=(delete)null {\n (delete)log; level 2\n}",
+            ]),
+            "server \"a\" port=2 {\n    level 1\n    level 2\n}\n"
+        );
+        assert_eq!(
+            merge_config(&["server \"a\" x=1", "(t)server \"b\""]),
+            "(t)server \"b\" x=1\n"
+        );
+    }
+
+    #[test]
+    fn alias() {
+        assert_eq!(
+            merge_config(&["server portNum=1 x=1", "srv portNum=2"]),
+            "srv portNum=2 x=1\n"
+        );
+        assert_eq!(
+            merge_config(&["srv 1", "server 2", "other"]),
+            "server 2\nother\n"
+        );
+    }
+
+    #[test]
+    fn replace() {
+        assert_eq!(
+            merge_config(&["mode \"a\" x=1\nallow 1\nallow 2", "mode \"b\"\nextra"]),
+            "mode \"b\"\nallow 1\nallow 2\nextra\n"
+        );
+        assert_eq!(
+            merge_config(&["allow 1\nallow 2\nother", "allow 3"]),
+            "other\nallow 3\n"
+        );
+        assert_eq!(
+            merge_config(&["allow 1\nallow 2", "(delete)allow 1"]),
+            "allow 2\n"
+        );
+    }
+}
@@ -0,0 +1,392 @@
+//! Migration of KDL 1.0 documents to KDL 2.0
+//!
+//! The document is converted token by token using the [lossless
+//! tree](crate::cst), so comments and formatting are kept intact:
+//!
+//! ```rust
+//! let migration = knus::migrate::v1_to_v2("config.kdl", r#"
+//!     // enable the feature
+//!     feature enabled=true path=r"C:\path" inf=1
+//! "#)?;
+//! assert_eq!(migration.text, r##"/- kdl-version 2
+//!
+//!     // enable the feature
+//!     feature enabled=#true path=#"C:\path"# "inf"=1
+//! "##);
+//! assert!(migration.issues.is_empty());
+//! # Ok::<(), knus::Error>(())
+//! ```
+use std::fmt::{self, Write};
+
+use crate::ast::{self, Document, SpannedNode};
+use crate::cst::{self, Token, TokenKind};
+use crate::errors::Error;
+use crate::grammar_v2::{is_bare_ident, is_disallowed, is_newline_char};
+use crate::span::Span;
+use crate::wrappers::{detect_version, KdlVersion, ParseOptions};
+
+/// Result of the migration
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Migration {
+    /// Text of the KDL 2.0 document
+    pub text: String,
+    /// Places of the source text that could not be converted safely
+    ///
+    /// These are kept as is and need to be fixed manually.
+    pub issues: Vec<Issue>,
+}
+
+/// Part of the source document that could not be converted
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Issue {
+    /// Byte range in the source (KDL 1.0) text
+    pub span: Span,
+    /// Description of the problem
+    pub message: String,
+}
+
+/// Convert KDL 1.0 text into equivalent KDL 2.0 text
+///
+/// The `/- kdl-version 2` marker is added to the start of the document (or
+/// the `/- kdl-version 1` marker is updated), so that the result is
+/// recognized as KDL 2.0 by [`parse`](crate::parse).
+///
+/// Documents that already start with the `/- kdl-version 2` marker are
+/// returned unchanged.
+///
+/// Returns error if the source is not a valid KDL 1.0 document.
+pub fn v1_to_v2(file_name: impl AsRef<str>, text: &str) -> Result<Migration, Error> {
+    if detect_version(text) == KdlVersion::V2 {
+        return Ok(Migration {
+            text: text.into(),
+            issues: Vec::new(),
+        });
+    }
+    let doc = ParseOptions::new()
+        .version(KdlVersion::V1)
+        .parse_cst(file_name.as_ref(), text)?;
+    let version = version_marker(&doc);
+    let mut migrator = Migrator {
+        output: String::with_capacity(text.len() + 20),
+        issues: Vec::new(),
+        offset: 0,
+    };
+    if text.starts_with('\u{FEFF}') {
+        migrator.output.push('\u{FEFF}');
+    }
+    if version.is_none() {
+        migrator.output.push_str("/- kdl-version 2\n");
+    }
+    doc.write_tokens(&mut |token| {
+        if version.is_some_and(|v| std::ptr::eq(v, token)) {
+            migrator.output.push('2');
+            migrator.offset += token.text().len();
+        } else {
+            migrator.token(token);
+        }
+    });
+    let Migrator {
+        output, mut issues, ..
+    } = migrator;
+    if issues.is_empty() {
+        let original: Document<Span> = ParseOptions::new()
+            .version(KdlVersion::V1)
+            .parse_ast(file_name.as_ref(), text)?;
+        let converted = ParseOptions::new()
+            .version(KdlVersion::V2)
+            .parse_ast::<Span>(file_name.as_ref(), &output);
+        let same = converted.is_ok_and(|converted| {
+            let same = same_nodes(&converted.nodes, &original.nodes);
+            ast::drop_nodes(converted.nodes);
+            same
+        });
+        ast::drop_nodes(original.nodes);
+        if !same {
+            issues.push(Issue {
+                span: Span(0, text.len()),
+                message: "converted document is not equivalent to the source".into(),
+            });
+        }
+    }
+    Ok(Migration {
+        text: output,
+        issues,
+    })
+}
+
+/// Compares the nodes ignoring spans
+///
+/// Same as `==` on the nodes, but doesn't recurse into children, so deeply
+/// nested documents don't overflow the stack.
+fn same_nodes(a: &[SpannedNode<Span>], b: &[SpannedNode<Span>]) -> bool {
+    let mut stack = vec![(a, b)];
+    while let Some((a, b)) = stack.pop() {
+        if a.len() != b.len() {
+            return false;
+        }
+        for (a, b) in a.iter().zip(b) {
+            if a.type_name != b.type_name
+                || a.node_name != b.node_name
+                || a.arguments != b.arguments
+                || a.properties != b.properties
+            {
+                return false;
+            }
+            match (&a.children, &b.children) {
+                (Some(a), Some(b)) => stack.push((a, b)),
+                (None, None) => {}
+                _ => return false,
+            }
+        }
+    }
+    true
+}
+
+/// Finds the version argument of the `/- kdl-version 1` marker
+fn version_marker(doc: &cst::Document) -> Option<&Token> {
+    let node = doc.nodes.first()?;
+    if !node.is_commented() || &*node.name() != "kdl-version" {
+        return None;
+    }
+    let value = node.arguments().next()?;
+    (value.literal.text() == "1").then_some(&value.literal)
+}
+
+struct Migrator {
+    output: String,
+    issues: Vec<Issue>,
+    /// Byte offset of the current token in the source text
+    offset: usize,
+}
+
+impl Migrator {
+    fn issue(&mut self, token: &Token, message: impl Into<String>) {
+        self.issues.push(Issue {
+            span: Span(self.offset, self.offset + token.text().len()),
+            message: message.into(),
+        });
+    }
+
+    fn token(&mut self, token: &Token) {
+        let text = token.text();
+        match token.kind() {
+            TokenKind::Keyword => {
+                self.output.push('#');
+                self.output.push_str(text);
+            }
+            TokenKind::Ident if !is_bare_ident(text) => {
+                write_string(&mut self.output, text).expect("can write to string");
+            }
+            TokenKind::RawString => {
+                let value = token.string_value();
+                if value
+                    .chars()
+                    .any(|c| is_newline_char(c) || is_disallowed(c))
+                {
+                    // raw strings are single-line in KDL 2.0 and multi-line
+                    // ones strip the whitespace-only lines
+                    write_string(&mut self.output, &value).expect("can write to string");
+                } else {
+                    let hashes = text[1..].bytes().take_while(|&b| b == b'#').count();
+                    let hashes = "#".repeat(hashes.max(1));
+                    write!(&mut self.output, "{0}\"{1}\"{0}", hashes, value)
+                        .expect("can write to string");
+                }
+            }
+            TokenKind::String => {
+                let mut chars = text.chars();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => match chars.next() {
+                            // `\/` escape is removed in KDL 2.0
+                            Some('/') => self.output.push('/'),
+                            Some(c) => {
+                                self.output.push('\\');
+                                self.output.push(c);
+                            }
+                            None => self.output.push('\\'),
+                        },
+                        c => write_char(&mut self.output, c).expect("can write to string"),
+                    }
+                }
+            }
+            TokenKind::Decimal if text.ends_with(['e', 'E', '+', '-']) => {
+                self.issue(token, "exponent without digits is not allowed in KDL 2.0");
+                self.output.push_str(text);
+            }
+            TokenKind::Whitespace => {
+                for (idx, c) in text.char_indices() {
+                    match c {
+                        // the leading byte order mark is written before
+                        // the version marker
+                        '\u{FEFF}' if self.offset + idx == 0 => {}
+                        // and is not allowed anywhere else
+                        '\u{FEFF}' => self.output.push(' '),
+                        c => self.output.push(c),
+                    }
+                }
+            }
+            TokenKind::LineComment | TokenKind::BlockComment if text.chars().any(is_disallowed) => {
+                self.issue(
+                    token,
+                    "comment contains characters that are not allowed in KDL 2.0",
+                );
+                self.output.push_str(text);
+            }
+            _ => self.output.push_str(text),
+        }
+        self.offset += text.len();
+    }
+}
+
+/// Writes the char of the quoted string, escaping the ones that are not
+/// allowed literally in KDL 2.0
+fn write_char(f: &mut impl Write, c: char) -> fmt::Result {
+    match c {
+        '\n' => f.write_str("\\n"),
+        '\r' => f.write_str("\\r"),
+        '\u{0008}' => f.write_str("\\b"),
+        '\u{000C}' => f.write_str("\\f"),
+        c if is_newline_char(c) || is_disallowed(c) => write!(f, "\\u{{{:x}}}", c as u32),
+        c => f.write_char(c),
+    }
+}
+
+/// Writes the quoted string in KDL 2.0 syntax
+pub(crate) fn write_string(f: &mut impl Write, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            c => write_char(f, c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+#[cfg(test)]
+mod test {
+    use super::{v1_to_v2, Issue};
+    use crate::ast::Document;
+    use crate::span::Span;
+    use crate::{KdlVersion, ParseOptions};
+
+    fn migrate(text: &str) -> String {
+        let migration = v1_to_v2("<test>", text).unwrap();
+        assert_eq!(migration.issues, vec![]);
+        migration.text
+    }
+
+    fn same_ast(v1: &str, v2: &str) {
+        let v1: Document<Span> = ParseOptions::new()
+            .version(KdlVersion::V1)
+            .parse_ast("<v1>", v1)
+            .unwrap();
+        let v2: Document<Span> = ParseOptions::new()
+            .version(KdlVersion::V2)
+            .parse_ast("<v2>", v2)
+            .unwrap();
+        assert_eq!(v1, v2);
+    }
+
+    #[test]
+    fn keywords() {
+        assert_eq!(
+            migrate("node true false null // true\n"),
+            "/- kdl-version 2\nnode #true #false #null // true\n"
+        );
+    }
+
+    #[test]
+    fn strings() {
+        let v1 = r####"node r"raw" r##"a"#b"## "\/\u{41}\n" "multi
+line" r"raw
+multi" "tab	x""####;
+        let v2 = migrate(v1);
+        assert_eq!(
+            v2,
+            "/- kdl-version 2\nnode #\"raw\"# ##\"a\"#b\"## \"/\\u{41}\\n\" \
+             \"multi\\nline\" \"raw\\nmulti\" \"tab\tx\""
+        );
+        same_ast(v1, &v2);
+    }
+
+    #[test]
+    fn identifiers() {
+        let v1 = r#"(inf)nan -inf=1 a#b=2 .5a=3 -.5=4 "<x>"=5 plain=6"#;
+        let v2 = migrate(v1);
+        assert_eq!(
+            v2,
+            "/- kdl-version 2\n(\"inf\")\"nan\" \"-inf\"=1 \"a#b\"=2 \".5a\"=3 \
+             \"-.5\"=4 \"<x>\"=5 plain=6"
+        );
+        same_ast(v1, &v2);
+    }
+
+    #[test]
+    fn version_marker() {
+        assert_eq!(
+            migrate("/- kdl-version 1\nnode null"),
+            "/- kdl-version 2\nnode #null"
+        );
+        assert_eq!(
+            migrate("\u{FEFF}node \u{FEFF}1"),
+            "\u{FEFF}/- kdl-version 2\nnode  1"
+        );
+        let v2 = "/- kdl-version 2\nnode #null";
+        assert_eq!(migrate(v2), v2);
+    }
+
+    #[test]
+    fn keeps_formatting() {
+        let v1 = r##"
+            // leading comment
+            (t)node "arg" 0x1f key=true /* note */ {
+                child; /- commented true
+                other \
+                    1.5e10 r#"x"#
+            }
+            /- gone {
+                really
+            }
+        "##;
+        let v2 = migrate(v1);
+        assert_eq!(
+            v2,
+            "/- kdl-version 2\n".to_string()
+                + &v1.replace("true", "#true").replace("r#\"x\"#", "#\"x\"#")
+        );
+        same_ast(v1, &v2);
+    }
+
+    #[test]
+    fn deep_nesting() {
+        let depth = 100_000;
+        let v1 = "a true {\n".repeat(depth) + &"}\n".repeat(depth);
+        let v2 = migrate(&v1);
+        assert_eq!(
+            v2,
+            "/- kdl-version 2\n".to_string() + &v1.replace("true", "#true")
+        );
+    }
+
+    #[test]
+    fn issues() {
+        let migration = v1_to_v2("<test>", "a 1e 2\n// \u{200E}\n").unwrap();
+        assert_eq!(
+            migration.issues,
+            vec![
+                Issue {
+                    span: Span(2, 4),
+                    message: "exponent without digits is not allowed in KDL 2.0".into(),
+                },
+                Issue {
+                    span: Span(7, 13),
+                    message: "comment contains characters that are not allowed in KDL 2.0".into(),
+                },
+            ]
+        );
+        v1_to_v2("<test>", "a {").unwrap_err();
+    }
+}
@@ -0,0 +1,732 @@
+//! Printing of the KDL document
+//!
+//! The [`Display`](fmt::Display) implementation of [`Document`], [`Node`] and
+//! [`Value`] uses default [`PrintOptions`]. Use options directly to configure
+//! the style of the output:
+//!
+//! ```rust
+//! use knus::ast::Document;
+//! use knus::print::{Identifiers, PrintOptions};
+//! use knus::span::Span;
+//!
+//! let doc: Document<Span> = knus::parse_ast("<test>", "node a=1 { child; }")?;
+//! let options = PrintOptions::new()
+//!     .indent(2)
+//!     .identifiers(Identifiers::Quoted);
+//! assert_eq!(
+//!     options.print_document(&doc),
+//!     "\"node\" \"a\"=1 {\n  \"child\"\n}\n",
+//! );
+//! # Ok::<(), knus::Error>(())
+//! ```
+//!
+//! Documents are written in KDL 1.0 unless they contain infinity or NaN,
+//! which can only be written in KDL 2.0. See [`PrintOptions::version`].
+use std::fmt::{self, Write};
+
+use crate::ast::{Decimal, Document, Integer, Literal, Node, Radix, Value};
+use crate::grammar::Syntax;
+use crate::grammar_v2;
+use crate::migrate;
+use crate::span::Spanned;
+use crate::traits::ErrorSpan;
+use crate::wrappers::KdlVersion;
+
+/// How node names, property names and type names are written
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[non_exhaustive]
+pub enum Identifiers {
+    /// Write bare identifiers where possible, quote others
+    #[default]
+    Bare,
+    /// Always write identifiers as quoted strings
+    Quoted,
+}
+
+/// Order in which properties of the node are written
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[non_exhaustive]
+pub enum PropertyOrder {
+    /// Sort properties by name
+    #[default]
+    Sorted,
+    /// Order properties by their position in the source code
+    ///
+    /// Properties which have same span (e.g. those created by encoder) are
+    /// sorted by name.
+    Source,
+}
+
+/// Style options of the printed KDL
+///
+/// Default options produce the same output as
+/// [`Display`](fmt::Display) implementation of the document.
+#[derive(Clone, Debug)]
+pub struct PrintOptions {
+    indent: usize,
+    identifiers: Identifiers,
+    raw_strings: bool,
+    keep_radix: bool,
+    property_order: PropertyOrder,
+    version: KdlVersion,
+}
+
+struct Printer<'a, S> {
+    options: &'a PrintOptions,
+    offset: Option<fn(&S) -> usize>,
+    syntax: Syntax,
+}
+
+impl Default for PrintOptions {
+    fn default() -> PrintOptions {
+        PrintOptions {
+            indent: 4,
+            identifiers: Identifiers::Bare,
+            raw_strings: false,
+            keep_radix: true,
+            property_order: PropertyOrder::Sorted,
+            version: KdlVersion::Auto,
+        }
+    }
+}
+
+impl PrintOptions {
+    /// Create options with the default style
+    pub fn new() -> PrintOptions {
+        PrintOptions::default()
+    }
+    /// Number of spaces per level of nesting (default is `4`)
+    pub fn indent(mut self, width: usize) -> Self {
+        self.indent = width;
+        self
+    }
+    /// How identifiers are quoted (default is [`Identifiers::Bare`])
+    pub fn identifiers(mut self, identifiers: Identifiers) -> Self {
+        self.identifiers = identifiers;
+        self
+    }
+    /// Prefer raw strings for values that would need escapes otherwise
+    /// (default is `false`)
+    ///
+    /// Only applies to string values, identifiers are always escaped.
+    pub fn raw_strings(mut self, value: bool) -> Self {
+        self.raw_strings = value;
+        self
+    }
+    /// Keep the radix of the integer as it was written in the source code
+    /// (default is `true`)
+    ///
+    /// When disabled, all integers are written in decimal.
+    pub fn keep_radix(mut self, value: bool) -> Self {
+        self.keep_radix = value;
+        self
+    }
+    /// Order in which properties are written (default is
+    /// [`PropertyOrder::Sorted`])
+    pub fn property_order(mut self, order: PropertyOrder) -> Self {
+        self.property_order = order;
+        self
+    }
+    /// Version of the KDL syntax (default is [`KdlVersion::Auto`])
+    ///
+    /// With [`KdlVersion::V2`] the document starts with the
+    /// `/- kdl-version 2` marker, so the parser detects the version.
+    ///
+    /// `Auto` writes KDL 1.0, unless the document contains infinity or NaN
+    /// that KDL 1.0 can't represent, in which case it's written in KDL 2.0
+    /// with the marker. `V1` works the same way, so the output can always be
+    /// parsed back.
+    pub fn version(mut self, version: KdlVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    fn printer<S: ErrorSpan>(&self, needs_v2: impl FnOnce() -> bool) -> Printer<'_, S> {
+        let offset = match self.property_order {
+            PropertyOrder::Sorted => None,
+            PropertyOrder::Source => Some(source_offset::<S> as fn(&S) -> usize),
+        };
+        Printer {
+            options: self,
+            offset,
+            syntax: self.syntax(needs_v2),
+        }
+    }
+
+    fn syntax(&self, needs_v2: impl FnOnce() -> bool) -> Syntax {
+        match self.version {
+            KdlVersion::V2 => Syntax::V2,
+            _ if needs_v2() => Syntax::V2,
+            _ => Syntax::V1,
+        }
+    }
+
+    /// Print the whole document into a string
+    pub fn print_document<S: ErrorSpan>(&self, doc: &Document<S>) -> String {
+        let mut buf = String::new();
+        self.write_document(&mut buf, doc)
+            .expect("writing to string never fails");
+        buf
+    }
+    /// Print a single node (with its children) into a string
+    ///
+    /// Unlike [`print_document`](Self::print_document) no trailing newline
+    /// is written.
+    pub fn print_node<S: ErrorSpan>(&self, node: &Node<S>) -> String {
+        let mut buf = String::new();
+        self.write_node(&mut buf, node)
+            .expect("writing to string never fails");
+        buf
+    }
+    /// Write the whole document into the writer
+    pub fn write_document<S: ErrorSpan>(
+        &self,
+        f: &mut impl Write,
+        doc: &Document<S>,
+    ) -> fmt::Result {
+        self.printer(|| needs_v2(doc.nodes.iter().map(|n| &**n)))
+            .write_document(f, doc)
+    }
+    /// Write a single node (with its children) into the writer
+    ///
+    /// The version marker is not written, even if the node is written in
+    /// KDL 2.0.
+    pub fn write_node<S: ErrorSpan>(&self, f: &mut impl Write, node: &Node<S>) -> fmt::Result {
+        self.printer(|| needs_v2([node])).write_node(f, node, 0)
+    }
+}
+
+/// Returns `true` for infinity and NaN, which only KDL 2.0 can represent
+fn is_non_finite(literal: &Literal) -> bool {
+    matches!(literal, Literal::Decimal(Decimal(d)) if matches!(&d[..], "inf" | "-inf" | "nan"))
+}
+
+/// Returns `true` if any of the nodes or their descendants has a value that
+/// can't be written in KDL 1.0
+fn needs_v2<'a, S: 'a>(nodes: impl IntoIterator<Item = &'a Node<S>>) -> bool {
+    let mut stack = nodes.into_iter().collect::<Vec<_>>();
+    while let Some(node) = stack.pop() {
+        let values = node.arguments.iter().chain(node.properties.values());
+        if values.into_iter().any(|v| is_non_finite(&v.literal)) {
+            return true;
+        }
+        stack.extend(node.children().map(|n| &**n));
+    }
+    false
+}
+
+fn source_offset<S: ErrorSpan>(span: &S) -> usize {
+    let span: miette::SourceSpan = span.clone().into();
+    span.offset()
+}
+
+pub(crate) fn is_bare_ident(s: &str) -> bool {
+    let mut chars = s.chars();
+    let first = match chars.next() {
+        Some(c) => c,
+        None => return false,
+    };
+    if first.is_ascii_digit() {
+        return false;
+    }
+    if first == '-' || first == '+' {
+        if let Some(second) = chars.next() {
+            if second.is_ascii_digit() {
+                return false;
+            }
+        }
+    }
+    !matches!(s, "true" | "false" | "null") && s.chars().all(is_id_char)
+}
+
+fn is_id_char(c: char) -> bool {
+    !matches!(c,
+        '\u{0000}'..='\u{0020}' |
+        '\\'|'/'|'('|')'|'{'|'}'|'<'|'>'|';'|'['|']'|'='|','|'"' |
+        '\u{00a0}' | '\u{1680}' |
+        '\u{2000}'..='\u{200A}' |
+        '\u{202F}' | '\u{205F}' | '\u{3000}' |
+        '\u{0085}' | '\u{2028}' | '\u{2029}' |
+        // byte order mark is whitespace
+        '\u{FEFF}'
+    )
+}
+
+fn needs_escape(c: char) -> bool {
+    matches!(c, '"' | '\\') || c.is_control()
+}
+
+pub(crate) fn write_string(f: &mut impl Write, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            '\u{0008}' => f.write_str("\\b")?,
+            '\u{000C}' => f.write_str("\\f")?,
+            c if c.is_control() => write!(f, "\\u{{{:x}}}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+/// Writes raw string if it's possible to represent the string verbatim
+///
+/// Returns `false` if nothing was written.
+pub(crate) fn write_raw_string(f: &mut impl Write, s: &str) -> Result<bool, fmt::Error> {
+    // Carriage return and other control chars are kept escaped to avoid
+    // them being mangled by editors
+    if s.chars().any(|c| c.is_control() && c != '\n' && c != '\t') {
+        return Ok(false);
+    }
+    let mut hashes = 0;
+    for part in s.split('"').skip(1) {
+        let run = part.bytes().take_while(|&b| b == b'#').count();
+        hashes = hashes.max(run + 1);
+    }
+    f.write_char('r')?;
+    for _ in 0..hashes {
+        f.write_char('#')?;
+    }
+    f.write_char('"')?;
+    f.write_str(s)?;
+    f.write_char('"')?;
+    for _ in 0..hashes {
+        f.write_char('#')?;
+    }
+    Ok(true)
+}
+
+pub(crate) fn write_integer(f: &mut impl Write, value: &Integer) -> fmt::Result {
+    let Integer(radix, digits) = value;
+    let (sign, digits) = match digits.as_bytes().first() {
+        Some(b'-' | b'+') => digits.split_at(1),
+        _ => ("", &digits[..]),
+    };
+    let prefix = match radix {
+        Radix::Bin => "0b",
+        Radix::Oct => "0o",
+        Radix::Dec => "",
+        Radix::Hex => "0x",
+    };
+    write!(f, "{}{}{}", sign, prefix, digits)
+}
+
+fn write_decimal_integer(f: &mut impl Write, value: &Integer) -> fmt::Result {
+    let Integer(radix, digits) = value;
+    let base = match radix {
+        Radix::Bin => 2,
+        Radix::Oct => 8,
+        Radix::Dec => return write_integer(f, value),
+        Radix::Hex => 16,
+    };
+    let (negative, digits) = match digits.as_bytes().first() {
+        Some(b'-') => (true, &digits[1..]),
+        Some(b'+') => (false, &digits[1..]),
+        _ => (false, &digits[..]),
+    };
+    match u128::from_str_radix(digits, base) {
+        Ok(num) if negative && num != 0 => write!(f, "-{}", num),
+        Ok(num) => write!(f, "{}", num),
+        // too large values are kept as is
+        Err(_) => write_integer(f, value),
+    }
+}
+
+/// Writes raw string of KDL 2.0 if it's possible to represent the string
+/// verbatim
+///
+/// Returns `false` if nothing was written.
+fn write_raw_string_v2(f: &mut impl Write, s: &str) -> Result<bool, fmt::Error> {
+    // raw strings of KDL 2.0 are single-line
+    if s.chars()
+        .any(|c| c.is_control() || grammar_v2::is_newline_char(c) || grammar_v2::is_disallowed(c))
+    {
+        return Ok(false);
+    }
+    let mut hashes = 1;
+    for part in s.split('"').skip(1) {
+        let run = part.bytes().take_while(|&b| b == b'#').count();
+        hashes = hashes.max(run + 1);
+    }
+    let hashes = "#".repeat(hashes);
+    write!(f, "{0}\"{1}\"{0}", hashes, s)?;
+    Ok(true)
+}
+
+impl<S> Printer<'_, S> {
+    fn write_ident(&self, f: &mut impl Write, s: &str) -> fmt::Result {
+        let bare = match self.syntax {
+            Syntax::V1 => is_bare_ident(s),
+            Syntax::V2 => grammar_v2::is_bare_ident(s),
+        };
+        match self.options.identifiers {
+            Identifiers::Bare if bare => f.write_str(s),
+            _ => self.write_string(f, s),
+        }
+    }
+
+    fn write_string(&self, f: &mut impl Write, s: &str) -> fmt::Result {
+        match self.syntax {
+            Syntax::V1 => write_string(f, s),
+            Syntax::V2 => migrate::write_string(f, s),
+        }
+    }
+
+    fn write_type_name(
+        &self,
+        f: &mut impl Write,
+        type_name: &Option<Spanned<crate::ast::TypeName, S>>,
+    ) -> fmt::Result {
+        if let Some(type_name) = type_name {
+            f.write_char('(')?;
+            self.write_ident(f, type_name.as_str())?;
+            f.write_char(')')?;
+        }
+        Ok(())
+    }
+
+    fn write_value(&self, f: &mut impl Write, value: &Value<S>) -> fmt::Result {
+        self.write_type_name(f, &value.type_name)?;
+        self.write_literal(f, &value.literal)
+    }
+
+    fn write_literal(&self, f: &mut impl Write, literal: &Literal) -> fmt::Result {
+        let v2 = self.syntax == Syntax::V2;
+        if v2 && matches!(literal, Literal::Null | Literal::Bool(_)) || is_non_finite(literal) {
+            // keywords of KDL 2.0
+            f.write_char('#')?;
+        }
+        match literal {
+            Literal::Null => f.write_str("null"),
+            Literal::Bool(true) => f.write_str("true"),
+            Literal::Bool(false) => f.write_str("false"),
+            Literal::Int(value) if self.options.keep_radix => write_integer(f, value),
+            Literal::Int(value) => write_decimal_integer(f, value),
+            Literal::Decimal(value) => {
+                f.write_str(&value.0)?;
+                if v2 && value.0.ends_with(['e', 'E', '+', '-']) {
+                    // KDL 1.0 allows exponent without digits
+                    f.write_char('0')?;
+                }
+                Ok(())
+            }
+            Literal::String(value) => {
+                if self.options.raw_strings && value.chars().any(needs_escape) {
+                    let raw = match self.syntax {
+                        Syntax::V1 => write_raw_string(f, value)?,
+                        Syntax::V2 => write_raw_string_v2(f, value)?,
+                    };
+                    if raw {
+                        return Ok(());
+                    }
+                }
+                self.write_string(f, value)
+            }
+        }
+    }
+
+    fn write_indent(&self, f: &mut impl Write, level: usize) -> fmt::Result {
+        write!(f, "{:1$}", "", level * self.options.indent)
+    }
+
+    fn write_document(&self, f: &mut impl Write, doc: &Document<S>) -> fmt::Result {
+        if self.syntax == Syntax::V2 {
+            f.write_str("/- kdl-version 2\n")?;
+        }
+        for node in &doc.nodes {
+            self.write_node(f, node, 0)?;
+            f.write_char('\n')?;
+        }
+        Ok(())
+    }
+
+    fn write_node(&self, f: &mut impl Write, node: &Node<S>, level: usize) -> fmt::Result {
+        // Children are written using an explicit stack, so that deeply nested
+        // documents don't overflow the stack
+        let mut stack = Vec::new();
+        self.write_node_head(f, node, level, &mut stack)?;
+        while let Some(step) = stack.pop() {
+            f.write_char('\n')?;
+            match step {
+                Step::Child(child, level) => {
+                    self.write_indent(f, level)?;
+                    self.write_node_head(f, child, level, &mut stack)?;
+                }
+                Step::Close(level) => {
+                    self.write_indent(f, level)?;
+                    f.write_char('}')?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the node up to its children, which are pushed to the `stack`
+    fn write_node_head<'n>(
+        &self,
+        f: &mut impl Write,
+        node: &'n Node<S>,
+        level: usize,
+        stack: &mut Vec<Step<'n, S>>,
+    ) -> fmt::Result {
+        self.write_type_name(f, &node.type_name)?;
+        self.write_ident(f, &node.node_name)?;
+        for arg in &node.arguments {
+            f.write_char(' ')?;
+            self.write_value(f, arg)?;
+        }
+        let mut properties = node.properties.iter().collect::<Vec<_>>();
+        if let Some(offset) = self.offset {
+            // stable sort keeps names sorted for the same offset
+            properties.sort_by_key(|(name, _)| offset(name.span()));
+        }
+        for (name, value) in properties {
+            f.write_char(' ')?;
+            self.write_ident(f, name)?;
+            f.write_char('=')?;
+            self.write_value(f, value)?;
+        }
+        if let Some(children) = &node.children {
+            if children.is_empty() {
+                f.write_str(" {}")?;
+            } else {
+                f.write_str(" {")?;
+                stack.push(Step::Close(level));
+                stack.extend(
+                    children
+                        .iter()
+                        .rev()
+                        .map(|child| Step::Child(child, level + 1)),
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Rest of the node being written, each step starts on a new line
+enum Step<'a, S> {
+    Child(&'a Node<S>, usize),
+    Close(usize),
+}
+
+impl<'a, S> Printer<'a, S> {
+    fn display(options: &'a PrintOptions, needs_v2: impl FnOnce() -> bool) -> Printer<'a, S> {
+        Printer {
+            options,
+            offset: None,
+            syntax: options.syntax(needs_v2),
+        }
+    }
+}
+
+impl<S> fmt::Display for Document<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Printer::display(&PrintOptions::default(), || {
+            needs_v2(self.nodes.iter().map(|n| &**n))
+        })
+        .write_document(f, self)
+    }
+}
+
+impl<S> fmt::Display for Node<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Printer::display(&PrintOptions::default(), || needs_v2([self])).write_node(f, self, 0)
+    }
+}
+
+impl<S> fmt::Display for Value<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Printer::display(&PrintOptions::default(), || is_non_finite(&self.literal))
+            .write_value(f, self)
+    }
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Printer::<()>::display(&PrintOptions::default(), || is_non_finite(self))
+            .write_literal(f, self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Identifiers, PrintOptions, PropertyOrder};
+    use crate::ast::Document;
+    use crate::span::Span;
+    use crate::{parse_ast, KdlVersion};
+
+    fn parse(text: &str) -> Document<Span> {
+        parse_ast("<test>", text).unwrap()
+    }
+
+    fn reprint(text: &str) -> String {
+        reprint_with(&PrintOptions::new(), text)
+    }
+
+    fn reprint_with(options: &PrintOptions, text: &str) -> String {
+        let doc = parse(text);
+        let printed = options.print_document(&doc);
+        let reparsed = parse_ast("<printed>", &printed).unwrap();
+        assert_eq!(doc, reparsed);
+        printed
+    }
+
+    #[test]
+    fn print_nodes() {
+        assert_eq!(reprint("node"), "node\n");
+        assert_eq!(
+            reprint(r#"(t)node 1 "two" (u8)3 key=true a=null"#),
+            "(t)node 1 \"two\" (u8)3 a=null key=true\n"
+        );
+        assert_eq!(
+            reprint("a { b { c; }; d {}; }"),
+            "a {\n    b {\n        c\n    }\n    d {}\n}\n"
+        );
+    }
+
+    #[test]
+    fn print_quoted() {
+        assert_eq!(reprint(r#""" "a\tb\"""#), "\"\" \"a\\tb\\\"\"\n");
+        assert_eq!(
+            reprint(r#""true" "1a" "-1" "a b"="x""#),
+            concat!(r#""true" "1a" "-1" "a b"="x""#, "\n")
+        );
+        assert_eq!(reprint(r##"node r#"\"#"##), "node \"\\\\\"\n");
+        assert_eq!(reprint("-; --a; -x"), "-\n--a\n-x\n");
+    }
+
+    #[test]
+    fn print_bom() {
+        assert_eq!(
+            reprint("\"\u{FEFF}a\" b\u{FEFF}c=1"),
+            "\"\u{FEFF}a\" \"b\u{FEFF}c\"=1\n"
+        );
+    }
+
+    #[test]
+    fn print_numbers() {
+        assert_eq!(
+            reprint("n 0x1f -0o17 +0b101 1_000 1.5e10"),
+            "n 0x1f -0o17 +0b101 1000 1.5e10\n"
+        );
+    }
+
+    #[test]
+    fn display_matches_default() {
+        let doc = parse(r#"(t)a 0x10 "x\ny" z=1 { b; }"#);
+        assert_eq!(doc.to_string(), PrintOptions::new().print_document(&doc));
+        assert_eq!(
+            doc.nodes[0].to_string(),
+            PrintOptions::new().print_node(&doc.nodes[0])
+        );
+    }
+
+    #[test]
+    fn print_indent() {
+        let options = PrintOptions::new().indent(2);
+        assert_eq!(
+            reprint_with(&options, "a { b { c; }; }"),
+            "a {\n  b {\n    c\n  }\n}\n"
+        );
+        let options = PrintOptions::new().indent(0);
+        assert_eq!(reprint_with(&options, "a { b; }"), "a {\nb\n}\n");
+    }
+
+    #[test]
+    fn print_identifiers() {
+        let options = PrintOptions::new().identifiers(Identifiers::Quoted);
+        assert_eq!(
+            reprint_with(&options, r#"(t)node (u8)1 key="val""#),
+            "(\"t\")\"node\" (\"u8\")1 \"key\"=\"val\"\n"
+        );
+    }
+
+    #[test]
+    fn print_raw_strings() {
+        let options = PrintOptions::new().raw_strings(true);
+        assert_eq!(
+            reprint_with(&options, r#"node "plain" "C:\\dir" "say \"hi\"""#),
+            "node \"plain\" r\"C:\\dir\" r#\"say \"hi\"\"#\n"
+        );
+        assert_eq!(
+            reprint_with(&options, r####"node r##"a"#b"##"####),
+            "node r##\"a\"#b\"##\n"
+        );
+        assert_eq!(
+            reprint_with(&options, r#"node "a\nb" "\r""#),
+            "node r\"a\nb\" \"\\r\"\n"
+        );
+    }
+
+    #[test]
+    fn print_radix() {
+        let options = PrintOptions::new().keep_radix(false);
+        assert_eq!(
+            options.print_document(&parse("n 0x1f -0o17 +0b101 -0x0 1_000")),
+            "n 31 -15 5 0 1000\n"
+        );
+        let huge = "n 0xffffffffffffffffffffffffffffffffff\n";
+        assert_eq!(options.print_document(&parse(huge)), huge);
+    }
+
+    #[test]
+    fn print_v2() {
+        let options = PrintOptions::new().version(KdlVersion::V2);
+        assert_eq!(
+            reprint_with(
+                &options,
+                r##"("true")node true null 1.5 "a\"#b" "\u{FEFF}" r"x" k=false"##
+            ),
+            concat!(
+                "/- kdl-version 2\n",
+                r##"("true")node #true #null 1.5 "a\"#b" "\u{feff}" "x" k=#false"##,
+                "\n",
+            )
+        );
+        let options = options.raw_strings(true);
+        assert_eq!(
+            reprint_with(&options, r##"n "say \"hi\"#" "a\nb" "C:\\""##),
+            concat!(
+                "/- kdl-version 2\n",
+                r###"n ##"say "hi"#"## "a\nb" #"C:\"#"###,
+                "\n",
+            )
+        );
+    }
+
+    #[test]
+    fn print_non_finite() {
+        let text = "/- kdl-version 2\nn #inf #-inf #nan #true 1.5 {\n    c #null\n}\n";
+        assert_eq!(reprint(text), text);
+        let options = PrintOptions::new().version(KdlVersion::V1);
+        assert_eq!(reprint_with(&options, text), text);
+        let doc = parse(text);
+        assert_eq!(doc.nodes[0].to_string(), &text[17..text.len() - 1]);
+        assert_eq!(doc.nodes[0].arguments[0].to_string(), "#inf");
+        assert_eq!(doc.nodes[0].arguments[3].to_string(), "true");
+        assert_eq!(reprint("/- kdl-version 2\nn 1.5 #true"), "n 1.5 true\n");
+    }
+
+    #[test]
+    fn deep_nesting() {
+        let depth = 100_000;
+        let text = "a {\n".repeat(depth) + "b\n" + &"}\n".repeat(depth);
+        let options = PrintOptions::new().indent(0);
+        let doc = parse(&text);
+        assert_eq!(options.print_document(&doc), text);
+        crate::ast::drop_nodes(doc.nodes);
+    }
+
+    #[test]
+    fn print_property_order() {
+        let options = PrintOptions::new().property_order(PropertyOrder::Source);
+        assert_eq!(reprint_with(&options, "n z=1 b=2 a=3"), "n z=1 b=2 a=3\n");
+        assert_eq!(reprint("n z=1 b=2 a=3"), "n a=3 b=2 z=1\n");
+    }
+}
@@ -0,0 +1,890 @@
+//! KDL Query Language
+//!
+//! Implements [KQL](https://github.com/kdl-org/kdl/blob/main/QUERY-SPEC.md)
+//! for selecting nodes from the [AST](crate::ast) without decoding the whole
+//! document:
+//!
+//! ```rust
+//! use knus::query::Query;
+//!
+//! let doc = knus::parse_ast::<knus::span::Span>("config.kdl", r#"
+//!     server {
+//!         plugin "auth" enabled=true
+//!         plugin "cache" enabled=false
+//!     }
+//! "#)?;
+//! let query: Query = "server > plugin[enabled=true]".parse()?;
+//! let nodes = query.select(&doc);
+//! assert_eq!(nodes.len(), 1);
+//! assert_eq!(nodes[0].arguments[0].literal.to_string(), r#""auth""#);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! # Syntax
+//!
+//! A query is a list of selectors separated by `||`, nodes matching any of
+//! them are returned in the document order. A selector is a list of node
+//! filters separated by combinators:
+//!
+//! * `a > b` selects `b` nodes that are direct children of `a` nodes
+//! * `a b` selects `b` nodes that are descendants of `a` nodes
+//! * `a + b` selects `b` nodes immediately following `a` siblings
+//! * `a ~ b` selects `b` nodes following `a` siblings
+//!
+//! The filter consists of an optional type name in parenthesis (`()` matches
+//! any type name), an optional node name and any number of matchers in square
+//! brackets. `top()` filter selects the top-level nodes, and `[]` matches any
+//! node.
+//!
+//! The matcher is an accessor optionally followed by an operator and a
+//! value. Without the operator it checks that the accessed value exists.
+//! Accessors are:
+//!
+//! * `val()` or `val(N)` – an argument, the first one by default
+//! * `prop(name)` or just `name` – a property
+//! * `name()` – the node name
+//! * `type()` (or `tag()`) – the type name of the node
+//!
+//! Operators are `=` and `!=` for any values, `>`, `>=`, `<` and `<=` for
+//! numbers, and `^=` (starts with), `$=` (ends with), `*=` (contains) for
+//! strings.
+//!
+//! Query can be followed by `=>` and an accessor, or a tuple of accessors
+//! like `(name(), val(0))`, to [extract](Query::extract) values from the
+//! selected nodes. `props()` and `values()` accessors are also allowed there.
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use crate::ast::{Document, Literal, SpannedName, SpannedNode, TypeName, Value};
+use crate::borrowed;
+use crate::decode::Context;
+use crate::errors::{DecodeError, ParseErrorKind, QueryError, TokenFormat};
+use crate::grammar::{Scanner, Syntax};
+use crate::grammar_v2;
+use crate::span::{Span, Spanned};
+use crate::traits::{Decode, ErrorSpan};
+
+/// Parsed KQL query
+///
+/// Use [`Query::parse`] or [`str::parse`] to create one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    selectors: Vec<Selector>,
+    mapping: Option<Mapping>,
+}
+
+/// Value extracted from the selected node by [`Query::extract`]
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Extracted<'a, S> {
+    /// The node itself, when query has no `=>` part
+    Node(&'a SpannedNode<S>),
+    /// Node name, `name()`
+    Name(&'a SpannedName<S>),
+    /// Type name of the node, `type()`
+    TypeName(Option<&'a Spanned<TypeName, S>>),
+    /// An argument or a property, `val()` or `prop(name)`
+    Value(Option<&'a Value<S>>),
+    /// All arguments, `values()`
+    Values(&'a [Value<S>]),
+    /// All properties, `props()`
+    Props(&'a BTreeMap<SpannedName<S>, Value<S>>),
+    /// Tuple of values, `(name(), val())`
+    Tuple(Vec<Extracted<'a, S>>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Selector {
+    /// Filters with the combinator that precedes them, the combinator of the
+    /// first filter is ignored
+    filters: Vec<(Combinator, Filter)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Combinator {
+    Descendant,
+    Child,
+    Next,
+    Following,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Filter {
+    top: bool,
+    /// `Some(None)` matches any type name
+    type_name: Option<Option<Box<str>>>,
+    name: Option<Box<str>>,
+    matchers: Vec<Matcher>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Matcher {
+    accessor: Accessor,
+    comparison: Option<(Operator, Literal)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Accessor {
+    Val(usize),
+    Prop(Box<str>),
+    Name,
+    TypeName,
+    Values,
+    Props,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    StartsWith,
+    EndsWith,
+    Contains,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Mapping {
+    Single(Accessor),
+    Tuple(Vec<Accessor>),
+}
+
+/// Node of the document along with its position in the tree
+struct Entry<'a, S> {
+    /// `None` for the document itself
+    node: Option<&'a SpannedNode<S>>,
+    parent: Option<usize>,
+    prev_sibling: Option<usize>,
+}
+
+enum Accessed<'a> {
+    Literal(&'a Literal),
+    Str(&'a str),
+}
+
+struct QueryParser<'a> {
+    scan: Scanner<'a, Span>,
+}
+
+type Error = ParseErrorKind<Span>;
+
+impl Query {
+    /// Parses the query text
+    pub fn parse(text: &str) -> Result<Query, QueryError> {
+        let mut parser = QueryParser {
+            scan: Scanner::new(text, Syntax::V2),
+        };
+        let result = parser.query();
+        if let Some(err) = parser.scan.errors.drain(..).next() {
+            return Err(QueryError::new(text, err));
+        }
+        result.map_err(|err| QueryError::new(text, err))
+    }
+
+    /// Returns the nodes of the document matching the query
+    ///
+    /// Nodes are returned in the document order, each node at most once.
+    pub fn select<'a, S>(&self, document: &'a Document<S>) -> Vec<&'a SpannedNode<S>> {
+        let entries = flatten(&document.nodes);
+        entries
+            .iter()
+            .enumerate()
+            .filter(|&(idx, _)| self.selectors.iter().any(|s| s.matches(&entries, idx)))
+            .filter_map(|(_, entry)| entry.node)
+            .collect()
+    }
+
+    /// Returns the values extracted from the nodes matching the query
+    ///
+    /// Nodes are extracted according to the `=>` part of the query, if there
+    /// is no such part, nodes are returned as is.
+    pub fn extract<'a, S>(&self, document: &'a Document<S>) -> Vec<Extracted<'a, S>> {
+        let nodes = self.select(document);
+        match &self.mapping {
+            None => nodes.into_iter().map(Extracted::Node).collect(),
+            Some(Mapping::Single(accessor)) => nodes
+                .into_iter()
+                .map(|node| accessor.extract(node))
+                .collect(),
+            Some(Mapping::Tuple(accessors)) => nodes
+                .into_iter()
+                .map(|node| Extracted::Tuple(accessors.iter().map(|a| a.extract(node)).collect()))
+                .collect(),
+        }
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+    fn from_str(s: &str) -> Result<Query, QueryError> {
+        Query::parse(s)
+    }
+}
+
+/// Decodes the nodes matching the query
+///
+/// Every selected node is decoded with the [`Decode`] implementation of `T`,
+/// errors of all the nodes are returned together.
+///
+/// ```rust
+/// #[derive(knus::Decode, Debug, PartialEq)]
+/// struct Plugin {
+///     #[knus(argument)]
+///     name: String,
+/// }
+///
+/// let doc = knus::parse_ast::<knus::span::Span>("config.kdl", r#"
+///     server { plugin "auth"; plugin "cache"; }
+/// "#)?;
+/// let query = "server > plugin".parse()?;
+/// let plugins: Vec<Plugin> = knus::query::decode_query(&query, &doc).unwrap();
+/// assert_eq!(plugins[1], Plugin { name: "cache".into() });
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn decode_query<T, S>(
+    query: &Query,
+    document: &Document<S>,
+) -> Result<Vec<T>, Vec<DecodeError<S>>>
+where
+    T: Decode<S>,
+    S: ErrorSpan,
+{
+    let mut ctx = Context::new();
+    let mut result = Vec::new();
+    for node in query.select(document) {
+        match T::decode_node(node, &mut ctx) {
+            Ok(value) => result.push(value),
+            Err(e) => ctx.emit_error(e),
+        }
+    }
+    if ctx.has_errors() {
+        Err(ctx.into_errors())
+    } else {
+        Ok(result)
+    }
+}
+
+/// Lists the nodes in the document order
+fn flatten<S>(nodes: &[SpannedNode<S>]) -> Vec<Entry<'_, S>> {
+    let mut entries = vec![Entry {
+        node: None,
+        parent: None,
+        prev_sibling: None,
+    }];
+    let mut stack = vec![(0, nodes.iter(), None)];
+    while let Some((parent, iter, prev_sibling)) = stack.last_mut() {
+        let node = match iter.next() {
+            Some(node) => node,
+            None => {
+                stack.pop();
+                continue;
+            }
+        };
+        let idx = entries.len();
+        entries.push(Entry {
+            node: Some(node),
+            parent: Some(*parent),
+            prev_sibling: *prev_sibling,
+        });
+        *prev_sibling = Some(idx);
+        if let Some(children) = &node.children {
+            stack.push((idx, children.iter(), None));
+        }
+    }
+    entries
+}
+
+impl Selector {
+    fn matches<S>(&self, entries: &[Entry<S>], idx: usize) -> bool {
+        match_filters(&self.filters, entries, idx)
+    }
+}
+
+fn match_filters<S>(filters: &[(Combinator, Filter)], entries: &[Entry<S>], idx: usize) -> bool {
+    let ((combinator, filter), rest) = match filters.split_last() {
+        Some(pair) => pair,
+        None => return true,
+    };
+    if !filter.matches(&entries[idx]) {
+        return false;
+    }
+    if rest.is_empty() {
+        return true;
+    }
+    let entry = &entries[idx];
+    let (mut next, repeat) = match combinator {
+        Combinator::Child => (entry.parent, false),
+        Combinator::Descendant => (entry.parent, true),
+        Combinator::Next => (entry.prev_sibling, false),
+        Combinator::Following => (entry.prev_sibling, true),
+    };
+    while let Some(other) = next {
+        if match_filters(rest, entries, other) {
+            return true;
+        }
+        if !repeat {
+            break;
+        }
+        next = match combinator {
+            Combinator::Descendant => entries[other].parent,
+            _ => entries[other].prev_sibling,
+        };
+    }
+    false
+}
+
+impl Filter {
+    fn matches<S>(&self, entry: &Entry<S>) -> bool {
+        let node = match entry.node {
+            Some(node) => node,
+            None => return self.top,
+        };
+        if self.top {
+            return false;
+        }
+        match (&self.type_name, &node.type_name) {
+            (Some(_), None) => return false,
+            (Some(Some(expected)), Some(type_name)) if expected[..] != *type_name.as_str() => {
+                return false;
+            }
+            _ => {}
+        }
+        if let Some(name) = &self.name {
+            if *name != *node.node_name {
+                return false;
+            }
+        }
+        self.matchers.iter().all(|m| m.matches(node))
+    }
+}
+
+impl Matcher {
+    fn matches<S>(&self, node: &SpannedNode<S>) -> bool {
+        let accessed = match self.accessor.access(node) {
+            Some(accessed) => accessed,
+            None => return false,
+        };
+        let (operator, value) = match &self.comparison {
+            Some((operator, value)) => (operator, value),
+            None => return true,
+        };
+        let number = match &accessed {
+            Accessed::Literal(lit) => as_number(lit),
+            Accessed::Str(_) => None,
+        };
+        match operator {
+            Operator::Eq => equal(&accessed, value),
+            Operator::Ne => !equal(&accessed, value),
+            Operator::Gt | Operator::Ge | Operator::Lt | Operator::Le => {
+                match (number, as_number(value)) {
+                    (Some(left), Some(right)) => match operator {
+                        Operator::Gt => left > right,
+                        Operator::Ge => left >= right,
+                        Operator::Lt => left < right,
+                        _ => left <= right,
+                    },
+                    _ => false,
+                }
+            }
+            Operator::StartsWith | Operator::EndsWith | Operator::Contains => {
+                match (as_str(&accessed), value) {
+                    (Some(left), Literal::String(right)) => match operator {
+                        Operator::StartsWith => left.starts_with(&right[..]),
+                        Operator::EndsWith => left.ends_with(&right[..]),
+                        _ => left.contains(&right[..]),
+                    },
+                    _ => false,
+                }
+            }
+        }
+    }
+}
+
+fn as_str<'a>(accessed: &Accessed<'a>) -> Option<&'a str> {
+    match accessed {
+        Accessed::Str(s) => Some(s),
+        Accessed::Literal(Literal::String(s)) => Some(s),
+        Accessed::Literal(_) => None,
+    }
+}
+
+pub(crate) fn as_number(literal: &Literal) -> Option<f64> {
+    match literal {
+        Literal::Int(value) => i64::try_from(value)
+            .map(|v| v as f64)
+            .or_else(|_| u64::try_from(value).map(|v| v as f64))
+            .ok(),
+        Literal::Decimal(value) => f64::try_from(value).ok(),
+        _ => None,
+    }
+}
+
+fn equal(accessed: &Accessed, value: &Literal) -> bool {
+    match (accessed, value) {
+        (Accessed::Str(left), Literal::String(right)) => **left == **right,
+        (Accessed::Str(_), _) => false,
+        (Accessed::Literal(left), right) => match (as_number(left), as_number(right)) {
+            (Some(left), Some(right)) => left == right,
+            _ => *left == right,
+        },
+    }
+}
+
+impl Accessor {
+    fn access<'a, S>(&self, node: &'a SpannedNode<S>) -> Option<Accessed<'a>> {
+        match self {
+            Accessor::Val(idx) => node
+                .arguments
+                .get(*idx)
+                .map(|v| Accessed::Literal(&v.literal)),
+            Accessor::Prop(name) => node
+                .properties
+                .get(&name[..])
+                .map(|v| Accessed::Literal(&v.literal)),
+            Accessor::Name => Some(Accessed::Str(&node.node_name)),
+            Accessor::TypeName => node.type_name.as_ref().map(|t| Accessed::Str(t.as_str())),
+            // only allowed in mapping
+            Accessor::Values | Accessor::Props => None,
+        }
+    }
+
+    fn extract<'a, S>(&self, node: &'a SpannedNode<S>) -> Extracted<'a, S> {
+        match self {
+            Accessor::Val(idx) => Extracted::Value(node.arguments.get(*idx)),
+            Accessor::Prop(name) => Extracted::Value(node.properties.get(&name[..])),
+            Accessor::Name => Extracted::Name(&node.node_name),
+            Accessor::TypeName => Extracted::TypeName(node.type_name.as_ref()),
+            Accessor::Values => Extracted::Values(&node.arguments),
+            Accessor::Props => Extracted::Props(&node.properties),
+        }
+    }
+}
+
+fn is_ident_char(c: char) -> bool {
+    grammar_v2::is_id_char(c)
+        && !matches!(c, '>' | '<' | '+' | '~' | ',' | '|' | '!' | '^' | '$' | '*')
+}
+
+impl<'a> QueryParser<'a> {
+    fn ws(&mut self) -> bool {
+        let start = self.scan.pos;
+        self.scan.skip_while(char::is_whitespace);
+        self.scan.pos > start
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), Error> {
+        if self.scan.peek_char() == Some(c) {
+            self.scan.pos += c.len_utf8();
+            Ok(())
+        } else {
+            Err(self.scan.unexpected(None, &[c.into()]))
+        }
+    }
+
+    fn query(&mut self) -> Result<Query, Error> {
+        self.ws();
+        let mut selectors = vec![self.selector()?];
+        self.ws();
+        while self.scan.starts_with("||") {
+            self.scan.pos += 2;
+            self.ws();
+            selectors.push(self.selector()?);
+            self.ws();
+        }
+        let mapping = if self.scan.starts_with("=>") {
+            self.scan.pos += 2;
+            self.ws();
+            Some(self.mapping()?)
+        } else {
+            None
+        };
+        self.ws();
+        if self.scan.peek().is_some() {
+            let mut expected = vec![TokenFormat::Eoi];
+            if mapping.is_none() {
+                expected.extend([TokenFormat::Token("||"), TokenFormat::Token("=>")]);
+            }
+            return Err(self.scan.unexpected(None, &expected));
+        }
+        Ok(Query { selectors, mapping })
+    }
+
+    fn selector(&mut self) -> Result<Selector, Error> {
+        let mut filters = vec![(Combinator::Descendant, self.filter()?)];
+        loop {
+            let space = self.ws();
+            let combinator = match self.scan.peek() {
+                Some(b'>') => Combinator::Child,
+                Some(b'+') => Combinator::Next,
+                Some(b'~') => Combinator::Following,
+                Some(b'|' | b'=') | None => break,
+                Some(_) if space => Combinator::Descendant,
+                Some(_) => break,
+            };
+            if combinator != Combinator::Descendant {
+                self.scan.pos += 1;
+                self.ws();
+            }
+            let filter_start = self.scan.pos;
+            let filter = self.filter()?;
+            if filter.top {
+                self.scan.pos = filter_start;
+                return Err(self.message(
+                    filter_start,
+                    filter_start + "top()".len(),
+                    "`top()` can only be used at the start of the selector",
+                ));
+            }
+            filters.push((combinator, filter));
+        }
+        if filters.len() == 1 && filters[0].1.top {
+            // `top()` alone selects all top-level nodes
+            filters.push((Combinator::Child, Filter::default()));
+        }
+        Ok(Selector { filters })
+    }
+
+    fn message(&mut self, start: usize, end: usize, message: &str) -> Error {
+        ParseErrorKind::Message {
+            label: None,
+            span: self.scan.span(start, end),
+            message: message.into(),
+        }
+    }
+
+    fn filter(&mut self) -> Result<Filter, Error> {
+        let mut filter = Filter::default();
+        if self.scan.starts_with("top()") {
+            self.scan.pos += "top()".len();
+            filter.top = true;
+            return Ok(filter);
+        }
+        let start = self.scan.pos;
+        if self.scan.peek() == Some(b'(') {
+            self.scan.pos += 1;
+            self.ws();
+            let name = if self.scan.peek() == Some(b')') {
+                None
+            } else {
+                Some(self.ident()?)
+            };
+            self.ws();
+            self.expect(')')?;
+            filter.type_name = Some(name);
+        }
+        if self.scan.peek() == Some(b'"') || self.scan.peek_char().is_some_and(is_ident_char) {
+            filter.name = Some(self.ident()?);
+        }
+        while self.scan.peek() == Some(b'[') {
+            self.scan.pos += 1;
+            self.ws();
+            if self.scan.peek() == Some(b']') {
+                // `[]` matches any node
+                self.scan.pos += 1;
+                continue;
+            }
+            let accessor = self.accessor(false)?;
+            self.ws();
+            let comparison = match self.operator() {
+                Some(operator) => {
+                    self.ws();
+                    let value = self.value()?;
+                    self.ws();
+                    Some((operator, value))
+                }
+                None => None,
+            };
+            self.expect(']')?;
+            filter.matchers.push(Matcher {
+                accessor,
+                comparison,
+            });
+        }
+        if self.scan.pos == start {
+            return Err(self.scan.unexpected(
+                None,
+                &[
+                    '('.into(),
+                    '['.into(),
+                    TokenFormat::Kind("node name"),
+                    TokenFormat::Token("top()"),
+                ],
+            ));
+        }
+        Ok(filter)
+    }
+
+    fn ident(&mut self) -> Result<Box<str>, Error> {
+        if self.scan.peek() == Some(b'"') {
+            return self.scan.escaped_string().map(Into::into);
+        }
+        let start = self.scan.pos;
+        self.scan.skip_while(is_ident_char);
+        if self.scan.pos == start {
+            return Err(self.scan.unexpected(
+                None,
+                &[TokenFormat::Kind("identifier"), TokenFormat::Kind("string")],
+            ));
+        }
+        Ok(self.scan.text[start..self.scan.pos].into())
+    }
+
+    fn accessor(&mut self, mapping: bool) -> Result<Accessor, Error> {
+        let start = self.scan.pos;
+        let name = self.ident()?;
+        if self.scan.peek() != Some(b'(') {
+            return Ok(Accessor::Prop(name));
+        }
+        self.scan.pos += 1;
+        self.ws();
+        let accessor = match &name[..] {
+            "val" => {
+                let idx_start = self.scan.pos;
+                match self.scan.number().map(borrowed::Literal::into_owned) {
+                    Some(Literal::Int(idx)) => {
+                        usize::try_from(&idx).map(Accessor::Val).map_err(|e| {
+                            let end = self.scan.pos;
+                            self.message(idx_start, end, &e.to_string())
+                        })?
+                    }
+                    Some(_) => {
+                        let end = self.scan.pos;
+                        return Err(self.message(idx_start, end, "index must be an integer"));
+                    }
+                    None => Accessor::Val(0),
+                }
+            }
+            "prop" => Accessor::Prop(self.ident()?),
+            "name" => Accessor::Name,
+            "type" | "tag" => Accessor::TypeName,
+            "values" | "props" if !mapping => {
+                let end = self.scan.pos - 1;
+                return Err(self.message(
+                    start,
+                    end,
+                    &format!("`{}()` can only be used after `=>`", name),
+                ));
+            }
+            "values" => Accessor::Values,
+            "props" => Accessor::Props,
+            _ => {
+                let end = self.scan.pos - 1;
+                return Err(self.message(
+                    start,
+                    end,
+                    &format!("unknown accessor `{}()`", name.escape_default()),
+                ));
+            }
+        };
+        self.ws();
+        self.expect(')')?;
+        Ok(accessor)
+    }
+
+    fn mapping(&mut self) -> Result<Mapping, Error> {
+        if self.scan.peek() != Some(b'(') {
+            return self.accessor(true).map(Mapping::Single);
+        }
+        self.scan.pos += 1;
+        self.ws();
+        let mut accessors = vec![self.accessor(true)?];
+        self.ws();
+        while self.scan.peek() == Some(b',') {
+            self.scan.pos += 1;
+            self.ws();
+            accessors.push(self.accessor(true)?);
+            self.ws();
+        }
+        self.expect(')')?;
+        Ok(Mapping::Tuple(accessors))
+    }
+
+    fn operator(&mut self) -> Option<Operator> {
+        let operators = [
+            ("!=", Operator::Ne),
+            (">=", Operator::Ge),
+            ("<=", Operator::Le),
+            ("^=", Operator::StartsWith),
+            ("$=", Operator::EndsWith),
+            ("*=", Operator::Contains),
+            ("=", Operator::Eq),
+            (">", Operator::Gt),
+            ("<", Operator::Lt),
+        ];
+        let (token, operator) = operators
+            .into_iter()
+            .find(|(token, _)| self.scan.starts_with(token))?;
+        self.scan.pos += token.len();
+        Some(operator)
+    }
+
+    fn value(&mut self) -> Result<Literal, Error> {
+        let start = self.scan.pos;
+        let digit_next = self.scan.peek_at(1).is_some_and(|b| b.is_ascii_digit());
+        match self.scan.peek() {
+            Some(b'"') => {
+                return self
+                    .scan
+                    .escaped_string()
+                    .map(|s| Literal::String(s.into()))
+            }
+            Some(b'0'..=b'9') => {}
+            Some(b'-' | b'+') if digit_next => {}
+            _ => {
+                let keyword = self.scan.starts_with("#");
+                if keyword {
+                    self.scan.pos += 1;
+                }
+                let value = self.ident()?;
+                return match &value[..] {
+                    "true" => Ok(Literal::Bool(true)),
+                    "false" => Ok(Literal::Bool(false)),
+                    "null" => Ok(Literal::Null),
+                    _ if keyword => {
+                        let end = self.scan.pos;
+                        Err(self.message(start, end, &format!("unknown keyword `#{}`", value)))
+                    }
+                    _ => Ok(Literal::String(value)),
+                };
+            }
+        }
+        let number = self
+            .scan
+            .number()
+            .expect("number starts with a digit")
+            .into_owned();
+        if self.scan.peek_char().is_some_and(is_ident_char) {
+            self.scan.skip_while(is_ident_char);
+            let end = self.scan.pos;
+            return Err(self.message(start, end, "invalid number"));
+        }
+        Ok(number)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Extracted, Query};
+    use crate::ast::{Document, Literal};
+    use crate::span::Span;
+
+    const DOC: &str = r#"
+        server {
+            plugin "auth" enabled=true priority=10
+            plugin "cache" enabled=false priority=2
+            (opt)plugin "log" enabled=true
+            listen 8080
+        }
+        client {
+            plugin "retry"
+        }
+        top-level "x"
+    "#;
+
+    fn select(query: &str) -> Vec<String> {
+        let doc: Document<Span> = crate::parse_ast("<test>", DOC).unwrap();
+        let query = Query::parse(query).unwrap();
+        query
+            .select(&doc)
+            .iter()
+            .map(|n| match n.arguments.first().map(|a| &*a.literal) {
+                Some(Literal::String(s)) => format!("{}:{}", &**n.node_name, s),
+                _ => n.node_name.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn combinators() {
+        assert_eq!(
+            select("plugin"),
+            ["plugin:auth", "plugin:cache", "plugin:log", "plugin:retry"]
+        );
+        assert_eq!(
+            select("server > plugin"),
+            ["plugin:auth", "plugin:cache", "plugin:log"]
+        );
+        assert_eq!(select("client plugin"), ["plugin:retry"]);
+        assert_eq!(select("top() > plugin"), Vec::<String>::new());
+        assert_eq!(select("top()"), ["server", "client", "top-level:x"]);
+        assert_eq!(select("plugin[val()=\"auth\"] + plugin"), ["plugin:cache"]);
+        assert_eq!(
+            select("plugin[val()=\"auth\"] ~ []"),
+            ["plugin:cache", "plugin:log", "listen"]
+        );
+        assert_eq!(
+            select("client>plugin || top-level"),
+            ["plugin:retry", "top-level:x"]
+        );
+        assert_eq!(select("plugin || server > []").len(), 5);
+    }
+
+    #[test]
+    fn matchers() {
+        assert_eq!(
+            select("plugin[enabled=true]"),
+            ["plugin:auth", "plugin:log"]
+        );
+        assert_eq!(select("plugin[enabled != true]"), ["plugin:cache"]);
+        assert_eq!(select("[priority > 5]"), ["plugin:auth"]);
+        assert_eq!(select("[priority <= 2.0]"), ["plugin:cache"]);
+        assert_eq!(select("[prop(priority)]").len(), 2);
+        assert_eq!(select("[val(1)]"), Vec::<String>::new());
+        assert_eq!(select("[val() ^= \"ca\"]"), ["plugin:cache"]);
+        assert_eq!(select("[val() $= th]"), ["plugin:auth"]);
+        assert_eq!(select("[val() *= \"o\"]"), ["plugin:log"]);
+        assert_eq!(select("[name() = listen]"), ["listen"]);
+        assert_eq!(select("listen[val() = 8080.0]"), ["listen"]);
+        assert_eq!(select("[type()]"), ["plugin:log"]);
+        assert_eq!(select("(opt)"), ["plugin:log"]);
+        assert_eq!(select("()plugin[tag() = opt]"), ["plugin:log"]);
+        assert_eq!(select("(other)plugin"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn extract() {
+        let doc: Document<Span> = crate::parse_ast("<test>", DOC).unwrap();
+        let query = Query::parse("server > plugin => (name(), val(0), props())").unwrap();
+        let values = query.extract(&doc);
+        assert_eq!(values.len(), 3);
+        match &values[0] {
+            Extracted::Tuple(items) => {
+                assert!(matches!(&items[0], Extracted::Name(n) if &n[..] == "plugin"));
+                assert!(matches!(&items[1], Extracted::Value(Some(v))
+                    if *v.literal == Literal::String("auth".into())));
+                assert!(matches!(&items[2], Extracted::Props(p) if p.len() == 2));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        let query = Query::parse("listen => val()").unwrap();
+        let values = query.extract(&doc);
+        assert!(matches!(&values[..], [Extracted::Value(Some(v))]
+            if v.literal.to_string() == "8080"));
+    }
+
+    #[test]
+    fn errors() {
+        let err = Query::parse("a >").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "found end of input, expected `(`, `[`, `top()` or node name"
+        );
+        assert_eq!(*err.span(), Span(3, 3));
+        let err = Query::parse("a[val(x)]").unwrap_err();
+        assert_eq!(err.to_string(), "found `x`, expected `)`");
+        let err = Query::parse("[props()]").unwrap_err();
+        assert_eq!(err.to_string(), "`props()` can only be used after `=>`");
+        let err = Query::parse("[foo()]").unwrap_err();
+        assert_eq!(err.to_string(), "unknown accessor `foo()`");
+        let err = Query::parse("a > top()").unwrap_err();
+        assert_eq!(*err.span(), Span(4, 9));
+        let err = Query::parse("a => name() b").unwrap_err();
+        assert_eq!(err.to_string(), "found `b`, expected end of input");
+        Query::parse("[val() = \"abc]").unwrap_err();
+        Query::parse("[val() = 1x]").unwrap_err();
+        Query::parse("[val() = #maybe]").unwrap_err();
+    }
+}
@@ -0,0 +1,1682 @@
+//! KDL Schema validation
+//!
+//! Implements a subset of the [KDL Schema
+//! Language](https://github.com/kdl-org/kdl/blob/main/SCHEMA-SPEC.md) that
+//! allows validating any [document](crate::ast::Document) without decoding it
+//! into a Rust type:
+//!
+//! ```rust
+//! use knus::schema::Schema;
+//! use miette::Diagnostic;
+//!
+//! let schema = Schema::parse("schema.kdl", r#"
+//!     document {
+//!         node "server" {
+//!             min 1
+//!             max 1
+//!             value { min 1; max 1; type "string"; }
+//!             prop "port" { required true; type "number"; format "u16"; }
+//!             children {
+//!                 node "plugin" { value { min 1; type "string"; }; }
+//!             }
+//!         }
+//!     }
+//! "#)?;
+//! let err = schema.validate_text("config.kdl", r#"
+//!     server "main" port=100000 {
+//!         plugin "auth"
+//!         plugin
+//!     }
+//! "#).unwrap_err();
+//! assert_eq!(err.related().unwrap().count(), 2);
+//! # Ok::<(), knus::Error>(())
+//! ```
+//!
+//! Violations are reported as [`DecodeError`] so they can be shown in the
+//! same way as decoding errors.
+//!
+//! # Supported Schema
+//!
+//! The schema document consists of a single `document` node which contains
+//! `node` definitions and an `other-nodes-allowed` flag. `info` node is
+//! accepted but ignored.
+//!
+//! The `node` has an optional name (nodes without name apply to any node not
+//! matched by name) and `description` property, and may contain:
+//!
+//! * `min` and `max` – the number of times the node may occur
+//! * `value` – the arguments, with `min` and `max` number of them and
+//!   validations
+//! * `prop "name"` – a property, with `required` flag and validations
+//! * `other-props-allowed` – allow properties that are not declared
+//! * `children` – the children nodes, with `node` definitions and
+//!   `other-nodes-allowed` flag
+//!
+//! Arguments, properties and children that are not declared are not allowed.
+//!
+//! As an extension, `node` and `prop` accept more than one name, e.g.
+//! `node "server" "srv"`. The names after the first one are aliases: nodes
+//! and properties named by any of them match the same rule and count
+//! towards the same `min`, `max` and `required` limits.
+//!
+//! Standard KDL Schema has no aliases, so encoded schemas only have the
+//! primary names unless [`EncodeAliases`] is set in the encoding context.
+//! Documents using the aliases don't validate against such a schema.
+//!
+//! Validations are:
+//!
+//! * `type` – one or more of `"string"`, `"number"`, `"boolean"` and `"null"`
+//! * `enum` – a list of allowed values
+//! * `min-length` and `max-length` – the number of characters in a string
+//! * `>`, `>=`, `<`, `<=` and `%` – numeric bounds and divisor
+//! * `format` – for numbers `"i8"` to `"u64"`, `"isize"` and `"usize"` check
+//!   the range of the integer, other formats are not checked
+//! * `pattern` – a regular expression that must match somewhere in the
+//!   string (use `^` and `$` to match the whole string), requires the
+//!   `schema-pattern` feature
+//!
+//! Definitions, references (`ref`) and type annotation (`tag`) rules are not
+//! supported yet, schemas using them are rejected by [`Schema::parse`] with
+//! an error. The same is true for `pattern` when the `schema-pattern` feature
+//! is disabled.
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+
+use miette::NamedSource;
+
+use crate::ast::{Decimal, Document, Integer, Literal, Node, Radix, SpannedName, SpannedNode};
+use crate::ast::{TypeName, Value};
+use crate::borrowed;
+use crate::decode::{Context, Kind};
+use crate::encode;
+use crate::errors::{DecodeError, EncodeError, Error};
+use crate::query::as_number;
+use crate::span::{Span, Spanned};
+use crate::traits::{Decode, DecodeChildren, DecodePartial, DecodeScalar, DecodeSchema};
+use crate::traits::{Encode, EncodeChildren, ErrorSpan, ScalarSchema};
+use crate::wrappers::ParseOptions;
+
+/// KDL schema
+///
+/// Can be decoded from the KDL Schema document using [`Schema::parse`] (or
+/// [`knus::parse`](crate::parse)), or built manually.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Schema {
+    /// Rules for the top-level nodes
+    pub nodes: Vec<NodeSchema>,
+    /// Allow top-level nodes not matched by any rule
+    pub other_nodes_allowed: bool,
+}
+
+/// Marker enabling the alias extension when encoding the schema
+///
+/// Set it in the encoding context to write the aliases as extra names of
+/// the `node` and `prop` rules:
+///
+/// ```rust
+/// # use knus::schema::{EncodeAliases, NodeSchema, Schema};
+/// let mut schema = Schema::default();
+/// schema.nodes.push(NodeSchema {
+///     name: Some("server".into()),
+///     aliases: vec!["srv".into()],
+///     ..NodeSchema::default()
+/// });
+/// let text = knus::to_document_with_context::<_, knus::span::Span, _>(
+///     &schema,
+///     |ctx| ctx.set(EncodeAliases),
+/// )?.to_string();
+/// assert!(text.contains(r#"node "server" "srv""#));
+/// assert!(!knus::to_string(&schema)?.contains("srv"));
+/// # Ok::<(), knus::errors::EncodeError>(())
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EncodeAliases;
+
+/// Rules for a node
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NodeSchema {
+    /// Name of the node, rules without name apply to any node
+    pub name: Option<String>,
+    /// Other names of the node matched by the same rule
+    pub aliases: Vec<String>,
+    /// Human-readable description
+    pub description: Option<String>,
+    /// Minimum number of occurrences of the node
+    pub min: Option<usize>,
+    /// Maximum number of occurrences of the node
+    pub max: Option<usize>,
+    /// Rules for arguments, no arguments allowed if `None`
+    pub values: Option<ValueSchema>,
+    /// Rules for properties
+    pub props: Vec<PropSchema>,
+    /// Allow properties not listed in `props`
+    pub other_props_allowed: bool,
+    /// Rules for children, no children allowed if `None`
+    pub children: Option<ChildrenSchema>,
+}
+
+/// Rules for node arguments
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValueSchema {
+    /// Human-readable description
+    pub description: Option<String>,
+    /// Minimum number of arguments
+    pub min: Option<usize>,
+    /// Maximum number of arguments
+    pub max: Option<usize>,
+    /// Validations applied to each argument
+    pub validations: Validations,
+}
+
+/// Rules for a node property
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PropSchema {
+    /// Name of the property
+    pub name: String,
+    /// Other names of the property matched by the same rule
+    pub aliases: Vec<String>,
+    /// Human-readable description
+    pub description: Option<String>,
+    /// Whether property is required
+    pub required: bool,
+    /// Validations applied to the value
+    pub validations: Validations,
+}
+
+/// Rules for node children
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChildrenSchema {
+    /// Human-readable description
+    pub description: Option<String>,
+    /// Rules for the children
+    pub nodes: Vec<NodeSchema>,
+    /// Allow children not matched by any rule
+    pub other_nodes_allowed: bool,
+}
+
+/// Validations of a single value
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Validations {
+    /// Allowed types of the value, any type allowed if empty
+    pub types: Vec<ValueType>,
+    /// Allowed values, any value allowed if empty
+    pub enum_values: Vec<Literal>,
+    /// Regular expression that must match the strings
+    ///
+    /// Only checked if the `schema-pattern` feature is enabled, invalid
+    /// expressions never match.
+    pub pattern: Option<String>,
+    /// Minimum number of characters in a string
+    pub min_length: Option<usize>,
+    /// Maximum number of characters in a string
+    pub max_length: Option<usize>,
+    /// Formats of the value, see [module documentation](self)
+    pub format: Vec<String>,
+    /// Number must be divisible by this value
+    pub multiple_of: Option<f64>,
+    /// Number must be greater than this value
+    pub greater: Option<f64>,
+    /// Number must be greater or equal to this value
+    pub greater_or_equal: Option<f64>,
+    /// Number must be less than this value
+    pub less: Option<f64>,
+    /// Number must be less or equal to this value
+    pub less_or_equal: Option<f64>,
+}
+
+/// Type of the value in [`Validations::types`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValueType {
+    /// A string
+    String,
+    /// An integer or a decimal
+    Number,
+    /// `true` or `false`
+    Boolean,
+    /// `null`
+    Null,
+}
+
+impl Schema {
+    /// Returns the schema of the document decoded into `T`
+    ///
+    /// The top-level nodes are the children of the
+    /// [`DecodeSchema::schema`] of the type:
+    ///
+    /// ```rust
+    /// #[derive(knus::Decode, knus::DecodeSchema)]
+    /// struct Config {
+    ///     #[knus(child, unwrap(argument))]
+    ///     name: String,
+    /// }
+    ///
+    /// let schema = knus::schema::Schema::for_document::<Config>();
+    /// assert!(schema.validate_text("config.kdl", r#"name "x""#).is_ok());
+    /// assert!(schema.validate_text("config.kdl", r#"name 1"#).is_err());
+    /// ```
+    pub fn for_document<T: DecodeSchema>() -> Schema {
+        let children = T::schema().children.unwrap_or_default();
+        Schema {
+            nodes: children.nodes,
+            other_nodes_allowed: children.other_nodes_allowed,
+        }
+    }
+
+    /// Parses the KDL Schema document
+    pub fn parse(file_name: impl AsRef<str>, text: &str) -> Result<Schema, Error> {
+        crate::parse(file_name, text)
+    }
+
+    /// Validates the document against the schema
+    pub fn validate<S: ErrorSpan>(
+        &self,
+        document: &Document<S>,
+    ) -> Result<(), Vec<DecodeError<S>>> {
+        let mut ctx = Context::new();
+        self.validate_nodes(&document.nodes, &mut ctx);
+        if ctx.has_errors() {
+            Err(ctx.into_errors())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Validates the top-level nodes emitting errors to the context
+    ///
+    /// This is useful for validating a part of the document in the manual
+    /// implementation of [`DecodeChildren`].
+    pub fn validate_nodes<S: ErrorSpan>(&self, nodes: &[SpannedNode<S>], ctx: &mut Context<S>) {
+        validate_nodes(&self.nodes, self.other_nodes_allowed, None, nodes, ctx);
+    }
+
+    /// Parses KDL text and validates it against the schema
+    pub fn validate_text(&self, file_name: impl AsRef<str>, text: &str) -> Result<(), Error> {
+        let document = ParseOptions::new().parse_ast::<Span>(file_name.as_ref(), text)?;
+        self.validate(&document).map_err(|errors| Error {
+            source_code: NamedSource::new(file_name, text.to_string()),
+            errors: errors.into_iter().map(Into::into).collect(),
+        })
+    }
+}
+
+thread_local! {
+    static BUILDING: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Builds the schema of the type `T` unless it's already being built
+///
+/// Used by the [`DecodeSchema`](derive@crate::DecodeSchema) derive for
+/// recursive types: as the schema has no references, the inner occurrence of
+/// the type is described by the `recursive` schema (usually
+/// [`NodeSchema::any`]) instead of recursing forever.
+pub fn guard_recursion<T: ?Sized, R>(recursive: R, build: impl FnOnce() -> R) -> R {
+    struct Pop;
+    impl Drop for Pop {
+        fn drop(&mut self) {
+            BUILDING.with(|b| b.borrow_mut().pop());
+        }
+    }
+    let name = std::any::type_name::<T>();
+    if BUILDING.with(|b| b.borrow().contains(&name)) {
+        return recursive;
+    }
+    BUILDING.with(|b| b.borrow_mut().push(name));
+    let _pop = Pop;
+    build()
+}
+
+impl NodeSchema {
+    /// Schema of the node with any arguments, properties and children
+    pub fn any() -> NodeSchema {
+        NodeSchema {
+            values: Some(ValueSchema::default()),
+            other_props_allowed: true,
+            children: Some(ChildrenSchema {
+                other_nodes_allowed: true,
+                ..ChildrenSchema::default()
+            }),
+            ..NodeSchema::default()
+        }
+    }
+
+    /// Adds properties and children of the other schema
+    ///
+    /// Used for `#[knus(flatten)]` fields.
+    pub fn flatten(&mut self, other: NodeSchema) {
+        self.props.extend(other.props);
+        self.other_props_allowed |= other.other_props_allowed;
+        if let Some(other) = other.children {
+            let children = self.children.get_or_insert_with(Default::default);
+            children.nodes.extend(other.nodes);
+            children.other_nodes_allowed |= other.other_nodes_allowed;
+        }
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        self.name.as_deref() == Some(name) || self.aliases.iter().any(|a| a == name)
+    }
+}
+
+impl PropSchema {
+    fn matches(&self, name: &str) -> bool {
+        self.name == name || self.aliases.iter().any(|a| a == name)
+    }
+}
+
+impl ValueSchema {
+    /// Returns the schema of the positional arguments
+    ///
+    /// `required` is the number of arguments that must be present,
+    /// `arguments` are validations of each argument and `rest` is validations
+    /// of the variable number of arguments after them. Returns `None` if no
+    /// arguments are allowed.
+    ///
+    /// Since the schema has the same validations for all the arguments, only
+    /// the types are checked if validations differ.
+    pub fn for_arguments(
+        required: usize,
+        arguments: Vec<Validations>,
+        rest: Option<Validations>,
+    ) -> Option<ValueSchema> {
+        if arguments.is_empty() && rest.is_none() {
+            return None;
+        }
+        let max = if rest.is_some() {
+            None
+        } else {
+            Some(arguments.len())
+        };
+        let mut all = arguments.into_iter().chain(rest);
+        let first = all.next().unwrap_or_default();
+        let validations = all.fold(first, |acc, item| {
+            if acc == item {
+                return acc;
+            }
+            let mut types = Vec::new();
+            if !acc.types.is_empty() && !item.types.is_empty() {
+                for ty in acc.types.into_iter().chain(item.types) {
+                    if !types.contains(&ty) {
+                        types.push(ty);
+                    }
+                }
+            }
+            Validations {
+                types,
+                ..Validations::default()
+            }
+        });
+        Some(ValueSchema {
+            description: None,
+            min: Some(required).filter(|&n| n > 0),
+            max,
+            validations,
+        })
+    }
+}
+
+impl Validations {
+    /// Validations that only check the type of the value
+    pub fn of_type(ty: ValueType) -> Validations {
+        Validations {
+            types: vec![ty],
+            ..Validations::default()
+        }
+    }
+
+    /// Allows `null` in addition to the types of the value
+    pub fn nullable(mut self) -> Validations {
+        if !self.types.is_empty() && !self.types.contains(&ValueType::Null) {
+            self.types.push(ValueType::Null);
+        }
+        self
+    }
+}
+
+fn validate_nodes<S: ErrorSpan>(
+    rules: &[NodeSchema],
+    other_nodes_allowed: bool,
+    parent: Option<&SpannedNode<S>>,
+    nodes: &[SpannedNode<S>],
+    ctx: &mut Context<S>,
+) {
+    for node in nodes {
+        let rule = rules
+            .iter()
+            .find(|r| r.matches(&node.node_name))
+            .or_else(|| rules.iter().find(|r| r.name.is_none()));
+        match rule {
+            Some(rule) => validate_node(rule, node, ctx),
+            None if other_nodes_allowed => {}
+            None => ctx.emit_error(DecodeError::unexpected(
+                &node.node_name,
+                "node",
+                format!("unexpected node `{}`", node.node_name.escape_default()),
+            )),
+        }
+    }
+    for rule in rules {
+        let name = match &rule.name {
+            Some(name) => name,
+            None => continue,
+        };
+        let mut matching = nodes.iter().filter(|n| rule.matches(&n.node_name));
+        if let Some(max) = rule.max {
+            for node in matching.clone().skip(max) {
+                ctx.emit_error(DecodeError::unexpected(
+                    &node.node_name,
+                    "node",
+                    format!(
+                        "node `{}` must not occur more than {} times",
+                        name.escape_default(),
+                        max
+                    ),
+                ));
+            }
+        }
+        let min = rule.min.unwrap_or(0);
+        if min > 0 && matching.nth(min - 1).is_none() {
+            let message = if min == 1 {
+                format!("child node `{}` is required", name.escape_default())
+            } else {
+                format!(
+                    "child node `{}` must occur at least {} times",
+                    name.escape_default(),
+                    min
+                )
+            };
+            match parent {
+                Some(parent) => ctx.emit_error(DecodeError::missing(parent, message)),
+                None => ctx.emit_error(DecodeError::MissingNode { message }),
+            }
+        }
+    }
+}
+
+fn validate_node<S: ErrorSpan>(rule: &NodeSchema, node: &SpannedNode<S>, ctx: &mut Context<S>) {
+    let (min, max) = match &rule.values {
+        Some(values) => (values.min.unwrap_or(0), values.max),
+        None => (0, Some(0)),
+    };
+    if node.arguments.len() < min {
+        ctx.emit_error(DecodeError::missing(
+            node,
+            format!("node requires at least {} arguments", min),
+        ));
+    }
+    for (idx, arg) in node.arguments.iter().enumerate() {
+        if max.is_some_and(|max| idx >= max) {
+            ctx.emit_error(DecodeError::unexpected(
+                &arg.literal,
+                "argument",
+                "unexpected argument",
+            ));
+        } else if let Some(values) = &rule.values {
+            values.validations.validate(arg, ctx);
+        }
+    }
+    for (name, value) in &node.properties {
+        match rule.props.iter().find(|p| p.matches(name)) {
+            Some(prop) => prop.validations.validate(value, ctx),
+            None if rule.other_props_allowed => {}
+            None => ctx.emit_error(DecodeError::unexpected(
+                name,
+                "property",
+                format!("unexpected property `{}`", name.escape_default()),
+            )),
+        }
+    }
+    for prop in &rule.props {
+        if prop.required && !node.properties.keys().any(|name| prop.matches(name)) {
+            ctx.emit_error(DecodeError::missing(
+                node,
+                format!("property `{}` is required", prop.name.escape_default()),
+            ));
+        }
+    }
+    let children = node.children.as_ref().map_or(&[][..], |c| &c[..]);
+    match &rule.children {
+        Some(rules) => {
+            validate_nodes(
+                &rules.nodes,
+                rules.other_nodes_allowed,
+                Some(node),
+                children,
+                ctx,
+            );
+        }
+        None => {
+            for child in children {
+                ctx.emit_error(DecodeError::unexpected(
+                    child,
+                    "node",
+                    format!("unexpected node `{}`", child.node_name.escape_default()),
+                ));
+            }
+        }
+    }
+}
+
+impl Validations {
+    /// Validates the value emitting errors to the context
+    pub fn validate<S: ErrorSpan>(&self, value: &Value<S>, ctx: &mut Context<S>) {
+        let literal = &value.literal;
+        if !self.types.is_empty() && !self.types.iter().any(|t| t.matches(literal)) {
+            let types = self
+                .types
+                .iter()
+                .map(|t| t.as_str())
+                .collect::<Vec<_>>()
+                .join(" or ");
+            ctx.emit_error(DecodeError::conversion(
+                literal,
+                format!("expected {}, found {}", types, Kind::from(&**literal)),
+            ));
+            return;
+        }
+        if !self.enum_values.is_empty() && !self.enum_values.iter().any(|v| equal(v, literal)) {
+            let values = self
+                .enum_values
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            ctx.emit_error(DecodeError::conversion(
+                literal,
+                format!("expected one of {}", values),
+            ));
+        }
+        match &**literal {
+            Literal::String(s) => {
+                let len = s.chars().count();
+                if let Some(min) = self.min_length.filter(|&min| len < min) {
+                    ctx.emit_error(DecodeError::conversion(
+                        literal,
+                        format!("string must be at least {} characters long", min),
+                    ));
+                }
+                if let Some(max) = self.max_length.filter(|&max| len > max) {
+                    ctx.emit_error(DecodeError::conversion(
+                        literal,
+                        format!("string must be at most {} characters long", max),
+                    ));
+                }
+                #[cfg(feature = "schema-pattern")]
+                if let Some(pattern) = &self.pattern {
+                    let matches = regex_lite::Regex::new(pattern).is_ok_and(|re| re.is_match(s));
+                    if !matches {
+                        ctx.emit_error(DecodeError::conversion(
+                            literal,
+                            format!("string must match pattern `{}`", pattern),
+                        ));
+                    }
+                }
+            }
+            Literal::Int(_) | Literal::Decimal(_) => self.validate_number(literal, ctx),
+            Literal::Bool(_) | Literal::Null => {}
+        }
+    }
+
+    fn validate_number<S: ErrorSpan>(&self, literal: &Spanned<Literal, S>, ctx: &mut Context<S>) {
+        for format in &self.format {
+            match &**literal {
+                Literal::Int(value) => {
+                    if let Some(Err(e)) = check_integer(value, format) {
+                        ctx.emit_error(DecodeError::conversion(literal, e));
+                    }
+                }
+                _ if is_integer_format(format) => {
+                    ctx.emit_error(DecodeError::scalar_kind(Kind::Int, literal));
+                }
+                _ => {}
+            }
+        }
+        let number = match as_number(literal) {
+            Some(number) => number,
+            None => return,
+        };
+        let bounds = [
+            (
+                self.greater,
+                "greater than",
+                number > self.greater.unwrap_or(0.),
+            ),
+            (
+                self.greater_or_equal,
+                "greater than or equal to",
+                number >= self.greater_or_equal.unwrap_or(0.),
+            ),
+            (self.less, "less than", number < self.less.unwrap_or(0.)),
+            (
+                self.less_or_equal,
+                "less than or equal to",
+                number <= self.less_or_equal.unwrap_or(0.),
+            ),
+        ];
+        for (bound, description, ok) in bounds {
+            if let Some(bound) = bound.filter(|_| !ok) {
+                ctx.emit_error(DecodeError::conversion(
+                    literal,
+                    format!("number must be {} {}", description, bound),
+                ));
+            }
+        }
+        if let Some(divisor) = self.multiple_of {
+            if number % divisor != 0. {
+                ctx.emit_error(DecodeError::conversion(
+                    literal,
+                    format!("number must be a multiple of {}", divisor),
+                ));
+            }
+        }
+    }
+}
+
+fn equal(left: &Literal, right: &Literal) -> bool {
+    match (as_number(left), as_number(right)) {
+        (Some(left), Some(right)) => left == right,
+        _ => left == right,
+    }
+}
+
+fn is_integer_format(format: &str) -> bool {
+    matches!(
+        format,
+        "i8" | "i16" | "i32" | "i64" | "isize" | "u8" | "u16" | "u32" | "u64" | "usize"
+    )
+}
+
+fn check_integer(value: &Integer, format: &str) -> Option<Result<(), std::num::ParseIntError>> {
+    let result = match format {
+        "i8" => i8::try_from(value).map(drop),
+        "i16" => i16::try_from(value).map(drop),
+        "i32" => i32::try_from(value).map(drop),
+        "i64" => i64::try_from(value).map(drop),
+        "isize" => isize::try_from(value).map(drop),
+        "u8" => u8::try_from(value).map(drop),
+        "u16" => u16::try_from(value).map(drop),
+        "u32" => u32::try_from(value).map(drop),
+        "u64" => u64::try_from(value).map(drop),
+        "usize" => usize::try_from(value).map(drop),
+        _ => return None,
+    };
+    Some(result)
+}
+
+impl ValueType {
+    /// Returns the name of the type as used in the schema
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            ValueType::String => "string",
+            ValueType::Number => "number",
+            ValueType::Boolean => "boolean",
+            ValueType::Null => "null",
+        }
+    }
+
+    fn matches(&self, literal: &Literal) -> bool {
+        matches!(
+            (self, literal),
+            (ValueType::String, Literal::String(_))
+                | (ValueType::Number, Literal::Int(_) | Literal::Decimal(_))
+                | (ValueType::Boolean, Literal::Bool(_))
+                | (ValueType::Null, Literal::Null)
+        )
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<S: ErrorSpan> DecodeScalar<S> for ValueType {
+    fn type_check(type_name: &Option<Spanned<TypeName, S>>, ctx: &mut Context<S>) {
+        <String as DecodeScalar<S>>::type_check(type_name, ctx)
+    }
+    fn raw_decode(
+        value: &Spanned<Literal, S>,
+        ctx: &mut Context<S>,
+    ) -> Result<ValueType, DecodeError<S>> {
+        match &**value {
+            Literal::String(s) => match &s[..] {
+                "string" => Ok(ValueType::String),
+                "number" => Ok(ValueType::Number),
+                "boolean" => Ok(ValueType::Boolean),
+                "null" => Ok(ValueType::Null),
+                _ => Err(DecodeError::conversion(
+                    value,
+                    "expected `string`, `number`, `boolean` or `null`",
+                )),
+            },
+            _ => {
+                ctx.emit_error(DecodeError::scalar_kind(Kind::String, value));
+                Ok(ValueType::String)
+            }
+        }
+    }
+}
+
+/// Decodes the only argument of the node that has no properties and children
+fn argument<T, S>(node: &SpannedNode<S>, ctx: &mut Context<S>) -> Result<T, DecodeError<S>>
+where
+    T: DecodeScalar<S>,
+    S: ErrorSpan,
+{
+    let mut values = arguments(node, ctx)?;
+    if values.len() > 1 {
+        ctx.emit_error(DecodeError::unexpected(
+            &node.arguments[1].literal,
+            "argument",
+            "unexpected argument",
+        ));
+    }
+    Ok(values.swap_remove(0))
+}
+
+/// Decodes one or more arguments of the node that has no properties and
+/// children
+fn arguments<T, S>(node: &SpannedNode<S>, ctx: &mut Context<S>) -> Result<Vec<T>, DecodeError<S>>
+where
+    T: DecodeScalar<S>,
+    S: ErrorSpan,
+{
+    literals(node, ctx)?;
+    node.arguments
+        .iter()
+        .map(|arg| DecodeScalar::decode(arg, ctx))
+        .collect()
+}
+
+/// Returns one or more arguments of the node as is
+fn literals<'a, S: ErrorSpan>(
+    node: &'a SpannedNode<S>,
+    ctx: &mut Context<S>,
+) -> Result<&'a [Value<S>], DecodeError<S>> {
+    if node.arguments.is_empty() {
+        return Err(DecodeError::missing(node, "argument is required"));
+    }
+    let mut plain = node.clone();
+    plain.arguments.clear();
+    crate::decode::check_flag_node(&plain, ctx);
+    Ok(&node.arguments)
+}
+
+/// Decodes `description` and `id` properties, and an optional name argument
+///
+/// Further arguments are decoded into `aliases` if they are allowed.
+fn header<S: ErrorSpan>(
+    node: &SpannedNode<S>,
+    ctx: &mut Context<S>,
+    description: &mut Option<String>,
+    aliases: Option<&mut Vec<String>>,
+) -> Result<Option<String>, DecodeError<S>> {
+    for (name, value) in &node.properties {
+        match &***name {
+            "description" => *description = Some(DecodeScalar::decode(value, ctx)?),
+            "id" => {}
+            "ref" => ctx.emit_error(DecodeError::unsupported(
+                name,
+                "references are not supported",
+            )),
+            _ => emit_unexpected_property(name, ctx),
+        }
+    }
+    let mut args = node.arguments.iter();
+    let name = args
+        .next()
+        .map(|a| DecodeScalar::decode(a, ctx))
+        .transpose()?;
+    match aliases {
+        Some(aliases) => {
+            for arg in args {
+                aliases.push(DecodeScalar::decode(arg, ctx)?);
+            }
+        }
+        None => {
+            for arg in args {
+                ctx.emit_error(DecodeError::unexpected(
+                    &arg.literal,
+                    "argument",
+                    "unexpected argument",
+                ));
+            }
+        }
+    }
+    Ok(name)
+}
+
+fn emit_unexpected_property<S: ErrorSpan>(name: &SpannedName<S>, ctx: &mut Context<S>) {
+    ctx.emit_error(DecodeError::unexpected(
+        name,
+        "property",
+        format!("unexpected property `{}`", name.escape_default()),
+    ));
+}
+
+fn emit_unexpected_node<S: ErrorSpan>(node: &SpannedNode<S>, ctx: &mut Context<S>) {
+    ctx.emit_error(DecodeError::unexpected(
+        node,
+        "node",
+        format!("unexpected node `{}`", node.node_name.escape_default()),
+    ));
+}
+
+fn emit_unsupported<S: ErrorSpan>(node: &SpannedNode<S>, ctx: &mut Context<S>) {
+    ctx.emit_error(DecodeError::unsupported(
+        &node.node_name,
+        format!("`{}` is not supported", node.node_name.escape_default()),
+    ));
+}
+
+fn children<S>(node: &SpannedNode<S>) -> &[SpannedNode<S>] {
+    node.children.as_ref().map_or(&[][..], |c| &c[..])
+}
+
+impl<S: ErrorSpan> DecodeChildren<S> for Schema {
+    fn decode_children(
+        nodes: &[SpannedNode<S>],
+        ctx: &mut Context<S>,
+    ) -> Result<Schema, DecodeError<S>> {
+        let mut schema = None;
+        for node in nodes {
+            match &**node.node_name {
+                "document" if schema.is_none() => {
+                    schema = Some(Decode::decode_node(node, ctx)?);
+                }
+                _ => emit_unexpected_node(node, ctx),
+            }
+        }
+        schema.ok_or_else(|| DecodeError::MissingNode {
+            message: "child node `document` is required".into(),
+        })
+    }
+}
+
+impl<S: ErrorSpan> Decode<S> for Schema {
+    fn decode_node(node: &SpannedNode<S>, ctx: &mut Context<S>) -> Result<Schema, DecodeError<S>> {
+        let mut plain = node.clone();
+        plain.children = None;
+        crate::decode::check_flag_node(&plain, ctx);
+        let mut schema = Schema::default();
+        for child in children(node) {
+            match &**child.node_name {
+                "info" => {}
+                "node" => schema.nodes.push(Decode::decode_node(child, ctx)?),
+                "other-nodes-allowed" => schema.other_nodes_allowed = argument(child, ctx)?,
+                "definitions" | "node-names" | "tag" | "tag-names" | "other-tags-allowed" => {
+                    emit_unsupported(child, ctx)
+                }
+                _ => emit_unexpected_node(child, ctx),
+            }
+        }
+        Ok(schema)
+    }
+}
+
+impl<S: ErrorSpan> Decode<S> for NodeSchema {
+    fn decode_node(
+        node: &SpannedNode<S>,
+        ctx: &mut Context<S>,
+    ) -> Result<NodeSchema, DecodeError<S>> {
+        let mut schema = NodeSchema::default();
+        schema.name = header(
+            node,
+            ctx,
+            &mut schema.description,
+            Some(&mut schema.aliases),
+        )?;
+        for child in children(node) {
+            match &**child.node_name {
+                "min" => schema.min = Some(argument(child, ctx)?),
+                "max" => schema.max = Some(argument(child, ctx)?),
+                "value" => schema.values = Some(Decode::decode_node(child, ctx)?),
+                "prop" => schema.props.push(Decode::decode_node(child, ctx)?),
+                "other-props-allowed" => schema.other_props_allowed = argument(child, ctx)?,
+                "children" => schema.children = Some(Decode::decode_node(child, ctx)?),
+                "prop-names" | "tag" => emit_unsupported(child, ctx),
+                _ => emit_unexpected_node(child, ctx),
+            }
+        }
+        Ok(schema)
+    }
+}
+
+impl<S: ErrorSpan> Decode<S> for ValueSchema {
+    fn decode_node(
+        node: &SpannedNode<S>,
+        ctx: &mut Context<S>,
+    ) -> Result<ValueSchema, DecodeError<S>> {
+        let mut schema = ValueSchema::default();
+        if let Some(arg) = header(node, ctx, &mut schema.description, None)? {
+            ctx.emit_error(DecodeError::unexpected(
+                &node.arguments[0].literal,
+                "argument",
+                format!("unexpected argument `{}`", arg.escape_default()),
+            ));
+        }
+        for child in children(node) {
+            match &**child.node_name {
+                "min" => schema.min = Some(argument(child, ctx)?),
+                "max" => schema.max = Some(argument(child, ctx)?),
+                _ => {
+                    if !schema.validations.insert_child(child, ctx)? {
+                        emit_unexpected_node(child, ctx);
+                    }
+                }
+            }
+        }
+        Ok(schema)
+    }
+}
+
+impl<S: ErrorSpan> Decode<S> for PropSchema {
+    fn decode_node(
+        node: &SpannedNode<S>,
+        ctx: &mut Context<S>,
+    ) -> Result<PropSchema, DecodeError<S>> {
+        let mut schema = PropSchema::default();
+        match header(
+            node,
+            ctx,
+            &mut schema.description,
+            Some(&mut schema.aliases),
+        )? {
+            Some(name) => schema.name = name,
+            None => return Err(DecodeError::missing(node, "property name is required")),
+        }
+        for child in children(node) {
+            match &**child.node_name {
+                "required" => schema.required = argument(child, ctx)?,
+                _ => {
+                    if !schema.validations.insert_child(child, ctx)? {
+                        emit_unexpected_node(child, ctx);
+                    }
+                }
+            }
+        }
+        Ok(schema)
+    }
+}
+
+impl<S: ErrorSpan> Decode<S> for ChildrenSchema {
+    fn decode_node(
+        node: &SpannedNode<S>,
+        ctx: &mut Context<S>,
+    ) -> Result<ChildrenSchema, DecodeError<S>> {
+        let mut schema = ChildrenSchema::default();
+        if let Some(arg) = header(node, ctx, &mut schema.description, None)? {
+            ctx.emit_error(DecodeError::unexpected(
+                &node.arguments[0].literal,
+                "argument",
+                format!("unexpected argument `{}`", arg.escape_default()),
+            ));
+        }
+        for child in children(node) {
+            match &**child.node_name {
+                "node" => schema.nodes.push(Decode::decode_node(child, ctx)?),
+                "other-nodes-allowed" => schema.other_nodes_allowed = argument(child, ctx)?,
+                "node-names" => emit_unsupported(child, ctx),
+                _ => emit_unexpected_node(child, ctx),
+            }
+        }
+        Ok(schema)
+    }
+}
+
+impl<S: ErrorSpan> DecodePartial<S> for Validations {
+    fn insert_child(
+        &mut self,
+        node: &SpannedNode<S>,
+        ctx: &mut Context<S>,
+    ) -> Result<bool, DecodeError<S>> {
+        match &**node.node_name {
+            "type" => self.types = arguments(node, ctx)?,
+            "enum" => {
+                self.enum_values = literals(node, ctx)?
+                    .iter()
+                    .map(|v| (*v.literal).clone())
+                    .collect();
+            }
+            "pattern" => self.pattern = Some(pattern(node, ctx)?),
+            "min-length" => self.min_length = Some(argument(node, ctx)?),
+            "max-length" => self.max_length = Some(argument(node, ctx)?),
+            "format" => self.format = arguments(node, ctx)?,
+            "%" => self.multiple_of = Some(number_argument(node, ctx)?),
+            ">" => self.greater = Some(number_argument(node, ctx)?),
+            ">=" => self.greater_or_equal = Some(number_argument(node, ctx)?),
+            "<" => self.less = Some(number_argument(node, ctx)?),
+            "<=" => self.less_or_equal = Some(number_argument(node, ctx)?),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+    fn insert_property(
+        &mut self,
+        _name: &SpannedName<S>,
+        _value: &Value<S>,
+        _ctx: &mut Context<S>,
+    ) -> Result<bool, DecodeError<S>> {
+        Ok(false)
+    }
+}
+
+#[cfg(feature = "schema-pattern")]
+fn pattern<S: ErrorSpan>(
+    node: &SpannedNode<S>,
+    ctx: &mut Context<S>,
+) -> Result<String, DecodeError<S>> {
+    let pattern: String = argument(node, ctx)?;
+    if let Err(e) = regex_lite::Regex::new(&pattern) {
+        ctx.emit_error(DecodeError::conversion(&node.arguments[0].literal, e));
+    }
+    Ok(pattern)
+}
+
+#[cfg(not(feature = "schema-pattern"))]
+fn pattern<S: ErrorSpan>(
+    node: &SpannedNode<S>,
+    ctx: &mut Context<S>,
+) -> Result<String, DecodeError<S>> {
+    ctx.emit_error(DecodeError::unsupported(
+        &node.node_name,
+        "`pattern` requires the `schema-pattern` feature",
+    ));
+    argument(node, ctx)
+}
+
+fn number_argument<S: ErrorSpan>(
+    node: &SpannedNode<S>,
+    ctx: &mut Context<S>,
+) -> Result<f64, DecodeError<S>> {
+    let values = literals(node, ctx)?;
+    for value in &values[1..] {
+        ctx.emit_error(DecodeError::unexpected(
+            &value.literal,
+            "argument",
+            "unexpected argument",
+        ));
+    }
+    let literal = &values[0].literal;
+    as_number(literal).ok_or_else(|| DecodeError::scalar_kind(Kind::Decimal, literal))
+}
+
+macro_rules! impl_number_schema {
+    ($($typ:ident)*) => {$(
+        impl ScalarSchema for $typ {
+            fn validations() -> Validations {
+                Validations {
+                    format: vec![stringify!($typ).into()],
+                    ..Validations::of_type(ValueType::Number)
+                }
+            }
+        }
+    )*};
+}
+
+impl_number_schema!(i8 u8 i16 u16 i32 u32 i64 u64 isize usize f32 f64);
+
+macro_rules! impl_string_schema {
+    ($($typ:ty),*) => {$(
+        impl ScalarSchema for $typ {
+            fn validations() -> Validations {
+                Validations::of_type(ValueType::String)
+            }
+        }
+    )*};
+}
+
+impl_string_schema!(String, PathBuf, Arc<Path>, Arc<str>, &str, Cow<'_, str>);
+
+impl ScalarSchema for bool {
+    fn validations() -> Validations {
+        Validations::of_type(ValueType::Boolean)
+    }
+}
+
+impl<T: ScalarSchema> ScalarSchema for Option<T> {
+    fn validations() -> Validations {
+        T::validations().nullable()
+    }
+}
+
+impl<T: ScalarSchema, S> ScalarSchema for Spanned<T, S> {
+    fn validations() -> Validations {
+        T::validations()
+    }
+}
+
+impl ScalarSchema for Literal {
+    fn validations() -> Validations {
+        Validations::default()
+    }
+}
+
+impl<S> ScalarSchema for Value<S> {
+    fn validations() -> Validations {
+        Validations::default()
+    }
+}
+
+impl ScalarSchema for borrowed::Literal<'_> {
+    fn validations() -> Validations {
+        Validations::default()
+    }
+}
+
+impl<S> ScalarSchema for borrowed::Value<'_, S> {
+    fn validations() -> Validations {
+        Validations::default()
+    }
+}
+
+impl<S> DecodeSchema for Node<S> {
+    fn schema() -> NodeSchema {
+        NodeSchema::any()
+    }
+}
+
+impl<S> DecodeSchema for SpannedNode<S> {
+    fn schema() -> NodeSchema {
+        NodeSchema::any()
+    }
+}
+
+impl<S> DecodeSchema for borrowed::Node<'_, S> {
+    fn schema() -> NodeSchema {
+        NodeSchema::any()
+    }
+}
+
+impl<S> DecodeSchema for borrowed::SpannedNode<'_, S> {
+    fn schema() -> NodeSchema {
+        NodeSchema::any()
+    }
+}
+
+macro_rules! impl_pointer_schema {
+    ($($ptr:ident)*) => {$(
+        impl<T: ScalarSchema> ScalarSchema for $ptr<T> {
+            fn validations() -> Validations {
+                T::validations()
+            }
+        }
+
+        impl<T: DecodeSchema> DecodeSchema for $ptr<T> {
+            fn schema() -> NodeSchema {
+                T::schema()
+            }
+            fn children_schema() -> Vec<NodeSchema> {
+                T::children_schema()
+            }
+        }
+    )*};
+}
+
+impl_pointer_schema!(Box Arc Rc);
+
+fn integer(value: usize) -> Literal {
+    Literal::Int(Integer(Radix::Dec, value.to_string().into()))
+}
+
+fn number(value: f64) -> Literal {
+    let text = value.to_string();
+    if text.contains(|c: char| !c.is_ascii_digit() && c != '-') {
+        Literal::Decimal(Decimal(text.into()))
+    } else {
+        Literal::Int(Integer(Radix::Dec, text.into()))
+    }
+}
+
+fn string(value: &str) -> Literal {
+    Literal::String(value.into())
+}
+
+/// Appends a child node with the arguments
+fn push_values<S: ErrorSpan + Default>(
+    node: &mut Node<S>,
+    name: &str,
+    values: impl IntoIterator<Item = Literal>,
+) {
+    let mut child = Node::new(name);
+    child.arguments.extend(values.into_iter().map(Value::new));
+    node.push_child(child);
+}
+
+fn encode_header<S: ErrorSpan + Default>(
+    node: &mut Node<S>,
+    ctx: &encode::Context,
+    node_name: &str,
+    name: Option<&str>,
+    aliases: &[String],
+    description: Option<&str>,
+) {
+    node.node_name = Spanned::new(node_name.into(), S::default());
+    node.arguments.extend(name.map(|n| Value::new(string(n))));
+    if ctx.get::<EncodeAliases>().is_some() {
+        node.arguments
+            .extend(aliases.iter().map(|a| Value::new(string(a))));
+    }
+    if let Some(description) = description {
+        node.properties.insert(
+            Spanned::new("description".into(), S::default()),
+            Value::new(string(description)),
+        );
+    }
+}
+
+impl EncodeChildren for Schema {
+    fn encode_children<S>(
+        &self,
+        nodes: &mut Vec<SpannedNode<S>>,
+        ctx: &mut encode::Context,
+    ) -> Result<(), EncodeError>
+    where
+        S: ErrorSpan + Default,
+    {
+        let mut document = Node::new("document");
+        for rule in &self.nodes {
+            let mut node = Node::new("node");
+            rule.encode_node(&mut node, ctx)?;
+            document.push_child(node);
+        }
+        if self.other_nodes_allowed {
+            push_values(&mut document, "other-nodes-allowed", [Literal::Bool(true)]);
+        }
+        nodes.push(Spanned::new(document, S::default()));
+        Ok(())
+    }
+}
+
+impl Encode for NodeSchema {
+    fn encode_node<S>(
+        &self,
+        node: &mut Node<S>,
+        ctx: &mut encode::Context,
+    ) -> Result<(), EncodeError>
+    where
+        S: ErrorSpan + Default,
+    {
+        encode_header(
+            node,
+            ctx,
+            "node",
+            self.name.as_deref(),
+            &self.aliases,
+            self.description.as_deref(),
+        );
+        if let Some(min) = self.min {
+            push_values(node, "min", [integer(min)]);
+        }
+        if let Some(max) = self.max {
+            push_values(node, "max", [integer(max)]);
+        }
+        if let Some(values) = &self.values {
+            let mut child = Node::new("value");
+            values.encode_node(&mut child, ctx)?;
+            node.push_child(child);
+        }
+        for prop in &self.props {
+            let mut child = Node::new("prop");
+            prop.encode_node(&mut child, ctx)?;
+            node.push_child(child);
+        }
+        if self.other_props_allowed {
+            push_values(node, "other-props-allowed", [Literal::Bool(true)]);
+        }
+        if let Some(children) = &self.children {
+            let mut child = Node::new("children");
+            children.encode_node(&mut child, ctx)?;
+            node.push_child(child);
+        }
+        Ok(())
+    }
+}
+
+impl Encode for ValueSchema {
+    fn encode_node<S>(
+        &self,
+        node: &mut Node<S>,
+        ctx: &mut encode::Context,
+    ) -> Result<(), EncodeError>
+    where
+        S: ErrorSpan + Default,
+    {
+        encode_header(node, ctx, "value", None, &[], self.description.as_deref());
+        if let Some(min) = self.min {
+            push_values(node, "min", [integer(min)]);
+        }
+        if let Some(max) = self.max {
+            push_values(node, "max", [integer(max)]);
+        }
+        self.validations.encode_children(node);
+        Ok(())
+    }
+}
+
+impl Encode for PropSchema {
+    fn encode_node<S>(
+        &self,
+        node: &mut Node<S>,
+        ctx: &mut encode::Context,
+    ) -> Result<(), EncodeError>
+    where
+        S: ErrorSpan + Default,
+    {
+        encode_header(
+            node,
+            ctx,
+            "prop",
+            Some(&self.name),
+            &self.aliases,
+            self.description.as_deref(),
+        );
+        if self.required {
+            push_values(node, "required", [Literal::Bool(true)]);
+        }
+        self.validations.encode_children(node);
+        Ok(())
+    }
+}
+
+impl Encode for ChildrenSchema {
+    fn encode_node<S>(
+        &self,
+        node: &mut Node<S>,
+        ctx: &mut encode::Context,
+    ) -> Result<(), EncodeError>
+    where
+        S: ErrorSpan + Default,
+    {
+        encode_header(
+            node,
+            ctx,
+            "children",
+            None,
+            &[],
+            self.description.as_deref(),
+        );
+        for rule in &self.nodes {
+            let mut child = Node::new("node");
+            rule.encode_node(&mut child, ctx)?;
+            node.push_child(child);
+        }
+        if self.other_nodes_allowed {
+            push_values(node, "other-nodes-allowed", [Literal::Bool(true)]);
+        }
+        Ok(())
+    }
+}
+
+impl Validations {
+    fn encode_children<S: ErrorSpan + Default>(&self, node: &mut Node<S>) {
+        if !self.types.is_empty() {
+            push_values(node, "type", self.types.iter().map(|t| string(t.as_str())));
+        }
+        if !self.enum_values.is_empty() {
+            push_values(node, "enum", self.enum_values.iter().cloned());
+        }
+        if let Some(pattern) = &self.pattern {
+            push_values(node, "pattern", [string(pattern)]);
+        }
+        if let Some(min) = self.min_length {
+            push_values(node, "min-length", [integer(min)]);
+        }
+        if let Some(max) = self.max_length {
+            push_values(node, "max-length", [integer(max)]);
+        }
+        if !self.format.is_empty() {
+            push_values(node, "format", self.format.iter().map(|f| string(f)));
+        }
+        let numbers = [
+            ("%", self.multiple_of),
+            (">", self.greater),
+            (">=", self.greater_or_equal),
+            ("<", self.less),
+            ("<=", self.less_or_equal),
+        ];
+        for (name, value) in numbers {
+            if let Some(value) = value {
+                push_values(node, name, [number(value)]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Schema, ValueType};
+    use crate::span::Span;
+
+    const SCHEMA: &str = r#"
+        document {
+            info { title "test"; }
+            node "server" {
+                min 1
+                max 1
+                value { min 1; max 2; type "string"; min-length 2; }
+                prop "port" { required true; type "number"; format "u16"; }
+                prop "mode" { enum "fast" "slow"; }
+                children {
+                    node "plugin" {
+                        value { min 1; max 1; }
+                        prop "priority" { type "number"; ">=" 0; "<" 100; "%" 5; }
+                    }
+                    node "flag"
+                }
+            }
+            other-nodes-allowed true
+        }
+    "#;
+
+    fn errors(text: &str) -> Vec<String> {
+        let schema = Schema::parse("<schema>", SCHEMA).unwrap();
+        let doc = crate::parse_ast::<Span>("<test>", text).unwrap();
+        match schema.validate(&doc) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors.iter().map(|e| e.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let schema = Schema::parse("<schema>", SCHEMA).unwrap();
+        assert!(schema.other_nodes_allowed);
+        let server = &schema.nodes[0];
+        assert_eq!(server.name.as_deref(), Some("server"));
+        assert_eq!((server.min, server.max), (Some(1), Some(1)));
+        assert_eq!(server.props[0].validations.types, [ValueType::Number]);
+        assert_eq!(server.props[1].validations.enum_values.len(), 2);
+        let plugin = &server.children.as_ref().unwrap().nodes[0];
+        assert_eq!(plugin.props[0].validations.greater_or_equal, Some(0.));
+        assert_eq!(plugin.props[0].validations.multiple_of, Some(5.));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let err = Schema::parse("<schema>", r#"document { node { max "x"; } }"#).unwrap_err();
+        assert_eq!(err.errors.len(), 1);
+        let err = Schema::parse("<schema>", "document { definitions; }").unwrap_err();
+        assert_eq!(err.errors[0].to_string(), "`definitions` is not supported");
+        let err = Schema::parse("<schema>", "nodes").unwrap_err();
+        assert_eq!(err.errors.len(), 2);
+        let err = Schema::parse("<schema>", r#"document { node ref="x"; }"#).unwrap_err();
+        assert_eq!(err.errors[0].to_string(), "references are not supported");
+        let err = Schema::parse("<schema>", r#"document { node "a" { tag; }; }"#).unwrap_err();
+        assert_eq!(err.errors[0].to_string(), "`tag` is not supported");
+        let err =
+            Schema::parse("<schema>", r#"document { node { value { tag; }; }; }"#).unwrap_err();
+        assert_eq!(err.errors[0].to_string(), "unexpected node `tag`");
+    }
+
+    #[test]
+    #[cfg(feature = "schema-pattern")]
+    fn pattern() {
+        let schema = Schema::parse(
+            "<schema>",
+            r#"document { node "a" { value { pattern "^[a-z]+$"; }; }; }"#,
+        )
+        .unwrap();
+        let validate = |text| {
+            let doc = crate::parse_ast::<Span>("<test>", text).unwrap();
+            match schema.validate(&doc) {
+                Ok(()) => Vec::new(),
+                Err(errors) => errors.iter().map(|e| e.to_string()).collect(),
+            }
+        };
+        assert_eq!(validate(r#"a "abc""#), Vec::<String>::new());
+        assert_eq!(
+            validate(r#"a "ab1""#),
+            ["string must match pattern `^[a-z]+$`"]
+        );
+        let err = Schema::parse(
+            "<schema>",
+            r#"document { node { value { pattern "[a-"; }; }; }"#,
+        )
+        .unwrap_err();
+        assert_eq!(err.errors.len(), 1);
+    }
+
+    #[test]
+    #[cfg(not(feature = "schema-pattern"))]
+    fn pattern_unsupported() {
+        let err = Schema::parse(
+            "<schema>",
+            r#"document { node { value { pattern "a"; }; }; }"#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.errors[0].to_string(),
+            "`pattern` requires the `schema-pattern` feature"
+        );
+    }
+
+    #[test]
+    fn valid() {
+        assert_eq!(errors(r#"server "main" port=80"#), Vec::<String>::new());
+        assert_eq!(
+            errors(
+                r#"
+                server "aa" "bb" port=8080 mode="fast" {
+                    plugin "x" priority=10
+                    flag
+                }
+                other 1 2 3
+            "#
+            ),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn aliases() {
+        let schema = Schema::parse(
+            "<schema>",
+            r#"
+            document {
+                node "server" "srv" {
+                    min 1
+                    max 1
+                    prop "max" "maximum" { required true; type "number"; }
+                }
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(schema.nodes[0].aliases, ["srv"]);
+        assert_eq!(schema.nodes[0].props[0].aliases, ["maximum"]);
+        let validate = |text| {
+            let doc = crate::parse_ast::<Span>("<test>", text).unwrap();
+            match schema.validate(&doc) {
+                Ok(()) => Vec::new(),
+                Err(errors) => errors.iter().map(|e| e.to_string()).collect(),
+            }
+        };
+        assert_eq!(validate("srv maximum=1"), Vec::<String>::new());
+        assert_eq!(
+            validate("server max=1; srv max=2"),
+            ["node `server` must not occur more than 1 times"]
+        );
+        assert_eq!(
+            validate(r#"srv maximum="1""#),
+            ["expected number, found string"]
+        );
+        assert_eq!(validate("srv"), ["property `max` is required"]);
+        let text = crate::to_string(&schema).unwrap();
+        assert!(text.contains(r#"node "server" {"#), "{}", text);
+        assert!(text.contains(r#"prop "max" {"#), "{}", text);
+        let plain = Schema::parse("<schema>", &text).unwrap();
+        assert!(plain.nodes[0].aliases.is_empty());
+        assert!(plain.nodes[0].props[0].aliases.is_empty());
+        let text = crate::to_document_with_context::<_, Span, _>(&schema, |ctx| {
+            ctx.set(super::EncodeAliases)
+        })
+        .unwrap()
+        .to_string();
+        assert!(text.contains(r#"node "server" "srv""#), "{}", text);
+        assert_eq!(Schema::parse("<schema>", &text).unwrap(), schema);
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(errors(""), ["child node `server` is required"]);
+        assert_eq!(
+            errors(r#"server "main" port=80; server "xx" port=1"#),
+            ["node `server` must not occur more than 1 times"]
+        );
+        assert_eq!(
+            errors(r#"server port=80"#),
+            ["node requires at least 1 arguments"]
+        );
+        assert_eq!(
+            errors(r#"server "aa" "bb" "cc" port="80" extra=1"#),
+            [
+                "unexpected argument",
+                "unexpected property `extra`",
+                "expected number, found string",
+            ]
+        );
+        assert_eq!(
+            errors(r#"server "aa" port=65536 mode="other""#),
+            [
+                "expected one of \"fast\", \"slow\"",
+                "number too large to fit in target type",
+            ]
+        );
+        assert_eq!(
+            errors(r#"server 1 mode=null"#),
+            [
+                "expected string, found integer",
+                "expected one of \"fast\", \"slow\"",
+                "property `port` is required",
+            ]
+        );
+        assert_eq!(
+            errors(r#"server "x" port=1"#),
+            ["string must be at least 2 characters long"]
+        );
+        assert_eq!(
+            errors(
+                r#"
+                server "main" port=1 {
+                    plugin priority=7
+                    plugin "a" priority=100
+                    flag 1
+                    unknown
+                }
+            "#
+            ),
+            [
+                "node requires at least 1 arguments",
+                "number must be a multiple of 5",
+                "number must be less than 100",
+                "unexpected argument",
+                "unexpected node `unknown`",
+            ]
+        );
+    }
+}
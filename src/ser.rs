@@ -0,0 +1,748 @@
+//! Serde serializer producing KDL documents
+//!
+//! This module is only available with the `serde` feature. It's a counterpart
+//! of the [`de`](crate::de) module and uses the same mapping, so the values
+//! written by [`to_string`] are read back by
+//! [`de::from_str`](crate::de::from_str):
+//!
+//! ```rust
+//! use serde::Serialize;
+//!
+//! #[derive(Serialize)]
+//! struct Config {
+//!     name: String,
+//!     tags: Vec<String>,
+//!     server: Server,
+//! }
+//!
+//! #[derive(Serialize)]
+//! struct Server {
+//!     host: String,
+//!     port: u16,
+//! }
+//!
+//! let text = knus::ser::to_string(&Config {
+//!     name: "app".into(),
+//!     tags: vec!["web".into(), "api".into()],
+//!     server: Server { host: "localhost".into(), port: 8080 },
+//! })?;
+//! assert_eq!(text, r#"name "app"
+//! tags "web" "api"
+//! server host="localhost" port=8080
+//! "#);
+//! # Ok::<(), knus::errors::EncodeError>(())
+//! ```
+//!
+//! # Mapping
+//!
+//! Fields of the top-level struct (or entries of a map) are written as nodes,
+//! elements of the top-level sequence are written as nodes named `-`.
+//!
+//! Inside of the node:
+//!
+//! * Scalar fields of structs and maps are written as properties, other
+//!   fields are children. The `$args` field is written as arguments.
+//! * Sequences of scalars are written as arguments, other sequences are
+//!   written as children named `-`.
+//! * `None` fields are skipped, `None` elements of sequences are `null`.
+//! * Unit variants are written as strings. For other variants the name of the
+//!   variant is the first argument, or the node name if the variant is an
+//!   element of a sequence.
+//! * Byte arrays (including `Vec<u8>`) are written as `(base64)` strings.
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::ser::{self, Serialize};
+
+use crate::ast::{Document, Integer, Literal, Node, Radix, Value};
+use crate::de::ARGUMENTS;
+use crate::encode;
+use crate::errors::EncodeError;
+use crate::span::{Span, Spanned};
+use crate::traits::{EncodeScalar, ErrorSpan};
+
+/// Name of the nodes that are elements of sequences
+const ELEMENT: &str = "-";
+
+/// Intermediate result of serializing a value
+enum Item<S> {
+    /// `None`, skipped in structs
+    Skip,
+    Scalar(Value<S>),
+    /// Element of the byte array
+    Byte(u8),
+    Node(Node<S>),
+    /// Enum variant with its contents
+    Variant(&'static str, Node<S>),
+}
+
+struct Serializer<S> {
+    /// Value is serialized at the document level
+    document: bool,
+    phantom: PhantomData<S>,
+}
+
+struct SeqSerializer<S> {
+    document: bool,
+    items: Vec<Item<S>>,
+}
+
+struct StructSerializer<S> {
+    document: bool,
+    node: Node<S>,
+    key: Option<Box<str>>,
+}
+
+struct VariantSerializer<T> {
+    name: &'static str,
+    inner: T,
+}
+
+impl ser::Error for EncodeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        EncodeError::Custom(msg.to_string().into())
+    }
+}
+
+/// Serialize the value into KDL document
+///
+/// Only structs, maps, sequences and enums can be serialized as a document.
+pub fn to_document<T, S>(value: &T) -> Result<Document<S>, EncodeError>
+where
+    T: Serialize + ?Sized,
+    S: ErrorSpan + Default,
+{
+    match value.serialize(Serializer::new(true))? {
+        Item::Skip => Ok(Document { nodes: Vec::new() }),
+        Item::Node(node) => {
+            if !node.arguments.is_empty() || !node.properties.is_empty() {
+                return Err(EncodeError::unsupported(
+                    "arguments are not allowed at the document level",
+                ));
+            }
+            Ok(Document {
+                nodes: node.children.map(|c| c.value).unwrap_or_default(),
+            })
+        }
+        Item::Variant(name, mut node) => {
+            node.node_name = Spanned::new(name.into(), S::default());
+            Ok(Document {
+                nodes: vec![Spanned::new(node, S::default())],
+            })
+        }
+        Item::Scalar(_) | Item::Byte(_) => Err(EncodeError::unsupported(
+            "scalar value cannot be serialized as a document",
+        )),
+    }
+}
+
+/// Serialize the value into KDL text
+pub fn to_string<T>(value: &T) -> Result<String, EncodeError>
+where
+    T: Serialize + ?Sized,
+{
+    to_document::<T, Span>(value).map(|doc| doc.to_string())
+}
+
+impl<S: Default> Item<S> {
+    fn is_scalar(&self) -> bool {
+        match self {
+            Item::Skip | Item::Scalar(_) | Item::Byte(_) => true,
+            Item::Variant(_, node) => is_empty(node),
+            Item::Node(_) => false,
+        }
+    }
+    /// Converts item to value, must only be called if `is_scalar()`
+    fn into_value(self) -> Value<S> {
+        match self {
+            Item::Scalar(value) => value,
+            Item::Byte(value) => Value::new(integer(value)),
+            Item::Variant(name, _) => Value::new(Literal::String(name.into())),
+            Item::Skip | Item::Node(_) => Value::new(Literal::Null),
+        }
+    }
+    /// Makes node for the struct field or map entry
+    fn into_named(self, name: &str) -> Option<Node<S>> {
+        let mut node = match self {
+            Item::Skip => return None,
+            Item::Node(node) => node,
+            Item::Variant(variant, mut node) => {
+                let value = Value::new(Literal::String(variant.into()));
+                node.arguments.insert(0, value);
+                node
+            }
+            item => {
+                let mut node = Node::new(name);
+                node.arguments.push(item.into_value());
+                node
+            }
+        };
+        node.node_name = Spanned::new(name.into(), S::default());
+        Some(node)
+    }
+    /// Makes node for the element of the sequence
+    fn into_element(self) -> Node<S> {
+        match self {
+            Item::Variant(variant, mut node) => {
+                node.node_name = Spanned::new(variant.into(), S::default());
+                node
+            }
+            Item::Node(mut node) => {
+                node.node_name = Spanned::new(ELEMENT.into(), S::default());
+                node
+            }
+            item => {
+                let mut node = Node::new(ELEMENT);
+                node.arguments.push(item.into_value());
+                node
+            }
+        }
+    }
+    /// Makes contents of the enum variant
+    fn into_contents(self) -> Node<S> {
+        match self {
+            Item::Skip => {
+                let mut node = Node::new(ELEMENT);
+                node.arguments.push(Value::new(Literal::Null));
+                node
+            }
+            item => item.into_named(ELEMENT).expect("item is not skipped"),
+        }
+    }
+}
+
+fn is_empty<S>(node: &Node<S>) -> bool {
+    node.arguments.is_empty() && node.properties.is_empty() && node.children.is_none()
+}
+
+fn integer(value: impl ToString) -> Literal {
+    Literal::Int(Integer(Radix::Dec, value.to_string().into()))
+}
+
+fn scalar<S: Default>(literal: Literal) -> Result<Item<S>, EncodeError> {
+    Ok(Item::Scalar(Value::new(literal)))
+}
+
+impl<S> Serializer<S> {
+    fn new(document: bool) -> Self {
+        Serializer {
+            document,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<S: ErrorSpan + Default> ser::Serializer for Serializer<S> {
+    type Ok = Item<S>;
+    type Error = EncodeError;
+    type SerializeSeq = SeqSerializer<S>;
+    type SerializeTuple = SeqSerializer<S>;
+    type SerializeTupleStruct = SeqSerializer<S>;
+    type SerializeTupleVariant = VariantSerializer<SeqSerializer<S>>;
+    type SerializeMap = StructSerializer<S>;
+    type SerializeStruct = StructSerializer<S>;
+    type SerializeStructVariant = VariantSerializer<StructSerializer<S>>;
+
+    fn serialize_bool(self, v: bool) -> Result<Item<S>, EncodeError> {
+        scalar(Literal::Bool(v))
+    }
+    fn serialize_i8(self, v: i8) -> Result<Item<S>, EncodeError> {
+        scalar(integer(v))
+    }
+    fn serialize_i16(self, v: i16) -> Result<Item<S>, EncodeError> {
+        scalar(integer(v))
+    }
+    fn serialize_i32(self, v: i32) -> Result<Item<S>, EncodeError> {
+        scalar(integer(v))
+    }
+    fn serialize_i64(self, v: i64) -> Result<Item<S>, EncodeError> {
+        scalar(integer(v))
+    }
+    fn serialize_i128(self, v: i128) -> Result<Item<S>, EncodeError> {
+        scalar(integer(v))
+    }
+    fn serialize_u8(self, v: u8) -> Result<Item<S>, EncodeError> {
+        Ok(Item::Byte(v))
+    }
+    fn serialize_u16(self, v: u16) -> Result<Item<S>, EncodeError> {
+        scalar(integer(v))
+    }
+    fn serialize_u32(self, v: u32) -> Result<Item<S>, EncodeError> {
+        scalar(integer(v))
+    }
+    fn serialize_u64(self, v: u64) -> Result<Item<S>, EncodeError> {
+        scalar(integer(v))
+    }
+    fn serialize_u128(self, v: u128) -> Result<Item<S>, EncodeError> {
+        scalar(integer(v))
+    }
+    fn serialize_f32(self, v: f32) -> Result<Item<S>, EncodeError> {
+        scalar(v.raw_encode(&mut encode::Context::new())?)
+    }
+    fn serialize_f64(self, v: f64) -> Result<Item<S>, EncodeError> {
+        scalar(v.raw_encode(&mut encode::Context::new())?)
+    }
+    fn serialize_char(self, v: char) -> Result<Item<S>, EncodeError> {
+        scalar(Literal::String(v.to_string().into()))
+    }
+    fn serialize_str(self, v: &str) -> Result<Item<S>, EncodeError> {
+        scalar(Literal::String(v.into()))
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<Item<S>, EncodeError> {
+        Ok(Item::Scalar(encode::bytes(v, &mut encode::Context::new())?))
+    }
+    fn serialize_none(self) -> Result<Item<S>, EncodeError> {
+        Ok(Item::Skip)
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Item<S>, EncodeError> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<Item<S>, EncodeError> {
+        Ok(Item::Node(Node::new(ELEMENT)))
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Item<S>, EncodeError> {
+        self.serialize_unit()
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Item<S>, EncodeError> {
+        Ok(Item::Variant(variant, Node::new(variant)))
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Item<S>, EncodeError> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Item<S>, EncodeError> {
+        let item = value.serialize(Serializer::new(false))?;
+        Ok(Item::Variant(variant, item.into_contents()))
+    }
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer<S>, EncodeError> {
+        Ok(SeqSerializer {
+            document: self.document,
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer<S>, EncodeError> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer<S>, EncodeError> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, EncodeError> {
+        Ok(VariantSerializer {
+            name: variant,
+            inner: Serializer::new(false).serialize_seq(Some(len))?,
+        })
+    }
+    fn serialize_map(self, _len: Option<usize>) -> Result<StructSerializer<S>, EncodeError> {
+        Ok(StructSerializer {
+            document: self.document,
+            node: Node::new(ELEMENT),
+            key: None,
+        })
+    }
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<StructSerializer<S>, EncodeError> {
+        self.serialize_map(Some(len))
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, EncodeError> {
+        Ok(VariantSerializer {
+            name: variant,
+            inner: Serializer::new(false).serialize_map(Some(len))?,
+        })
+    }
+}
+
+impl<S: ErrorSpan + Default> SeqSerializer<S> {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), EncodeError> {
+        self.items.push(value.serialize(Serializer::new(false))?);
+        Ok(())
+    }
+    fn finish(self) -> Result<Item<S>, EncodeError> {
+        let mut node = Node::new(ELEMENT);
+        if !self.document
+            && !self.items.is_empty()
+            && self.items.iter().all(|item| matches!(item, Item::Byte(_)))
+        {
+            let bytes = self
+                .items
+                .into_iter()
+                .map(|item| match item {
+                    Item::Byte(value) => value,
+                    _ => unreachable!(),
+                })
+                .collect::<Vec<_>>();
+            return Ok(Item::Scalar(encode::bytes(
+                &bytes,
+                &mut encode::Context::new(),
+            )?));
+        } else if !self.document && self.items.iter().all(Item::is_scalar) {
+            node.arguments = self.items.into_iter().map(Item::into_value).collect();
+        } else {
+            for item in self.items {
+                node.push_child(item.into_element());
+            }
+        }
+        Ok(Item::Node(node))
+    }
+}
+
+impl<S: ErrorSpan + Default> ser::SerializeSeq for SeqSerializer<S> {
+    type Ok = Item<S>;
+    type Error = EncodeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), EncodeError> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Item<S>, EncodeError> {
+        self.finish()
+    }
+}
+
+impl<S: ErrorSpan + Default> ser::SerializeTuple for SeqSerializer<S> {
+    type Ok = Item<S>;
+    type Error = EncodeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), EncodeError> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Item<S>, EncodeError> {
+        self.finish()
+    }
+}
+
+impl<S: ErrorSpan + Default> ser::SerializeTupleStruct for SeqSerializer<S> {
+    type Ok = Item<S>;
+    type Error = EncodeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), EncodeError> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Item<S>, EncodeError> {
+        self.finish()
+    }
+}
+
+impl<S: ErrorSpan + Default> ser::SerializeTupleVariant for VariantSerializer<SeqSerializer<S>> {
+    type Ok = Item<S>;
+    type Error = EncodeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), EncodeError> {
+        self.inner.push(value)
+    }
+    fn end(self) -> Result<Item<S>, EncodeError> {
+        let contents = self.inner.finish()?.into_contents();
+        Ok(Item::Variant(self.name, contents))
+    }
+}
+
+impl<S: ErrorSpan + Default> StructSerializer<S> {
+    fn field<T: Serialize + ?Sized>(&mut self, name: &str, value: &T) -> Result<(), EncodeError> {
+        let item = value.serialize(Serializer::new(false))?;
+        if name == ARGUMENTS && !self.document {
+            match item {
+                Item::Skip => {}
+                Item::Node(node) if node.properties.is_empty() && node.children.is_none() => {
+                    self.node.arguments.extend(node.arguments);
+                }
+                item if item.is_scalar() => self.node.arguments.push(item.into_value()),
+                _ => {
+                    return Err(EncodeError::unsupported(
+                        "arguments must be a sequence of scalars",
+                    ))
+                }
+            }
+        } else if !self.document && item.is_scalar() {
+            if !matches!(item, Item::Skip) {
+                self.node
+                    .properties
+                    .insert(Spanned::new(name.into(), S::default()), item.into_value());
+            }
+        } else if let Some(node) = item.into_named(name) {
+            self.node.push_child(node);
+        }
+        Ok(())
+    }
+}
+
+impl<S: ErrorSpan + Default> ser::SerializeMap for StructSerializer<S> {
+    type Ok = Item<S>;
+    type Error = EncodeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), EncodeError> {
+        let item: Item<S> = key.serialize(Serializer::new(false))?;
+        if !item.is_scalar() {
+            return Err(EncodeError::unsupported("map keys must be scalars"));
+        }
+        let key = match item.into_value().literal.value {
+            Literal::String(value) => value,
+            Literal::Int(value) => value.1,
+            Literal::Decimal(value) => value.0,
+            Literal::Bool(value) => value.to_string().into(),
+            Literal::Null => return Err(EncodeError::unsupported("map keys must not be null")),
+        };
+        self.key = Some(key);
+        Ok(())
+    }
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), EncodeError> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| EncodeError::unsupported("map value without a key"))?;
+        self.field(&key, value)
+    }
+    fn end(self) -> Result<Item<S>, EncodeError> {
+        Ok(Item::Node(self.node))
+    }
+}
+
+impl<S: ErrorSpan + Default> ser::SerializeStruct for StructSerializer<S> {
+    type Ok = Item<S>;
+    type Error = EncodeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), EncodeError> {
+        self.field(key, value)
+    }
+    fn end(self) -> Result<Item<S>, EncodeError> {
+        Ok(Item::Node(self.node))
+    }
+}
+
+impl<S: ErrorSpan + Default> ser::SerializeStructVariant
+    for VariantSerializer<StructSerializer<S>>
+{
+    type Ok = Item<S>;
+    type Error = EncodeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), EncodeError> {
+        self.inner.field(key, value)
+    }
+    fn end(self) -> Result<Item<S>, EncodeError> {
+        Ok(Item::Variant(self.name, self.inner.node))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::to_string;
+    use crate::de::from_str;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Config {
+        name: String,
+        port: Option<u16>,
+        #[serde(default)]
+        tags: Vec<String>,
+        mode: Mode,
+        #[serde(default)]
+        plugins: Vec<Plugin>,
+        server: Option<Server>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Server {
+        #[serde(rename = "$args")]
+        hosts: Vec<String>,
+        timeout: f64,
+        limits: BTreeMap<String, u32>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Mode {
+        Fast,
+        Slow,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "kebab-case")]
+    enum Plugin {
+        Logger,
+        Http(String),
+        Proxy(String, u16),
+        Cache { size: u32 },
+        Nested(Option<Mode>),
+    }
+
+    fn roundtrip<T>(value: &T) -> String
+    where
+        T: Serialize + serde::de::DeserializeOwned + PartialEq + std::fmt::Debug,
+    {
+        let text = to_string(value).unwrap();
+        let parsed: T = from_str("<test>", &text).unwrap();
+        assert_eq!(&parsed, value);
+        text
+    }
+
+    #[test]
+    fn structs() {
+        let config = Config {
+            name: "app".into(),
+            port: None,
+            tags: vec!["a".into(), "b".into()],
+            mode: Mode::Slow,
+            plugins: vec![
+                Plugin::Logger,
+                Plugin::Http("/api".into()),
+                Plugin::Proxy("localhost".into(), 8080),
+                Plugin::Cache { size: 100 },
+                Plugin::Nested(Some(Mode::Fast)),
+            ],
+            server: Some(Server {
+                hosts: vec!["a.example".into(), "b.example".into()],
+                timeout: 1.5,
+                limits: [("conn".to_string(), 10)].into_iter().collect(),
+            }),
+        };
+        assert_eq!(
+            roundtrip(&config),
+            r#"name "app"
+tags "a" "b"
+mode "slow"
+plugins {
+    logger
+    http "/api"
+    proxy "localhost" 8080
+    cache size=100
+    nested "fast"
+}
+server "a.example" "b.example" timeout=1.5 {
+    limits conn=10
+}
+"#
+        );
+    }
+
+    #[test]
+    fn sequences() {
+        let value = vec![Plugin::Logger, Plugin::Nested(None)];
+        assert_eq!(roundtrip(&value), "logger\nnested null\n");
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Item {
+            name: String,
+        }
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Items {
+            items: Vec<Item>,
+            empty: Vec<Item>,
+            matrix: Vec<Vec<u32>>,
+            optional: Vec<Option<u32>>,
+            modes: Vec<Mode>,
+        }
+        let items = Items {
+            items: vec![Item { name: "a".into() }, Item { name: "b".into() }],
+            empty: vec![],
+            matrix: vec![vec![1, 2], vec![3]],
+            optional: vec![Some(1), None],
+            modes: vec![Mode::Fast, Mode::Slow],
+        };
+        assert_eq!(
+            roundtrip(&items),
+            r#"items {
+    - name="a"
+    - name="b"
+}
+empty
+matrix {
+    - 1 2
+    - 3
+}
+optional 1 null
+modes "fast" "slow"
+"#
+        );
+    }
+
+    #[test]
+    fn maps() {
+        let map: BTreeMap<String, BTreeMap<String, i64>> = [
+            (
+                "a".to_string(),
+                [("x".to_string(), 1)].into_iter().collect(),
+            ),
+            ("b".to_string(), BTreeMap::new()),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(roundtrip(&map), "a x=1\nb\n");
+        let map: BTreeMap<u32, bool> = [(1, true)].into_iter().collect();
+        assert_eq!(to_string(&map).unwrap(), "\"1\" true\n");
+    }
+
+    #[test]
+    #[cfg(feature = "base64")]
+    fn bytes() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Data {
+            data: Vec<u8>,
+            chunks: Vec<Vec<u8>>,
+            empty: Vec<u8>,
+        }
+        let data = Data {
+            data: b"hello".to_vec(),
+            chunks: vec![b"a".to_vec(), b"bc".to_vec()],
+            empty: vec![],
+        };
+        assert_eq!(
+            roundtrip(&data),
+            "data (base64)\"aGVsbG8=\"\n\
+             chunks (base64)\"YQ==\" (base64)\"YmM=\"\nempty\n"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            to_string(&1).unwrap_err().to_string(),
+            "scalar value cannot be serialized as a document"
+        );
+        assert_eq!(
+            to_string(&[f64::NAN]).unwrap_err().to_string(),
+            "NaN cannot be represented in KDL"
+        );
+    }
+}
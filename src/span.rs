@@ -3,6 +3,9 @@
 //! 1. [`Span`] which only tracks byte offset from the start of the source code
 //! 2. [`LineSpan`] which also track line numbers
 //!
+//! Documents combined from several files by [`include`](crate::include) have
+//! [`FileSpan`] that also refers to the file.
+//!
 //! This distinction is important during parsing stage as [`Span`] is normally
 //! faster. And [`LineSpan`] is still faster than find out line/column number
 //! for each span separately, and is also more convenient if you need this
@@ -13,6 +16,7 @@
 //! as a separate kind of span. See [`traits::DecodeSpan`].
 use std::fmt;
 use std::ops::Range;
+use std::sync::Arc;
 
 use crate::decode::Context;
 use crate::traits;
@@ -62,19 +66,68 @@ pub struct LineSpan(
     #[cfg_attr(feature = "minicbor", n(1))] pub LinePos,
 );
 
+/// Name and text of the source file
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct SourceFile {
+    /// File name as shown in the error messages
+    pub name: String,
+    /// Source code of the file
+    pub text: String,
+}
+
+/// Byte offset span in the specific source file
+///
+/// Debug, comparison and hashing only use the name of the file, not its
+/// text.
+#[derive(Clone)]
+pub struct FileSpan {
+    /// The file the span points into
+    pub file: Arc<SourceFile>,
+    /// Byte offsets in the file
+    pub span: Span,
+}
+
 #[allow(missing_debug_implementations)]
 mod sealed {
 
-    pub struct OffsetTracker {
-        pub(crate) offset: usize,
-    }
+    pub struct OffsetTracker;
 
     #[cfg(feature = "line-numbers")]
     pub struct LineTracker {
-        pub(crate) offset: usize,
+        pub(crate) pos: super::LinePos,
         pub(crate) caret_return: bool,
-        pub(crate) line: usize,
-        pub(crate) column: usize,
+    }
+}
+
+impl LinePos {
+    /// Moves the position past the character `c`
+    ///
+    /// `caret_return` tells whether the previous character was `\r`, so
+    /// that `\r\n` counts as a single line break.
+    pub(crate) fn advance(&mut self, c: char, caret_return: bool) {
+        self.offset += c.len_utf8();
+        match c {
+            '\n' if caret_return => {}
+            '\r' | '\n' | '\x0C' | '\u{0085}' | '\u{2028}' | '\u{2029}' => {
+                self.line += 1;
+                self.column = 0;
+            }
+            c => self.column += char_width(c),
+        }
+    }
+}
+
+/// Number of columns the character takes, tabs are 8 columns wide
+pub(crate) fn char_width(c: char) -> usize {
+    match c {
+        '\t' => 8,
+        // treat control chars as zero-length
+        #[cfg(feature = "line-numbers")]
+        c => unicode_width::UnicodeWidthChar::width(c).unwrap_or(0),
+        #[cfg(not(feature = "line-numbers"))]
+        c if c.is_control() => 0,
+        #[cfg(not(feature = "line-numbers"))]
+        _ => 1,
     }
 }
 
@@ -91,189 +144,78 @@ impl From<Span> for ErrorSpan {
     }
 }
 
+impl From<FileSpan> for ErrorSpan {
+    fn from(val: FileSpan) -> Self {
+        val.span.into()
+    }
+}
+
 impl From<LineSpan> for ErrorSpan {
     fn from(val: LineSpan) -> Self {
         (val.0.offset, val.1.offset.saturating_sub(val.0.offset)).into()
     }
 }
 
-impl chumsky::Span for Span {
-    type Context = ();
-    type Offset = usize;
-    fn new(_context: (), range: std::ops::Range<usize>) -> Self {
-        Span(range.start(), range.end())
-    }
-    fn context(&self) {}
-    fn start(&self) -> usize {
-        self.0
-    }
-    fn end(&self) -> usize {
-        self.1
-    }
-}
 impl traits::sealed::SpanTracker for sealed::OffsetTracker {
-    type Span = Span;
-    fn next_span(&mut self, c: char) -> Span {
-        let start = self.offset;
-        self.offset += c.len_utf8();
-        Span(start, self.offset)
+    type Offset = usize;
+    fn offset(&mut self, _text: &str, byte: usize) -> usize {
+        byte
     }
 }
 
 impl traits::sealed::Sealed for Span {
+    type Offset = usize;
     type Tracker = sealed::OffsetTracker;
-    fn at_start(&self, chars: usize) -> Self {
-        Span(self.0, self.0 + chars)
-    }
-
-    fn at_end(&self) -> Self {
-        Span(self.1, self.1)
+    fn new(start: usize, end: usize) -> Self {
+        Span(start, end)
     }
-
-    fn before_start(&self, chars: usize) -> Self {
-        Span(self.0.saturating_sub(chars), self.0)
+    fn start(&self) -> usize {
+        self.0
     }
-
-    fn length(&self) -> usize {
-        self.1.saturating_sub(self.0)
+    fn end(&self) -> usize {
+        self.1
     }
-
-    fn stream(text: &str) -> traits::sealed::Stream<'_, Self, Self::Tracker>
-    where
-        Self: chumsky::Span,
-    {
-        chumsky::Stream::from_iter(
-            Span(text.len(), text.len()),
-            traits::sealed::Map(text.chars(), sealed::OffsetTracker { offset: 0 }),
-        )
+    fn tracker() -> Self::Tracker {
+        sealed::OffsetTracker
     }
 }
 
 impl traits::Span for Span {}
 
-impl chumsky::Span for LineSpan {
-    type Context = ();
-    type Offset = LinePos;
-    fn new(_context: (), range: std::ops::Range<LinePos>) -> Self {
-        LineSpan(range.start, range.end)
-    }
-    fn context(&self) {}
-    fn start(&self) -> LinePos {
-        self.0
-    }
-    fn end(&self) -> LinePos {
-        self.1
-    }
-}
-
 #[cfg(feature = "line-numbers")]
 impl traits::sealed::SpanTracker for sealed::LineTracker {
-    type Span = LineSpan;
-    fn next_span(&mut self, c: char) -> LineSpan {
-        let offset = self.offset;
-        let line = self.line;
-        let column = self.column;
-        self.offset += c.len_utf8();
-        match c {
-            '\n' if self.caret_return => {}
-            '\r' | '\n' | '\x0C' | '\u{0085}' | '\u{2028}' | '\u{2029}' => {
-                self.line += 1;
-                self.column = 0;
-            }
-            '\t' => self.column += 8,
-            c => {
-                self.column += unicode_width::UnicodeWidthChar::width(c).unwrap_or(0);
-                // treat control chars as zero-length
-            }
+    type Offset = LinePos;
+    fn offset(&mut self, text: &str, byte: usize) -> LinePos {
+        if byte < self.pos.offset {
+            self.pos = LinePos::default();
+            self.caret_return = false;
         }
-        self.caret_return = c == '\r';
-        LineSpan(
-            LinePos {
-                line,
-                column,
-                offset,
-            },
-            LinePos {
-                line: self.line,
-                column: self.column,
-                offset: self.offset,
-            },
-        )
+        for c in text[self.pos.offset..byte].chars() {
+            self.pos.advance(c, self.caret_return);
+            self.caret_return = c == '\r';
+        }
+        self.pos
     }
 }
 
 #[cfg(feature = "line-numbers")]
 impl traits::sealed::Sealed for LineSpan {
+    type Offset = LinePos;
     type Tracker = sealed::LineTracker;
-    /// Note assuming ascii, single-width, non-newline chars here
-    fn at_start(&self, chars: usize) -> Self {
-        LineSpan(
-            self.0,
-            LinePos {
-                offset: self.0.offset + chars,
-                column: self.0.column + chars,
-                ..self.0
-            },
-        )
-    }
-
-    fn at_end(&self) -> Self {
-        LineSpan(self.1, self.1)
+    fn new(start: LinePos, end: LinePos) -> Self {
+        LineSpan(start, end)
     }
-
-    /// Note assuming ascii, single-width, non-newline chars here
-    fn before_start(&self, chars: usize) -> Self {
-        LineSpan(
-            LinePos {
-                offset: self.0.offset.saturating_sub(chars),
-                column: self.0.column.saturating_sub(chars),
-                ..self.0
-            },
-            self.0,
-        )
+    fn start(&self) -> LinePos {
+        self.0
     }
-
-    fn length(&self) -> usize {
-        self.1.offset.saturating_sub(self.0.offset)
+    fn end(&self) -> LinePos {
+        self.1
     }
-
-    fn stream(text: &str) -> traits::sealed::Stream<'_, Self, Self::Tracker>
-    where
-        Self: chumsky::Span,
-    {
-        let mut caret_return = false;
-        let mut line = 0;
-        let mut last_line = text;
-        let mut iter = text.chars();
-        while let Some(c) = iter.next() {
-            match c {
-                '\n' if caret_return => {}
-                '\r' | '\n' | '\x0C' | '\u{0085}' | '\u{2028}' | '\u{2029}' => {
-                    line += 1;
-                    last_line = iter.as_str();
-                }
-                _ => {}
-            }
-            caret_return = c == '\r';
+    fn tracker() -> Self::Tracker {
+        sealed::LineTracker {
+            pos: LinePos::default(),
+            caret_return: false,
         }
-        let column = unicode_width::UnicodeWidthStr::width(last_line);
-        let eoi = LinePos {
-            line,
-            column,
-            offset: text.len(),
-        };
-        chumsky::Stream::from_iter(
-            LineSpan(eoi, eoi),
-            traits::sealed::Map(
-                text.chars(),
-                sealed::LineTracker {
-                    caret_return: false,
-                    offset: 0,
-                    line: 0,
-                    column: 0,
-                },
-            ),
-        )
     }
 }
 
@@ -287,7 +229,21 @@ impl traits::DecodeSpan<LineSpan> for Span {
     }
 }
 
+impl traits::DecodeSpan<FileSpan> for Span {
+    fn decode_span(span: &FileSpan, _: &mut Context<FileSpan>) -> Self {
+        span.span
+    }
+}
+
 impl<T, S> Spanned<T, S> {
+    /// Attaches span to the value
+    pub fn new(value: T, span: S) -> Spanned<T, S> {
+        Spanned { span, value }
+    }
+    /// Returns the value dropping the span
+    pub fn into_inner(self) -> T {
+        self.value
+    }
     /// Converts value but keeps the same span attached
     pub fn map<R>(self, f: impl FnOnce(T) -> R) -> Spanned<R, S> {
         Spanned {
@@ -402,3 +358,38 @@ impl From<Range<usize>> for Span {
         Span(r.start, r.end)
     }
 }
+
+impl fmt::Display for FileSpan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.file.name.fmt(f)?;
+        ":".fmt(f)?;
+        self.span.fmt(f)
+    }
+}
+
+impl fmt::Debug for FileSpan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FileSpan")
+            .field("file", &self.file.name)
+            .field("span", &self.span)
+            .finish()
+    }
+}
+
+impl PartialEq for FileSpan {
+    fn eq(&self, other: &FileSpan) -> bool {
+        self.file.name == other.file.name && self.span == other.span
+    }
+}
+
+impl Eq for FileSpan {}
+
+impl std::hash::Hash for FileSpan {
+    fn hash<H>(&self, state: &mut H)
+    where
+        H: std::hash::Hasher,
+    {
+        self.file.name.hash(state);
+        self.span.hash(state);
+    }
+}
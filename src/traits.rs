@@ -6,9 +6,12 @@
 //! documentation of the derives to implement these traits.
 use std::fmt;
 
-use crate::ast::{Literal, SpannedNode, TypeName, Value};
+use crate::ast::{Literal, Node, SpannedNode, TypeName, Value};
 use crate::decode::Context;
-use crate::errors::DecodeError;
+use crate::encode;
+use crate::errors::{DecodeError, EncodeError};
+use crate::merge;
+use crate::schema::{NodeSchema, Validations};
 use crate::span::Spanned;
 
 /// Trait to decode KDL node from the AST
@@ -100,6 +103,122 @@ impl<T: ErrorSpan> DecodeSpan<T> for T {
     }
 }
 
+/// Trait to describe the KDL node the value is decoded from
+///
+/// Implemented by the [`DecodeSchema`](derive@crate::DecodeSchema) derive,
+/// see [`schema`](crate::schema) module for validating documents.
+pub trait DecodeSchema {
+    /// Returns the schema of the node
+    ///
+    /// The name of the node is usually determined by the parent, so it's
+    /// left empty here.
+    fn schema() -> NodeSchema;
+    /// Returns the schemas of the nodes when the type is used for `children`
+    ///
+    /// By default it's a single [`schema`](DecodeSchema::schema) matching any
+    /// node name. Enums return a named schema for each variant.
+    fn children_schema() -> Vec<NodeSchema> {
+        vec![Self::schema()]
+    }
+}
+
+/// Trait to describe the scalar value the value is decoded from
+pub trait ScalarSchema {
+    /// Returns the validations of the value
+    fn validations() -> Validations;
+}
+
+/// Trait to merge the nodes of several layers of configuration
+///
+/// Implemented by the [`Merge`](derive@crate::Merge) derive, see
+/// [`merge`](crate::merge) module for the merge rules. All the methods work
+/// on the AST, so merged document is decoded only once.
+pub trait Merge<S> {
+    /// Merges the node of the next layer into the node of the previous layers
+    ///
+    /// By default it's [`merge::merge_node`](crate::merge::merge_node).
+    fn merge_node(base: &mut SpannedNode<S>, layer: SpannedNode<S>) {
+        merge::merge_node::<Self, S>(base, layer)
+    }
+    /// Merges the nodes of the next layer into the nodes of the previous
+    /// layers, when the type is used for a document or children of the node
+    ///
+    /// By default it's
+    /// [`merge::merge_children`](crate::merge::merge_children).
+    fn merge_children(base: &mut Vec<SpannedNode<S>>, layer: Vec<SpannedNode<S>>) {
+        merge::merge_children::<Self, S>(base, layer)
+    }
+    /// Merges single child of the next layer into the existing nodes
+    ///
+    /// Returns the node back if it should be appended to the children. By
+    /// default all the nodes are appended.
+    fn merge_child(
+        _base: &mut Vec<SpannedNode<S>>,
+        child: SpannedNode<S>,
+    ) -> Option<SpannedNode<S>> {
+        Some(child)
+    }
+    /// Removes existing nodes that are replaced by the nodes of the layer
+    ///
+    /// Called before nodes of the layer are merged. Does nothing by default.
+    fn remove_replaced(_base: &mut Vec<SpannedNode<S>>, _layer: &[SpannedNode<S>]) {}
+}
+
+/// Trait to encode Rust value into a KDL node
+///
+/// This is a counterpart of [`Decode`]. The node passed to the encoder is
+/// created by the parent, so node name is usually already set (either to the
+/// name of the field, or to `-` for anonymous nodes). Implementations that
+/// know their own name (like enums) overwrite it.
+pub trait Encode {
+    /// Encodes the value into the node
+    fn encode_node<S>(
+        &self,
+        node: &mut Node<S>,
+        ctx: &mut encode::Context,
+    ) -> Result<(), EncodeError>
+    where
+        S: ErrorSpan + Default;
+}
+
+/// Trait to encode Rust value as children of the KDL node, mostly used for
+/// root document
+pub trait EncodeChildren {
+    /// Encodes the value appending nodes to the list
+    fn encode_children<S>(
+        &self,
+        nodes: &mut Vec<SpannedNode<S>>,
+        ctx: &mut encode::Context,
+    ) -> Result<(), EncodeError>
+    where
+        S: ErrorSpan + Default;
+}
+
+/// The trait that encodes Rust value into a scalar
+pub trait EncodeScalar {
+    /// Type name to attach to the value
+    ///
+    /// Most types have no type name attached, which is the default.
+    fn type_name(&self) -> Option<TypeName> {
+        None
+    }
+    /// Encode value without type name
+    fn raw_encode(&self, ctx: &mut encode::Context) -> Result<Literal, EncodeError>;
+    /// Encode the value with type name
+    ///
+    /// This should not be overriden and uses `type_name` in combination with
+    /// `raw_encode`.
+    fn encode<S>(&self, ctx: &mut encode::Context) -> Result<Value<S>, EncodeError>
+    where
+        S: ErrorSpan + Default,
+    {
+        Ok(Value {
+            type_name: self.type_name().map(|t| Spanned::new(t, S::default())),
+            literal: Spanned::new(self.raw_encode(ctx)?, S::default()),
+        })
+    }
+}
+
 /// Span must implement this trait to be used in the error messages
 ///
 /// Custom span types can be used for this unlike for [`Span`]
@@ -115,41 +234,25 @@ where
 ///
 /// It's sealed because needs some tight interoperation with the parser. Use
 /// [`DecodeSpan`] to convert spans whenever needed.
-pub trait Span: sealed::Sealed + chumsky::Span + ErrorSpan {}
+pub trait Span: sealed::Sealed + ErrorSpan {}
 
 #[allow(missing_debug_implementations)]
 pub(crate) mod sealed {
-    pub type Stream<'a, S, T> = chumsky::Stream<'a, char, S, Map<std::str::Chars<'a>, T>>;
-
-    pub struct Map<I, F>(pub(crate) I, pub(crate) F);
-
     pub trait SpanTracker {
-        type Span;
-        fn next_span(&mut self, c: char) -> Self::Span;
-    }
-
-    impl<I, T> Iterator for Map<I, T>
-    where
-        I: Iterator<Item = char>,
-        T: SpanTracker,
-    {
-        type Item = (char, T::Span);
-        fn next(&mut self) -> Option<(char, T::Span)> {
-            self.0.next().map(|c| (c, self.1.next_span(c)))
-        }
+        type Offset: Copy;
+        /// Returns position of the byte offset in the text
+        ///
+        /// Offsets are expected to be requested mostly in the increasing
+        /// order, going back rescans the text from the start.
+        fn offset(&mut self, text: &str, byte: usize) -> Self::Offset;
     }
 
     pub trait Sealed {
-        type Tracker: SpanTracker<Span = Self>;
-        /// Note assuming ascii, single-width, non-newline chars here
-        fn at_start(&self, chars: usize) -> Self;
-        fn at_end(&self) -> Self;
-        /// Note assuming ascii, single-width, non-newline chars here
-        fn before_start(&self, chars: usize) -> Self;
-        fn length(&self) -> usize;
-
-        fn stream(s: &str) -> Stream<'_, Self, Self::Tracker>
-        where
-            Self: chumsky::Span;
+        type Offset: Copy;
+        type Tracker: SpanTracker<Offset = Self::Offset>;
+        fn new(start: Self::Offset, end: Self::Offset) -> Self;
+        fn start(&self) -> Self::Offset;
+        fn end(&self) -> Self::Offset;
+        fn tracker() -> Self::Tracker;
     }
 }
@@ -1,24 +1,310 @@
-use chumsky::Parser;
 use miette::NamedSource;
 
-use crate::ast::Document;
+use crate::ast::{self, Document};
+use crate::borrowed;
+use crate::cst;
 use crate::decode::Context;
-use crate::errors::Error;
-use crate::grammar;
+use crate::encode;
+use crate::errors::{DecodeError, EncodeError, Error, ParseError, Warning, Warnings};
+use crate::grammar::{self, Scalar, Scanner, Syntax};
 use crate::span::Span;
-use crate::traits::{self, DecodeChildren};
+use crate::traits::{self, DecodeChildren, EncodeChildren};
+
+/// Version of the KDL syntax
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum KdlVersion {
+    /// Use KDL 2.0 if the document starts with the `/- kdl-version 2`
+    /// marker, and KDL 1.0 otherwise
+    #[default]
+    Auto,
+    /// KDL 1.0: `true`, `null`, `r#"raw"#` strings
+    V1,
+    /// KDL 2.0: `#true`, `#null`, `#"raw"#` and multi-line strings
+    V2,
+}
+
+/// Options for parsing KDL text
+///
+/// ```rust
+/// use knus::{KdlVersion, ParseOptions};
+///
+/// let doc = ParseOptions::new()
+///     .version(KdlVersion::V2)
+///     .parse_ast::<knus::span::Span>("config.kdl", "node #true")?;
+/// # Ok::<(), knus::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ParseOptions {
+    version: KdlVersion,
+}
+
+impl ParseOptions {
+    /// Create options with the version detected from the document
+    pub fn new() -> ParseOptions {
+        ParseOptions::default()
+    }
+    /// Version of the KDL syntax (default is [`KdlVersion::Auto`])
+    pub fn version(mut self, version: KdlVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Returns the version of the syntax used for the text
+    fn syntax_of(&self, text: &str) -> Syntax {
+        let version = match self.version {
+            KdlVersion::Auto => detect_version(text),
+            version => version,
+        };
+        match version {
+            KdlVersion::V2 => Syntax::V2,
+            _ => Syntax::V1,
+        }
+    }
+
+    /// Parse KDL text and return AST
+    pub fn parse_ast<S: traits::Span>(
+        &self,
+        file_name: impl AsRef<str>,
+        text: &str,
+    ) -> Result<Document<S>, Error> {
+        grammar::parse(text, self.syntax_of(text)).map_err(|errors| Error {
+            source_code: NamedSource::new(file_name, text.to_string()),
+            errors: errors.into_iter().map(Into::into).collect(),
+        })
+    }
+
+    /// Parse KDL text and return AST borrowing from the text
+    ///
+    /// See [`borrowed`](crate::borrowed) module for details.
+    pub fn parse_ast_borrowed<'a, S: traits::Span>(
+        &self,
+        file_name: impl AsRef<str>,
+        text: &'a str,
+    ) -> Result<borrowed::Document<'a, S>, Error> {
+        borrowed::document(text, self.syntax_of(text)).map_err(|errors| Error {
+            source_code: NamedSource::new(file_name, text.to_string()),
+            errors: errors.into_iter().map(Into::into).collect(),
+        })
+    }
+
+    /// Parse KDL text into lossless concrete syntax tree
+    ///
+    /// See [`cst`](crate::cst) module for details.
+    pub fn parse_cst(
+        &self,
+        file_name: impl AsRef<str>,
+        text: &str,
+    ) -> Result<cst::Document, Error> {
+        cst::document(text, self.syntax_of(text)).map_err(|errors| Error {
+            source_code: NamedSource::new(file_name, text.to_string()),
+            errors: errors.into_iter().map(Into::into).collect(),
+        })
+    }
+
+    /// Parse KDL text and return AST, recovering from syntax errors
+    ///
+    /// Nodes that fail to parse are skipped up to the end of the node
+    /// (including their children block), and parsing continues with the
+    /// next node. Returns the AST of the remaining nodes along with all the
+    /// [`ParseError`]s found. The document is `None` only if parser could
+    /// not recover at all.
+    ///
+    /// Children blocks that are not closed are closed at the end of input,
+    /// so the nodes parsed up to there are kept.
+    ///
+    /// The errors don't carry the source code, use
+    /// [`Diagnostic::with_line_index`](crate::diagnostic::Diagnostic::with_line_index)
+    /// or [`miette::Report::with_source_code`] to show them.
+    pub fn parse_ast_recovery<S: traits::Span>(
+        &self,
+        text: &str,
+    ) -> (Option<Document<S>>, Vec<ParseError<S>>) {
+        let (document, errors) = grammar::parse_recovery(text, self.syntax_of(text));
+        (document, errors.into_iter().map(ParseError).collect())
+    }
+
+    /// Parse KDL text and decode Rust object
+    pub fn parse<T>(&self, file_name: impl AsRef<str>, text: &str) -> Result<T, Error>
+    where
+        T: DecodeChildren<Span>,
+    {
+        self.parse_with_context(file_name, text, |_| {})
+            .map(|(value, _)| value)
+    }
+
+    /// Parse KDL text and decode Rust object providing extra context for the
+    /// decoder
+    ///
+    /// Returns the warnings emitted to the context along with the value.
+    /// Entities skipped as unknown (see [`Context::emit_ignored`]) are
+    /// included as warnings too.
+    pub fn parse_with_context<T, S, F>(
+        &self,
+        file_name: impl AsRef<str>,
+        text: &str,
+        set_ctx: F,
+    ) -> Result<(T, Warnings), Error>
+    where
+        F: FnOnce(&mut Context<S>),
+        T: DecodeChildren<S>,
+        S: traits::Span,
+    {
+        let ast = self.parse_ast(file_name.as_ref(), text)?;
+
+        let mut ctx = Context::new();
+        set_ctx(&mut ctx);
+        let result = DecodeChildren::decode_children(&ast.nodes, &mut ctx);
+        ast::drop_nodes(ast.nodes);
+        finish(
+            result,
+            ctx,
+            NamedSource::new(file_name, text.to_string()),
+            Into::into,
+            Into::into,
+        )
+    }
+
+    /// Parse KDL text and decode Rust object borrowing from the text
+    pub fn parse_borrowed<'a, T>(
+        &self,
+        file_name: impl AsRef<str>,
+        text: &'a str,
+    ) -> Result<T, Error>
+    where
+        T: borrowed::DecodeChildren<'a, Span>,
+    {
+        self.parse_borrowed_with_context(file_name, text, |_| {})
+            .map(|(value, _)| value)
+    }
+
+    /// Parse KDL text and decode Rust object borrowing from the text,
+    /// providing extra context for the decoder
+    ///
+    /// Returns the warnings emitted to the context along with the value.
+    pub fn parse_borrowed_with_context<'a, T, S, F>(
+        &self,
+        file_name: impl AsRef<str>,
+        text: &'a str,
+        set_ctx: F,
+    ) -> Result<(T, Warnings), Error>
+    where
+        F: FnOnce(&mut Context<S>),
+        T: borrowed::DecodeChildren<'a, S>,
+        S: traits::Span,
+    {
+        let ast = self.parse_ast_borrowed(file_name.as_ref(), text)?;
+
+        let mut ctx = Context::new();
+        set_ctx(&mut ctx);
+        let result = borrowed::DecodeChildren::decode_children(&ast.nodes, &mut ctx);
+        borrowed::drop_nodes(ast.nodes);
+        finish(
+            result,
+            ctx,
+            NamedSource::new(file_name, text.to_string()),
+            Into::into,
+            Into::into,
+        )
+    }
+}
+
+/// Returns the decoded value with the warnings emitted to the context
+///
+/// If there are errors, warnings are reported after them in the [`Error`].
+pub(crate) fn finish<T, S, E, W>(
+    result: Result<T, DecodeError<S>>,
+    mut ctx: Context<S>,
+    source_code: NamedSource<String>,
+    error: E,
+    warning: W,
+) -> Result<(T, Warnings), Error>
+where
+    S: traits::ErrorSpan,
+    E: FnMut(DecodeError<S>) -> miette::Report,
+    W: FnMut(Warning<S>) -> miette::Report,
+{
+    let value = match result {
+        Ok(v) if !ctx.has_errors() => Some(v),
+        Ok(_) => None,
+        Err(e) => {
+            ctx.emit_error(e);
+            None
+        }
+    };
+    let (errors, warnings) = ctx.into_diagnostics();
+    let warnings = warnings.into_iter().map(warning);
+    match value {
+        Some(value) => Ok((
+            value,
+            Warnings {
+                source_code,
+                warnings: warnings.collect(),
+            },
+        )),
+        None => Err(Error {
+            source_code,
+            errors: errors.into_iter().map(error).chain(warnings).collect(),
+        }),
+    }
+}
+
+/// Returns the version from the `/- kdl-version N` marker at the start of the
+/// document, or [`KdlVersion::V1`] if there is no marker
+pub(crate) fn detect_version(text: &str) -> KdlVersion {
+    let mut scan = Scanner::<Span>::new(text, Syntax::V1);
+    // comments and newlines are only allowed before the marker
+    if scan.line_space().is_err() || !scan.starts_with("/-") {
+        return KdlVersion::V1;
+    }
+    scan.pos += 2;
+    if scan.node_space().is_err() {
+        return KdlVersion::V1;
+    }
+    match scan.scalar() {
+        Ok(Some(Scalar::Ident(name))) if name == "kdl-version" => {}
+        _ => return KdlVersion::V1,
+    }
+    // the version must be separated from the `kdl-version`
+    if !matches!(scan.node_space(), Ok(true)) {
+        return KdlVersion::V1;
+    }
+    let start = scan.pos;
+    match scan.number() {
+        Some(_) if &text[start..scan.pos] == "2" => KdlVersion::V2,
+        _ => KdlVersion::V1,
+    }
+}
 
 /// Parse KDL text and return AST
+///
+/// The version of the syntax is detected from the document, see
+/// [`ParseOptions`] for choosing it explicitly.
 pub fn parse_ast<S: traits::Span>(
     file_name: impl AsRef<str>,
     text: &str,
 ) -> Result<Document<S>, Error> {
-    grammar::document()
-        .parse(S::stream(text))
-        .map_err(|errors| Error {
-            source_code: NamedSource::new(file_name, text.to_string()),
-            errors: errors.into_iter().map(Into::into).collect(),
-        })
+    ParseOptions::new().parse_ast(file_name, text)
+}
+
+/// Parse KDL text and return AST, recovering from syntax errors
+///
+/// This is useful for editors and other tools that need to work with
+/// incomplete documents. See [`ParseOptions::parse_ast_recovery`] for
+/// details.
+///
+/// ```rust
+/// use knus::span::Span;
+///
+/// let (doc, errors) = knus::parse_ast_recovery::<Span>("a 1 =x\nb 2");
+/// assert_eq!(doc.unwrap().nodes.len(), 1);
+/// assert_eq!(errors.len(), 1);
+/// assert_eq!(errors[0].span(), &Span(4, 5));
+/// ```
+pub fn parse_ast_recovery<S: traits::Span>(
+    text: &str,
+) -> (Option<Document<S>>, Vec<ParseError<S>>) {
+    ParseOptions::new().parse_ast_recovery(text)
 }
 
 /// Parse KDL text and decode Rust object
@@ -26,37 +312,57 @@ pub fn parse<T>(file_name: impl AsRef<str>, text: &str) -> Result<T, Error>
 where
     T: DecodeChildren<Span>,
 {
-    parse_with_context(file_name, text, |_| {})
+    ParseOptions::new().parse(file_name, text)
 }
 
 /// Parse KDL text and decode Rust object providing extra context for the
 /// decoder
+///
+/// Returns the warnings emitted to the context along with the value.
+/// Entities skipped as unknown (see [`Context::emit_ignored`]) are included as
+/// warnings too.
 pub fn parse_with_context<T, S, F>(
     file_name: impl AsRef<str>,
     text: &str,
     set_ctx: F,
-) -> Result<T, Error>
+) -> Result<(T, Warnings), Error>
 where
     F: FnOnce(&mut Context<S>),
     T: DecodeChildren<S>,
     S: traits::Span,
 {
-    let ast = parse_ast(file_name.as_ref(), text)?;
+    ParseOptions::new().parse_with_context(file_name, text, set_ctx)
+}
+
+/// Encode Rust object into KDL AST
+pub fn to_document<T, S>(value: &T) -> Result<Document<S>, EncodeError>
+where
+    T: EncodeChildren + ?Sized,
+    S: traits::ErrorSpan + Default,
+{
+    to_document_with_context(value, |_| {})
+}
 
-    let mut ctx = Context::new();
+/// Encode Rust object into KDL AST providing extra context for the encoder
+pub fn to_document_with_context<T, S, F>(value: &T, set_ctx: F) -> Result<Document<S>, EncodeError>
+where
+    F: FnOnce(&mut encode::Context),
+    T: EncodeChildren + ?Sized,
+    S: traits::ErrorSpan + Default,
+{
+    let mut ctx = encode::Context::new();
     set_ctx(&mut ctx);
-    let errors = match DecodeChildren::decode_children(&ast.nodes, &mut ctx) {
-        Ok(_) if ctx.has_errors() => ctx.into_errors(),
-        Err(e) => {
-            ctx.emit_error(e);
-            ctx.into_errors()
-        }
-        Ok(v) => return Ok(v),
-    };
-    Err(Error {
-        source_code: NamedSource::new(file_name, text.to_string()),
-        errors: errors.into_iter().map(Into::into).collect(),
-    })
+    let mut nodes = Vec::new();
+    value.encode_children(&mut nodes, &mut ctx)?;
+    Ok(Document { nodes })
+}
+
+/// Encode Rust object into KDL text
+pub fn to_string<T>(value: &T) -> Result<String, EncodeError>
+where
+    T: EncodeChildren + ?Sized,
+{
+    to_document::<T, Span>(value).map(|doc| doc.to_string())
 }
 
 #[test]
@@ -65,3 +371,81 @@ fn normal() {
     assert_eq!(doc.nodes.len(), 1);
     assert_eq!(&**doc.nodes[0].node_name, "node");
 }
+
+#[test]
+fn version() {
+    use crate::ast::Literal;
+
+    let doc = parse_ast::<Span>("<test>", "/- kdl-version 2\nnode #true").unwrap();
+    assert_eq!(*doc.nodes[0].arguments[0].literal, Literal::Bool(true));
+    let doc = parse_ast::<Span>("<test>", "// c\n/-kdl-version 1\nnode true").unwrap();
+    assert_eq!(*doc.nodes[0].arguments[0].literal, Literal::Bool(true));
+    parse_ast::<Span>("<test>", "node #true").unwrap_err();
+    parse_ast::<Span>("<test>", "node 1\n/- kdl-version 2\nnode #true").unwrap_err();
+    let doc = ParseOptions::new()
+        .version(KdlVersion::V2)
+        .parse_ast::<Span>("<test>", "node #false")
+        .unwrap();
+    assert_eq!(*doc.nodes[0].arguments[0].literal, Literal::Bool(false));
+    ParseOptions::new()
+        .version(KdlVersion::V1)
+        .parse_ast::<Span>("<test>", "/- kdl-version 2\nnode #false")
+        .unwrap_err();
+}
+
+#[test]
+fn recovery() {
+    fn messages(errors: Vec<ParseError<Span>>) -> Vec<(Span, String)> {
+        errors.iter().map(|e| (*e.span(), e.to_string())).collect()
+    }
+
+    let (doc, err) = parse_ast_recovery::<Span>("a 1 =x 3\nb 2\nc {\n  d\n}\n}\ne");
+    let names = doc
+        .unwrap()
+        .nodes
+        .iter()
+        .map(|n| n.node_name.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["b", "c", "e"]);
+    let errors = messages(err);
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].0, Span(4, 5));
+    assert_eq!(errors[1].1, "found `}` without matching `{`");
+
+    let (doc, err) = parse_ast_recovery::<Span>("/- kdl-version 2\na #true\nb true\n");
+    assert_eq!(doc.unwrap().nodes.len(), 1);
+    assert_eq!(messages(err).len(), 1);
+
+    let (doc, err) = parse_ast_recovery::<Span>("a {\n  b =\n  c\n}\nd");
+    let doc = doc.unwrap();
+    assert_eq!(doc.nodes.len(), 2);
+    assert_eq!(doc.nodes[0].children.as_ref().unwrap().len(), 1);
+    assert_eq!(messages(err).len(), 1);
+
+    let (doc, err) = parse_ast_recovery::<Span>("a {\n  b\n  c");
+    let doc = doc.unwrap();
+    assert_eq!(doc.nodes.len(), 1);
+    let children = doc.nodes[0].children.as_ref().unwrap();
+    assert_eq!(children.span(), &Span(2, 11));
+    assert_eq!(children.len(), 2);
+    assert_eq!(messages(err).len(), 1);
+
+    let (doc, err) = parse_ast_recovery::<Span>("a {\n b 1\n c {\n  d\n");
+    let doc = doc.unwrap();
+    assert_eq!(doc.nodes.len(), 1);
+    let children = doc.nodes[0].children().collect::<Vec<_>>();
+    assert_eq!(children.len(), 2);
+    assert_eq!(children[0].arguments.len(), 1);
+    assert_eq!(
+        children[1].children().next().unwrap().node_name.as_ref(),
+        "d"
+    );
+    assert_eq!(messages(err).len(), 2);
+
+    let (doc, err) = parse_ast_recovery::<Span>("a 1; b { c 2; }\n");
+    assert_eq!(
+        doc.unwrap(),
+        parse_ast("<test>", "a 1; b { c 2; }\n").unwrap()
+    );
+    assert!(err.is_empty());
+}
@@ -0,0 +1,788 @@
+//! XML-in-KDL (XiK) conversion
+//!
+//! This module converts between the stream of XML [events](Event) and KDL
+//! documents as defined by the
+//! [XiK](https://github.com/kdl-org/kdl/blob/main/XML-IN-KDL.md)
+//! specification. Events can be produced by any XML parser and written back
+//! as XML text using their `Display` implementation:
+//!
+//! ```rust
+//! use knus::xik::Event;
+//!
+//! let document = knus::parse_ast::<knus::span::Span>("config.kdl", r#"
+//!     ?xml version="1.0"
+//!     config debug="true" {
+//!         name "app"
+//!         p {
+//!             - "Hello, "
+//!             b "world"
+//!             - "!"
+//!         }
+//!     }
+//! "#)?;
+//! let events = knus::xik::to_events(&document).unwrap();
+//! let xml = events.iter().map(Event::to_string).collect::<String>();
+//! assert_eq!(xml, concat!(
+//!     r#"<?xml version="1.0"?><config debug="true">"#,
+//!     r#"<name>app</name><p>Hello, <b>world</b>!</p>"#,
+//!     r#"</config>"#,
+//! ));
+//!
+//! let converted = knus::xik::from_events::<knus::span::Span, _>(events)?;
+//! assert_eq!(converted.to_string(), document.to_string());
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! # Mapping
+//!
+//! * Elements are nodes, attributes are properties and children are child
+//!   nodes. Attributes are sorted by name, except in the XML declaration.
+//! * Element that contains only text has it as the single argument.
+//!   Otherwise, each piece of text is a `-` node with the text as argument.
+//!   Text consisting only of whitespace is kept too, unless it contains a
+//!   line break (i.e. it is indentation) or is outside of the root element.
+//! * `?target "content"` is a processing instruction, `?xml` node with
+//!   properties is the XML declaration.
+//! * `!doctype "content"` is the document type declaration, and
+//!   `!-- "text"` is a comment.
+//!
+//! When converting to XML, numbers and booleans are accepted in place of
+//! strings, and multiple arguments of an element are concatenated. Node and
+//! property names must be valid XML names, comments can't contain `--` or end
+//! with `-`, processing instructions can't contain `?>`, and the XML and
+//! document type declarations are only allowed at the top level. Otherwise,
+//! [`to_events`] returns an error pointing to the offending node.
+//!
+//! # Limitations
+//!
+//! Converting XML to KDL and back doesn't preserve everything:
+//!
+//! * attributes are sorted by name, as properties of KDL nodes are unordered
+//! * text consisting only of whitespace and line breaks between elements
+//!   (i.e. indentation) is dropped
+//! * CDATA sections become plain text
+//!
+//! The `Display` implementation of [`Event`] doesn't validate the event, so
+//! events that are not produced by [`to_events`] or an XML parser may be
+//! written as malformed XML.
+use std::fmt;
+
+use crate::ast::{Document, Literal, Node, SpannedNode, Value};
+use crate::decode::{Context, Kind};
+use crate::errors::{DecodeError, XikError};
+use crate::span::Spanned;
+use crate::traits::ErrorSpan;
+
+/// Single event of the XML document
+///
+/// All strings are unescaped, i.e. `&amp;` in the text is `&` in the event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Event {
+    /// XML declaration, i.e. `<?xml version="1.0"?>`
+    Declaration(Vec<(String, String)>),
+    /// Processing instruction, i.e. `<?target content?>`
+    ProcessingInstruction {
+        /// The name of the application the instruction is for
+        target: String,
+        /// The rest of the instruction
+        content: String,
+    },
+    /// Document type declaration, i.e. `<!DOCTYPE content>`
+    Doctype(String),
+    /// Start tag of the element, i.e. `<name attr="value">`
+    Start {
+        /// Element name
+        name: String,
+        /// Attributes in the order they are written
+        attributes: Vec<(String, String)>,
+    },
+    /// End tag of the element, i.e. `</name>`
+    End(String),
+    /// Text content, including the CDATA sections
+    Text(String),
+    /// Comment, i.e. `<!--text-->`
+    Comment(String),
+}
+
+/// Converts the XiK document into XML events
+pub fn to_events<S: ErrorSpan>(document: &Document<S>) -> Result<Vec<Event>, Vec<DecodeError<S>>> {
+    let mut ctx = Context::new();
+    let mut events = Vec::new();
+    // Elements are converted using an explicit stack, so that deeply nested
+    // documents don't overflow the stack
+    let mut stack = document
+        .nodes
+        .iter()
+        .rev()
+        .map(|node| Step::Node(node, true))
+        .collect::<Vec<_>>();
+    while let Some(step) = stack.pop() {
+        match step {
+            Step::Node(node, top_level) => {
+                node_events(node, top_level, &mut events, &mut stack, &mut ctx)
+            }
+            Step::End(name) => events.push(Event::End(name.into())),
+        }
+    }
+    if ctx.has_errors() {
+        Err(ctx.into_errors())
+    } else {
+        Ok(events)
+    }
+}
+
+/// Converts XML events into the XiK document
+///
+/// Spans of the resulting document are set to default values.
+pub fn from_events<S, I>(events: I) -> Result<Document<S>, XikError>
+where
+    S: Default,
+    I: IntoIterator<Item = Event>,
+{
+    let mut root = Vec::new();
+    let mut stack: Vec<(Node<S>, Vec<Content<S>>)> = Vec::new();
+    for event in events {
+        let content = match stack.last_mut() {
+            Some((_, content)) => content,
+            None => &mut root,
+        };
+        match event {
+            Event::Declaration(attributes) => {
+                let mut node = Node::new("?xml");
+                set_attributes(&mut node, attributes);
+                content.push(Content::Node(node));
+            }
+            Event::ProcessingInstruction {
+                target,
+                content: text,
+            } => {
+                let mut node = Node::new(format!("?{}", target));
+                if !text.is_empty() {
+                    node.arguments.push(string(text));
+                }
+                content.push(Content::Node(node));
+            }
+            Event::Doctype(text) => content.push(Content::Node(text_node("!doctype", text))),
+            Event::Comment(text) => content.push(Content::Node(text_node("!--", text))),
+            Event::Text(text) => match content.last_mut() {
+                Some(Content::Text(prev)) => prev.push_str(&text),
+                _ => content.push(Content::Text(text)),
+            },
+            Event::Start { name, attributes } => {
+                let mut node = Node::new(name);
+                set_attributes(&mut node, attributes);
+                stack.push((node, Vec::new()));
+            }
+            Event::End(name) => {
+                let (mut node, content) = stack
+                    .pop()
+                    .ok_or_else(|| XikError::UnexpectedEnd(name.clone()))?;
+                if **node.node_name != *name {
+                    return Err(XikError::MismatchedEnd {
+                        expected: node.node_name.to_string(),
+                        found: name,
+                    });
+                }
+                if content.iter().all(|c| matches!(c, Content::Text(_))) {
+                    node.arguments
+                        .extend(content.into_iter().filter_map(|c| match c {
+                            Content::Text(text) if !text.is_empty() => Some(string(text)),
+                            _ => None,
+                        }));
+                } else {
+                    for child in mixed_content(content, false) {
+                        node.push_child(child);
+                    }
+                }
+                match stack.last_mut() {
+                    Some((_, content)) => content.push(Content::Node(node)),
+                    None => root.push(Content::Node(node)),
+                }
+            }
+        }
+    }
+    if let Some((node, _)) = stack.pop() {
+        return Err(XikError::Unclosed(node.node_name.to_string()));
+    }
+    Ok(Document {
+        nodes: mixed_content(root, true)
+            .map(|node| Spanned::new(node, S::default()))
+            .collect(),
+    })
+}
+
+enum Content<S> {
+    Text(String),
+    Node(Node<S>),
+}
+
+/// Converts content of an element that has child elements into nodes
+///
+/// Whitespace between elements is kept only if it's not indentation, as it
+/// may be significant, e.g. in `<b>a</b> <i>b</i>`.
+fn mixed_content<S: Default>(
+    content: Vec<Content<S>>,
+    top_level: bool,
+) -> impl Iterator<Item = Node<S>> {
+    content.into_iter().filter_map(move |c| match c {
+        Content::Node(node) => Some(node),
+        Content::Text(text)
+            if text.trim().is_empty() && (top_level || text.contains(['\n', '\r'])) =>
+        {
+            None
+        }
+        Content::Text(text) => Some(text_node("-", text)),
+    })
+}
+
+fn string<S: Default>(text: impl Into<Box<str>>) -> Value<S> {
+    Value::new(Literal::String(text.into()))
+}
+
+fn text_node<S: Default>(name: &str, text: String) -> Node<S> {
+    let mut node = Node::new(name);
+    node.arguments.push(string(text));
+    node
+}
+
+fn set_attributes<S: Default>(node: &mut Node<S>, attributes: Vec<(String, String)>) {
+    for (name, value) in attributes {
+        node.properties
+            .insert(Spanned::new(name.into(), S::default()), string(value));
+    }
+}
+
+enum Step<'a, S> {
+    /// Node and whether it's at the top level of the document
+    Node(&'a SpannedNode<S>, bool),
+    /// End of the element after its children
+    End(&'a str),
+}
+
+/// Pushes events of the node, children of the element are pushed to the
+/// `stack` to be converted later
+fn node_events<'a, S: ErrorSpan>(
+    node: &'a SpannedNode<S>,
+    top_level: bool,
+    events: &mut Vec<Event>,
+    stack: &mut Vec<Step<'a, S>>,
+    ctx: &mut Context<S>,
+) {
+    if let Some(type_name) = &node.type_name {
+        ctx.emit_error(DecodeError::unexpected(
+            type_name,
+            "type name",
+            "type names are not supported in XiK",
+        ));
+    }
+    let name = &**node.node_name;
+    if name == "-" {
+        events.push(Event::Text(text_argument(node, ctx)));
+    } else if name == "!--" {
+        let text = text_argument(node, ctx);
+        if text.contains("--") || text.ends_with('-') {
+            ctx.emit_error(DecodeError::conversion(
+                &node.arguments[0].literal,
+                "comment can't contain `--` or end with `-`",
+            ));
+        }
+        events.push(Event::Comment(text));
+    } else if name.eq_ignore_ascii_case("!doctype") {
+        check_top_level(node, top_level, ctx);
+        events.push(Event::Doctype(text_argument(node, ctx)));
+    } else if name == "?xml" {
+        check_top_level(node, top_level, ctx);
+        check_no_children(node, ctx);
+        for arg in &node.arguments {
+            ctx.emit_error(DecodeError::unexpected(
+                &arg.literal,
+                "argument",
+                "XML declaration can only have properties",
+            ));
+        }
+        let mut attributes = attributes(node, ctx);
+        attributes.sort_by_key(|(name, _)| match &name[..] {
+            "version" => 0,
+            "encoding" => 1,
+            "standalone" => 2,
+            _ => 3,
+        });
+        events.push(Event::Declaration(attributes));
+    } else if let Some(target) = name.strip_prefix('?') {
+        check_name(node, target, ctx);
+        let content = if node.arguments.is_empty() {
+            check_no_children(node, ctx);
+            check_no_properties(node, ctx);
+            String::new()
+        } else {
+            let content = text_argument(node, ctx);
+            if content.contains("?>") {
+                ctx.emit_error(DecodeError::conversion(
+                    &node.arguments[0].literal,
+                    "processing instruction can't contain `?>`",
+                ));
+            }
+            content
+        };
+        events.push(Event::ProcessingInstruction {
+            target: target.into(),
+            content,
+        });
+    } else {
+        check_name(node, name, ctx);
+        events.push(Event::Start {
+            name: name.into(),
+            attributes: attributes(node, ctx),
+        });
+        for arg in &node.arguments {
+            events.push(Event::Text(text(arg, ctx)));
+        }
+        stack.push(Step::End(name));
+        let children = node.children.iter().flat_map(|children| children.iter());
+        stack.extend(children.rev().map(|child| Step::Node(child, false)));
+    }
+}
+
+fn attributes<S: ErrorSpan>(node: &SpannedNode<S>, ctx: &mut Context<S>) -> Vec<(String, String)> {
+    node.properties
+        .iter()
+        .map(|(name, value)| {
+            if !is_name(name) {
+                ctx.emit_error(DecodeError::conversion(
+                    name,
+                    format!("`{}` is not a valid XML name", name.escape_default()),
+                ));
+            }
+            (name.to_string(), text(value, ctx))
+        })
+        .collect()
+}
+
+fn check_name<S: ErrorSpan>(node: &SpannedNode<S>, name: &str, ctx: &mut Context<S>) {
+    if !is_name(name) {
+        ctx.emit_error(DecodeError::conversion(
+            &node.node_name,
+            format!("`{}` is not a valid XML name", name.escape_default()),
+        ));
+    }
+}
+
+fn check_top_level<S: ErrorSpan>(node: &SpannedNode<S>, top_level: bool, ctx: &mut Context<S>) {
+    if !top_level {
+        ctx.emit_error(DecodeError::unexpected(
+            &node.node_name,
+            "node",
+            format!(
+                "`{}` is only allowed at the top level",
+                node.node_name.escape_default()
+            ),
+        ));
+    }
+}
+
+/// Checks the `Name` production of the XML specification
+fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(is_name_start_char) && chars.all(is_name_char)
+}
+
+fn is_name_start_char(c: char) -> bool {
+    matches!(c,
+        ':' | 'A'..='Z' | '_' | 'a'..='z'
+        | '\u{C0}'..='\u{D6}' | '\u{D8}'..='\u{F6}' | '\u{F8}'..='\u{2FF}'
+        | '\u{370}'..='\u{37D}' | '\u{37F}'..='\u{1FFF}' | '\u{200C}'..='\u{200D}'
+        | '\u{2070}'..='\u{218F}' | '\u{2C00}'..='\u{2FEF}' | '\u{3001}'..='\u{D7FF}'
+        | '\u{F900}'..='\u{FDCF}' | '\u{FDF0}'..='\u{FFFD}' | '\u{10000}'..='\u{EFFFF}'
+    )
+}
+
+fn is_name_char(c: char) -> bool {
+    is_name_start_char(c)
+        || matches!(c,
+            '-' | '.' | '0'..='9' | '\u{B7}' | '\u{300}'..='\u{36F}' | '\u{203F}'..='\u{2040}'
+        )
+}
+
+fn text<S: ErrorSpan>(value: &Value<S>, ctx: &mut Context<S>) -> String {
+    match &*value.literal {
+        Literal::String(s) => s.to_string(),
+        Literal::Bool(b) => b.to_string(),
+        Literal::Decimal(d) => d.0.to_string(),
+        literal @ Literal::Int(_) => literal.to_string(),
+        Literal::Null => {
+            ctx.emit_error(DecodeError::scalar_kind(Kind::String, &value.literal));
+            String::new()
+        }
+    }
+}
+
+/// Returns the single argument of `-`, `!--` and similar nodes
+fn text_argument<S: ErrorSpan>(node: &SpannedNode<S>, ctx: &mut Context<S>) -> String {
+    check_no_children(node, ctx);
+    check_no_properties(node, ctx);
+    let mut args = node.arguments.iter();
+    let text = match args.next() {
+        Some(arg) => text(arg, ctx),
+        None => {
+            ctx.emit_error(DecodeError::missing(node, "argument is required"));
+            String::new()
+        }
+    };
+    for arg in args {
+        ctx.emit_error(DecodeError::unexpected(
+            &arg.literal,
+            "argument",
+            "unexpected argument",
+        ));
+    }
+    text
+}
+
+fn check_no_properties<S: ErrorSpan>(node: &SpannedNode<S>, ctx: &mut Context<S>) {
+    for name in node.properties.keys() {
+        ctx.emit_error(DecodeError::unexpected(
+            name,
+            "property",
+            format!("unexpected property `{}`", name.escape_default()),
+        ));
+    }
+}
+
+fn check_no_children<S: ErrorSpan>(node: &SpannedNode<S>, ctx: &mut Context<S>) {
+    for child in node.children() {
+        ctx.emit_error(DecodeError::unexpected(
+            child,
+            "node",
+            format!("unexpected node `{}`", child.node_name.escape_default()),
+        ));
+    }
+}
+
+struct Escape<'a>(&'a str, bool);
+
+impl fmt::Display for Escape<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Escape(text, attribute) = *self;
+        let mut start = 0;
+        for (idx, c) in text.char_indices() {
+            let escaped = match c {
+                '&' => "&amp;",
+                '<' => "&lt;",
+                '>' => "&gt;",
+                '"' if attribute => "&quot;",
+                _ => continue,
+            };
+            f.write_str(&text[start..idx])?;
+            f.write_str(escaped)?;
+            start = idx + c.len_utf8();
+        }
+        f.write_str(&text[start..])
+    }
+}
+
+fn write_attributes(f: &mut fmt::Formatter, attributes: &[(String, String)]) -> fmt::Result {
+    for (name, value) in attributes {
+        write!(f, " {}=\"{}\"", name, Escape(value, true))?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::Declaration(attributes) => {
+                f.write_str("<?xml")?;
+                write_attributes(f, attributes)?;
+                f.write_str("?>")
+            }
+            Event::ProcessingInstruction { target, content } if content.is_empty() => {
+                write!(f, "<?{}?>", target)
+            }
+            Event::ProcessingInstruction { target, content } => {
+                write!(f, "<?{} {}?>", target, content)
+            }
+            Event::Doctype(content) => write!(f, "<!DOCTYPE {}>", content),
+            Event::Start { name, attributes } => {
+                write!(f, "<{}", name)?;
+                write_attributes(f, attributes)?;
+                f.write_str(">")
+            }
+            Event::End(name) => write!(f, "</{}>", name),
+            Event::Text(text) => Escape(text, false).fmt(f),
+            Event::Comment(text) => write!(f, "<!--{}-->", text),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{from_events, to_events, Event};
+    use crate::ast::Document;
+    use crate::errors::{DecodeError, XikError};
+    use crate::span::Span;
+
+    fn start(name: &str, attributes: &[(&str, &str)]) -> Event {
+        Event::Start {
+            name: name.into(),
+            attributes: attributes
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    fn end(name: &str) -> Event {
+        Event::End(name.into())
+    }
+
+    fn text(text: &str) -> Event {
+        Event::Text(text.into())
+    }
+
+    fn xml(text: &str) -> String {
+        let document = crate::parse_ast::<Span>("<test>", text).unwrap();
+        let events = to_events(&document).unwrap();
+        events.iter().map(Event::to_string).collect()
+    }
+
+    fn errors(text: &str) -> Vec<String> {
+        let document = crate::parse_ast::<Span>("<test>", text).unwrap();
+        to_events(&document)
+            .unwrap_err()
+            .iter()
+            .map(|e| e.to_string())
+            .collect()
+    }
+
+    fn kdl(events: Vec<Event>) -> String {
+        from_events::<Span, _>(events).unwrap().to_string()
+    }
+
+    #[test]
+    fn elements() {
+        assert_eq!(xml("a"), "<a></a>");
+        assert_eq!(
+            xml(r#"a y="1" x=2 { b "t"; c; }"#),
+            r#"<a x="2" y="1"><b>t</b><c></c></a>"#
+        );
+        assert_eq!(xml(r#"a "x" 1 true"#), "<a>x1true</a>");
+        assert_eq!(
+            xml(r#"p { - "a < b & "; i "c"; - " d"; }"#),
+            "<p>a &lt; b &amp; <i>c</i> d</p>"
+        );
+        assert_eq!(
+            xml(r#"a title="\"x\" & y""#),
+            r#"<a title="&quot;x&quot; &amp; y"></a>"#
+        );
+    }
+
+    #[test]
+    fn special_nodes() {
+        assert_eq!(
+            xml(r#"?xml standalone="yes" encoding="utf-8" version="1.0""#),
+            r#"<?xml version="1.0" encoding="utf-8" standalone="yes"?>"#
+        );
+        assert_eq!(xml(r#"?pi "data""#), "<?pi data?>");
+        assert_eq!(xml(r#"?pi"#), "<?pi?>");
+        assert_eq!(xml(r#"!doctype "html""#), "<!DOCTYPE html>");
+        assert_eq!(xml(r#"!-- " note ""#), "<!-- note -->");
+    }
+
+    #[test]
+    fn from_xml() {
+        assert_eq!(
+            kdl(vec![
+                Event::Declaration(vec![("version".into(), "1.0".into())]),
+                text("\n"),
+                Event::Doctype("config".into()),
+                start("config", &[("b", "2"), ("a", "1")]),
+                text("\n  "),
+                start("name", &[]),
+                text("app "),
+                text("x"),
+                end("name"),
+                text("\n  "),
+                Event::Comment(" c ".into()),
+                start("p", &[]),
+                text("Hello, "),
+                start("b", &[]),
+                end("b"),
+                text("!"),
+                end("p"),
+                start("empty", &[]),
+                text(" "),
+                end("empty"),
+                Event::ProcessingInstruction {
+                    target: "pi".into(),
+                    content: String::new(),
+                },
+                text("\n"),
+                end("config"),
+            ]),
+            r#"?xml version="1.0"
+!doctype "config"
+config a="1" b="2" {
+    name "app x"
+    !-- " c "
+    p {
+        - "Hello, "
+        b
+        - "!"
+    }
+    empty " "
+    ?pi
+}
+"#
+        );
+    }
+
+    #[test]
+    fn roundtrip() {
+        let text = r#"
+            ?xml version="1.0"
+            !doctype "html"
+            html lang="en" {
+                !-- "comment"
+                body {
+                    p class="x" {
+                        - "text "
+                        b "bold"
+                        ?php "echo 1;"
+                    }
+                    br
+                }
+            }
+        "#;
+        let document = crate::parse_ast::<Span>("<test>", text).unwrap();
+        let events = to_events(&document).unwrap();
+        let converted: Document<Span> = from_events(events.clone()).unwrap();
+        assert_eq!(converted.to_string(), document.to_string());
+        assert_eq!(to_events(&converted).unwrap(), events);
+    }
+
+    #[test]
+    fn deep_nesting() {
+        let depth = 100_000;
+        let text = "a {\n".repeat(depth) + "- \"x\"\n" + &"}\n".repeat(depth);
+        let document = crate::parse_ast::<Span>("<test>", &text).unwrap();
+        let events = to_events(&document).unwrap();
+        assert_eq!(events.len(), 2 * depth + 1);
+        assert_eq!(events[depth - 1], start("a", &[]));
+        assert_eq!(events[depth], self::text("x"));
+        assert_eq!(events[depth + 1], end("a"));
+        let converted: Document<Span> = from_events(events.clone()).unwrap();
+        assert_eq!(to_events(&converted).unwrap(), events);
+        crate::ast::drop_nodes(document.nodes);
+        crate::ast::drop_nodes(converted.nodes);
+    }
+
+    #[test]
+    fn roundtrip_mixed_content() {
+        let events = vec![
+            start("p", &[]),
+            start("b", &[]),
+            text("a"),
+            end("b"),
+            text(" "),
+            start("i", &[]),
+            text("b"),
+            end("i"),
+            text("\n  "),
+            start("br", &[]),
+            end("br"),
+            end("p"),
+        ];
+        let converted: Document<Span> = from_events(events).unwrap();
+        assert_eq!(
+            converted.to_string(),
+            "p {\n    b \"a\"\n    - \" \"\n    i \"b\"\n    br\n}\n"
+        );
+        let xml: String = to_events(&converted)
+            .unwrap()
+            .iter()
+            .map(Event::to_string)
+            .collect();
+        assert_eq!(xml, "<p><b>a</b> <i>b</i><br></br></p>");
+    }
+
+    #[test]
+    fn event_errors() {
+        assert_eq!(
+            from_events::<Span, _>(vec![start("a", &[]), end("b")]).unwrap_err(),
+            XikError::MismatchedEnd {
+                expected: "a".into(),
+                found: "b".into(),
+            }
+        );
+        assert_eq!(
+            from_events::<Span, _>(vec![end("a")]).unwrap_err(),
+            XikError::UnexpectedEnd("a".into())
+        );
+        assert_eq!(
+            from_events::<Span, _>(vec![start("a", &[]), start("b", &[]), end("b")])
+                .unwrap_err()
+                .to_string(),
+            "element `<a>` is not closed"
+        );
+    }
+
+    #[test]
+    fn document_errors() {
+        assert_eq!(errors("(t)a"), ["type names are not supported in XiK"]);
+        assert_eq!(errors("a x=null"), ["expected string scalar, found null"]);
+        assert_eq!(
+            errors(r#"- "a" "b" x="y" { c; }"#),
+            [
+                "unexpected node `c`",
+                "unexpected property `x`",
+                "unexpected argument"
+            ]
+        );
+        assert_eq!(errors("!--"), ["argument is required"]);
+        assert_eq!(
+            errors(r#"?xml "1.0""#),
+            ["XML declaration can only have properties"]
+        );
+    }
+
+    #[test]
+    fn malformed_xml() {
+        assert_eq!(
+            errors(r#""a b"; "1x" "2y"=1; "?p i"; _ok:x-1.2"#),
+            [
+                "`a b` is not a valid XML name",
+                "`1x` is not a valid XML name",
+                "`2y` is not a valid XML name",
+                "`p i` is not a valid XML name",
+            ]
+        );
+        assert_eq!(
+            errors(r#"!-- "a -- b"; !-- "a-"; !-- "a - b""#),
+            [
+                "comment can't contain `--` or end with `-`",
+                "comment can't contain `--` or end with `-`",
+            ]
+        );
+        assert_eq!(
+            errors(r#"?pi "x ?> y""#),
+            ["processing instruction can't contain `?>`"]
+        );
+        assert_eq!(
+            errors(r#"a { !doctype "html"; ?xml version="1.0"; }"#),
+            [
+                "`!doctype` is only allowed at the top level",
+                "`?xml` is only allowed at the top level",
+            ]
+        );
+        let document = crate::parse_ast::<Span>("<test>", r#"a { "b c"; }"#).unwrap();
+        let err = &to_events(&document).unwrap_err()[0];
+        assert!(matches!(
+            err,
+            DecodeError::Conversion {
+                span: Span(4, 9),
+                ..
+            }
+        ));
+    }
+}